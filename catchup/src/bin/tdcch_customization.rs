@@ -1,5 +1,6 @@
 // Metric dependent part of CATCHUp preprocessing - the customization - with reporting for experiments.
 // Takes as input one directory arg which should contain the all data and to which results will be written.
+// An optional second arg selects the customization strategies, e.g. `no-precustomization,approx=500` (see `CatchupConfig`).
 
 use std::{env, error::Error, path::Path};
 
@@ -11,8 +12,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     let _reporter = enable_reporting("tdcch_customization");
     report!("num_threads", rayon::current_num_threads());
 
-    let arg = &env::args().skip(1).next().ok_or(CliErr("No directory arg given"))?;
+    let mut args = env::args().skip(1);
+    let arg = &args.next().ok_or(CliErr("No directory arg given"))?;
     let path = Path::new(arg);
+    let config: CatchupConfig = match args.next() {
+        Some(options) => options.parse()?,
+        None => CatchupConfig::default(),
+    };
 
     let graph = TDGraph::reconstruct_from(&path)?;
 
@@ -24,7 +30,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let customized_folder = path.join("customized");
 
     let _cch_customization_ctxt = algo_runs_ctxt.push_collection_item();
-    let td_cch_graph = ftd_cch::customize(&cch, &graph);
+    let td_cch_graph = ftd_cch::customize_with_config(&cch, &graph, &config);
     td_cch_graph.deconstruct_to(&customized_folder)?;
 
     Ok(())
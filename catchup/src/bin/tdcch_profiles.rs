@@ -51,7 +51,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                     let _blocked = block_reporting();
                     let at = start + 0.5 * (end - start);
                     let mut result = ea_server.td_query(TDQuery { from, to, departure: at });
-                    assert!(PeriodicPiecewiseLinearFunction::new(&tt).evaluate(at).fuzzy_eq(result.distance().unwrap()));
+                    assert!(PeriodicPiecewiseLinearFunction::new(&tt, g.horizon().period())
+                        .evaluate(at)
+                        .fuzzy_eq(result.distance().unwrap()));
                     let gt_path = result.node_path().unwrap();
                     g.check_path(&gt_path);
                     g.check_path(&g.get_path_with_times(at, &path));
@@ -59,7 +61,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 for paths in paths.windows(2) {
                     check_segment(paths[0].0, paths[1].0, &paths[0].1);
                 }
-                check_segment(paths.last().unwrap().0, period(), &paths.last().unwrap().1);
+                check_segment(paths.last().unwrap().0, g.horizon().period(), &paths.last().unwrap().1);
 
                 report!("path_switches", paths.len() - 1);
                 let mut paths: Vec<_> = paths.into_iter().map(|(_, path)| path).collect();
@@ -1,6 +1,7 @@
 // Ad hoc CATCHUp query experiments for quick results.
 // The real experiments use pregenerated query sets.
 // Takes as input one directory arg which should contain the all data.
+// An optional second arg selects the query strategies, e.g. `lazy,no-astar` (see `CatchupConfig`).
 
 use std::{env, error::Error, path::Path};
 
@@ -20,16 +21,23 @@ fn main() -> Result<(), Box<dyn Error>> {
     // let _reporter = enable_reporting();
     report!("program", "tdcch_queries");
 
-    setup(Path::new(&env::args().skip(1).next().unwrap()), |g, rng, cch, td_cch_graph| {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap();
+    let config: CatchupConfig = match args.next() {
+        Some(options) => options.parse()?,
+        None => CatchupConfig::default(),
+    };
+
+    setup(Path::new(&path), |g, rng, cch, td_cch_graph| {
         let mut algo_runs_ctxt = push_collection_context("algo_runs");
         let mut td_dijk_server = DijkServer::new(g);
-        let mut server = Server::new(&cch, &td_cch_graph);
+        let mut server = Server::new_with_config(&cch, &td_cch_graph, config);
 
         let mut rank_times = vec![Vec::new(); 64];
 
         for _ in 0..50 {
             let from: NodeId = rng.gen_range(0..g.num_nodes() as NodeId);
-            let at = Timestamp::new(rng.gen_range(0.0..f64::from(period())));
+            let at = Timestamp::new(rng.gen_range(0.0..f64::from(g.horizon().period())));
             td_dijk_server.ranks(from, at, |to, ea_ground_truth, rank| {
                 let _tdcch_query_ctxt = algo_runs_ctxt.push_collection_item();
                 let (mut result, duration) = measure(|| server.td_query(TDQuery { from, to, departure: at }).found().unwrap());
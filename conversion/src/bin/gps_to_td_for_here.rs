@@ -1,16 +1,17 @@
-#![feature(array_windows)]
+// Build a TD graph with historic speed profiles for a HERE graph from gzipped CSV files with map matched GPS speeds.
+// Observations between the given start and end timestamp are folded into one period of the resulting travel time functions.
 
 use conversion::here::link_id_mapper::*;
-use conversion::speed_profile_to_tt_profile;
 use rust_road_router::{
     cli::CliErr,
     datastr::{
-        graph::{time_dependent::*, *},
+        graph::{time_dependent::period, *},
         rank_select_map::*,
     },
     io::*,
+    link_speed_estimates::speed_profiles::*,
 };
-use std::{collections::BTreeMap, env, error::Error, fs::File, path::Path};
+use std::{env, error::Error, fs::File, path::Path};
 
 use csv::ReaderBuilder;
 use flate2::read::GzDecoder;
@@ -18,7 +19,6 @@ use flate2::read::GzDecoder;
 const NUM_BUCKETS: usize = 24 * 4;
 const MIN_NUM_SAMPELS: usize = 3;
 const TOO_FAST_FACTOR: f64 = 1.5;
-const BUCKET_LEN: u32 = period() / NUM_BUCKETS as u32;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut args = env::args().skip(1);
@@ -38,18 +38,22 @@ fn main() -> Result<(), Box<dyn Error>> {
     let tt_units_per_s = Vec::<u32>::load_from(path.join("tt_units_per_s"))?[0];
     let dist_units_per_m = Vec::<u32>::load_from(path.join("dist_units_per_m"))?[0];
 
-    let speeds_kmph: Box<[_]> = graph
-        .weight()
-        .iter()
-        .zip(geo_distance.iter())
-        .map(|(tt, d)| (d * tt_units_per_s * 36) / (tt * dist_units_per_m * 10))
-        .collect();
-
-    let mut speed_profiles = vec![BTreeMap::<usize, Vec<(Timestamp, f64)>>::new(); graph.num_arcs()];
+    let lengths = geo_distance.iter().map(|&d| f64::from(d) / f64::from(dist_units_per_m)).collect();
+    let free_flow_travel_times = graph.weight().iter().map(|&tt| f64::from(tt) / f64::from(tt_units_per_s)).collect();
+    let mut builder = SpeedProfileBuilder::new(
+        lengths,
+        free_flow_travel_times,
+        SpeedProfileParams {
+            // ms to s
+            period: f64::from(period()) / 1000.0,
+            num_buckets: NUM_BUCKETS,
+            min_num_samples: MIN_NUM_SAMPELS,
+            too_fast_factor: TOO_FAST_FACTOR,
+            ..SpeedProfileParams::default()
+        },
+    );
 
     let mut total = 0;
-    let mut applied = 0;
-    let mut too_fast = 0;
     let mut no_edge = 0;
     let mut out_of_range = 0;
     let mut invalid = 0;
@@ -113,111 +117,22 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
 
             if let Some(edge_idx) = id_mapper.here_to_local_link_id(link_id, dir) {
-                // check if speed too large
-                if speeds_kmph[edge_idx as usize] as f64 * TOO_FAST_FACTOR < speed_kmph {
-                    too_fast += 1;
-                    continue;
-                }
                 if timestamp < start_time || timestamp >= end_time {
                     out_of_range += 1;
                     continue;
                 }
-                let t = (timestamp - start_time) as Timestamp % period();
-                // timestamp to bucket
-                let bucket = ((timestamp - start_time) / BUCKET_LEN as u64) as usize % NUM_BUCKETS;
-                let entry = speed_profiles[edge_idx as usize].entry(bucket).or_insert(Vec::new());
-                entry.push((t, speed_kmph));
-                applied += 1;
+                builder.add(edge_idx, timestamp - start_time, speed_kmph, 1.0);
             } else {
                 no_edge += 1;
             }
         }
     }
 
-    dbg!(total, applied, too_fast, no_edge, out_of_range, invalid);
-
-    let mut first_ipp_of_arc = Vec::with_capacity(graph.num_arcs() + 1);
-    first_ipp_of_arc.push(0u32);
-    let mut ipp_departure_time = Vec::<Timestamp>::new();
-    let mut ipp_travel_time = Vec::<Weight>::new();
-
-    for (edge_idx, speed_profile) in speed_profiles.iter_mut().enumerate() {
-        let mut constant = true;
-        if !speed_profile.is_empty() {
-            let mut flat_speed_profile = vec![speeds_kmph[edge_idx]; NUM_BUCKETS];
-            for (&bucket_id, speeds) in speed_profile.iter_mut() {
-                if speeds.len() >= MIN_NUM_SAMPELS {
-                    speeds.sort_unstable_by_key(|&(t, _)| t);
-                    let mut speed_sum = 0.0;
-                    let mut weight_sum = 0.0;
-
-                    let weight = (speeds[1].0 - (bucket_id as u32 * BUCKET_LEN)) as f64;
-                    weight_sum += weight;
-                    speed_sum += speeds[0].1 * weight;
-                    for [(t_prev, _), (_, speed), (t_next, _)] in speeds.array_windows() {
-                        let weight = (t_next - t_prev) as f64;
-                        weight_sum += weight;
-                        speed_sum += speed * weight;
-                    }
-                    let weight = (((bucket_id + 1) as u32 * BUCKET_LEN) - speeds[speeds.len() - 2].0) as f64;
-                    weight_sum += weight;
-                    speed_sum += speeds[speeds.len() - 1].1 * weight;
-
-                    flat_speed_profile[bucket_id] = std::cmp::min(speeds_kmph[edge_idx], std::cmp::max(1, (speed_sum / weight_sum).round() as u32));
-                }
-            }
-
-            let mut linear_speed_profile = flat_speed_profile.iter().enumerate().fold(Vec::new(), |mut acc, (idx, &speed)| {
-                if let Some(&(_, last_speed)) = acc.last() {
-                    if speed != last_speed {
-                        acc.push((idx as u32 * BUCKET_LEN, speed));
-                    }
-                } else {
-                    debug_assert_eq!(idx, 0);
-                    acc.push((idx as u32 * BUCKET_LEN, speed));
-                }
-                acc
-            });
-            if linear_speed_profile.len() > 1 {
-                linear_speed_profile.push((period(), linear_speed_profile[0].1));
-
-                let tt_profile = speed_profile_to_tt_profile(&linear_speed_profile, geo_distance[edge_idx] / dist_units_per_m);
-
-                for points in tt_profile.windows(2) {
-                    debug_assert!(points[0].0 < points[1].0, "{:#?} {:?}", points, linear_speed_profile);
-                    debug_assert!(
-                        points[0].0 + points[0].1 <= points[1].0 + points[1].1,
-                        "{:#?} {:?}",
-                        points,
-                        linear_speed_profile
-                    );
-                }
-                let last = tt_profile.len() - 1;
-                debug_assert!(
-                    tt_profile[last].0 + tt_profile[last].1 <= tt_profile[1].0 + tt_profile[1].1 + period(),
-                    "{:#?} {:?}",
-                    tt_profile,
-                    linear_speed_profile
-                );
-
-                constant = false;
-                for &(dt, tt) in &tt_profile {
-                    ipp_departure_time.push(dt);
-                    ipp_travel_time.push(tt);
-                }
-                first_ipp_of_arc.push(first_ipp_of_arc.last().unwrap() + tt_profile.len() as u32);
-            }
-        }
-        if constant {
-            ipp_departure_time.push(0);
-            ipp_travel_time.push(graph.weight()[edge_idx]);
-            first_ipp_of_arc.push(first_ipp_of_arc.last().unwrap() + 1);
-        }
-    }
+    let stats = builder.stats();
+    dbg!(total, stats.applied, stats.too_fast, no_edge, out_of_range, invalid);
 
-    first_ipp_of_arc.write_to(&path.join("first_ipp_of_arc"))?;
-    ipp_departure_time.write_to(&path.join("ipp_departure_time"))?;
-    ipp_travel_time.write_to(&path.join("ipp_travel_time"))?;
+    let graph = builder.build(graph.first_out().to_vec(), graph.head().to_vec(), DayType::Any);
+    graph.deconstruct_to(&path)?;
 
     Ok(())
 }
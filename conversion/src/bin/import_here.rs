@@ -27,6 +27,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     data.lng.write_to(&out_dir.join("longitude"))?;
     data.link_id_mapping.write_to(&out_dir.join("link_id_mapping"))?;
     data.here_rank_to_link_id.write_to(&out_dir.join("here_rank_to_link_id"))?;
+    data.edge_geometry.deconstruct_to(&out_dir)?;
     data.link_lengths
         .into_iter()
         .map(|d| d as u32)
@@ -1,5 +1,6 @@
 use rust_road_router::datastr::graph::*;
 use rust_road_router::datastr::rank_select_map::{BitVec, RankSelectMap};
+use rust_road_router::datastr::spatial_index::EdgeGeometry;
 use rust_road_router::util::in_range_option::*;
 use std::error::Error;
 use std::fmt;
@@ -134,6 +135,10 @@ impl RdfLinkGeometry {
             (self.z_coord.unwrap_or(0) as f64) / 100.,
         )
     }
+
+    fn lat_lng(&self) -> (f32, f32) {
+        (((self.lat as f64) / 10_000_000.) as f32, ((self.lon as f64) / 10_000_000.) as f32)
+    }
 }
 
 pub struct HereData {
@@ -144,6 +149,9 @@ pub struct HereData {
     pub lng: Vec<f32>,
     pub link_id_mapping: RankSelectMap,
     pub here_rank_to_link_id: Vec<(InRangeOption<EdgeId>, InRangeOption<EdgeId>)>,
+    /// Shape points of each edge in travel direction.
+    /// These may include the coordinates of the end points, which is harmless for the spatial index.
+    pub edge_geometry: EdgeGeometry,
 }
 
 pub trait RdfDataSource {
@@ -280,6 +288,7 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
     let mut link_lengths: Vec<f64> = vec![0.0; m as usize];
     let mut functional_road_classes: Vec<u8> = vec![0; m as usize];
     let mut here_rank_to_link_id: Vec<(InRangeOption<EdgeId>, InRangeOption<EdgeId>)> = vec![(InRangeOption::NONE, InRangeOption::NONE); links.len()];
+    let mut shapes: Vec<Vec<(f32, f32)>> = vec![Vec::new(); m as usize];
 
     eprintln!("calculate weights");
     // iterate over all links and insert head and weight
@@ -296,6 +305,9 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
             };
             let from_node = node_id_mapping.at(link.ref_node_id as usize);
             let to_node = node_id_mapping.at(link.nonref_node_id as usize);
+            // geometries are sorted starting at the reference node
+            let from_ref_shape = || link_geometries[link_index].iter().map(RdfLinkGeometry::lat_lng).collect::<Vec<_>>();
+            let to_ref_shape = || link_geometries[link_index].iter().rev().map(RdfLinkGeometry::lat_lng).collect::<Vec<_>>();
 
             let from_weight = (1000. * length / nav_link.speed_in_m_per_s(RdfLinkDirection::FromRef)).round() as Weight;
             let to_weight = (1000. * length / nav_link.speed_in_m_per_s(RdfLinkDirection::ToRef)).round() as Weight;
@@ -307,6 +319,7 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
                     link_lengths[first_out[from_node] as usize] = length;
                     functional_road_classes[first_out[from_node] as usize] = nav_link.functional_class;
                     here_rank_to_link_id[link_index].0 = InRangeOption::some(first_out[from_node]);
+                    shapes[first_out[from_node] as usize] = from_ref_shape();
                     first_out[from_node] += 1;
                 }
                 RdfLinkDirection::ToRef => {
@@ -315,6 +328,7 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
                     link_lengths[first_out[to_node] as usize] = length;
                     functional_road_classes[first_out[to_node] as usize] = nav_link.functional_class;
                     here_rank_to_link_id[link_index].1 = InRangeOption::some(first_out[to_node]);
+                    shapes[first_out[to_node] as usize] = to_ref_shape();
                     first_out[to_node] += 1;
                 }
                 RdfLinkDirection::Both => {
@@ -323,6 +337,7 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
                     link_lengths[first_out[from_node] as usize] = length;
                     functional_road_classes[first_out[from_node] as usize] = nav_link.functional_class;
                     here_rank_to_link_id[link_index].0 = InRangeOption::some(first_out[from_node]);
+                    shapes[first_out[from_node] as usize] = from_ref_shape();
                     first_out[from_node] += 1;
 
                     head[first_out[to_node] as usize] = from_node as NodeId;
@@ -330,6 +345,7 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
                     link_lengths[first_out[to_node] as usize] = length;
                     functional_road_classes[first_out[to_node] as usize] = nav_link.functional_class;
                     here_rank_to_link_id[link_index].1 = InRangeOption::some(first_out[to_node]);
+                    shapes[first_out[to_node] as usize] = to_ref_shape();
                     first_out[to_node] += 1;
                 }
             }
@@ -353,6 +369,7 @@ pub fn read_graph(source: &dyn RdfDataSource, (min_lat, min_lon): (i64, i64), (m
         lng,
         link_id_mapping,
         here_rank_to_link_id,
+        edge_geometry: EdgeGeometry::from_shapes(shapes),
     }
 }
 
@@ -22,6 +22,9 @@ pub const FILE_QUERY_ORIGINAL_TO_EDGES: &str = "query_original_to_edges";
 pub const FILE_QUERIES_FROM: &str = "queries_from";
 pub const FILE_QUERIES_TO: &str = "queries_to";
 pub const FILE_QUERIES_DEPARTURE: &str = "queries_departure";
+/// each line i contains the vType of query i, trips without a type have the vType `DEFAULT_VTYPE`
+pub const FILE_QUERY_VTYPES: &str = "query_vtypes";
+pub const DEFAULT_VTYPE: &str = "DEFAULT_VEHTYPE";
 /// contains all edges of all alternative paths of queries used during DTA, where edges are encoded as u32 indices
 pub const FILE_DTA_QUERIES_EDGE_IDS: &str = "edge_ids";
 /// contains the index of the first alternative to a set of alternative paths.
@@ -63,6 +66,12 @@ pub const FILE_EDGE_LANES: &str = "edge_lanes";
 // contains the speed in m/s of the edges
 pub const FILE_EDGE_SPEEDS: &str = "edge_speed";
 
+// contains the tolls of the edges, given by the edge param "toll"
+pub const FILE_EDGE_TOLLS: &str = "edge_tolls";
+
+// contains the SUMO priorities of the edges, -1 for edges without one
+pub const FILE_EDGE_PRIORITIES: &str = "edge_priorities";
+
 /// contains the traffic model parameters for each edge
 /// the parameters are stored in the same order for each edge as f64 values
 pub const FILE_EDGE_TRAFFIC_MODEL_PARAMS: &str = "edge_traffic_model_params";
@@ -85,8 +94,23 @@ pub const FILE_EDGE_SPEED_OBSERVATIONS: &str = "edge_speed_observations";
 /// starting from index `first_speed_observation_of_edge[i]` in `speed_observations`
 pub const FILE_EDGE_FIRST_SPEED_OBSERVATION: &str = "first_speed_observation_of_edge";
 
+/// csv file with the goodness of fit (RMSE, R²) of the traffic model of each edge and whether it was fitted on its own observations
+pub const FILE_EDGE_TRAFFIC_MODEL_FIT: &str = "edge_traffic_model_fit.csv";
+
+/// csv file with the observed lane densities and speeds of each edge and the speeds of its traffic model
+pub const FILE_EDGE_SPEED_DENSITY_SCATTER: &str = "edge_speed_density_scatter.csv";
+
 pub const FILE_RELATIVE_GAPS: &str = "rel_gaps.txt";
 
+/// per iteration convergence report, written to the iteration directory
+pub const FILE_CONVERGENCE_REPORT: &str = "convergence.json";
+/// one line of the convergence report per iteration
+pub const FILE_CONVERGENCE_SUMMARY: &str = "convergence.csv";
+
+/// directory for the simulations and the fit statistics of the OD matrix estimation
+pub const DIR_OD_ESTIMATION: &str = "od_estimation";
+pub const FILE_OD_ESTIMATION_FIT: &str = "od_estimation_fit.csv";
+
 /// in milliseconds
 pub type SerializedTravelTime = u32;
 /// in milliseconds
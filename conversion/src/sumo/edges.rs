@@ -38,6 +38,10 @@ pub struct Edge {
     #[serde(rename = "@priority")]
     pub priority: Option<i32>,
 
+    /// geometry of the edge as space separated list of `x,y` positions, including the end points
+    #[serde(rename = "@shape")]
+    pub shape: Option<String>,
+
     #[serde(rename = "lane", default)]
     pub lanes: Vec<Lane>,
 
@@ -58,6 +62,31 @@ impl Edge {
         })
     }
 
+    /// returns the positions of the shape, empty if no shape is set or it could not be parsed
+    pub fn get_shape(&self) -> Vec<(SumoPosition, SumoPosition)> {
+        let Some(shape) = &self.shape else {
+            return Vec::new();
+        };
+        shape
+            .split_whitespace()
+            .map(|position| {
+                let (x, y) = position.split_once(',')?;
+                Some((x.parse().ok()?, y.parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default()
+    }
+
+    /// returns the toll of the edge given by a param with key "toll", 0 if there is none
+    pub fn get_toll(&self) -> f64 {
+        self.params
+            .iter()
+            .find(|param| param.key == "toll")
+            .and_then(|param| param.value.as_ref())
+            .and_then(|value| value.parse::<f64>().ok())
+            .unwrap_or(0.0)
+    }
+
     /// returns the capacity of the edge in vehicles per hour
     /// based on the formula from https://sumo.dlr.de/docs/Simulation/RoadCapacity.html
     pub fn get_capacity(&self) -> f64 {
@@ -15,7 +15,26 @@ use crate::{
     SerializedTimestamp, FILE_QUERY_IDS,
 };
 
+/// optional attributes of a written route, mirroring duarouter's `--exit-times` and `--route-length`
+#[derive(Debug, Clone, Default)]
+pub struct RouteDetails {
+    /// the time (in seconds) at which the vehicle leaves each edge of the route
+    pub exit_times: Option<Vec<f64>>,
+    /// the length of the route in meters
+    pub length: Option<f64>,
+}
+
+impl RouteDetails {
+    fn exit_times_attribute(&self) -> Option<String> {
+        self.exit_times
+            .as_ref()
+            .map(|times| times.iter().map(|t| format!("{t:.2}")).collect::<Vec<_>>().join(" "))
+    }
+}
+
 /// only writes the .rou.xml file
+///
+/// `route_details` is either empty or has the same shape as `path_sets`
 pub fn write_paths_as_sumo_routes(
     input_dir: &Path,
     input_prefix: &String,
@@ -27,7 +46,10 @@ pub fn write_paths_as_sumo_routes(
     departures: &Vec<SerializedTimestamp>,
     edge_indices_to_id: &Vec<String>,
     write_alternative_paths: bool,
+    route_details: &Vec<Vec<RouteDetails>>,
 ) {
+    debug_assert!(route_details.is_empty() || route_details.len() == path_sets.len());
+
     let trip_ids: Vec<String> = read_strings_from_file(&input_dir.join(FILE_QUERY_IDS)).unwrap();
 
     // transform path_sets from EdgeId to Sumo Ids (which are strings) using the edge_indices_to_id mapping
@@ -36,7 +58,13 @@ pub fn write_paths_as_sumo_routes(
     // extract paths from alternative_lists from choices:
     let paths: Vec<&String> = get_chosen_paths_from_alternatives(&path_sets, &choices);
 
-    let sumo_routes = convert_to_sumo_routes(paths, &trip_ids, departures);
+    let chosen_details: Vec<Option<&RouteDetails>> = choices
+        .iter()
+        .enumerate()
+        .map(|(i, &choice)| route_details.get(i).map(|details| &details[choice]))
+        .collect();
+
+    let sumo_routes = convert_to_sumo_routes(paths, &trip_ids, departures, &chosen_details);
 
     let current_iteration_dir = input_dir.join(format!("{iteration:0>3}"));
     let route_file_prefix = format!("{input_prefix}_{iteration:0>3}");
@@ -44,7 +72,7 @@ pub fn write_paths_as_sumo_routes(
     SumoRoutesWriter::write(&current_iteration_dir.join(format!("{route_file_prefix}{ROUTES}")), &sumo_routes).expect("Failed to write SUMO routes to file");
 
     if write_alternative_paths {
-        let sumo_alt_routes = convert_to_sumo_alt_routes(&path_sets, &trip_ids, costs, probabilities, choices, departures);
+        let sumo_alt_routes = convert_to_sumo_alt_routes(&path_sets, &trip_ids, costs, probabilities, choices, departures, route_details);
         SumoRoutesWriter::write(&current_iteration_dir.join(format!("{route_file_prefix}{ALT_ROUTES}")), &sumo_alt_routes)
             .expect("Failed to write SUMO alternative routes to file");
     }
@@ -70,7 +98,7 @@ pub fn write_batch_routes_for_sumo(
         })
         .collect();
 
-    let sumo_routes = convert_to_sumo_routes(sumo_paths.iter().collect(), trip_ids, departures);
+    let sumo_routes = convert_to_sumo_routes(sumo_paths.iter().collect(), trip_ids, departures, &vec![None; sumo_paths.len()]);
 
     SumoRoutesWriter::write(output_path, &sumo_routes)?;
 
@@ -78,7 +106,12 @@ pub fn write_batch_routes_for_sumo(
 }
 
 /// prepares a datastructure which can be serialized into a *.rou.xml for SUMO
-fn convert_to_sumo_routes(paths: Vec<&String>, trip_ids: &Vec<String>, departures: &Vec<SerializedTimestamp>) -> RoutesDocumentRoot {
+fn convert_to_sumo_routes(
+    paths: Vec<&String>,
+    trip_ids: &Vec<String>,
+    departures: &Vec<SerializedTimestamp>,
+    details: &Vec<Option<&RouteDetails>>,
+) -> RoutesDocumentRoot {
     // create RoutesDocumentRoot
     let mut routes = RoutesDocumentRoot { vehicles: Vec::new() };
 
@@ -93,6 +126,8 @@ fn convert_to_sumo_routes(paths: Vec<&String>, trip_ids: &Vec<String>, departure
                 edges: path.clone(),
                 cost: None,
                 probability: None,
+                exit_times: details[i].and_then(|d| d.exit_times_attribute()),
+                route_length: details[i].and_then(|d| d.length),
             }),
             route_distribution: None,
         };
@@ -111,6 +146,7 @@ fn convert_to_sumo_alt_routes(
     probabilities: &Vec<Vec<f64>>,
     choices: &Vec<usize>,
     departures: &Vec<SerializedTimestamp>,
+    route_details: &Vec<Vec<RouteDetails>>,
 ) -> RoutesDocumentRoot {
     debug_assert_eq!(trip_ids.len(), path_sets.len());
     debug_assert_eq!(trip_ids.len(), costs.len());
@@ -127,11 +163,14 @@ fn convert_to_sumo_alt_routes(
             // create a route for each alternative path
             let cost = costs[i][j];
             let probability = probabilities[i][j];
+            let details = route_details.get(i).map(|details| &details[j]);
 
             alternative_routes.push(Route {
                 edges: path.clone(),
                 cost: Some(cost.into()),
                 probability: Some(probability),
+                exit_times: details.and_then(|d| d.exit_times_attribute()),
+                route_length: details.and_then(|d| d.length),
             });
         }
         let vehicle = Vehicle {
@@ -35,6 +35,10 @@ pub struct Route {
     pub cost: Option<SumoTravelTime>,
     #[serde(default, rename = "@probability")]
     pub probability: Option<f64>,
+    #[serde(default, rename = "@exitTimes")]
+    pub exit_times: Option<String>,
+    #[serde(default, rename = "@routeLength")]
+    pub route_length: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,4 +1,7 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use rayon::prelude::*;
 
@@ -9,10 +12,10 @@ use rust_road_router::{
 
 use crate::{
     sumo::{
-        meandata::{Edge, MeandataDocumentRoot},
+        meandata::{Edge, Interval, MeandataDocumentRoot},
         meandata_reader::SumoMeandataReader,
         sumo_find_file::get_meandata_file,
-        FileReader,
+        FileReader, SumoTravelTime,
     },
     SerializedTimestamp, SerializedTravelTime, FILE_EDGE_DEFAULT_TRAVEL_TIMES, FILE_FIRST_IPP_OF_ARC, FILE_IPP_DEPARTURE_TIME, FILE_IPP_TRAVEL_TIME,
     MIN_EDGE_WEIGHT,
@@ -20,6 +23,33 @@ use crate::{
 
 const FIFO_BUFFER_MS: SerializedTravelTime = 0;
 
+/// the edgeData attribute that is used as edge weight, see duarouter's `--weight-attribute`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WeightAttribute {
+    TravelTime,
+    /// the time needed to pass the edge, also defined for vehicles which did not fully pass the edge within the interval
+    #[default]
+    OverlapTravelTime,
+}
+
+impl WeightAttribute {
+    /// maps the attribute name used in SUMO's edgeData output, returns None for unsupported attributes
+    pub fn from_sumo_name(name: &str) -> Option<Self> {
+        match name {
+            "traveltime" => Some(WeightAttribute::TravelTime),
+            "overlapTraveltime" => Some(WeightAttribute::OverlapTravelTime),
+            _ => None,
+        }
+    }
+
+    fn value_of(&self, edge: &Edge) -> Option<SumoTravelTime> {
+        match self {
+            WeightAttribute::TravelTime => edge.traveltime,
+            WeightAttribute::OverlapTravelTime => edge.overlap_traveltime,
+        }
+    }
+}
+
 pub fn get_graph_with_travel_times_from_previous_iteration(input_dir: &Path, iteration: u32, edge_ids: &Vec<String>) -> TDGraph {
     // if iteration > 0, we load the previous iteration's travel times
     if iteration > 0 {
@@ -32,6 +62,52 @@ pub fn get_graph_with_travel_times_from_previous_iteration(input_dir: &Path, ite
     TDGraph::reconstruct_from(&input_dir).expect("Failed to reconstruct the time-dependent graph")
 }
 
+/// like duarouter's `--weight-files`: the edge weights are read from the given edgeData files instead of the previous iteration's dump.
+/// Intervals with the same begin are merged, for edges present in several files the last file wins.
+pub fn get_graph_with_travel_times_from_weight_files(
+    input_dir: &Path,
+    weight_files: &Vec<PathBuf>,
+    weight_attribute: WeightAttribute,
+    edge_ids: &Vec<String>,
+) -> TDGraph {
+    let meandata = merge_meandata(
+        weight_files
+            .iter()
+            .map(|file| SumoMeandataReader::read(file).expect("Failed to read SUMO weight file"))
+            .collect(),
+    );
+
+    let (first_ipp_of_arc, ipp_travel_time, ipp_departure_time) = extract_interpolation_points_from_meandata_with_attribute(
+        &meandata,
+        &edge_ids,
+        &Vec::<SerializedTravelTime>::load_from(input_dir.join(FILE_EDGE_DEFAULT_TRAVEL_TIMES)).unwrap(),
+        weight_attribute,
+    );
+
+    first_ipp_of_arc.write_to(&input_dir.join(FILE_FIRST_IPP_OF_ARC)).unwrap();
+    ipp_travel_time.write_to(&input_dir.join(FILE_IPP_TRAVEL_TIME)).unwrap();
+    ipp_departure_time.write_to(&input_dir.join(FILE_IPP_DEPARTURE_TIME)).unwrap();
+
+    TDGraph::reconstruct_from(&input_dir).expect("Failed to reconstruct the time-dependent graph")
+}
+
+fn merge_meandata(documents: Vec<MeandataDocumentRoot>) -> MeandataDocumentRoot {
+    let mut intervals: Vec<Interval> = Vec::new();
+
+    for document in documents {
+        for interval in document.intervals {
+            match intervals.iter_mut().find(|existing| existing.begin == interval.begin) {
+                Some(existing) => existing.edges.extend(interval.edges),
+                None => intervals.push(interval),
+            }
+        }
+    }
+
+    intervals.sort_by(|a, b| a.begin.partial_cmp(&b.begin).unwrap());
+
+    MeandataDocumentRoot { intervals }
+}
+
 pub fn extract_travel_times_from_iteration_directory(iteration_dir: &Path, path_to_graph_weights: &Path, edge_indices_to_id: &Vec<String>) {
     // dump file starts with "dump_" and ends with ".xml"
     let dump_file = get_meandata_file(&iteration_dir);
@@ -64,11 +140,20 @@ pub fn extract_interpolation_points_from_meandata(
     meandata: &MeandataDocumentRoot,
     edge_indices_to_id: &Vec<String>,
     edge_default_travel_times: &Vec<SerializedTravelTime>,
+) -> (Vec<EdgeId>, Vec<SerializedTravelTime>, Vec<SerializedTimestamp>) {
+    extract_interpolation_points_from_meandata_with_attribute(meandata, edge_indices_to_id, edge_default_travel_times, WeightAttribute::default())
+}
+
+pub fn extract_interpolation_points_from_meandata_with_attribute(
+    meandata: &MeandataDocumentRoot,
+    edge_indices_to_id: &Vec<String>,
+    edge_default_travel_times: &Vec<SerializedTravelTime>,
+    weight_attribute: WeightAttribute,
 ) -> (Vec<EdgeId>, Vec<SerializedTravelTime>, Vec<SerializedTimestamp>) {
     get_ipp_vectors(
         &meandata,
         edge_indices_to_id,
-        &preprocess_tt(&meandata, edge_indices_to_id, edge_default_travel_times),
+        &preprocess_tt(&meandata, edge_indices_to_id, edge_default_travel_times, weight_attribute),
     )
 }
 
@@ -76,6 +161,7 @@ fn preprocess_tt<'a>(
     meandata: &MeandataDocumentRoot,
     edge_indices_to_id: &'a Vec<String>,
     edge_default_travel_times: &Vec<SerializedTravelTime>,
+    weight_attribute: WeightAttribute,
 ) -> HashMap<&'a String, HashMap<SerializedTimestamp, SerializedTravelTime>> {
     let mut original_edge_by_edge_id_and_interval: HashMap<&String, HashMap<SerializedTimestamp, &Edge>> = HashMap::with_capacity(edge_indices_to_id.len());
 
@@ -107,7 +193,7 @@ fn preprocess_tt<'a>(
 
                 let mut tt = edge_tts
                     .get(&timestamp)
-                    .and_then(|edge| edge.overlap_traveltime) // travel time in seconds
+                    .and_then(|edge| weight_attribute.value_of(edge)) // travel time in seconds
                     // should not go lower than the default travel time
                     .map(|val| (u32::max((val * 1000.0) as u32, default_travel_time)) as SerializedTravelTime)
                     .unwrap_or(default_travel_time);
@@ -362,6 +448,49 @@ pub mod tests {
         TDGraph::new(first_out, head, first_ipp_of_arc, ipp_departure_time, ipp_travel_time);
     }
 
+    #[test]
+    fn test_weight_attribute_and_merged_weight_files() {
+        let edges: Vec<String> = vec!["edge1".to_string()];
+        let edge_default_travel_times: Vec<u32> = vec![3_000];
+
+        let first = meandata::MeandataDocumentRoot {
+            intervals: vec![meandata::Interval::create(
+                "interval2".to_string(),
+                10.0,
+                20.0,
+                vec![meandata::Edge {
+                    id: "edge1".to_string(),
+                    traveltime: Some(5.0),
+                    overlap_traveltime: Some(7.0),
+                    ..Default::default()
+                }],
+            )],
+        };
+        let second = meandata::MeandataDocumentRoot {
+            intervals: vec![meandata::Interval::create(
+                "interval1".to_string(),
+                0.0,
+                10.0,
+                vec![meandata::Edge {
+                    id: "edge1".to_string(),
+                    traveltime: Some(4.0),
+                    overlap_traveltime: None,
+                    ..Default::default()
+                }],
+            )],
+        };
+
+        let merged = super::merge_meandata(vec![first, second]);
+        assert_eq!(merged.intervals.iter().map(|i| i.begin).collect::<Vec<_>>(), vec![0.0, 10.0]);
+
+        let (_, ipp_travel_time, _) =
+            super::extract_interpolation_points_from_meandata_with_attribute(&merged, &edges, &edge_default_travel_times, super::WeightAttribute::TravelTime);
+        assert_eq!(ipp_travel_time, vec![4_000, 5_000]);
+
+        let (_, ipp_travel_time, _) = super::extract_interpolation_points_from_meandata(&merged, &edges, &edge_default_travel_times);
+        assert_eq!(ipp_travel_time, vec![3_000, 7_000]);
+    }
+
     #[test]
     fn test_fifo_ensured_backwards() {
         let edges: Vec<String> = vec!["edge1".to_string()];
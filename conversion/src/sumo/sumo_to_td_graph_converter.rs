@@ -3,7 +3,10 @@ use std::{collections::HashMap, path::Path};
 #[cfg(feature = "expand-sumo-nodes")]
 use std::collections::HashSet;
 
-use rust_road_router::io::{write_strings_to_file, Store};
+use rust_road_router::{
+    datastr::spatial_index::EdgeGeometry,
+    io::{write_strings_to_file, Deconstruct, Store},
+};
 
 use crate::{
     sumo::{
@@ -15,10 +18,11 @@ use crate::{
         trips_reader::SumoTripsReader,
         FileReader, RoutingKitTDGraph, SumoTimestamp, SumoTravelTime, EDG_XML, NOD_XML,
     },
-    SerializedPosition, SerializedTimestamp, SerializedTravelTime, FILE_EDGE_CAPACITIES, FILE_EDGE_DEFAULT_TRAVEL_TIMES, FILE_EDGE_INDICES_TO_ID,
-    FILE_EDGE_LANES, FILE_EDGE_LENGTHS, FILE_EDGE_SPEEDS, FILE_FIRST_IPP_OF_ARC, FILE_FIRST_OUT, FILE_HEAD, FILE_IPP_DEPARTURE_TIME, FILE_IPP_TRAVEL_TIME,
-    FILE_LATITUDE, FILE_LONGITUDE, FILE_QUERIES_DEPARTURE, FILE_QUERIES_FROM, FILE_QUERIES_TO, FILE_QUERY_IDS, FILE_QUERY_ORIGINAL_FROM_EDGES,
-    FILE_QUERY_ORIGINAL_TO_EDGES, GLOBAL_FREE_FLOW_SPEED_FACTOR, MIN_EDGE_WEIGHT, SUMO_DEFAULT_SPEED,
+    SerializedPosition, SerializedTimestamp, SerializedTravelTime, DEFAULT_VTYPE, FILE_EDGE_CAPACITIES, FILE_EDGE_DEFAULT_TRAVEL_TIMES,
+    FILE_EDGE_INDICES_TO_ID, FILE_EDGE_LANES, FILE_EDGE_LENGTHS, FILE_EDGE_PRIORITIES, FILE_EDGE_SPEEDS, FILE_EDGE_TOLLS, FILE_FIRST_IPP_OF_ARC,
+    FILE_FIRST_OUT, FILE_HEAD, FILE_IPP_DEPARTURE_TIME, FILE_IPP_TRAVEL_TIME, FILE_LATITUDE, FILE_LONGITUDE, FILE_QUERIES_DEPARTURE, FILE_QUERIES_FROM,
+    FILE_QUERIES_TO, FILE_QUERY_IDS, FILE_QUERY_ORIGINAL_FROM_EDGES, FILE_QUERY_ORIGINAL_TO_EDGES, FILE_QUERY_VTYPES, GLOBAL_FREE_FLOW_SPEED_FACTOR,
+    MIN_EDGE_WEIGHT, SUMO_DEFAULT_SPEED,
 };
 
 #[cfg(feature = "expand-sumo-nodes")]
@@ -94,6 +98,7 @@ impl Clone for FlattenedSumoEdge {
 /// - edges_by_id: a file containing the edge ids in the order of the edges in the graph
 /// - latitude: the latitude of each node
 /// - longitude: the longitude of each node
+/// - first_shape_point, shape_latitude, shape_longitude: the shapes of the edges (in the same coordinates as the nodes)
 /// - queries-from: a file containing the from nodes of the queries
 /// - queries-to: a file containing the to nodes of the queries
 /// - queries-departure: a file containing the departure times of the queries
@@ -161,8 +166,40 @@ pub fn convert_sumo_to_routing_kit_and_queries(
     capas.write_to(&output_dir.join(FILE_EDGE_CAPACITIES))?;
 
     write_strings_to_file(&output_dir.join(FILE_EDGE_INDICES_TO_ID), &edge_indices_to_id.iter().collect())?;
+
+    // internal edges of expanded nodes have no shape
+    let edges_by_id: HashMap<&String, &Edge> = edges.edges.iter().map(|edge| (&edge.id, edge)).collect();
+    EdgeGeometry::from_shapes(edge_indices_to_id.iter().map(|id| {
+        edges_by_id
+            .get(id)
+            .map(|edge| edge.get_shape())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(x, y)| (y as SerializedPosition, x as SerializedPosition))
+    }))
+    .deconstruct_to(&output_dir)?;
+
+    let tolls: Vec<f64> = edge_indices_to_id
+        .iter()
+        .map(|id| edges_by_id.get(id).map(|edge| edge.get_toll()).unwrap_or(0.0))
+        .collect();
+    tolls.write_to(&output_dir.join(FILE_EDGE_TOLLS))?;
+
+    let priorities: Vec<i32> = edge_indices_to_id
+        .iter()
+        .map(|id| edges_by_id.get(id).and_then(|edge| edge.priority).unwrap_or(-1))
+        .collect();
+    priorities.write_to(&output_dir.join(FILE_EDGE_PRIORITIES))?;
+
     write_strings_to_file(&output_dir.join(FILE_QUERY_IDS), &trip_ids)?;
 
+    let vtypes: Vec<String> = trips
+        .trips
+        .iter()
+        .map(|trip| trip.vtype.clone().unwrap_or_else(|| DEFAULT_VTYPE.to_string()))
+        .collect();
+    write_strings_to_file(&output_dir.join(FILE_QUERY_VTYPES), &vtypes.iter().collect())?;
+
     original_trip_from_edges.write_to(&output_dir.join(FILE_QUERY_ORIGINAL_FROM_EDGES))?;
     original_trip_to_edges.write_to(&output_dir.join(FILE_QUERY_ORIGINAL_TO_EDGES))?;
 
@@ -731,6 +768,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
                 Edge {
                     id: String::from("e1"),
@@ -742,6 +780,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
             ],
         };
@@ -789,6 +828,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
                 Edge {
                     id: String::from("e1"),
@@ -800,6 +840,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
             ],
         };
@@ -849,6 +890,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
                 Edge {
                     id: String::from("e1"),
@@ -860,6 +902,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
             ],
         };
@@ -913,6 +956,7 @@ mod tests {
                         lanes: vec![],
                         params: vec![],
                         priority: Some(-1),
+                        shape: None,
                     },
                     Edge {
                         id: String::from("e1"),
@@ -924,6 +968,7 @@ mod tests {
                         lanes: vec![],
                         params: vec![],
                         priority: Some(-1),
+                        shape: None,
                     },
                 ],
             };
@@ -968,6 +1013,7 @@ mod tests {
                         lanes: vec![],
                         params: vec![],
                         priority: Some(-1),
+                        shape: None,
                     },
                     Edge {
                         id: String::from("e1"),
@@ -979,6 +1025,7 @@ mod tests {
                         lanes: vec![],
                         params: vec![],
                         priority: Some(-1),
+                        shape: None,
                     },
                 ],
             };
@@ -1033,6 +1080,7 @@ mod tests {
                         lanes: vec![],
                         params: vec![],
                         priority: Some(-1),
+                        shape: None,
                     },
                     Edge {
                         id: String::from("e1"),
@@ -1044,6 +1092,7 @@ mod tests {
                         lanes: vec![],
                         params: vec![],
                         priority: Some(-1),
+                        shape: None,
                     },
                 ],
             };
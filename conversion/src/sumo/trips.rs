@@ -25,6 +25,8 @@ pub struct Trip {
     pub depart_pos: Option<String>,
     #[serde(default, rename = "@departSpeed")]
     pub depart_speed: Option<String>,
+    #[serde(default, rename = "@type")]
+    pub vtype: Option<String>,
 }
 
 /// Struct for reading a MATSim CSV trip file containing the following headers:
@@ -64,6 +66,7 @@ impl MatsimCsvTrip {
             depart_lane: Some("best".to_string()),
             depart_pos: Some("base".to_string()),
             depart_speed: Some("max".to_string()),
+            vtype: None,
         }
     }
 
@@ -1,7 +1,6 @@
 use std::{env, fs::File, io::Write, path::Path};
 
 fn main() {
-    println!("cargo::rustc-check-cfg=cfg(override_traffic_max_query_time)");
     println!("cargo::rustc-check-cfg=cfg(override_tdcch_approx_threshold)");
     println!("cargo::rustc-check-cfg=cfg(override_tdcch_approx)");
     // write build time info
@@ -31,12 +30,4 @@ fn main() {
         println!("cargo:rustc-cfg=override_tdcch_approx");
     }
     println!("cargo:rerun-if-env-changed=TDCCH_APPROX");
-
-    if let Ok(val) = env::var("TRAFFIC_MAX_QUERY_TIME") {
-        let dest_path = Path::new(&out_dir).join("TRAFFIC_MAX_QUERY_TIME");
-        let mut f = File::create(&dest_path).unwrap();
-        f.write_all(val.as_bytes()).unwrap();
-        println!("cargo:rustc-cfg=override_traffic_max_query_time");
-    }
-    println!("cargo:rerun-if-env-changed=TRAFFIC_MAX_QUERY_TIME");
 }
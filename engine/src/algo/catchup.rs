@@ -5,7 +5,8 @@ use crate::report::*;
 use crate::util::*;
 use std::cmp::*;
 
-pub use crate::algo::customizable_contraction_hierarchy::ftd_cch::customize;
+pub use crate::algo::customizable_contraction_hierarchy::ftd_cch::{customize, customize_with_config};
+pub use crate::datastr::graph::floating_time_dependent::CatchupConfig;
 
 pub mod floating_td_stepped_elimination_tree;
 pub mod partial_profiles;
@@ -56,15 +57,37 @@ pub struct Server<'a> {
 
     from: NodeId,
     to: NodeId,
+
+    // Selected query strategies
+    config: CatchupConfig,
 }
 
 impl<'a> Server<'a> {
+    /// Create a new Server instance with the default config (determined by the enabled cargo features).
     pub fn new(cch_graph: &'a CCH, customized_graph: &'a CustomizedGraph<'a>) -> Self {
         let forward = FloatingTDSteppedEliminationTree::new(customized_graph.upward_bounds_graph(), cch_graph.elimination_tree());
         let backward = FloatingTDSteppedEliminationTree::new(customized_graph.downward_bounds_graph(), cch_graph.elimination_tree());
         Self::new_with_elimination_trees(cch_graph, customized_graph, forward, backward)
     }
 
+    /// Create a new Server instance using the query strategies selected in `config`.
+    pub fn new_with_config(cch_graph: &'a CCH, customized_graph: &'a CustomizedGraph<'a>, config: CatchupConfig) -> Self {
+        let mut server = Self::new(cch_graph, customized_graph);
+        server.set_config(config);
+        server
+    }
+
+    /// Switch the query strategies for all subsequent queries.
+    pub fn set_config(&mut self, config: CatchupConfig) {
+        self.forward.set_corridor(config.query_corridor);
+        self.backward.set_corridor(config.query_corridor);
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &CatchupConfig {
+        &self.config
+    }
+
     /// Create a new Server instance using pre-created elimination trees.
     /// This is more efficient when creating multiple servers, as the elimination trees
     /// can be cloned (which is cheaper than creating them from scratch).
@@ -93,6 +116,7 @@ impl<'a> Server<'a> {
             relevant_upward: FastClearBitVec::new(m),
             from: 0,
             to: 0,
+            config: CatchupConfig::default(),
         }
     }
 
@@ -159,9 +183,7 @@ impl<'a> Server<'a> {
                 };
 
                 // skip the node if it cannot possibly be in the shortest path corridor
-                if self.forward.node_data(self.forward.peek_next().unwrap()).lower_bound > tentative_distance.1
-                    || (cfg!(feature = "tdcch-stall-on-demand") && stall())
-                {
+                if self.forward.node_data(self.forward.peek_next().unwrap()).lower_bound > tentative_distance.1 || (self.config.stall_on_demand && stall()) {
                     self.forward.skip_next();
                 } else if let QueryProgress::Progress(node) = self.forward.next_step() {
                     if cfg!(feature = "detailed-stats") {
@@ -295,7 +317,7 @@ impl<'a> Server<'a> {
                     // lazy label filtering
                     .filter(|label| !upper_bound.fuzzy_lt(label.lower_bound))
                 {
-                    if cfg!(feature = "tdcch-query-corridor") {
+                    if self.config.query_corridor {
                         debug_assert!(self.customized_graph.outgoing.bounds()[label.shortcut_id as usize]
                             .0
                             .fuzzy_eq(label.lower_bound - self.forward.node_data(label.parent).lower_bound));
@@ -320,6 +342,8 @@ impl<'a> Server<'a> {
         #[cfg(feature = "tdcch-query-detailed-timing")]
         let forward_select_time = timer.get_passed();
 
+        let query_lazy = self.config.query_lazy;
+        let query_astar = self.config.query_astar;
         let relevant_upward = &mut self.relevant_upward;
         // debug_assert!(
         //     tentative_distance.0.fuzzy_eq(self.lower_bounds_to_target[self.from as usize]),
@@ -365,7 +389,7 @@ impl<'a> Server<'a> {
 
                     let lower_bound_target = lower_bounds_to_target[target as usize];
 
-                    let (time, next_on_path, evaled_edge_id) = if cfg!(feature = "tdcch-query-lazy") {
+                    let (time, next_on_path, evaled_edge_id) = if query_lazy {
                         // partially relax the edge
                         // that means actually relax the first real edge that is on the path that the shortcut represents.
                         // doing so requires to recursively unpack the first edge of each triangle.
@@ -393,7 +417,7 @@ impl<'a> Server<'a> {
                         };
                         (val, self.customized_graph.outgoing.head()[shortcut_id as usize], shortcut_id)
                     };
-                    let lower = if cfg!(feature = "tdcch-query-astar") {
+                    let lower = if query_astar {
                         lower_bounds_to_target[next_on_path as usize]
                     } else {
                         FlWeight::ZERO
@@ -445,7 +469,7 @@ impl<'a> Server<'a> {
                         }
 
                         let lower_bound_target = lower_bounds_to_target[label.parent as usize];
-                        let (time, next_on_path, evaled_edge_id) = if cfg!(feature = "tdcch-query-lazy") {
+                        let (time, next_on_path, evaled_edge_id) = if query_lazy {
                             // if so do the same crazy relaxation as for upward edges
                             self.customized_graph
                                 .incoming
@@ -470,7 +494,7 @@ impl<'a> Server<'a> {
                             };
                             (val, label.parent, label.shortcut_id)
                         };
-                        let lower = if cfg!(feature = "tdcch-query-astar") {
+                        let lower = if query_astar {
                             lower_bounds_to_target[next_on_path as usize]
                         } else {
                             FlWeight::ZERO
@@ -626,7 +650,15 @@ impl<'a> TDQueryServer<Timestamp, FlWeight> for Server<'a> {
     where
         Self: 's;
 
+    /// Departures and arrivals outside of the horizon of the graph yield no result.
     fn td_query(&mut self, query: TDQuery<Timestamp>) -> QueryResult<Self::P<'_>, FlWeight> {
-        QueryResult::new(self.distance(query.from, query.to, query.departure), PathServerWrapper(self))
+        let horizon = self.customized_graph.original_graph.horizon();
+        let distance = if horizon.contains(query.departure) {
+            self.distance(query.from, query.to, query.departure)
+                .filter(|&distance| horizon.contains(query.departure + distance))
+        } else {
+            None
+        };
+        QueryResult::new(distance, PathServerWrapper(self))
     }
 }
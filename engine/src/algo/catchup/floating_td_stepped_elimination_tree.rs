@@ -35,6 +35,7 @@ pub struct FloatingTDSteppedEliminationTree<'a, 'b> {
     elimination_tree: &'b [InRangeOption<NodeId>],
     next: Option<NodeId>,
     origin: Option<NodeId>,
+    corridor: bool,
 }
 
 impl<'a, 'b> Clone for FloatingTDSteppedEliminationTree<'a, 'b> {
@@ -45,6 +46,7 @@ impl<'a, 'b> Clone for FloatingTDSteppedEliminationTree<'a, 'b> {
             elimination_tree: self.elimination_tree,
             next: self.next,
             origin: self.origin,
+            corridor: self.corridor,
         }
     }
 }
@@ -66,9 +68,16 @@ impl<'a, 'b> FloatingTDSteppedEliminationTree<'a, 'b> {
             elimination_tree,
             next: None,
             origin: None,
+            corridor: CatchupConfig::default().query_corridor,
         }
     }
 
+    /// Track lower and upper bounds to obtain a shortest path corridor.
+    /// When disabled, the query just collects the entire search space.
+    pub fn set_corridor(&mut self, corridor: bool) {
+        self.corridor = corridor;
+    }
+
     pub fn initialize_query(&mut self, from: NodeId) {
         // clean up previous data.
         if let Some(from) = self.origin {
@@ -104,7 +113,7 @@ impl<'a, 'b> FloatingTDSteppedEliminationTree<'a, 'b> {
             {
                 let next;
                 let next_upper_bound;
-                if cfg!(feature = "tdcch-query-corridor") {
+                if self.corridor {
                     next = Label {
                         parent: node,
                         lower_bound: shortcut_lower_bound + current_state_lower_bound,
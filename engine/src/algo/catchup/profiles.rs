@@ -13,8 +13,6 @@ use crate::datastr::rank_select_map::{BitVec, FastClearBitVec};
 #[cfg(feature = "tdcch-query-detailed-timing")]
 use crate::report::benchmark::Timer;
 
-use std::convert::TryInto;
-
 /// Query server struct for CATCHUp.
 /// Implements the common query trait.
 pub struct Server<'a> {
@@ -100,6 +98,8 @@ impl<'a> Server<'a> {
         assert_ne!(from_node, to_node);
         report!("algo", "Floating TDCCH Profile Query");
 
+        let period = self.customized_graph.original_graph.horizon().period();
+
         let mut timer = Timer::new();
 
         self.from = self.cch_graph.node_order().rank(from_node);
@@ -269,7 +269,7 @@ impl<'a> Server<'a> {
                     if cfg!(feature = "tdcch-profiles-iterative-reconstruction") {
                         self.incoming_reconstruction_states[label.shortcut_id as usize]
                             .requested_times
-                            .push((Timestamp::ZERO, period()));
+                            .push((Timestamp::ZERO, period));
                         self.reconstruction_queue.push(Reverse(ReconstructionQueueElement {
                             t: Timestamp::ZERO,
                             upper_node: node,
@@ -277,7 +277,7 @@ impl<'a> Server<'a> {
                             shortcut_id: ShortcutId::Incoming(label.shortcut_id),
                         }));
                     } else {
-                        reconstruction_graph.cache_recursive(ShortcutId::Incoming(label.shortcut_id), Timestamp::ZERO, period(), &mut self.buffers);
+                        reconstruction_graph.cache_recursive(ShortcutId::Incoming(label.shortcut_id), Timestamp::ZERO, period, &mut self.buffers);
                     }
 
                     if label.parent == to {
@@ -317,7 +317,7 @@ impl<'a> Server<'a> {
                     if cfg!(feature = "tdcch-profiles-iterative-reconstruction") {
                         self.outgoing_reconstruction_states[label.shortcut_id as usize]
                             .requested_times
-                            .push((Timestamp::ZERO, period()));
+                            .push((Timestamp::ZERO, period));
                         self.reconstruction_queue.push(Reverse(ReconstructionQueueElement {
                             t: Timestamp::ZERO,
                             upper_node: node,
@@ -325,7 +325,7 @@ impl<'a> Server<'a> {
                             shortcut_id: ShortcutId::Outgoing(label.shortcut_id),
                         }));
                     } else {
-                        reconstruction_graph.cache_recursive(ShortcutId::Outgoing(label.shortcut_id), Timestamp::ZERO, period(), &mut self.buffers);
+                        reconstruction_graph.cache_recursive(ShortcutId::Outgoing(label.shortcut_id), Timestamp::ZERO, period, &mut self.buffers);
                     }
 
                     if label.parent == from {
@@ -398,7 +398,7 @@ impl<'a> Server<'a> {
             shortcut.set_cache(
                 reconstruction_graph
                     .take_cache(ShortcutId::Outgoing(edge_id))
-                    .map(|ttf| ttf.try_into().unwrap()),
+                    .map(|ttf| ttf.into_periodic(period).unwrap()),
             );
             shortcut.upper_bound = min(
                 shortcut.upper_bound,
@@ -416,7 +416,7 @@ impl<'a> Server<'a> {
             shortcut.set_cache(
                 reconstruction_graph
                     .take_cache(ShortcutId::Incoming(edge_id))
-                    .map(|ttf| ttf.try_into().unwrap()),
+                    .map(|ttf| ttf.into_periodic(period).unwrap()),
             );
             shortcut.upper_bound = min(
                 shortcut.upper_bound,
@@ -575,7 +575,7 @@ impl<'a> Server<'a> {
 
         let mut target = self.buffers.unpacking_target.push_plf();
         if st_shortcut.is_valid_path() {
-            st_shortcut.reconstruct_exact_ttf(Timestamp::ZERO, period(), &profile_graph, &mut target, &mut self.buffers.unpacking_tmp);
+            st_shortcut.reconstruct_exact_ttf(Timestamp::ZERO, period, &profile_graph, &mut target, &mut self.buffers.unpacking_tmp);
         }
         report!("profile_complexity", target.len());
 
@@ -583,7 +583,7 @@ impl<'a> Server<'a> {
         timer.restart();
 
         let paths = if st_shortcut.is_valid_path() {
-            let switchpoints = st_shortcut.get_switchpoints(Timestamp::ZERO, period(), &profile_graph).0;
+            let switchpoints = st_shortcut.get_switchpoints(Timestamp::ZERO, period, &profile_graph).0;
             switchpoints.into_iter().map(|(valid_from, path, _)| (valid_from, path)).collect()
         } else {
             Vec::new()
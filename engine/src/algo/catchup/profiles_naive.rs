@@ -93,6 +93,7 @@ impl<'a> Server<'a> {
         self.to = self.cch_graph.node_order().rank(to_node);
 
         let n = self.customized_graph.original_graph.num_nodes();
+        let period = self.customized_graph.original_graph.horizon().period();
 
         // initialize
         let mut tentative_distance = (FlWeight::INFINITY, FlWeight::INFINITY);
@@ -470,8 +471,8 @@ impl<'a> Server<'a> {
                     continue;
                 }
 
-                let linked = PeriodicPiecewiseLinearFunction::new(self.distances[node as usize].as_ref().unwrap()).link(&edge_ttf);
-                let linked_ttf = PeriodicPiecewiseLinearFunction::new(&linked[..]);
+                let linked = PeriodicPiecewiseLinearFunction::new(self.distances[node as usize].as_ref().unwrap(), period).link(&edge_ttf);
+                let linked_ttf = PeriodicPiecewiseLinearFunction::new(&linked[..], period);
                 let linked_lower_bound = linked_ttf.lower_bound();
                 let linked_upper_bound = linked_ttf.upper_bound();
 
@@ -490,7 +491,7 @@ impl<'a> Server<'a> {
                 }
 
                 if let Some(current_ttf) = self.distances[head as usize].as_ref() {
-                    let (merged_raw, intersections) = PeriodicPiecewiseLinearFunction::new(current_ttf).merge(&linked_ttf, &mut Vec::new());
+                    let (merged_raw, intersections) = PeriodicPiecewiseLinearFunction::new(current_ttf, period).merge(&linked_ttf, &mut Vec::new());
 
                     match &intersections[..] {
                         &[(_, true)] => {
@@ -506,7 +507,7 @@ impl<'a> Server<'a> {
                             );
                         }
                         _ => {
-                            let merged = PeriodicPiecewiseLinearFunction::new(&merged_raw);
+                            let merged = PeriodicPiecewiseLinearFunction::new(&merged_raw, period);
                             let merged_lower_bound = merged.lower_bound();
                             let merged_upper_bound = merged.upper_bound();
                             update(
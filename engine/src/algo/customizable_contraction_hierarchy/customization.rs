@@ -249,6 +249,57 @@ fn customize_basic(cch: &CCH, mut upward_weights: Vec<Weight>, mut downward_weig
     CustomizedBasic::new(cch, upward_weights, downward_weights, upward_unpack, downward_unpack)
 }
 
+/// Redo the basic customization for the upward arcs of `nodes` only, with `metric` giving the weight of each original arc.
+/// `nodes` has to be sorted and closed under elimination tree ancestors,
+/// i.e. it has to contain the lower endpoints of all CCH arcs with changed input weights and all their ancestors.
+/// The arcs of all other nodes keep their weights, so this is a lot cheaper than a full customization for few changed arcs.
+pub fn recustomize_nodes(customized: &mut CustomizedBasic<CCH>, nodes: &[NodeId], metric: impl Fn(EdgeId) -> Weight) {
+    let cch = customized.cch;
+    debug_assert!(nodes.windows(2).all(|w| w[0] < w[1]));
+
+    for &current_node in nodes {
+        let edges = cch.neighbor_edge_indices_usize(current_node);
+        for edge in edges.clone() {
+            customized.upward[edge] = cch.forward_cch_edge_to_orig_arc()[edge]
+                .iter()
+                .map(|&EdgeIdT(arc)| metric(arc))
+                .min()
+                .unwrap_or(INFINITY);
+            customized.downward[edge] = cch.backward_cch_edge_to_orig_arc()[edge]
+                .iter()
+                .map(|&EdgeIdT(arc)| metric(arc))
+                .min()
+                .unwrap_or(INFINITY);
+            customized.up_unpacking[edge] = (InRangeOption::NONE, InRangeOption::NONE);
+            customized.down_unpacking[edge] = (InRangeOption::NONE, InRangeOption::NONE);
+        }
+        let heads = &cch.head()[edges.clone()];
+
+        // same lower triangle relaxation as in `customize_basic`, just looking up the target edge instead of using a workspace
+        for (NodeIdT(low_node), Reversed(EdgeIdT(first_edge_id))) in cch.inverted.link_iter(current_node) {
+            let first_down_weight = customized.downward[first_edge_id as usize];
+            let first_up_weight = customized.upward[first_edge_id as usize];
+            for (node, second_edge_id) in cch.neighbor_iter(low_node).zip(cch.neighbor_edge_indices_usize(low_node)).rev() {
+                if node <= current_node {
+                    break;
+                }
+                let edge = edges.start + heads.binary_search(&node).expect("chordal supergraph missing a triangle arc");
+
+                let triang_weight = customized.upward[second_edge_id] + first_down_weight;
+                if triang_weight < customized.upward[edge] {
+                    customized.upward[edge] = triang_weight;
+                    customized.up_unpacking[edge] = (InRangeOption::some(first_edge_id), InRangeOption::some(second_edge_id as EdgeId));
+                }
+                let triang_weight = customized.downward[second_edge_id] + first_up_weight;
+                if triang_weight < customized.downward[edge] {
+                    customized.downward[edge] = triang_weight;
+                    customized.down_unpacking[edge] = (InRangeOption::some(second_edge_id as EdgeId), InRangeOption::some(first_edge_id));
+                }
+            }
+        }
+    }
+}
+
 pub fn customize_perfect(mut customized: CustomizedBasic<CCH>) -> CustomizedPerfect<CCH> {
     let (upward_modified, downward_modified) = customize_perfect_without_rebuild(&mut customized);
     rebuild_customized_perfect(customized, &upward_modified, &downward_modified)
@@ -457,12 +508,13 @@ pub fn rebuild_customized_perfect<'c, C: CCHT + Sync>(
 
             let mut edges_of_each_thread = vec![(0, 0); k + 1];
             let mut local_edge_counts = &mut edges_of_each_thread[1..];
-            let target_edges_per_thread = (m_fw + k - 1) / k;
-            let first_node_of_chunk: Vec<_> = cch
-                .forward_tail()
-                .chunks(target_edges_per_thread)
-                .map(|chunk| chunk[0] as usize)
-                .chain(std::iter::once(n))
+            let target_edges_per_thread = std::cmp::max((m_fw + k - 1) / k, 1);
+            // Small graphs have fewer chunks than threads, so we pad with empty chunks at the end.
+            // The first chunk always starts at node 0, even if that node has no forward edges.
+            let first_node_of_chunk: Vec<_> = std::iter::once(0)
+                .chain(cch.forward_tail().chunks(target_edges_per_thread).skip(1).map(|chunk| chunk[0] as usize))
+                .chain(std::iter::repeat(n))
+                .take(k + 1)
                 .collect();
 
             // let nodes_per_thread = (n + k - 1) / k;
@@ -16,14 +16,24 @@ scoped_thread_local!(static DOWNWARD_WORKSPACE: RefCell<Vec<(FlWeight, FlWeight)
 // because we map to the edge id instead of the values.
 scoped_thread_local!(static PERFECT_WORKSPACE: RefCell<Vec<InRangeOption<EdgeId>>>);
 
-/// Run CATCHUp customization
+/// Run CATCHUp customization with the default config (determined by the enabled cargo features)
 pub fn customize<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> CustomizedGraph<'a> {
-    let (upward, downward) = customize_internal(cch, metric);
+    customize_with_config(cch, metric, &CatchupConfig::default())
+}
+
+/// Run CATCHUp customization with the strategies selected in `config`
+pub fn customize_with_config<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph, config: &CatchupConfig) -> CustomizedGraph<'a> {
+    let (upward, downward) = customize_internal_with_config(cch, metric, config);
     CustomizedGraph::new(metric, &cch.first_out, &cch.head, upward, downward)
 }
 
 pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec<Shortcut>, Vec<Shortcut>) {
+    customize_internal_with_config(cch, metric, &CatchupConfig::default())
+}
+
+pub fn customize_internal_with_config<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph, config: &CatchupConfig) -> (Vec<Shortcut>, Vec<Shortcut>) {
     report!("algo", "Floating TDCCH Customization");
+    report!("catchup_config", config.to_string());
 
     let n = (cch.first_out.len() - 1) as NodeId;
     let m = cch.head.len();
@@ -212,7 +222,7 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
         }
     };
 
-    if cfg!(feature = "tdcch-precustomization") {
+    if config.precustomization {
         // execute CATCHUp precustomization
         let _subctxt = push_context("precustomization");
         report_time("TD-CCH Pre-Customization", || {
@@ -232,6 +242,11 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
             upward.par_iter_mut().zip(upward_preliminary_bounds.par_iter()).for_each(disable_dominated);
             downward.par_iter_mut().zip(downward_preliminary_bounds.par_iter()).for_each(disable_dominated);
         });
+    } else {
+        // without precustomization, the lower bounds of the original edges are not valid for the shortcuts.
+        // Zero is, and it will be replaced by the bound of the final function.
+        upward.par_iter_mut().for_each(|s| s.lower_bound = FlWeight::ZERO);
+        downward.par_iter_mut().for_each(|s| s.lower_bound = FlWeight::ZERO);
     }
 
     // block for main CATCHUp customization
@@ -249,10 +264,10 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
             cch,
             // routines created in this function
             // we customize many cells in parallel - so iterate over triangles sequentially
-            create_customization_fn(cch, metric, SeqIter(cch)),
+            create_customization_fn(cch, metric, config.triangle_sorting, SeqIter(cch)),
             // the final separator can only be customized, once everything else is done, but it still takes up a significant amount of time
             // But we can still parallelize the processing of edges from one node within this separator.
-            create_customization_fn(cch, metric, ParIter(cch)),
+            create_customization_fn(cch, metric, config.triangle_sorting, ParIter(cch)),
         );
 
         report_time("TD-CCH Customization", || {
@@ -335,7 +350,7 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
             // execute main customization
 
             customization.customize(&mut upward, &mut downward, |cb| {
-                MERGE_BUFFERS.set(&RefCell::new(MergeBuffers::new()), || {
+                MERGE_BUFFERS.set(&RefCell::new(MergeBuffers::with_config(config)), || {
                     cb();
                 });
             });
@@ -385,9 +400,9 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
         report!("num_performed_unnecessary_links", UNNECESSARY_LINKED.load(Ordering::Relaxed));
     }
     report!("approx", f64::from(APPROX));
-    report!("approx_threshold", APPROX_THRESHOLD);
+    report!("approx_threshold", config.approx_threshold);
 
-    if cfg!(feature = "tdcch-postcustomization") {
+    if config.postcustomization {
         // do perfect bound based customization again, because we now have better bounds and can get rid of some additional shortcuts
         let _subctxt = push_context("postcustomization");
         report_time("TD-CCH Post-Customization", || {
@@ -434,11 +449,11 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
                 let downward_active = &mut downward_above[0..cch.neighbor_edge_indices(current_node as NodeId).len()];
 
                 for shortcut in upward_active {
-                    shortcut.reenable_required(downward_below, upward_below);
+                    shortcut.reenable_required(downward_below, upward_below, metric.horizon().period());
                 }
 
                 for shortcut in downward_active {
-                    shortcut.reenable_required(downward_below, upward_below);
+                    shortcut.reenable_required(downward_below, upward_below, metric.horizon().period());
                 }
             }
 
@@ -469,7 +484,12 @@ pub fn customize_internal<'a, 'b: 'a>(cch: &'a CCH, metric: &'b TDGraph) -> (Vec
 
 // Encapsulates the creation of the CATCHUp main customization lambdas
 // The function signature gives us some additional control of lifetimes and stuff
-fn create_customization_fn<'s, F: 's>(cch: &'s CCH, metric: &'s TDGraph, merge_iter: F) -> impl Fn(Range<usize>, usize, &mut [Shortcut], &mut [Shortcut]) + 's
+fn create_customization_fn<'s, F: 's>(
+    cch: &'s CCH,
+    metric: &'s TDGraph,
+    triangle_sorting: bool,
+    merge_iter: F,
+) -> impl Fn(Range<usize>, usize, &mut [Shortcut], &mut [Shortcut]) + 's
 where
     for<'p> F: ForEachIter<'p, 's, Shortcut>,
 {
@@ -526,7 +546,7 @@ where
                                 }
                             };
                         }
-                        if cfg!(feature = "tdcch-triangle-sorting") {
+                        if triangle_sorting {
                             triangles.sort_by_key(|&(down, up)| shortcut_graph.get_incoming(down).lower_bound + shortcut_graph.get_outgoing(up).lower_bound);
                         }
                         for &edges in &triangles {
@@ -542,7 +562,7 @@ where
                             ARCS_PROCESSED.fetch_add(1, Ordering::Relaxed);
                         }
 
-                        if cfg!(feature = "tdcch-triangle-sorting") {
+                        if triangle_sorting {
                             triangles.sort_by_key(|&(up, down)| shortcut_graph.get_incoming(down).lower_bound + shortcut_graph.get_outgoing(up).lower_bound);
                         }
                         for &(up, down) in &triangles {
@@ -29,6 +29,8 @@ pub struct PotData {
     pub fw_sources: Vec<(Timestamp, ShortcutSourceData)>,
     pub bw_sources: Vec<(Timestamp, ShortcutSourceData)>,
     pub bucket_to_metric: Vec<usize>,
+    /// Period of the graph the sources were customized for.
+    pub period: Timestamp,
 }
 
 impl<'a> crate::io::Deconstruct for PotData {
@@ -44,6 +46,7 @@ impl<'a> crate::io::Deconstruct for PotData {
         store("fw_sources", &self.fw_sources)?;
         store("bw_sources", &self.bw_sources)?;
         store("bucket_to_metric", &self.bucket_to_metric)?;
+        store("period", &vec![f64::from(self.period)])?;
         Ok(())
     }
 }
@@ -54,6 +57,18 @@ impl<'a> crate::io::Reconstruct for PotData {
         let fw_bucket_bounds: Vec<Weight> = loader.load("fw_bucket_bounds")?;
         let m = fw_static_bound.len();
         let num_buckets = fw_bucket_bounds.len() / m;
+        let period = if loader.path().join("period").exists() {
+            let period: Vec<f64> = loader.load("period")?;
+            let &[period] = &period[..] else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("period file must contain exactly one value, got {}", period.len()),
+                ));
+            };
+            Timestamp::new(period)
+        } else {
+            Timestamp::new(DEFAULT_PERIOD)
+        };
         Ok(Self {
             fw_static_bound,
             fw_bucket_bounds,
@@ -69,12 +84,19 @@ impl<'a> crate::io::Reconstruct for PotData {
                 dbg!(err);
                 (0..num_buckets).collect()
             }),
+            period,
         })
     }
 }
 
+/// Run CATCHUp customization for potentials with the default config (determined by the enabled cargo features)
 pub fn customize<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGraph) -> PotData {
-    let mut catchup = customize_internal::<K>(cch, metric);
+    customize_with_config::<K>(cch, metric, &CatchupConfig::default())
+}
+
+/// Run CATCHUp customization for potentials with the strategies selected in `config`
+pub fn customize_with_config<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGraph, config: &CatchupConfig) -> PotData {
+    let mut catchup = customize_internal::<K>(cch, metric, config);
 
     let upper_bound = (0..metric.num_arcs() as EdgeId)
         .map(|edge_id| extract_upper_bound(metric.travel_time_function(edge_id).upper_bound()))
@@ -91,8 +113,9 @@ pub fn customize<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGraph)
     catchup
 }
 
-fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGraph) -> PotData {
+fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGraph, config: &CatchupConfig) -> PotData {
     report!("algo", "Floating TDCCH Customization");
+    report!("catchup_config", config.to_string());
 
     let n = (cch.first_out.len() - 1) as NodeId;
     let m = cch.head.len();
@@ -263,7 +286,7 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
         }
     };
 
-    if cfg!(feature = "tdcch-precustomization") {
+    if config.precustomization {
         // execute CATCHUp precustomization
         let _subctxt = push_context("precustomization");
         report_time("TD-CCH Pre-Customization", || {
@@ -280,6 +303,10 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
             upward.par_iter_mut().for_each(disable_dominated);
             downward.par_iter_mut().for_each(disable_dominated);
         });
+    } else {
+        // lower bounds of the original edges are not valid for the shortcuts, see `ftd::customize`
+        upward.par_iter_mut().for_each(|s| s.lower_bound = FlWeight::ZERO);
+        downward.par_iter_mut().for_each(|s| s.lower_bound = FlWeight::ZERO);
     }
 
     // block for main CATCHUp customization
@@ -297,10 +324,10 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
             cch,
             // routines created in this function
             // we customize many cells in parallel - so iterate over triangles sequentially
-            create_customization_fn(cch, metric, SeqIter(cch)),
+            create_customization_fn(cch, metric, config.triangle_sorting, SeqIter(cch)),
             // the final separator can only be customized, once everything else is done, but it still takes up a significant amount of time
             // But we can still parallelize the processing of edges from one node within this separator.
-            create_customization_fn(cch, metric, ParIter(cch)),
+            create_customization_fn(cch, metric, config.triangle_sorting, ParIter(cch)),
         );
 
         report_time("TD-CCH Customization", || {
@@ -382,7 +409,7 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
 
             // execute main customization
             customization.customize_with_aux(&mut upward, &mut downward, &mut fw_bucket_weights, &mut bw_bucket_weights, |cb| {
-                MERGE_BUFFERS.set(&RefCell::new(MergeBuffers::new()), || {
+                MERGE_BUFFERS.set(&RefCell::new(MergeBuffers::with_config(config)), || {
                     cb();
                 });
             });
@@ -432,7 +459,7 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
         report!("num_performed_unnecessary_links", UNNECESSARY_LINKED.load(Ordering::Relaxed));
     }
     report!("approx", f64::from(APPROX));
-    report!("approx_threshold", APPROX_THRESHOLD);
+    report!("approx_threshold", config.approx_threshold);
 
     // if cfg!(feature = "tdcch-postcustomization") {
     //     // do perfect bound based customization again, because we now have better bounds and can get rid of some additional shortcuts
@@ -481,9 +508,9 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
     let fw_required: Vec<_> = upward.iter().map(|s| s.required).collect();
     let bw_required: Vec<_> = downward.iter().map(|s| s.required).collect();
 
-    let fw_sources = upward.iter().flat_map(|s| s.sources_iter()).collect();
+    let fw_sources = upward.iter().flat_map(|s| s.sources_iter(metric.horizon().period())).collect();
     let fw_first_source = degrees_to_first_out(upward.iter().map(|shortcut| shortcut.num_sources() as u32)).collect();
-    let bw_sources = downward.iter().flat_map(|s| s.sources_iter()).collect();
+    let bw_sources = downward.iter().flat_map(|s| s.sources_iter(metric.horizon().period())).collect();
     let bw_first_source = degrees_to_first_out(downward.iter().map(|shortcut| shortcut.num_sources() as u32)).collect();
 
     let fw_static_bound: Vec<_> = upward
@@ -521,6 +548,7 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
         bw_sources,
         bw_first_source,
         bucket_to_metric: (0..K).collect(),
+        period: metric.horizon().period(),
     }
 }
 
@@ -529,6 +557,7 @@ fn customize_internal<'a, 'b: 'a, const K: usize>(cch: &'a CCH, metric: &'b TDGr
 fn create_customization_fn<'s, F: 's, const K: usize>(
     cch: &'s CCH,
     metric: &'s TDGraph,
+    triangle_sorting: bool,
     merge_iter: F,
 ) -> impl Fn(Range<usize>, usize, &mut [Shortcut], &mut [Shortcut], &mut [[Weight; K]], &mut [[Weight; K]]) + 's
 where
@@ -587,7 +616,7 @@ where
                                 }
                             };
                         }
-                        if cfg!(feature = "tdcch-triangle-sorting") {
+                        if triangle_sorting {
                             triangles.sort_by_key(|&(down, up)| shortcut_graph.get_incoming(down).lower_bound + shortcut_graph.get_outgoing(up).lower_bound);
                         }
                         for &edges in &triangles {
@@ -602,7 +631,7 @@ where
                             ARCS_PROCESSED.fetch_add(1, Ordering::Relaxed);
                         }
 
-                        if cfg!(feature = "tdcch-triangle-sorting") {
+                        if triangle_sorting {
                             triangles.sort_by_key(|&(up, down)| shortcut_graph.get_incoming(down).lower_bound + shortcut_graph.get_outgoing(up).lower_bound);
                         }
                         for &(up, down) in &triangles {
@@ -629,8 +658,8 @@ where
                     .zip(bw_buckets[edge_id as usize - edge_offset].iter_mut())
                     .enumerate()
                 {
-                    let start = Timestamp::new(i as f64 * f64::from(period()) / K as f64);
-                    let end = Timestamp::new((i + 1) as f64 * f64::from(period()) / K as f64);
+                    let start = Timestamp::new(i as f64 * f64::from(metric.horizon().period()) / K as f64);
+                    let end = Timestamp::new((i + 1) as f64 * f64::from(metric.horizon().period()) / K as f64);
                     *fw_bucket = extract_lower_bound(
                         sc_up
                             .partial_ttf(&shortcut_graph, start, end)
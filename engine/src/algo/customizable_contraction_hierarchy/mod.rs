@@ -13,7 +13,7 @@ mod contraction;
 use contraction::*;
 pub mod customization;
 pub use customization::ftd as ftd_cch;
-pub use customization::{customize, customize_directed, customize_directed_perfect, customize_perfect};
+pub use customization::{customize, customize_directed, customize_directed_perfect, customize_perfect, recustomize_nodes};
 pub mod separator_decomposition;
 use separator_decomposition::*;
 mod reorder;
@@ -431,6 +431,37 @@ pub trait Customized {
     fn backward_unpacking(&self) -> &[(InRangeOption<EdgeId>, InRangeOption<EdgeId>)];
 }
 
+impl<C: Customized> Customized for &C {
+    type CCH = C::CCH;
+    fn forward_graph(&self) -> BorrowedGraph<'_> {
+        (**self).forward_graph()
+    }
+    fn backward_graph(&self) -> BorrowedGraph<'_> {
+        (**self).backward_graph()
+    }
+    fn cch(&self) -> &Self::CCH {
+        (**self).cch()
+    }
+    fn unpack_outgoing(&self, edge: EdgeIdT) -> Option<(EdgeIdT, EdgeIdT, NodeIdT)> {
+        (**self).unpack_outgoing(edge)
+    }
+    fn unpack_incoming(&self, edge: EdgeIdT) -> Option<(EdgeIdT, EdgeIdT, NodeIdT)> {
+        (**self).unpack_incoming(edge)
+    }
+    fn forward_tail(&self) -> &[NodeId] {
+        (**self).forward_tail()
+    }
+    fn backward_tail(&self) -> &[NodeId] {
+        (**self).backward_tail()
+    }
+    fn forward_unpacking(&self) -> &[(InRangeOption<EdgeId>, InRangeOption<EdgeId>)] {
+        (**self).forward_unpacking()
+    }
+    fn backward_unpacking(&self) -> &[(InRangeOption<EdgeId>, InRangeOption<EdgeId>)] {
+        (**self).backward_unpacking()
+    }
+}
+
 /// A struct containing the results of the second preprocessing phase.
 pub struct CustomizedBasic<'a, CCH> {
     pub cch: &'a CCH,
@@ -506,6 +537,21 @@ pub struct CustomizedPerfect<'a, CCH> {
     backward_tail: Vec<NodeId>,
 }
 
+// Manual impl because deriving would require `CCH: Clone`, but we only hold a reference.
+impl<CCH> Clone for CustomizedPerfect<'_, CCH> {
+    fn clone(&self) -> Self {
+        Self {
+            cch: self.cch,
+            upward: self.upward.clone(),
+            downward: self.downward.clone(),
+            up_unpacking: self.up_unpacking.clone(),
+            down_unpacking: self.down_unpacking.clone(),
+            forward_tail: self.forward_tail.clone(),
+            backward_tail: self.backward_tail.clone(),
+        }
+    }
+}
+
 impl<'a, C: CCHT> CustomizedPerfect<'a, C> {
     fn new(
         cch: &'a C,
@@ -41,6 +41,10 @@ impl<C: Customized> Server<C> {
         &self.customized
     }
 
+    pub fn customized_mut(&mut self) -> &mut C {
+        &mut self.customized
+    }
+
     fn distance(&mut self, from: NodeId, to: NodeId) -> Option<Weight> {
         self.walked_nodes = 0;
         self.relaxed_edges = 0;
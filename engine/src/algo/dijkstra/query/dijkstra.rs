@@ -10,12 +10,13 @@ where
 {
     graph: GraphBorrow,
     dijkstra: DijkstraData<Ops::Label, Ops::PredecessorLink>,
+    ops: Ops,
     potential: P,
 }
 
 impl<Graph, Ops, GraphBorrow> Server<Graph, Ops, ZeroPotential, GraphBorrow>
 where
-    Ops: DijkstraOps<Graph, Label = Weight>,
+    Ops: DijkstraOps<Graph, Label = Weight> + Default,
     Graph: LinkIterable<Ops::Arc>,
     GraphBorrow: Borrow<Graph>,
 {
@@ -23,11 +24,21 @@ where
         Self {
             dijkstra: DijkstraData::new(graph.borrow().num_nodes()),
             graph,
+            ops: Ops::default(),
             potential: ZeroPotential(),
         }
     }
 }
 
+impl<Graph, Ops, P, GraphBorrow> Server<Graph, Ops, P, GraphBorrow>
+where
+    Ops: DijkstraOps<Graph>,
+{
+    pub fn ops(&mut self) -> &mut Ops {
+        &mut self.ops
+    }
+}
+
 impl<Graph, Ops, P, GraphBorrow> Server<Graph, Ops, P, GraphBorrow>
 where
     Ops: DijkstraOps<Graph, Label = Weight> + Default,
@@ -39,6 +50,7 @@ where
         Self {
             dijkstra: DijkstraData::new(graph.borrow().num_nodes()),
             graph,
+            ops: Ops::default(),
             potential,
         }
     }
@@ -46,8 +58,7 @@ where
     fn distance(&mut self, query: impl GenQuery<Weight>) -> Option<Weight> {
         report!("algo", "Dijkstra Query");
         let to = query.to();
-        let mut ops = Ops::default();
-        let mut dijkstra = DijkstraRun::query(self.graph.borrow(), &mut self.dijkstra, &mut ops, DijkstraInit::from_query(&query));
+        let mut dijkstra = DijkstraRun::query(self.graph.borrow(), &mut self.dijkstra, &mut self.ops, DijkstraInit::from_query(&query));
         self.potential.init(to);
 
         let potential = &mut self.potential;
@@ -81,8 +92,7 @@ where
     where
         F: (FnMut(NodeId, Weight, usize)),
     {
-        let mut ops = Ops::default();
-        let mut dijkstra = DijkstraRun::query(self.graph.borrow(), &mut self.dijkstra, &mut ops, DijkstraInit::from(from));
+        let mut dijkstra = DijkstraRun::query(self.graph.borrow(), &mut self.dijkstra, &mut self.ops, DijkstraInit::from(from));
 
         let mut i: usize = 0;
         while let Some(node) = dijkstra.next() {
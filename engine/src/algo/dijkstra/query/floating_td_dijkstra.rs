@@ -40,14 +40,21 @@ impl<'a> Server<'a> {
         }
     }
 
+    /// Departures and arrivals outside of the horizon of the graph yield no result.
     fn distance(&mut self, query: TDQuery<Timestamp>) -> Option<FlWeight> {
         report!("algo", "Floating TD-Dijkstra");
+        let horizon = self.graph.horizon();
+        if !horizon.contains(query.departure) {
+            return None;
+        }
+
         let mut ops = FlTDDijkstraOps();
         let mut dijkstra = DijkstraRun::query(self.graph, &mut self.data, &mut ops, DijkstraInit::from_query(&query));
 
         while let Some(node) = dijkstra.next() {
             if node == query.to {
-                return Some(*dijkstra.tentative_distance(node) - query.departure);
+                let arrival = *dijkstra.tentative_distance(node);
+                return Some(arrival - query.departure).filter(|_| horizon.contains(arrival));
             }
         }
 
@@ -111,7 +118,7 @@ impl TDQueryServer<Timestamp, FlWeight> for Server<'_> {
 }
 
 #[derive(Default)]
-struct FlTDDijkstraOps();
+pub struct FlTDDijkstraOps();
 
 impl DijkstraOps<TDGraph> for FlTDDijkstraOps {
     type Label = Timestamp;
@@ -0,0 +1,422 @@
+//! Per query closures: avoiding single edges, road categories or restricted areas for individual queries only.
+//!
+//! Excluded edges are collected into an `ExcludedLinks` set and handed to a query server implementing `ExcludeLinks`.
+//! Searches based on Dijkstra or A* realize the exclusion through `ExclusionOps`, which drops excluded links while relaxing.
+//! Since exclusions only make paths longer, lower bound potentials (CH, CCH, multi metric) computed without them stay valid.
+//! CCH queries cannot ignore edges, so `CCHExclusionServer` instead recustomizes the part of the CCH affected by the exclusion.
+//! Links are identified by tail and head only, because the searches only see the head of each relaxed link.
+//! Parallel edges between the same two nodes can therefore only be excluded together.
+
+use super::{
+    a_star::Potential,
+    customizable_contraction_hierarchy::{
+        query::{PathServerWrapper as CCHPathServer, Server as CCHServer},
+        recustomize_nodes, CustomizedBasic, CCH, CCHT,
+    },
+    dijkstra::{query::dijkstra::Server as DijkServer, *},
+    *,
+};
+use crate::datastr::rank_select_map::BitVec;
+use std::collections::HashSet;
+
+/// Links which must not be used, identified by their tail and head.
+/// Parallel edges between the same nodes are excluded together, even when only one of them is passed to `exclude_edges`.
+#[derive(Debug, Clone, Default)]
+pub struct ExcludedLinks {
+    links: HashSet<(NodeId, NodeId)>,
+}
+
+impl ExcludedLinks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn exclude_link(&mut self, tail: NodeId, head: NodeId) {
+        self.links.insert((tail, head));
+    }
+
+    /// Exclude all edges for which `predicate(tail, head, edge_id)` holds.
+    pub fn exclude_where<G: LinkIterable<(NodeIdT, EdgeIdT)>>(&mut self, graph: &G, mut predicate: impl FnMut(NodeId, NodeId, EdgeId) -> bool) {
+        for tail in 0..graph.num_nodes() as NodeId {
+            for (NodeIdT(head), EdgeIdT(edge)) in LinkIterable::<(NodeIdT, EdgeIdT)>::link_iter(graph, tail) {
+                if predicate(tail, head, edge) {
+                    self.exclude_link(tail, head);
+                }
+            }
+        }
+    }
+
+    pub fn exclude_edges<G: LinkIterable<(NodeIdT, EdgeIdT)>>(&mut self, graph: &G, edges: &[EdgeId]) {
+        let mut excluded = BitVec::new(graph.num_arcs());
+        for &edge in edges {
+            excluded.set(edge as usize);
+        }
+        self.exclude_where(graph, |_, _, edge| excluded.get(edge as usize));
+    }
+
+    /// Exclude all edges with a category sharing a bit with `mask`, e.g. toll roads, tunnels or freeways in `arc_category`.
+    pub fn exclude_categories<G: LinkIterable<(NodeIdT, EdgeIdT)>>(&mut self, graph: &G, arc_category: &[u8], mask: u8) {
+        self.exclude_where(graph, |_, _, edge| arc_category[edge as usize] & mask != 0)
+    }
+
+    /// Exclude all edges entering, leaving or inside of an area given as closed ring of `(latitude, longitude)` pairs.
+    /// Nodes inside the area can thus neither be reached nor left.
+    pub fn exclude_area<G: LinkIterable<(NodeIdT, EdgeIdT)>>(&mut self, graph: &G, latitude: &[f32], longitude: &[f32], polygon: &[(f32, f32)]) {
+        let inside: Vec<bool> = (0..graph.num_nodes())
+            .map(|node| point_in_polygon((latitude[node], longitude[node]), polygon))
+            .collect();
+        self.exclude_where(graph, |tail, head, _| inside[tail as usize] || inside[head as usize])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, NodeId)> + '_ {
+        self.links.iter().copied()
+    }
+
+    pub fn contains(&self, tail: NodeId, head: NodeId) -> bool {
+        self.links.contains(&(tail, head))
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.links.clear();
+    }
+
+    /// Translate into the node ids of a graph permutated with `order`.
+    pub fn permutated(&self, order: &NodeOrder) -> Self {
+        Self {
+            links: self.links.iter().map(|&(tail, head)| (order.rank(tail), order.rank(head))).collect(),
+        }
+    }
+}
+
+/// Ray casting, points exactly on the boundary may end up on either side.
+fn point_in_polygon((lat, lng): (f32, f32), polygon: &[(f32, f32)]) -> bool {
+    let mut inside = false;
+    for (&(lat_a, lng_a), &(lat_b, lng_b)) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (lat_a > lat) != (lat_b > lat) && lng < lng_a + (lat - lat_a) / (lat_b - lat_a) * (lng_b - lng_a) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Wraps other ops and drops all links contained in the exclusion set.
+#[derive(Debug, Clone, Default)]
+pub struct ExclusionOps<O> {
+    ops: O,
+    excluded: ExcludedLinks,
+}
+
+impl<O> ExclusionOps<O> {
+    pub fn new(ops: O) -> Self {
+        Self {
+            ops,
+            excluded: ExcludedLinks::default(),
+        }
+    }
+
+    /// Exclusion for all following searches. Node ids have to match the graph the search runs on.
+    pub fn set_excluded(&mut self, excluded: ExcludedLinks) {
+        self.excluded = excluded;
+    }
+
+    pub fn excluded(&self) -> &ExcludedLinks {
+        &self.excluded
+    }
+
+    pub fn reset(&mut self) {
+        self.excluded.clear();
+    }
+}
+
+impl<G, O: DijkstraOps<G>> DijkstraOps<G> for ExclusionOps<O> {
+    type Label = O::Label;
+    type Arc = O::Arc;
+    type LinkResult = Option<O::LinkResult>;
+    type PredecessorLink = O::PredecessorLink;
+
+    #[inline(always)]
+    fn link(&mut self, graph: &G, parents: &[(NodeId, Self::PredecessorLink)], tail: NodeIdT, label: &Self::Label, link: &Self::Arc) -> Self::LinkResult {
+        if !self.excluded.is_empty() && self.excluded.contains(tail.0, link.head()) {
+            return None;
+        }
+        Some(self.ops.link(graph, parents, tail, label, link))
+    }
+
+    #[inline(always)]
+    fn merge(&mut self, label: &mut Self::Label, linked: Self::LinkResult) -> bool {
+        linked.map(|linked| self.ops.merge(label, linked)).unwrap_or(false)
+    }
+
+    #[inline(always)]
+    fn predecessor_link(&self, link: &Self::Arc) -> Self::PredecessorLink {
+        self.ops.predecessor_link(link)
+    }
+}
+
+/// Query servers which can exclude links from their queries.
+pub trait ExcludeLinks {
+    /// Exclude links (with the node ids of the original graph) from all following queries.
+    /// Pass an empty set to lift the exclusion again.
+    fn set_excluded_links(&mut self, excluded: &ExcludedLinks);
+}
+
+impl<G, O, P, B> ExcludeLinks for DijkServer<G, ExclusionOps<O>, P, B>
+where
+    O: DijkstraOps<G>,
+{
+    fn set_excluded_links(&mut self, excluded: &ExcludedLinks) {
+        self.ops().set_excluded(excluded.clone());
+    }
+}
+
+impl<G, O, P, const BCC_CORE: bool, const SKIP_DEG_2: bool, const SKIP_DEG_3: bool> ExcludeLinks
+    for ch_potentials::query::Server<G, ExclusionOps<O>, P, BCC_CORE, SKIP_DEG_2, SKIP_DEG_3>
+where
+    O: DijkstraOps<G, Label = Weight>,
+    G: LinkIterable<NodeIdT> + LinkIterable<O::Arc>,
+    P: Potential,
+{
+    fn set_excluded_links(&mut self, excluded: &ExcludedLinks) {
+        let excluded = excluded.permutated(self.order());
+        self.ops().set_excluded(excluded);
+    }
+}
+
+impl<G, O, P, const BCC_CORE: bool, const SKIP_DEG_2: bool, const SKIP_DEG_3: bool> ExcludeLinks
+    for ch_potentials::td_query::Server<G, ExclusionOps<O>, P, BCC_CORE, SKIP_DEG_2, SKIP_DEG_3>
+where
+    O: DijkstraOps<G, Label = Weight>,
+    G: LinkIterable<NodeIdT> + LinkIterable<O::Arc>,
+    P: td_astar::TDPotential,
+{
+    fn set_excluded_links(&mut self, excluded: &ExcludedLinks) {
+        let excluded = excluded.permutated(self.order());
+        self.ops().set_excluded(excluded);
+    }
+}
+
+/// CCH queries with closures.
+/// Setting an exclusion recustomizes the upward arcs of the lower endpoints of all excluded links and their elimination tree ancestors
+/// with infinite weights for the excluded links, restoring the arcs recustomized for the previous exclusion at the same time.
+/// This is a lot cheaper than a full customization, as long as the exclusion affects only few elimination tree paths.
+pub struct CCHExclusionServer<'a, G> {
+    server: CCHServer<CustomizedBasic<'a, CCH>>,
+    graph: &'a G,
+    recustomized: Vec<NodeId>,
+}
+
+impl<'a, G> CCHExclusionServer<'a, G>
+where
+    G: EdgeIdGraph + EdgeRandomAccessGraph<Link>,
+{
+    /// `graph` must be the graph `customized` was customized with.
+    pub fn new(customized: CustomizedBasic<'a, CCH>, graph: &'a G) -> Self {
+        Self {
+            server: CCHServer::new(customized),
+            graph,
+            recustomized: Vec::new(),
+        }
+    }
+}
+
+impl<'a, G> ExcludeLinks for CCHExclusionServer<'a, G>
+where
+    G: EdgeIdGraph + EdgeRandomAccessGraph<Link>,
+{
+    fn set_excluded_links(&mut self, excluded: &ExcludedLinks) {
+        let cch = self.server.customized().cch;
+        let order = cch.node_order();
+        let elimination_tree = cch.elimination_tree();
+
+        let mut excluded_arcs = Vec::new();
+        let mut nodes = std::mem::take(&mut self.recustomized);
+        for (tail, head) in excluded.iter() {
+            excluded_arcs.extend(self.graph.edge_indices(tail, head).map(|EdgeIdT(edge)| edge));
+            let mut node = Some(std::cmp::min(order.rank(tail), order.rank(head)));
+            while let Some(current) = node {
+                nodes.push(current);
+                node = elimination_tree[current as usize].value();
+            }
+        }
+        nodes.sort_unstable();
+        nodes.dedup();
+        excluded_arcs.sort_unstable();
+
+        let graph = self.graph;
+        recustomize_nodes(self.server.customized_mut(), &nodes, |arc| {
+            if excluded_arcs.binary_search(&arc).is_ok() {
+                INFINITY
+            } else {
+                graph.link(arc).weight
+            }
+        });
+
+        // without exclusion, the recustomized arcs have their original weights again
+        if !excluded.is_empty() {
+            self.recustomized = nodes;
+        }
+    }
+}
+
+impl<'a, G> QueryServer for CCHExclusionServer<'a, G> {
+    type P<'s>
+        = CCHPathServer<'s, CustomizedBasic<'a, CCH>>
+    where
+        Self: 's;
+
+    fn query(&mut self, query: Query) -> QueryResult<Self::P<'_>, Weight> {
+        self.server.query(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_contains_points() {
+        let square = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)];
+        assert!(point_in_polygon((0.5, 0.5), &square));
+        assert!(!point_in_polygon((1.5, 0.5), &square));
+        assert!(!point_in_polygon((0.5, -0.5), &square));
+    }
+
+    #[test]
+    fn excluded_links_are_not_relaxed() {
+        let graph = FirstOutGraph::new(&[0, 2, 3, 3][..], &[1, 2, 2][..], &[1, 5, 1][..]);
+        let mut server = DijkServer::<_, ExclusionOps<DefaultOps>>::new(graph.clone());
+        assert_eq!(server.query(Query { from: 0, to: 2 }).distance(), Some(2));
+
+        let mut excluded = ExcludedLinks::new();
+        excluded.exclude_edges(&graph, &[2]);
+        server.set_excluded_links(&excluded);
+        assert_eq!(server.query(Query { from: 0, to: 2 }).distance(), Some(5));
+
+        server.set_excluded_links(&ExcludedLinks::new());
+        assert_eq!(server.query(Query { from: 0, to: 2 }).distance(), Some(2));
+    }
+
+    // bidirected 4x4 grid with irregular weights
+    fn grid() -> (Vec<EdgeId>, Vec<NodeId>, Vec<Weight>) {
+        let (mut first_out, mut head, mut weight) = (vec![0], Vec::new(), Vec::new());
+        for node in 0..16 {
+            let (row, col) = (node / 4, node % 4);
+            let mut neighbors = Vec::new();
+            if row > 0 {
+                neighbors.push(node - 4);
+            }
+            if col > 0 {
+                neighbors.push(node - 1);
+            }
+            if col < 3 {
+                neighbors.push(node + 1);
+            }
+            if row < 3 {
+                neighbors.push(node + 4);
+            }
+            for neighbor in neighbors {
+                head.push(neighbor);
+                weight.push(1 + (node * 7 + neighbor * 3) % 5);
+            }
+            first_out.push(head.len() as EdgeId);
+        }
+        (first_out, head, weight)
+    }
+
+    fn without_edge((first_out, head, weight): &(Vec<EdgeId>, Vec<NodeId>, Vec<Weight>), edge: EdgeId) -> OwnedGraph {
+        let first_out = first_out.iter().map(|&e| if e > edge { e - 1 } else { e }).collect();
+        let mut head = head.clone();
+        let mut weight = weight.clone();
+        head.remove(edge as usize);
+        weight.remove(edge as usize);
+        OwnedGraph::new(first_out, head, weight)
+    }
+
+    // For every edge: exclude it, check all pairs against Dijkstra on the graph without it and that no path uses it.
+    fn check_against_dijkstra_without_edge(mut query: impl FnMut(&ExcludedLinks, NodeId, NodeId) -> Option<(Weight, Vec<NodeId>)>) {
+        let data = grid();
+        let graph = FirstOutGraph::new(&data.0[..], &data.1[..], &data.2[..]);
+        for edge in 0..graph.num_arcs() as EdgeId {
+            let tail = (0..16).find(|&node| graph.neighbor_edge_indices(node).contains(&edge)).unwrap();
+            let head = data.1[edge as usize];
+            let mut excluded = ExcludedLinks::new();
+            excluded.exclude_edges(&graph, &[edge]);
+
+            let mut dijkstra = DijkServer::<_, DefaultOps>::new(without_edge(&data, edge));
+            for from in 0..16 {
+                for to in 0..16 {
+                    let expected = dijkstra.query(Query { from, to }).distance();
+                    let result = query(&excluded, from, to);
+                    assert_eq!(result.as_ref().map(|(distance, _)| *distance), expected, "without {tail} -> {head}");
+                    if let Some((_, path)) = result {
+                        assert!(path.windows(2).all(|w| w != [tail, head]));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn topo_server_matches_dijkstra_without_excluded_edge() {
+        let data = grid();
+        let graph = OwnedGraph::new(data.0.clone(), data.1.clone(), data.2.clone());
+        let mut server: ch_potentials::query::Server<OwnedGraph, _, _, true, true, true> =
+            ch_potentials::query::Server::new(&graph, a_star::ZeroPotential(), ExclusionOps::new(DefaultOps()));
+        check_against_dijkstra_without_edge(|excluded, from, to| {
+            server.set_excluded_links(excluded);
+            let mut result = server.query(Query { from, to }).found()?;
+            Some((result.distance(), result.node_path()))
+        });
+    }
+
+    #[test]
+    fn td_topo_server_matches_dijkstra_without_excluded_edge() {
+        use crate::datastr::graph::time_dependent::{run_test_with_periodicity, TDGraph};
+        use dijkstra::query::td_dijkstra::TDDijkstraOps;
+
+        run_test_with_periodicity(1000, || {
+            let (first_out, head, weight) = grid();
+            let m = head.len() as u32;
+            let graph = TDGraph::new(first_out, head, (0..=m).collect(), vec![0; m as usize], weight);
+            let mut server: ch_potentials::td_query::Server<TDGraph, _, _, true, true, true> =
+                ch_potentials::td_query::Server::new(&graph, a_star::ZeroPotential(), ExclusionOps::new(TDDijkstraOps()));
+            check_against_dijkstra_without_edge(|excluded, from, to| {
+                server.set_excluded_links(excluded);
+                let mut result = server.td_query(TDQuery { from, to, departure: 42 }).found()?;
+                Some((result.distance(), result.node_path()))
+            });
+        });
+    }
+
+    #[test]
+    fn cch_server_matches_dijkstra_without_excluded_edge() {
+        use customizable_contraction_hierarchy::{contract, customize};
+
+        let data = grid();
+        let graph = OwnedGraph::new(data.0.clone(), data.1.clone(), data.2.clone());
+        let cch = contract(&graph, NodeOrder::identity(16));
+        let mut server = CCHExclusionServer::new(customize(&cch, &graph), &graph);
+        check_against_dijkstra_without_edge(|excluded, from, to| {
+            server.set_excluded_links(excluded);
+            let mut result = server.query(Query { from, to }).found()?;
+            Some((result.distance(), result.node_path()))
+        });
+
+        // lifting the exclusion restores the original customization
+        server.set_excluded_links(&ExcludedLinks::new());
+        let mut dijkstra = DijkServer::<_, DefaultOps>::new(graph.clone());
+        for from in 0..16 {
+            for to in 0..16 {
+                assert_eq!(server.query(Query { from, to }).distance(), dijkstra.query(Query { from, to }).distance());
+            }
+        }
+    }
+}
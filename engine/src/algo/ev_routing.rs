@@ -0,0 +1,658 @@
+//! Routing for electric vehicles with a limited battery.
+//!
+//! Energy consumption is derived from edge lengths and node elevations and becomes negative on steep enough descents (recuperation).
+//! `EvServer` searches the fastest path for which the state of charge (SoC) never drops below zero.
+//! Recuperated energy exceeding the battery capacity is lost.
+//! Labels carry travel time and SoC and a label is dominated when another label at the same node is at least as fast with at least as much charge left.
+//!
+//! Consumption lower bounds come from CH potentials on a nonnegative reduced metric:
+//! with the node potential `phi(v) = recuperation * per_meter_ascent * elevation(v)` the reduced consumption
+//! `c(u, v) - phi(v) + phi(u)` is never negative, so `dist_reduced(v, t) + phi(t) - phi(v)` bounds the consumption from `v` to `t` from below.
+//!
+//! Optionally, the vehicle may charge at given nodes according to a concave piecewise linear charging function.
+//! Charging amounts are restricted to the breakpoints of the charging function.
+
+use super::{
+    a_star::{Potential, ZeroPotential},
+    ch_potentials::CCHPotData,
+    customizable_contraction_hierarchy::CCH,
+    dijkstra::{generic_dijkstra::*, *},
+    *,
+};
+use crate::{datastr::timestamped_vector::*, report::*, util::in_range_option::InRangeOption};
+
+/// Energy in mWh. Negative values are recuperated energy.
+pub type Energy = i32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConsumptionModel {
+    /// Consumption on flat ground in mWh per meter.
+    pub per_meter: f64,
+    /// Additional consumption per meter of ascent in mWh.
+    pub per_meter_ascent: f64,
+    /// Share of the potential energy recovered when going downhill, between 0 and 1.
+    pub recuperation: f64,
+}
+
+impl ConsumptionModel {
+    pub fn for_vehicle(mass_kg: f64, per_meter: f64, recuperation: f64) -> Self {
+        assert!((0.0..=1.0).contains(&recuperation));
+        Self {
+            per_meter,
+            // m * g * h in J, 3.6 J = 1 mWh
+            per_meter_ascent: mass_kg * 9.81 / 3.6,
+            recuperation,
+        }
+    }
+
+    pub fn consumption(&self, length: Weight, ascent: f64) -> f64 {
+        let gravity = if ascent > 0.0 {
+            self.per_meter_ascent * ascent
+        } else {
+            self.recuperation * self.per_meter_ascent * ascent
+        };
+        self.per_meter * length as f64 + gravity
+    }
+
+    fn node_potential(&self, elevation: f32) -> f64 {
+        self.recuperation * self.per_meter_ascent * elevation as f64
+    }
+}
+
+impl Default for ConsumptionModel {
+    fn default() -> Self {
+        Self::for_vehicle(2000.0, 150.0, 0.6)
+    }
+}
+
+/// Per edge consumption and the reduced metric for the lower bounds.
+#[derive(Debug, Clone)]
+pub struct ConsumptionMetric {
+    consumption: Vec<Energy>,
+    reduced: Vec<Weight>,
+    node_potentials: Vec<f64>,
+}
+
+impl ConsumptionMetric {
+    /// `geo_distance` in meters per edge, `elevation` in meters per node.
+    pub fn new<FO, H, W>(graph: &FirstOutGraph<FO, H, W>, geo_distance: &[Weight], elevation: &[f32], model: &ConsumptionModel) -> Self
+    where
+        FO: AsRef<[EdgeId]>,
+        H: AsRef<[NodeId]>,
+        W: AsRef<[Weight]>,
+    {
+        assert_eq!(geo_distance.len(), graph.head().len());
+        assert_eq!(elevation.len(), graph.first_out().len() - 1);
+
+        let node_potentials: Vec<f64> = elevation.iter().map(|&h| model.node_potential(h)).collect();
+        let mut consumption = Vec::with_capacity(geo_distance.len());
+        let mut reduced = Vec::with_capacity(geo_distance.len());
+        for (tail, edges) in graph.first_out().windows(2).enumerate() {
+            let edges = edges[0] as usize..edges[1] as usize;
+            for (&head, &distance) in graph.head()[edges.clone()].iter().zip(&geo_distance[edges]) {
+                let head = head as usize;
+                let potential_diff = node_potentials[head] - node_potentials[tail];
+                let exact = model.consumption(distance, (elevation[head] - elevation[tail]) as f64);
+                // never below the potential difference, so the reduced metric stays nonnegative despite rounding
+                let rounded = f64::max(exact.round(), potential_diff.ceil());
+                consumption.push(rounded as Energy);
+                reduced.push((rounded - potential_diff).floor() as Weight);
+            }
+        }
+
+        Self {
+            consumption,
+            reduced,
+            node_potentials,
+        }
+    }
+
+    pub fn consumption(&self) -> &[Energy] {
+        &self.consumption
+    }
+
+    /// CH potentials for consumption lower bounds, to be passed to `EvServer::with_potentials` as the consumption potential.
+    pub fn lower_bound_potential_data<'c, FO, H, W>(&self, cch: &'c CCH, graph: &FirstOutGraph<FO, H, W>) -> CCHPotData<'c>
+    where
+        FO: AsRef<[EdgeId]>,
+        H: AsRef<[NodeId]>,
+        W: AsRef<[Weight]>,
+    {
+        CCHPotData::new(cch, &FirstOutGraph::new(graph.first_out(), graph.head(), &self.reduced[..]))
+    }
+}
+
+/// Concave piecewise linear charging function: SoC reached after charging for some time, starting with an empty battery.
+#[derive(Debug, Clone)]
+pub struct ChargingFunction {
+    breakpoints: Vec<(Weight, Energy)>,
+}
+
+impl ChargingFunction {
+    /// Breakpoints as (time in ms, SoC), starting at `(0, 0)` with strictly increasing time and SoC.
+    pub fn new(breakpoints: Vec<(Weight, Energy)>) -> Self {
+        assert_eq!(breakpoints.first(), Some(&(0, 0)));
+        assert!(breakpoints.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        Self { breakpoints }
+    }
+
+    /// Charging with constant power (mWh per hour) up to the capacity.
+    pub fn constant_power(power: f64, capacity: Energy) -> Self {
+        Self::new(vec![(0, 0), ((capacity as f64 / power * 3_600_000.0).round() as Weight, capacity)])
+    }
+
+    // time to charge an empty battery to soc
+    fn time_to(&self, soc: Energy) -> Weight {
+        let idx = self.breakpoints.partition_point(|&(_, s)| s < soc);
+        if idx == 0 {
+            return 0;
+        }
+        let Some(&(t1, s1)) = self.breakpoints.get(idx) else {
+            return INFINITY;
+        };
+        let (t0, s0) = self.breakpoints[idx - 1];
+        t0 + ((t1 - t0) as f64 * (soc - s0) as f64 / (s1 - s0) as f64).round() as Weight
+    }
+
+    pub fn charging_time(&self, from: Energy, to: Energy) -> Weight {
+        debug_assert!(from <= to);
+        self.time_to(to) - self.time_to(from)
+    }
+
+    fn levels_above(&self, soc: Energy, capacity: Energy) -> impl Iterator<Item = Energy> + '_ {
+        let max = std::cmp::min(capacity, self.breakpoints.last().unwrap().1);
+        self.breakpoints
+            .iter()
+            .map(|&(_, s)| s)
+            .filter(move |&s| s > soc && s < max)
+            .chain(std::iter::once(max).filter(move |&max| max > soc))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvLabel {
+    pub travel_time: Weight,
+    pub soc: Energy,
+    entry: u32,
+}
+
+impl EvLabel {
+    fn dominates(&self, other: &Self) -> bool {
+        self.travel_time <= other.travel_time && self.soc >= other.soc
+    }
+}
+
+impl Reset for EvLabel {
+    const DEFAULT: Self = EvLabel {
+        travel_time: INFINITY,
+        soc: Energy::MIN,
+        entry: u32::MAX,
+    };
+}
+
+impl Label for EvLabel {
+    type Key = Weight;
+    fn neutral() -> Self {
+        Self::DEFAULT
+    }
+    fn key(&self) -> Self::Key {
+        self.travel_time
+    }
+}
+
+impl PartialEq for NodeQueueLabelOrder<EvLabel> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.0.travel_time, self.0.soc).eq(&(other.0.travel_time, other.0.soc))
+    }
+}
+impl PartialOrd for NodeQueueLabelOrder<EvLabel> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Eq for NodeQueueLabelOrder<EvLabel> {}
+impl Ord for NodeQueueLabelOrder<EvLabel> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // switched for reversing, higher SoC first on equal travel time
+        (other.0.travel_time, self.0.soc).cmp(&(self.0.travel_time, other.0.soc))
+    }
+}
+
+/// Parent pointer of a label, kept in an arena since labels may get removed from the node heaps.
+#[derive(Debug, Clone, Copy)]
+pub struct LabelEntry {
+    node: NodeId,
+    edge: EdgeId,
+    parent: u32,
+    // charging time and SoC before and after charging at the tail of `edge`
+    charged: Option<(Weight, Energy, Energy)>,
+}
+
+/// Result of relaxing an edge, expanded into one label per charging option during merging.
+pub struct EvLinked {
+    label: Option<EvLabel>,
+    entry: LabelEntry,
+    // charging function, SoC and travel time at the tail
+    charging: Option<(u32, Energy, Weight)>,
+    edge_travel_time: Weight,
+    edge_consumption: Energy,
+    consumption_lower_bound: Energy,
+}
+
+pub struct EvOps<'a, PC> {
+    metric: &'a ConsumptionMetric,
+    capacity: Energy,
+    consumption_potential: PC,
+    target: NodeId,
+    stations: Vec<InRangeOption<u32>>,
+    charging_functions: Vec<ChargingFunction>,
+    entries: Vec<LabelEntry>,
+}
+
+impl<'a, PC: Potential> EvOps<'a, PC> {
+    fn consumption_lower_bound(&mut self, node: NodeId) -> Option<Energy> {
+        let reduced = self.consumption_potential.potential(node)? as f64;
+        Some((reduced + self.metric.node_potentials[self.target as usize] - self.metric.node_potentials[node as usize]).floor() as Energy)
+    }
+
+    // without charging stations, labels which can not make it to the target even with the lower bound consumption are useless
+    fn is_hopeless(&self, label: &EvLabel, consumption_lower_bound: Energy) -> bool {
+        self.charging_functions.is_empty() && label.soc < consumption_lower_bound
+    }
+
+    fn insert(&mut self, label: &mut MultiCritNodeData<EvLabel>, mut linked: EvLabel, entry: LabelEntry) -> bool {
+        let mut dominated = false;
+        label.retain(|NodeQueueLabelOrder(old_l)| {
+            if old_l.dominates(&linked) {
+                dominated = true;
+            }
+            dominated || !linked.dominates(old_l)
+        });
+
+        if !dominated {
+            linked.entry = self.entries.len() as u32;
+            self.entries.push(entry);
+            label.push(NodeQueueLabelOrder(linked));
+        }
+        !dominated
+    }
+}
+
+impl<'a, G, PC> MultiCritDijkstraOps<G> for EvOps<'a, PC>
+where
+    G: EdgeRandomAccessGraph<Link>,
+    PC: Potential,
+{
+    type Label = EvLabel;
+    type Arc = (NodeIdT, EdgeIdT);
+    type LinkResult = Option<EvLinked>;
+    type PredecessorLink = ();
+
+    fn link(
+        &mut self,
+        graph: &G,
+        _labels: &TimestampedVector<MultiCritNodeData<Self::Label>>,
+        _parents: &[(NodeId, Self::PredecessorLink)],
+        NodeIdT(tail): NodeIdT,
+        _key: Weight,
+        label: &Self::Label,
+        &(NodeIdT(head), EdgeIdT(edge)): &Self::Arc,
+    ) -> Self::LinkResult {
+        let edge_travel_time = graph.link(edge).weight;
+        let edge_consumption = self.metric.consumption[edge as usize];
+        let consumption_lower_bound = self.consumption_lower_bound(head)?;
+
+        let soc = std::cmp::min(self.capacity, label.soc - edge_consumption);
+        let linked = EvLabel {
+            travel_time: label.travel_time + edge_travel_time,
+            soc,
+            entry: label.entry,
+        };
+        let linked = Some(linked).filter(|l| l.soc >= 0 && !self.is_hopeless(l, consumption_lower_bound));
+        let charging = self.stations[tail as usize].value().map(|station| (station, label.soc, label.travel_time));
+        if linked.is_none() && charging.is_none() {
+            return None;
+        }
+
+        Some(EvLinked {
+            label: linked,
+            entry: LabelEntry {
+                node: head,
+                edge,
+                parent: label.entry,
+                charged: None,
+            },
+            charging,
+            edge_travel_time,
+            edge_consumption,
+            consumption_lower_bound,
+        })
+    }
+
+    fn merge(&mut self, label: &mut MultiCritNodeData<Self::Label>, linked: Self::LinkResult) -> Option<Weight> {
+        let linked = linked?;
+        let mut improved = None;
+        if let Some(l) = linked.label {
+            if self.insert(label, l, linked.entry) {
+                improved = Some(l.travel_time);
+            }
+        }
+
+        if let Some((station, tail_soc, tail_travel_time)) = linked.charging {
+            let function = &self.charging_functions[station as usize];
+            let options: Vec<_> = function
+                .levels_above(tail_soc, self.capacity)
+                .map(|level| (level, function.charging_time(tail_soc, level)))
+                .collect();
+            for (level, charging_time) in options {
+                let charged = EvLabel {
+                    travel_time: tail_travel_time + charging_time + linked.edge_travel_time,
+                    soc: std::cmp::min(self.capacity, level - linked.edge_consumption),
+                    entry: linked.entry.parent,
+                };
+                if charged.soc < 0 || self.is_hopeless(&charged, linked.consumption_lower_bound) {
+                    continue;
+                }
+                let entry = LabelEntry {
+                    charged: Some((charging_time, tail_soc, level)),
+                    ..linked.entry
+                };
+                if self.insert(label, charged, entry) {
+                    improved = Some(improved.map_or(charged.travel_time, |i: Weight| i.min(charged.travel_time)));
+                }
+            }
+        }
+
+        improved
+    }
+
+    fn predecessor_link(&self, _link: &Self::Arc) -> Self::PredecessorLink {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvQuery {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub initial_soc: Energy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChargingStop {
+    pub node: NodeId,
+    pub charging_time: Weight,
+    pub soc_before: Energy,
+    pub soc_after: Energy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvPath {
+    /// Including the time spent charging.
+    pub travel_time: Weight,
+    pub final_soc: Energy,
+    pub node_path: Vec<NodeId>,
+    pub edge_path: Vec<EdgeId>,
+    pub charging_stops: Vec<ChargingStop>,
+}
+
+/// Fastest feasible path search.
+/// The travel time is taken from the graph weights.
+pub struct EvServer<'a, G, PT = ZeroPotential, PC = ZeroPotential> {
+    graph: &'a G,
+    data: DijkstraData<EvLabel, (), MultiCritNodeData<EvLabel>>,
+    ops: EvOps<'a, PC>,
+    travel_time_potential: PT,
+    num_labels: usize,
+}
+
+impl<'a, G: Graph> EvServer<'a, G> {
+    pub fn new(graph: &'a G, metric: &'a ConsumptionMetric, capacity: Energy) -> Self {
+        Self::with_potentials(graph, metric, capacity, ZeroPotential(), ZeroPotential())
+    }
+}
+
+impl<'a, G: Graph, PT, PC> EvServer<'a, G, PT, PC> {
+    /// `travel_time_potential` has to be a lower bound of the travel time, e.g. CH potentials,
+    /// and `consumption_potential` a potential from `ConsumptionMetric::lower_bound_potential_data`.
+    pub fn with_potentials(graph: &'a G, metric: &'a ConsumptionMetric, capacity: Energy, travel_time_potential: PT, consumption_potential: PC) -> Self {
+        assert_eq!(metric.consumption.len(), graph.num_arcs());
+        assert!(capacity > 0);
+        Self {
+            graph,
+            data: DijkstraData::new(graph.num_nodes()),
+            ops: EvOps {
+                metric,
+                capacity,
+                consumption_potential,
+                target: 0,
+                stations: vec![InRangeOption::NONE; graph.num_nodes()],
+                charging_functions: Vec::new(),
+                entries: Vec::new(),
+            },
+            travel_time_potential,
+            num_labels: 0,
+        }
+    }
+
+    /// Allow charging at `node`, replacing any previous charging function there.
+    pub fn add_charging_station(&mut self, node: NodeId, function: ChargingFunction) {
+        self.ops.stations[node as usize] = InRangeOption::some(self.ops.charging_functions.len() as u32);
+        self.ops.charging_functions.push(function);
+    }
+
+    /// Number of labels created during the last query.
+    pub fn num_labels(&self) -> usize {
+        self.num_labels
+    }
+}
+
+impl<'a, G, PT, PC> EvServer<'a, G, PT, PC>
+where
+    G: LinkIterable<(NodeIdT, EdgeIdT)> + EdgeRandomAccessGraph<Link>,
+    PT: Potential,
+    PC: Potential,
+{
+    pub fn query(&mut self, EvQuery { from, to, initial_soc }: EvQuery) -> Option<EvPath> {
+        report!("algo", "EV Dijkstra Query");
+        assert!((0..=self.ops.capacity).contains(&initial_soc));
+
+        self.travel_time_potential.init(to);
+        self.ops.consumption_potential.init(to);
+        self.ops.target = to;
+        self.ops.entries.clear();
+        self.ops.entries.push(LabelEntry {
+            node: from,
+            edge: EdgeId::MAX,
+            parent: u32::MAX,
+            charged: None,
+        });
+        self.num_labels = 1;
+
+        let initial = EvLabel {
+            travel_time: 0,
+            soc: initial_soc,
+            entry: 0,
+        };
+        let lower_bound = self.ops.consumption_lower_bound(from)?;
+        if self.ops.is_hopeless(&initial, lower_bound) {
+            return None;
+        }
+
+        let travel_time_potential = &mut self.travel_time_potential;
+        let mut run = MultiCritDijkstraRun::query(
+            self.graph,
+            &mut self.data,
+            &mut self.ops,
+            DijkstraInit {
+                source: NodeIdT(from),
+                initial_state: initial,
+            },
+            |node| travel_time_potential.potential(node),
+        );
+
+        let mut num_queue_pops: usize = 0;
+        let mut found = None;
+        while let Some(node) = run.next_step_with_potential(|node| travel_time_potential.potential(node)) {
+            num_queue_pops += 1;
+            if node == to {
+                found = Some(run.tentative_distance(to).popped()[0].0);
+                break;
+            }
+        }
+        report!("num_queue_pops", num_queue_pops);
+        report!("num_relaxed_arcs", run.num_relaxed_arcs());
+        self.num_labels = self.ops.entries.len();
+        report!("num_labels", self.num_labels);
+
+        let label = found?;
+        let mut path = EvPath {
+            travel_time: label.travel_time,
+            final_soc: label.soc,
+            node_path: Vec::new(),
+            edge_path: Vec::new(),
+            charging_stops: Vec::new(),
+        };
+        let mut entry = label.entry;
+        while entry != u32::MAX {
+            let LabelEntry { node, edge, parent, charged } = self.ops.entries[entry as usize];
+            path.node_path.push(node);
+            if parent != u32::MAX {
+                path.edge_path.push(edge);
+            }
+            if let Some((charging_time, soc_before, soc_after)) = charged {
+                path.charging_stops.push(ChargingStop {
+                    node: self.ops.entries[parent as usize].node,
+                    charging_time,
+                    soc_before,
+                    soc_after,
+                });
+            }
+            entry = parent;
+        }
+        path.node_path.reverse();
+        path.edge_path.reverse();
+        path.charging_stops.reverse();
+        report!("num_charging_stops", path.charging_stops.len());
+
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::ch_potentials::BorrowedCCHPot;
+    use crate::algo::customizable_contraction_hierarchy::{contract, customize};
+    use crate::datastr::node_order::NodeOrder;
+
+    // 0 -> 1 -> 3 climbs over a hill at 1 and is fast, 0 -> 2 -> 3 stays flat but is slow and needs less energy.
+    // 3 -> 4 goes down into a valley.
+    fn setup() -> (OwnedGraph, ConsumptionMetric) {
+        let graph = OwnedGraph::new(vec![0, 2, 3, 4, 5, 5], vec![1, 2, 3, 3, 4], vec![10, 20, 10, 20, 10]);
+        let geo_distance = vec![100, 150, 100, 150, 100];
+        let elevation = vec![0.0, 30.0, 0.0, 0.0, -100.0];
+        let model = ConsumptionModel {
+            per_meter: 1.0,
+            per_meter_ascent: 10.0,
+            recuperation: 0.5,
+        };
+        let metric = ConsumptionMetric::new(&graph, &geo_distance, &elevation, &model);
+        (graph, metric)
+    }
+
+    #[test]
+    fn consumption_with_recuperation() {
+        let (_, metric) = setup();
+        assert_eq!(metric.consumption(), &[400, 150, -50, 150, -400]);
+    }
+
+    #[test]
+    fn soc_bounds_the_path() {
+        let (graph, metric) = setup();
+        let mut server = EvServer::new(&graph, &metric, 1000);
+
+        let fast = server
+            .query(EvQuery {
+                from: 0,
+                to: 3,
+                initial_soc: 1000,
+            })
+            .unwrap();
+        assert_eq!(fast.node_path, vec![0, 1, 3]);
+        assert_eq!((fast.travel_time, fast.final_soc), (20, 650));
+
+        let flat = server
+            .query(EvQuery {
+                from: 0,
+                to: 3,
+                initial_soc: 350,
+            })
+            .unwrap();
+        assert_eq!(flat.node_path, vec![0, 2, 3]);
+        assert_eq!((flat.travel_time, flat.final_soc), (40, 50));
+
+        assert_eq!(
+            server.query(EvQuery {
+                from: 0,
+                to: 3,
+                initial_soc: 250
+            }),
+            None
+        );
+
+        // recuperation is capped by the capacity
+        let valley = server
+            .query(EvQuery {
+                from: 0,
+                to: 4,
+                initial_soc: 1000,
+            })
+            .unwrap();
+        assert_eq!(valley.final_soc, 1000);
+    }
+
+    #[test]
+    fn charging_makes_paths_feasible() {
+        let (graph, metric) = setup();
+        let mut server = EvServer::new(&graph, &metric, 1000);
+        server.add_charging_station(0, ChargingFunction::new(vec![(0, 0), (100, 500), (300, 1000)]));
+
+        let path = server
+            .query(EvQuery {
+                from: 0,
+                to: 3,
+                initial_soc: 250,
+            })
+            .unwrap();
+        assert_eq!(path.node_path, vec![0, 1, 3]);
+        assert_eq!(
+            path.charging_stops,
+            vec![ChargingStop {
+                node: 0,
+                charging_time: 50,
+                soc_before: 250,
+                soc_after: 500
+            }]
+        );
+        assert_eq!((path.travel_time, path.final_soc), (70, 150));
+    }
+
+    #[test]
+    fn potentials_give_same_result() {
+        let (graph, metric) = setup();
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let travel_time = customize(&cch, &graph);
+        let consumption = metric.lower_bound_potential_data(&cch, &graph);
+        let mut server = EvServer::with_potentials(
+            &graph,
+            &metric,
+            1000,
+            BorrowedCCHPot::new_from_customized(&travel_time),
+            consumption.forward_potential(),
+        );
+
+        for initial_soc in [1000, 350, 250] {
+            let query = EvQuery { from: 0, to: 3, initial_soc };
+            assert_eq!(server.query(query), EvServer::new(&graph, &metric, 1000).query(query));
+        }
+    }
+}
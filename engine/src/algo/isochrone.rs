@@ -0,0 +1,350 @@
+//! Isochrone queries: which nodes and edges can be reached from a source within a given time budget?
+//!
+//! For static metrics we run a PHAST style sweep over a customized CCH.
+//! That is an upward search from the source followed by a linear sweep over all nodes in the order of the elimination tree.
+//! We reuse `RPHAST` for this with all nodes selected, so the selection has to be computed only once.
+//!
+//! For time-dependent metrics we run a floating TD-Dijkstra which stops as soon as the budget is exhausted.
+//!
+//! Both variants return an `Isochrone` with the reachable nodes and the edges on the border, that is edges which can be entered but not completely traversed.
+
+use super::*;
+use crate::algo::{
+    customizable_contraction_hierarchy::*,
+    dijkstra::{generic_dijkstra::DijkstraRun, query::floating_td_dijkstra::FlTDDijkstraOps, DijkstraData, DijkstraInit},
+    rphast::*,
+};
+use crate::datastr::graph::floating_time_dependent::{FlWeight, TDGraph, Timestamp, PLF};
+
+/// An edge which can be entered within the budget but not completely traversed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundaryEdge {
+    pub tail: NodeId,
+    pub head: NodeId,
+    pub edge: EdgeId,
+    /// Fraction in `[0, 1)` of the edge which can be traversed within the budget.
+    pub coverage: f64,
+}
+
+impl BoundaryEdge {
+    fn new(tail: NodeId, head: NodeId, edge: EdgeId, coverage: f64) -> Self {
+        Self {
+            tail,
+            head,
+            edge,
+            coverage: coverage.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Result of an isochrone query.
+#[derive(Debug, Clone, Default)]
+pub struct Isochrone {
+    /// All nodes which can be reached within the budget, sorted by node id.
+    pub reachable: Vec<NodeId>,
+    pub boundary_edges: Vec<BoundaryEdge>,
+}
+
+impl Isochrone {
+    /// Outline of the isochrone as a closed ring of `(latitude, longitude)` pairs.
+    /// This is the convex hull of the reachable nodes and the partially covered end points of the boundary edges,
+    /// so it will overapproximate very non-convex isochrones.
+    /// Coordinates of partially covered edges are linearly interpolated between tail and head.
+    /// When all these points are equal or collinear, the outline is a single point or a segment, see `convex_hull`.
+    pub fn polygon(&self, latitude: &[f32], longitude: &[f32]) -> Vec<(f32, f32)> {
+        let mut points: Vec<(f32, f32)> = self.reachable.iter().map(|&node| (latitude[node as usize], longitude[node as usize])).collect();
+
+        for edge in &self.boundary_edges {
+            let (tail, head) = (edge.tail as usize, edge.head as usize);
+            let coverage = edge.coverage as f32;
+            points.push((
+                latitude[tail] + coverage * (latitude[head] - latitude[tail]),
+                longitude[tail] + coverage * (longitude[head] - longitude[tail]),
+            ));
+        }
+
+        convex_hull(points)
+    }
+}
+
+/// Andrews monotone chain algorithm.
+/// Returns the hull in counter clockwise order, starting at the smallest point and with the first point repeated at the end.
+/// Degenerate hulls are not closed: no points yield an empty vector, a single distinct point is returned alone
+/// and two distinct or only collinear points yield the two end points of their segment.
+/// So the result is a closed ring if and only if it has more than two points.
+pub fn convex_hull(mut points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    points.dedup();
+
+    if points.len() < 3 {
+        return points;
+    }
+
+    fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    }
+
+    let mut hull: Vec<(f32, f32)> = Vec::with_capacity(2 * points.len());
+    // lower hull
+    for &p in &points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    // upper hull
+    let lower_len = hull.len() + 1;
+    for &p in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+
+    if hull.len() < 4 {
+        // all points are collinear, the hull went to the last point and back
+        hull.truncate(2);
+    }
+    hull
+}
+
+/// Isochrone queries on a customized CCH.
+pub struct CCHIsochrone<'a> {
+    rphast: RPHAST<BorrowedGraph<'a>, BorrowedGraph<'a>>,
+    query: RPHASTQuery,
+    distances: Vec<Weight>,
+}
+
+impl<'a> CCHIsochrone<'a> {
+    pub fn new<C: Customized>(customized: &'a C) -> Self {
+        let n = customized.forward_graph().num_nodes();
+        let mut rphast = RPHAST::new(customized.forward_graph(), customized.backward_graph(), customized.cch().node_order().clone());
+        // Selecting everything turns RPHAST into PHAST.
+        rphast.select(&(0..n as NodeId).collect::<Vec<_>>());
+
+        Self {
+            query: RPHASTQuery::new(&rphast),
+            rphast,
+            distances: vec![INFINITY; n],
+        }
+    }
+
+    /// Compute the isochrone of all nodes reachable from `source` within `budget`.
+    /// The graph has to be the one the CCH was customized with.
+    pub fn query<G>(&mut self, graph: &G, source: NodeId, budget: Weight) -> Isochrone
+    where
+        G: LinkIterable<(NodeIdT, Weight, EdgeIdT)>,
+    {
+        let result = self.query.query(source, &self.rphast);
+        for (node, dist) in self.distances.iter_mut().enumerate() {
+            *dist = result.distance(node as NodeId);
+        }
+
+        let mut isochrone = Isochrone::default();
+        for node in 0..graph.num_nodes() as NodeId {
+            let dist = self.distances[node as usize];
+            if dist > budget {
+                continue;
+            }
+            isochrone.reachable.push(node);
+
+            for (NodeIdT(head), weight, EdgeIdT(edge)) in graph.link_iter(node) {
+                if dist + weight > budget {
+                    isochrone
+                        .boundary_edges
+                        .push(BoundaryEdge::new(node, head, edge, f64::from(budget - dist) / f64::from(weight)));
+                }
+            }
+        }
+
+        isochrone
+    }
+
+    /// Distances of the last query, indexed by node id.
+    pub fn distances(&self) -> &[Weight] {
+        &self.distances
+    }
+}
+
+/// Isochrone queries on a floating time-dependent graph with a given departure time.
+pub struct TDIsochrone<'a> {
+    graph: &'a TDGraph,
+    data: DijkstraData<Timestamp>,
+    settled: Vec<NodeId>,
+}
+
+impl<'a> TDIsochrone<'a> {
+    pub fn new(graph: &'a TDGraph) -> Self {
+        Self {
+            data: DijkstraData::new(graph.num_nodes()),
+            settled: Vec::new(),
+            graph,
+        }
+    }
+
+    /// Compute the isochrone of all nodes reachable from `source` when departing at `departure` and traveling at most `budget`.
+    pub fn query(&mut self, source: NodeId, departure: Timestamp, budget: FlWeight) -> Isochrone {
+        let arrival_limit = departure + budget;
+        self.settled.clear();
+
+        {
+            let mut ops = FlTDDijkstraOps();
+            let mut dijkstra = DijkstraRun::query(
+                self.graph,
+                &mut self.data,
+                &mut ops,
+                DijkstraInit {
+                    source: NodeIdT(source),
+                    initial_state: departure,
+                },
+            );
+
+            while dijkstra.queue().peek().map(|state| state.key.fuzzy_leq(arrival_limit)).unwrap_or(false) {
+                self.settled.push(dijkstra.next().unwrap());
+            }
+        }
+
+        let mut isochrone = Isochrone::default();
+        self.settled.sort_unstable();
+        for &node in &self.settled {
+            isochrone.reachable.push(node);
+
+            let arrival = self.data.distances[node as usize];
+            for (NodeIdT(head), EdgeIdT(edge)) in LinkIterable::<(NodeIdT, EdgeIdT)>::link_iter(self.graph, node) {
+                let travel_time = self.graph.travel_time_function(edge).evaluate(arrival);
+                if arrival_limit.fuzzy_lt(arrival + travel_time) {
+                    let coverage = f64::from(arrival_limit - arrival) / f64::from(travel_time);
+                    isochrone.boundary_edges.push(BoundaryEdge::new(node, head, edge, coverage));
+                }
+            }
+        }
+
+        isochrone
+    }
+
+    /// Earliest arrival time at `node` of the last query.
+    /// Only meaningful for nodes which were reachable.
+    pub fn arrival(&self, node: NodeId) -> Timestamp {
+        self.data.distances[node as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::dijkstra::query::floating_td_dijkstra::Server as TDDijkServer;
+
+    #[test]
+    fn convex_hull_of_degenerate_inputs() {
+        assert_eq!(convex_hull(vec![]), vec![]);
+        assert_eq!(convex_hull(vec![(1.0, 2.0)]), vec![(1.0, 2.0)]);
+        assert_eq!(convex_hull(vec![(1.0, 2.0), (1.0, 2.0), (1.0, 2.0)]), vec![(1.0, 2.0)]);
+        assert_eq!(convex_hull(vec![(3.0, 0.0), (1.0, 2.0), (3.0, 0.0)]), vec![(1.0, 2.0), (3.0, 0.0)]);
+        // collinear points, with duplicates and in arbitrary order
+        assert_eq!(
+            convex_hull(vec![(2.0, 2.0), (0.0, 0.0), (1.0, 1.0), (3.0, 3.0), (1.0, 1.0), (0.0, 0.0)]),
+            vec![(0.0, 0.0), (3.0, 3.0)]
+        );
+        assert_eq!(convex_hull(vec![(0.0, 1.0), (0.0, 3.0), (0.0, 2.0)]), vec![(0.0, 1.0), (0.0, 3.0)]);
+    }
+
+    #[test]
+    fn convex_hull_is_closed_counter_clockwise_ring() {
+        // a square with points inside, on its edges and duplicated corners
+        let points = vec![
+            (2.0, 2.0),
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 2.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 0.0),
+            (2.0, 2.0),
+            (0.5, 1.5),
+        ];
+        assert_eq!(convex_hull(points), vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)]);
+
+        // a triangle with a collinear point on one side
+        assert_eq!(
+            convex_hull(vec![(0.0, 0.0), (4.0, 0.0), (2.0, 0.0), (0.0, 4.0)]),
+            vec![(0.0, 0.0), (4.0, 0.0), (0.0, 4.0), (0.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn polygon_includes_partially_covered_edges() {
+        let latitude = [0.0, 0.0, 2.0, 4.0];
+        let longitude = [0.0, 2.0, 0.0, 4.0];
+
+        let isochrone = Isochrone {
+            reachable: vec![0, 1, 2],
+            boundary_edges: vec![BoundaryEdge::new(1, 3, 0, 0.5), BoundaryEdge::new(0, 3, 1, 0.0)],
+        };
+        // the edge from 1 to 3 is covered up to (2, 3), the one from 0 to 3 adds no new point
+        assert_eq!(
+            isochrone.polygon(&latitude, &longitude),
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 3.0), (0.0, 2.0), (0.0, 0.0)]
+        );
+
+        // only the source is reachable and an edge is half covered
+        let isochrone = Isochrone {
+            reachable: vec![0],
+            boundary_edges: vec![BoundaryEdge::new(0, 3, 1, 0.5)],
+        };
+        assert_eq!(isochrone.polygon(&latitude, &longitude), vec![(0.0, 0.0), (2.0, 2.0)]);
+        assert_eq!(Isochrone::default().polygon(&latitude, &longitude), vec![]);
+    }
+
+    #[test]
+    fn td_isochrone_matches_td_dijkstra() {
+        // 0 -> 1 gets slower from 10s at 0s to 30s at 100s, 2 -> 3 from 5s at 0s to 40s at 50s, all other edges are constant
+        let graph = TDGraph::new(
+            vec![0, 2, 3, 5, 6, 6],
+            vec![1, 2, 3, 3, 4, 4],
+            vec![0, 2, 3, 4, 6, 7, 8],
+            vec![0, 100_000, 0, 0, 0, 50_000, 0, 0],
+            vec![10_000, 30_000, 15_000, 10_000, 5_000, 40_000, 50_000, 20_000],
+        );
+        let mut isochrone_server = TDIsochrone::new(&graph);
+        let mut dijkstra = TDDijkServer::new(&graph);
+
+        for departure in [0.0, 20.0, 60.0, 120.0] {
+            let departure = Timestamp::new(departure);
+            let distances: Vec<Option<FlWeight>> = (0..graph.num_nodes() as NodeId)
+                .map(|to| dijkstra.td_query(TDQuery { from: 0, to, departure }).distance())
+                .collect();
+
+            for budget in [4.5, 12.5, 26.5, 41.5, 70.5, 1000.0] {
+                let budget = FlWeight::new(budget);
+                let isochrone = isochrone_server.query(0, departure, budget);
+
+                let expected: Vec<NodeId> = (0..graph.num_nodes() as NodeId)
+                    .filter(|&node| distances[node as usize].map(|d| d.fuzzy_leq(budget)).unwrap_or(false))
+                    .collect();
+                assert_eq!(isochrone.reachable, expected, "departure {:?}, budget {:?}", departure, budget);
+                for &node in &isochrone.reachable {
+                    assert!((isochrone_server.arrival(node) - departure).fuzzy_eq(distances[node as usize].unwrap()));
+                }
+
+                for edge in &isochrone.boundary_edges {
+                    assert!(isochrone.reachable.binary_search(&edge.tail).is_ok());
+                    assert!(edge.coverage < 1.0);
+                    let arrival = isochrone_server.arrival(edge.tail);
+                    let travel_time = graph.travel_time_function(edge.edge).evaluate(arrival);
+                    assert!((departure + budget).fuzzy_lt(arrival + travel_time));
+                }
+                // every edge which can not be traversed completely within the budget is reported
+                let num_boundary = isochrone
+                    .reachable
+                    .iter()
+                    .flat_map(|&node| LinkIterable::<(NodeIdT, EdgeIdT)>::link_iter(&graph, node).map(move |(_, EdgeIdT(edge))| (node, edge)))
+                    .filter(|&(node, edge)| {
+                        let arrival = isochrone_server.arrival(node);
+                        (departure + budget).fuzzy_lt(arrival + graph.travel_time_function(edge).evaluate(arrival))
+                    })
+                    .count();
+                assert_eq!(isochrone.boundary_edges.len(), num_boundary);
+            }
+        }
+    }
+}
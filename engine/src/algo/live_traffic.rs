@@ -0,0 +1,601 @@
+//! Ingestion of streamed live traffic updates.
+//!
+//! Updates (travel times, speeds or closures of single edges, each valid for a limited duration) arrive as CSV or JSON lines,
+//! either from a tailed file or from local TCP or unix socket connections.
+//! They are collected into a `LiveTrafficState` which keeps the live data of a `PessimisticLiveTDGraph` and drops it again once it expired.
+//! The `LiveTrafficService` periodically turns the current state into a snapshot, runs the live customization of a `MultiMetricPreprocessed` on it
+//! and publishes the result as a new generation to all subscribed `LiveQueryServer`s.
+//! Query servers pick up the latest generation between queries, so running queries are never interrupted.
+
+use super::{ch_potentials::td_query::Server, dijkstra::query::td_dijkstra::PessimisticLiveTDDijkstraOps, td_astar::*, *};
+use crate::{datastr::graph::time_dependent::*, util::in_range_option::*};
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Weak,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// What a live update reports about an edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LiveUpdateKind {
+    /// Current travel time in ms.
+    TravelTime(Weight),
+    /// Current speed in km/h. Requires edge lengths.
+    Speed(f64),
+    /// The edge can currently not be traversed.
+    Closure,
+}
+
+/// A live traffic report for a single edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveUpdate {
+    pub edge: EdgeId,
+    pub kind: LiveUpdateKind,
+    /// For how long (in ms, starting from the time the update is applied) the report is valid.
+    pub duration: Timestamp,
+}
+
+/// Parses a single line.
+///
+/// Lines starting with `{` are parsed as JSON objects with an `edge` and a `duration` field
+/// and one of `travel_time`, `speed` or `closed: true`.
+/// All other lines are parsed as CSV, either `edge,travel_time,duration` (the format of the `live_data` files)
+/// or `edge,kind,value,duration` where kind is one of `tt`, `speed` or `closed` (value may be empty for closures).
+impl FromStr for LiveUpdate {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            parse_json_update(line)
+        } else {
+            parse_csv_update(line)
+        }
+    }
+}
+
+fn parse_field<T: FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("invalid {name}: {value:?}"))
+}
+
+fn parse_csv_update(line: &str) -> Result<LiveUpdate, String> {
+    let fields: Vec<&str> = line.split(',').collect();
+    match fields[..] {
+        [edge, travel_time, duration] => Ok(LiveUpdate {
+            edge: parse_field(edge, "edge")?,
+            kind: LiveUpdateKind::TravelTime(parse_field(travel_time, "travel time")?),
+            duration: parse_field(duration, "duration")?,
+        }),
+        [edge, kind, value, duration] => {
+            let kind = match kind.trim() {
+                "tt" => LiveUpdateKind::TravelTime(parse_field(value, "travel time")?),
+                "speed" => LiveUpdateKind::Speed(parse_field(value, "speed")?),
+                "closed" => LiveUpdateKind::Closure,
+                other => return Err(format!("unknown update kind: {other:?}")),
+            };
+            Ok(LiveUpdate {
+                edge: parse_field(edge, "edge")?,
+                kind,
+                duration: parse_field(duration, "duration")?,
+            })
+        }
+        _ => Err(format!("expected 3 or 4 fields, got {}", fields.len())),
+    }
+}
+
+fn parse_json_update(line: &str) -> Result<LiveUpdate, String> {
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let uint_field = |name: &str| -> Result<Option<u32>, String> {
+        match value.get(name) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(field) => field
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .map(Some)
+                .ok_or_else(|| format!("invalid {name}: {field}")),
+        }
+    };
+
+    let edge = uint_field("edge")?.ok_or("missing field: edge")?;
+    let duration = uint_field("duration")?.ok_or("missing field: duration")?;
+    let kind = if value.get("closed").and_then(serde_json::Value::as_bool).unwrap_or(false) {
+        LiveUpdateKind::Closure
+    } else if let Some(travel_time) = uint_field("travel_time")? {
+        LiveUpdateKind::TravelTime(travel_time)
+    } else if let Some(speed) = value.get("speed") {
+        LiveUpdateKind::Speed(speed.as_f64().ok_or_else(|| format!("invalid speed: {speed}"))?)
+    } else {
+        return Err("expected one of travel_time, speed or closed".to_string());
+    };
+
+    Ok(LiveUpdate { edge, kind, duration })
+}
+
+/// The live data currently known for a graph.
+pub struct LiveTrafficState {
+    graph: TDGraph,
+    lengths: Option<Vec<Weight>>,
+    live: Vec<InRangeOption<(Weight, Timestamp)>>,
+    num_active: usize,
+}
+
+impl LiveTrafficState {
+    pub fn new(graph: TDGraph) -> Self {
+        let m = graph.num_arcs();
+        Self {
+            graph,
+            lengths: None,
+            live: vec![InRangeOption::NONE; m],
+            num_active: 0,
+        }
+    }
+
+    /// Edge lengths in meters are needed to convert speed updates into travel times.
+    pub fn with_lengths(graph: TDGraph, lengths: Vec<Weight>) -> Self {
+        assert_eq!(lengths.len(), graph.num_arcs());
+        Self {
+            lengths: Some(lengths),
+            ..Self::new(graph)
+        }
+    }
+
+    pub fn graph(&self) -> &TDGraph {
+        &self.graph
+    }
+
+    /// Number of edges which currently have live data.
+    pub fn num_active(&self) -> usize {
+        self.num_active
+    }
+
+    /// Apply an update received at `now`, replacing earlier live data of the edge.
+    ///
+    /// Live travel times are clamped to at least the lower bound of the predicted travel time function,
+    /// so the lower bounds used by the potentials remain valid.
+    /// Updates valid for a period or longer are not live data but long term changes and get rejected.
+    pub fn apply(&mut self, update: &LiveUpdate, now: Timestamp) -> Result<(), String> {
+        if update.edge as usize >= self.live.len() {
+            return Err(format!("edge {} out of range", update.edge));
+        }
+        if update.duration >= period() {
+            return Err(format!("duration {} not shorter than the period", update.duration));
+        }
+
+        let travel_time = match update.kind {
+            LiveUpdateKind::TravelTime(travel_time) => travel_time,
+            LiveUpdateKind::Speed(speed) => {
+                let lengths = self.lengths.as_ref().ok_or("speed update but no edge lengths available")?;
+                if speed.is_nan() || speed < 0.0 {
+                    return Err(format!("invalid speed: {speed}"));
+                }
+                if speed == 0.0 {
+                    INFINITY
+                } else {
+                    (lengths[update.edge as usize] as f64 * 3600.0 / speed).round().min(INFINITY as f64) as Weight
+                }
+            }
+            LiveUpdateKind::Closure => INFINITY,
+        };
+        let lower_bound = self.graph.travel_time_function(update.edge).lower_bound();
+        let travel_time = std::cmp::min(std::cmp::max(travel_time, lower_bound), INFINITY);
+
+        let entry = &mut self.live[update.edge as usize];
+        if entry.value().is_none() {
+            self.num_active += 1;
+        }
+        *entry = InRangeOption::some((travel_time, now + update.duration));
+        Ok(())
+    }
+
+    /// Drop all live data which is no longer valid at `now`. Returns the number of dropped entries.
+    pub fn expire(&mut self, now: Timestamp) -> usize {
+        let mut num_expired = 0;
+        for entry in &mut self.live {
+            if let Some((_, soon)) = entry.value() {
+                if soon <= now {
+                    *entry = InRangeOption::NONE;
+                    num_expired += 1;
+                }
+            }
+        }
+        self.num_active -= num_expired;
+        num_expired
+    }
+
+    pub fn snapshot(&self) -> PessimisticLiveTDGraph {
+        PessimisticLiveTDGraph::new(self.graph.clone(), self.live.clone())
+    }
+}
+
+/// Where live updates are read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateSource {
+    /// Follow a file, like `tail -f`.
+    File(PathBuf),
+    /// Listen for connections on a TCP address, each sending lines of updates.
+    Tcp(String),
+    /// Listen for connections on a unix socket, each sending lines of updates.
+    Unix(PathBuf),
+}
+
+/// Parses `tcp:<addr>`, `unix:<path>` or a plain file path.
+impl FromStr for UpdateSource {
+    type Err = std::convert::Infallible;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Ok(if let Some(addr) = source.strip_prefix("tcp:") {
+            UpdateSource::Tcp(addr.to_string())
+        } else if let Some(path) = source.strip_prefix("unix:") {
+            UpdateSource::Unix(PathBuf::from(path))
+        } else {
+            UpdateSource::File(PathBuf::from(source))
+        })
+    }
+}
+
+impl UpdateSource {
+    /// Start a background thread which parses incoming lines and forwards the updates.
+    /// Invalid lines are reported on stderr and skipped.
+    /// The thread terminates once the receiving end of `updates` is dropped and the next line arrives.
+    pub fn spawn(self, updates: Sender<LiveUpdate>) -> std::io::Result<JoinHandle<()>> {
+        match self {
+            UpdateSource::File(path) => {
+                let file = std::fs::File::open(path)?;
+                Ok(std::thread::spawn(move || tail(BufReader::new(file), &updates)))
+            }
+            UpdateSource::Tcp(addr) => {
+                let listener = std::net::TcpListener::bind(addr)?;
+                Ok(std::thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let updates = updates.clone();
+                        std::thread::spawn(move || forward_lines(BufReader::new(stream), &updates));
+                    }
+                }))
+            }
+            UpdateSource::Unix(path) => {
+                let listener = std::os::unix::net::UnixListener::bind(path)?;
+                Ok(std::thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let updates = updates.clone();
+                        std::thread::spawn(move || forward_lines(BufReader::new(stream), &updates));
+                    }
+                }))
+            }
+        }
+    }
+}
+
+fn forward_line(line: &str, updates: &Sender<LiveUpdate>) -> bool {
+    if line.trim().is_empty() || line.starts_with('#') {
+        return true;
+    }
+    match line.parse::<LiveUpdate>() {
+        Ok(update) => updates.send(update).is_ok(),
+        Err(e) => {
+            eprintln!("skipping invalid live update {:?}: {}", line.trim(), e);
+            true
+        }
+    }
+}
+
+/// Forward all lines until the reader is exhausted or nobody listens anymore.
+fn forward_lines(reader: impl BufRead, updates: &Sender<LiveUpdate>) {
+    for line in reader.lines() {
+        match line {
+            Ok(line) => {
+                if !forward_line(&line, updates) {
+                    return;
+                }
+            }
+            Err(e) => {
+                eprintln!("failed to read live updates: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Like `forward_lines` but waits for more data at the end instead of stopping.
+fn tail(mut reader: impl BufRead, updates: &Sender<LiveUpdate>) {
+    let mut line = String::new();
+    loop {
+        match reader.read_line(&mut line) {
+            Ok(0) => std::thread::sleep(Duration::from_millis(200)),
+            // only complete lines, the writer might still be in the middle of one
+            Ok(_) if line.ends_with('\n') => {
+                if !forward_line(&line, updates) {
+                    return;
+                }
+                line.clear();
+            }
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("failed to read live updates: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Maps wall clock time to timestamps of the graph.
+#[derive(Debug, Clone, Copy)]
+pub struct LiveClock {
+    started: Instant,
+    t_start: Timestamp,
+}
+
+impl LiveClock {
+    /// `t_start` is the graph time corresponding to the moment of construction.
+    pub fn new(t_start: Timestamp) -> Self {
+        Self {
+            started: Instant::now(),
+            t_start,
+        }
+    }
+
+    /// Current graph time. Does not wrap around but stays at the end of the period.
+    pub fn now(&self) -> Timestamp {
+        let elapsed = self.started.elapsed().as_millis().min(period() as u128) as Timestamp;
+        std::cmp::min(self.t_start.saturating_add(elapsed), period() - 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveTrafficParams {
+    /// Minimum time between two customizations.
+    pub customization_interval: Duration,
+    /// Number of applied updates or expired entries required to trigger a customization.
+    pub min_num_changes: usize,
+}
+
+impl Default for LiveTrafficParams {
+    fn default() -> Self {
+        Self {
+            customization_interval: Duration::from_secs(60),
+            min_num_changes: 1,
+        }
+    }
+}
+
+/// The result of one live customization, as handed to subscribers.
+pub struct LiveGeneration<'a> {
+    pub generation: u64,
+    pub t_live: Timestamp,
+    pub graph: PessimisticLiveTDGraph,
+    pub potential: MultiMetric<'a>,
+}
+
+/// Collects live updates and periodically publishes freshly customized potentials.
+pub struct LiveTrafficService<'a> {
+    state: LiveTrafficState,
+    preprocessed: MultiMetricPreprocessed<'a>,
+    params: LiveTrafficParams,
+    subscribers: Vec<(Sender<LiveGeneration<'a>>, Weak<()>)>,
+    generation: u64,
+    num_pending_changes: usize,
+    last_customization: Instant,
+}
+
+impl<'a> LiveTrafficService<'a> {
+    /// `preprocessed` must not contain a live metric yet; each customization starts from a copy of it.
+    pub fn new(state: LiveTrafficState, preprocessed: MultiMetricPreprocessed<'a>, params: LiveTrafficParams) -> Self {
+        Self {
+            state,
+            preprocessed,
+            params,
+            subscribers: Vec::new(),
+            generation: 0,
+            num_pending_changes: 0,
+            last_customization: Instant::now(),
+        }
+    }
+
+    pub fn state(&self) -> &LiveTrafficState {
+        &self.state
+    }
+
+    /// Number of generations published so far.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Subscribers only receive generations customized after subscribing.
+    pub fn subscribe(&mut self) -> LiveQueryServer<'a> {
+        let (sender, receiver) = channel();
+        let alive = Arc::new(());
+        self.subscribers.push((sender, Arc::downgrade(&alive)));
+        LiveQueryServer {
+            updates: receiver,
+            current: None,
+            _alive: alive,
+        }
+    }
+
+    pub fn apply(&mut self, update: &LiveUpdate, now: Timestamp) -> Result<(), String> {
+        self.state.apply(update, now)?;
+        self.num_pending_changes += 1;
+        Ok(())
+    }
+
+    pub fn customization_due(&self) -> bool {
+        self.num_pending_changes >= self.params.min_num_changes && self.last_customization.elapsed() >= self.params.customization_interval
+    }
+
+    /// Customize the current state and publish it to all subscribers.
+    /// Subscribers which were dropped get removed.
+    pub fn customize(&mut self, now: Timestamp) -> u64 {
+        self.state.expire(now);
+        let graph = self.state.snapshot();
+        let mut preprocessed = self.preprocessed.clone();
+        preprocessed.reserve_space_for_additional_metrics(1);
+        preprocessed.customize_live(&graph, now);
+
+        self.generation += 1;
+        let generation = self.generation;
+        self.subscribers.retain(|(subscriber, _)| {
+            subscriber
+                .send(LiveGeneration {
+                    generation,
+                    t_live: now,
+                    graph: graph.clone(),
+                    potential: MultiMetric::new(preprocessed.clone()),
+                })
+                .is_ok()
+        });
+        self.num_pending_changes = 0;
+        self.last_customization = Instant::now();
+        generation
+    }
+
+    /// Apply incoming updates and customize whenever due.
+    /// Returns when the update stream ends or no subscribers are left.
+    pub fn run(&mut self, updates: &Receiver<LiveUpdate>, clock: &LiveClock) {
+        loop {
+            self.subscribers.retain(|(_, alive)| alive.strong_count() > 0);
+            if self.subscribers.is_empty() {
+                return;
+            }
+
+            // without enough changes, nothing will be due before the next update or expiry, so don't spin
+            let timeout = if self.num_pending_changes < self.params.min_num_changes {
+                self.params.customization_interval
+            } else {
+                self.params.customization_interval.saturating_sub(self.last_customization.elapsed())
+            };
+            match updates.recv_timeout(timeout) {
+                Ok(update) => {
+                    if let Err(e) = self.apply(&update, clock.now()) {
+                        eprintln!("rejected live update {update:?}: {e}");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            self.num_pending_changes += self.state.expire(clock.now());
+            if self.customization_due() {
+                self.customize(clock.now());
+            }
+        }
+    }
+}
+
+type LiveServer<'a> = Server<PessimisticLiveTDGraph, PessimisticLiveTDDijkstraOps, MultiMetric<'a>, true, true, true>;
+
+/// Query server which switches to the latest published generation between queries.
+pub struct LiveQueryServer<'a> {
+    updates: Receiver<LiveGeneration<'a>>,
+    current: Option<(u64, Timestamp, LiveServer<'a>)>,
+    // lets the service notice when the server is gone, even while nothing gets published
+    _alive: Arc<()>,
+}
+
+impl<'a> LiveQueryServer<'a> {
+    fn switch_to(&mut self, generation: LiveGeneration<'a>) {
+        let server = Server::new(&generation.graph, generation.potential, PessimisticLiveTDDijkstraOps::default());
+        self.current = Some((generation.generation, generation.t_live, server));
+    }
+
+    /// Switch to the latest generation, if a new one was published. Older pending generations are skipped.
+    pub fn refresh(&mut self) -> bool {
+        if let Some(generation) = self.updates.try_iter().last() {
+            self.switch_to(generation);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Block until at least one generation is available. Returns false if the service is gone without publishing any.
+    pub fn wait_for_generation(&mut self) -> bool {
+        if self.refresh() || self.current.is_some() {
+            return true;
+        }
+        match self.updates.recv() {
+            Ok(generation) => {
+                self.switch_to(generation);
+                self.refresh();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn generation(&self) -> Option<u64> {
+        self.current.as_ref().map(|&(generation, _, _)| generation)
+    }
+
+    /// The time the live data of the current generation refers to.
+    pub fn t_live(&self) -> Option<Timestamp> {
+        self.current.as_ref().map(|&(_, t_live, _)| t_live)
+    }
+
+    /// Travel time of the quickest route with the latest live data, `None` if unreachable or no generation has been published yet.
+    pub fn query(&mut self, query: TDQuery<Timestamp>) -> Option<Weight> {
+        self.refresh();
+        self.current.as_mut()?.2.td_query(query).distance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_updates() {
+        assert_eq!(
+            "3,1500,60000".parse(),
+            Ok(LiveUpdate {
+                edge: 3,
+                kind: LiveUpdateKind::TravelTime(1500),
+                duration: 60000
+            })
+        );
+        assert_eq!(
+            "3,closed,,60000".parse(),
+            Ok(LiveUpdate {
+                edge: 3,
+                kind: LiveUpdateKind::Closure,
+                duration: 60000
+            })
+        );
+        assert_eq!(
+            r#"{"edge": 4, "speed": 30.5, "duration": 1000}"#.parse(),
+            Ok(LiveUpdate {
+                edge: 4,
+                kind: LiveUpdateKind::Speed(30.5),
+                duration: 1000
+            })
+        );
+        assert!("3,fast,1,1".parse::<LiveUpdate>().is_err());
+        assert!(r#"{"edge": 4, "duration": 1000}"#.parse::<LiveUpdate>().is_err());
+    }
+
+    #[test]
+    fn apply_and_expire() {
+        run_test_with_periodicity(86_400_000, || {
+            let graph = TDGraph::new(vec![0, 1, 1], vec![1], vec![0, 1], vec![0], vec![1000]);
+            let mut state = LiveTrafficState::with_lengths(graph, vec![100]);
+
+            let update = |kind, duration| LiveUpdate { edge: 0, kind, duration };
+            assert!(state.apply(&update(LiveUpdateKind::TravelTime(500), 86_400_000), 0).is_err());
+            assert!(state.apply(&update(LiveUpdateKind::TravelTime(500), 50_000), 0).is_ok());
+            // clamped to the lower bound
+            assert_eq!(state.snapshot().eval(0, 0), 1000);
+            assert!(state.apply(&update(LiveUpdateKind::Speed(36.0), 50_000), 0).is_ok());
+            assert_eq!(state.snapshot().eval(0, 0), 10000);
+            assert_eq!(state.num_active(), 1);
+
+            assert_eq!(state.expire(49_999), 0);
+            assert_eq!(state.expire(50_000), 1);
+            assert_eq!(state.num_active(), 0);
+            assert_eq!(state.snapshot().eval(0, 50_000), 1000);
+        });
+    }
+}
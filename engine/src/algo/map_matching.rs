@@ -0,0 +1,331 @@
+//! HMM based map matching of GPS traces.
+//!
+//! Follows the approach of Newson and Krumm (Hidden Markov Map Matching Through Noise and Sparseness, 2009).
+//! The candidates for each GPS point are the edges within a search radius, found through an `EdgeGridIndex`.
+//! Emission probabilities are Gaussian in the distance between the GPS point and the candidate.
+//! Transition probabilities are exponential in the difference between the route distance and the great circle distance of consecutive points.
+//! Route distances are obtained through CCH queries on the `geo_distance` metric.
+//! The most likely sequence of candidates is determined with the Viterbi algorithm.
+//! When no transition between two consecutive points is possible, the trace is split and matching continues with a new segment.
+
+use super::*;
+use crate::algo::customizable_contraction_hierarchy::{query::Server, Customized};
+use crate::datastr::spatial_index::*;
+use crate::link_speed_estimates::TraceData;
+use std::collections::HashMap;
+
+/// A raw GPS measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPoint {
+    pub timestamp: u64, // [ms]
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Parameters of the HMM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapMatchingParams {
+    /// Standard deviation of the GPS noise in meters.
+    pub gps_sigma: f64,
+    /// Scale of the exponential distribution for the difference between route and great circle distance in meters.
+    pub beta: f64,
+    /// Radius in meters in which candidate edges are searched.
+    pub search_radius: f64,
+    /// Maximum number of candidates per GPS point, the closest ones are kept.
+    pub max_candidates: usize,
+    /// Transitions with a route distance longer than this factor times the great circle distance (plus the search radius) are impossible.
+    pub max_detour_factor: f64,
+}
+
+impl Default for MapMatchingParams {
+    fn default() -> Self {
+        Self {
+            gps_sigma: 10.0,
+            beta: 5.0,
+            search_radius: 50.0,
+            max_candidates: 8,
+            max_detour_factor: 5.0,
+        }
+    }
+}
+
+/// A GPS point assigned to an edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchedPoint {
+    /// Index of the point in the input trace
+    pub point: usize,
+    pub edge: EdgeId,
+    /// Position on the edge as fraction from tail to head.
+    pub fraction: f64,
+    /// Distance in meters between the GPS point and its position on the edge.
+    pub distance: f64,
+}
+
+/// A consecutive part of a trace for which a connected path could be found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedSegment {
+    pub points: Vec<MatchedPoint>,
+    /// All edges traversed between the first and the last matched point, including their edges.
+    pub path: Vec<EdgeId>,
+}
+
+impl MatchedSegment {
+    /// Convert the matched points into the input format of `link_speed_estimates::estimate_iter`.
+    pub fn trace_data(&self, trace: &[GpsPoint]) -> Vec<TraceData> {
+        self.points
+            .iter()
+            .map(|point| TraceData {
+                timestamp: trace[point.point].timestamp,
+                link_id: point.edge as u64,
+                traversed_in_travel_direction_fraction: point.fraction as f32,
+            })
+            .collect()
+    }
+}
+
+/// Map matcher for GPS traces.
+/// `customized` has to be a CCH customized with the `geo_distance` metric.
+pub struct MapMatcher<'a, C> {
+    index: EdgeGridIndex<'a>,
+    first_out: &'a [EdgeId],
+    head: &'a [NodeId],
+    geo_distance: &'a [Weight],
+    server: Server<C>,
+    params: MapMatchingParams,
+    // route distances between nodes already queried for the current trace
+    distance_cache: HashMap<(NodeId, NodeId), Option<Weight>>,
+}
+
+impl<'a, C: Customized> MapMatcher<'a, C> {
+    pub fn new(
+        first_out: &'a [EdgeId],
+        head: &'a [NodeId],
+        geo_distance: &'a [Weight],
+        latitude: &'a [f32],
+        longitude: &'a [f32],
+        customized: C,
+        params: MapMatchingParams,
+    ) -> Self {
+        assert_eq!(head.len(), geo_distance.len());
+        Self {
+            index: EdgeGridIndex::new(first_out, head, latitude, longitude),
+            first_out,
+            head,
+            geo_distance,
+            server: Server::new(customized),
+            params,
+            distance_cache: HashMap::new(),
+        }
+    }
+
+    pub fn params(&self) -> &MapMatchingParams {
+        &self.params
+    }
+
+    /// Match a trace ordered by timestamp.
+    /// Points without any candidate are skipped.
+    /// Returns one segment for each part of the trace which could be matched to a connected path.
+    pub fn match_trace(&mut self, trace: &[GpsPoint]) -> Vec<MatchedSegment> {
+        let mut segments = Vec::new();
+
+        // candidates of the points in the current segment
+        let mut layers: Vec<(usize, Vec<EdgeCandidate>)> = Vec::new();
+        // index of the best predecessor in the previous layer for each candidate
+        let mut predecessors: Vec<Vec<usize>> = Vec::new();
+        // log probability of the most likely sequence ending in each candidate of the last layer
+        let mut scores: Vec<f64> = Vec::new();
+
+        for (idx, point) in trace.iter().enumerate() {
+            let mut candidates = self.index.edges_within(point.latitude, point.longitude, self.params.search_radius);
+            candidates.truncate(self.params.max_candidates);
+            if candidates.is_empty() {
+                continue;
+            }
+            let emission: Vec<f64> = candidates.iter().map(|candidate| self.emission_log_prob(candidate.distance)).collect();
+
+            let mut next_scores = vec![f64::NEG_INFINITY; candidates.len()];
+            let mut next_predecessors = vec![usize::MAX; candidates.len()];
+
+            if let Some((prev_idx, prev_candidates)) = layers.last() {
+                let prev = &trace[*prev_idx];
+                let great_circle = haversine_distance(prev.latitude, prev.longitude, point.latitude, point.longitude);
+                let max_route = self.params.max_detour_factor * (great_circle + self.params.search_radius);
+
+                for (to_idx, to) in candidates.iter().enumerate() {
+                    for (from_idx, from) in prev_candidates.iter().enumerate() {
+                        if scores[from_idx] == f64::NEG_INFINITY {
+                            continue;
+                        }
+                        match self.route_distance(from, to) {
+                            Some(route) if route <= max_route => {
+                                let score = scores[from_idx] + self.transition_log_prob(route, great_circle) + emission[to_idx];
+                                if score > next_scores[to_idx] {
+                                    next_scores[to_idx] = score;
+                                    next_predecessors[to_idx] = from_idx;
+                                }
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+
+                if next_scores.iter().all(|&score| score == f64::NEG_INFINITY) {
+                    // HMM break - finish the current segment and start a new one with this point
+                    segments.push(self.backtrack(&layers, &predecessors, &scores));
+                    layers.clear();
+                    predecessors.clear();
+                    next_scores.copy_from_slice(&emission);
+                }
+            } else {
+                next_scores.copy_from_slice(&emission);
+            }
+
+            layers.push((idx, candidates));
+            predecessors.push(next_predecessors);
+            scores = next_scores;
+        }
+
+        if !layers.is_empty() {
+            segments.push(self.backtrack(&layers, &predecessors, &scores));
+        }
+        self.distance_cache.clear();
+
+        segments
+    }
+
+    fn emission_log_prob(&self, distance: f64) -> f64 {
+        let sigma = self.params.gps_sigma;
+        -0.5 * (distance / sigma).powi(2) - (sigma * (2.0 * std::f64::consts::PI).sqrt()).ln()
+    }
+
+    fn transition_log_prob(&self, route: f64, great_circle: f64) -> f64 {
+        -(route - great_circle).abs() / self.params.beta - self.params.beta.ln()
+    }
+
+    fn edge_length(&self, edge: EdgeId) -> f64 {
+        self.geo_distance[edge as usize] as f64
+    }
+
+    fn node_distance(&mut self, from: NodeId, to: NodeId) -> Option<Weight> {
+        let server = &mut self.server;
+        *self
+            .distance_cache
+            .entry((from, to))
+            .or_insert_with(|| server.query(Query { from, to }).distance())
+    }
+
+    // Distance in meters along the graph from one position to another, `None` if unreachable.
+    fn route_distance(&mut self, from: &EdgeCandidate, to: &EdgeCandidate) -> Option<f64> {
+        if from.edge == to.edge && self.stays_on_edge(from.edge, from.fraction, to.fraction) {
+            return Some((to.fraction - from.fraction).max(0.0) * self.edge_length(from.edge));
+        }
+        let between = self.node_distance(self.head[from.edge as usize], self.index.tail(to.edge))?;
+        Some((1.0 - from.fraction) * self.edge_length(from.edge) + between as f64 + to.fraction * self.edge_length(to.edge))
+    }
+
+    // Consecutive points on the same edge are interpreted as staying on the edge.
+    // Small backward movements (within the GPS noise) are treated as standing still.
+    fn stays_on_edge(&self, edge: EdgeId, from_fraction: f64, to_fraction: f64) -> bool {
+        (from_fraction - to_fraction) * self.edge_length(edge) <= self.params.gps_sigma
+    }
+
+    fn backtrack(&mut self, layers: &[(usize, Vec<EdgeCandidate>)], predecessors: &[Vec<usize>], scores: &[f64]) -> MatchedSegment {
+        let mut best = (0..scores.len()).max_by(|&a, &b| scores[a].partial_cmp(&scores[b]).unwrap()).unwrap();
+        let mut points = Vec::with_capacity(layers.len());
+        for ((point, candidates), predecessors) in layers.iter().zip(predecessors).rev() {
+            let candidate = candidates[best];
+            points.push(MatchedPoint {
+                point: *point,
+                edge: candidate.edge,
+                fraction: candidate.fraction,
+                distance: candidate.distance,
+            });
+            best = predecessors[best];
+        }
+        points.reverse();
+
+        let mut path = vec![points[0].edge];
+        for i in 1..points.len() {
+            let (prev, next) = (points[i - 1], points[i]);
+            if prev.edge == next.edge && self.stays_on_edge(prev.edge, prev.fraction, next.fraction) {
+                // keep positions on the same edge monotone for speed estimation
+                points[i].fraction = points[i].fraction.max(prev.fraction);
+                continue;
+            }
+
+            let from = self.head[prev.edge as usize];
+            let to = self.index.tail(next.edge);
+            let nodes = self.server.query(Query { from, to }).node_path().unwrap();
+            for pair in nodes.windows(2) {
+                path.push(self.shortest_edge(pair[0], pair[1]));
+            }
+            path.push(next.edge);
+        }
+
+        MatchedSegment { points, path }
+    }
+
+    // The edge between two adjacent nodes with the smallest `geo_distance`
+    fn shortest_edge(&self, from: NodeId, to: NodeId) -> EdgeId {
+        (self.first_out[from as usize]..self.first_out[from as usize + 1])
+            .filter(|&edge| self.head[edge as usize] == to)
+            .min_by_key(|&edge| self.geo_distance[edge as usize])
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::customizable_contraction_hierarchy::*;
+    use crate::datastr::node_order::NodeOrder;
+
+    #[test]
+    fn matches_noisy_trace_onto_straight_road() {
+        // two parallel one way roads along the equator, 0 -> 1 -> 2 and 5 -> 4 -> 3 about 100m further north.
+        // roughly 111m per 0.001 degrees
+        let first_out = vec![0, 1, 2, 2, 2, 3, 4];
+        let head = vec![1, 2, 3, 4];
+        let geo_distance = vec![111, 111, 111, 111];
+        let latitude = vec![0.0, 0.0, 0.0, 0.0009, 0.0009, 0.0009];
+        let longitude = vec![0.0, 0.001, 0.002, 0.002, 0.001, 0.0];
+        let graph = FirstOutGraph::new(&first_out[..], &head[..], &geo_distance[..]);
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let customized = customize(&cch, &graph);
+
+        let mut matcher = MapMatcher::new(
+            &first_out,
+            &head,
+            &geo_distance,
+            &latitude,
+            &longitude,
+            customized,
+            MapMatchingParams::default(),
+        );
+        let trace = [
+            GpsPoint {
+                timestamp: 0,
+                latitude: 0.00005,
+                longitude: 0.0002,
+            },
+            GpsPoint {
+                timestamp: 10_000,
+                latitude: -0.00003,
+                longitude: 0.0008,
+            },
+            GpsPoint {
+                timestamp: 20_000,
+                latitude: 0.00004,
+                longitude: 0.0015,
+            },
+        ];
+
+        let segments = matcher.match_trace(&trace);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].path, vec![0, 1]);
+        assert_eq!(segments[0].points.iter().map(|p| p.edge).collect::<Vec<_>>(), vec![0, 0, 1]);
+        let traces = segments[0].trace_data(&trace);
+        assert_eq!(traces[2].link_id, 1);
+        assert!((traces[2].traversed_in_travel_direction_fraction - 0.5).abs() < 0.01);
+    }
+}
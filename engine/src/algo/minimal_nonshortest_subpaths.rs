@@ -1,7 +1,7 @@
 use super::*;
 
 use crate::{
-    algo::{a_star::*, ch_potentials::*, customizable_contraction_hierarchy::*, traffic_aware::TRAFFIC_MAX_QUERY_TIME},
+    algo::{a_star::*, ch_potentials::*, customizable_contraction_hierarchy::*, traffic_aware::traffic_max_query_time},
     datastr::{graph::first_out_graph::BorrowedGraph, rank_select_map::BitVec},
     report::*,
     util::{in_range_option::InRangeOption, with_index},
@@ -127,7 +127,7 @@ impl<'a> MinimalNonShortestSubPaths<'a> {
             self.fix_violating_subpaths_int(result.as_deref().unwrap_or(orig_path), epsilon)
         } {
             let _it = iterations_ctxt.push_collection_item();
-            if TRAFFIC_MAX_QUERY_TIME.map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
+            if traffic_max_query_time().map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
                 report!("num_iterations", i);
                 return Err(fixed);
             }
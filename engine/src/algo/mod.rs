@@ -11,14 +11,21 @@ pub mod ch_potentials;
 pub mod contraction_hierarchy;
 pub mod customizable_contraction_hierarchy;
 pub mod dijkstra;
+pub mod edge_exclusion;
+pub mod ev_routing;
 pub mod hl;
+pub mod isochrone;
+pub mod live_traffic;
+pub mod map_matching;
 pub mod metric_merging;
 pub mod minimal_nonshortest_subpaths;
+pub mod pareto;
 pub mod rphast;
 pub mod td_astar;
 pub mod time_dependent_sampling;
 pub mod topocore;
 pub mod traffic_aware;
+pub mod vrp;
 
 pub trait GenQuery<Label> {
     fn new(from: NodeId, to: NodeId, initial_state: Label) -> Self;
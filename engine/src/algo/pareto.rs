@@ -0,0 +1,471 @@
+//! Bicriteria routing: travel time against a second additive metric such as distance or tolls.
+//!
+//! `ParetoServer` runs an exact label setting search on `MultiCritDijkstraRun` and returns the full Pareto front.
+//! Labels dominated by another label at the same node are dropped.
+//! With lower bound potentials for both criteria (e.g. CH potentials on each metric), labels which can not
+//! improve on what has already been found at the target are pruned early.
+//! `LinearCombinationServer` only finds the supported solutions of the front (the ones on its convex hull)
+//! by answering CCH queries on `alpha * travel_time + (1 - alpha) * secondary` for a fixed set of alphas.
+
+use super::{
+    a_star::{Potential, ZeroPotential},
+    customizable_contraction_hierarchy::{customize, query::Server as CCHServer, CustomizedBasic, CCH},
+    dijkstra::{gen_topo_dijkstra::Neutral, generic_dijkstra::*, *},
+    *,
+};
+use crate::{datastr::timestamped_vector::*, report::*};
+
+/// Tentative label: both criteria and the id of the entry to reconstruct its path from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParetoLabel {
+    pub travel_time: Weight,
+    pub secondary: Weight,
+    entry: u32,
+}
+
+impl ParetoLabel {
+    /// Weak dominance, so only one of two labels with equal criteria survives.
+    fn dominates(&self, other: &Self) -> bool {
+        self.travel_time <= other.travel_time && self.secondary <= other.secondary
+    }
+}
+
+impl Reset for ParetoLabel {
+    const DEFAULT: Self = ParetoLabel {
+        travel_time: INFINITY,
+        secondary: INFINITY,
+        entry: u32::MAX,
+    };
+}
+
+impl Label for ParetoLabel {
+    type Key = Weight;
+    fn neutral() -> Self {
+        Self::DEFAULT
+    }
+    fn key(&self) -> Self::Key {
+        self.travel_time
+    }
+}
+
+impl PartialEq for NodeQueueLabelOrder<ParetoLabel> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.0.travel_time, self.0.secondary).eq(&(other.0.travel_time, other.0.secondary))
+    }
+}
+impl PartialOrd for NodeQueueLabelOrder<ParetoLabel> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Eq for NodeQueueLabelOrder<ParetoLabel> {}
+impl Ord for NodeQueueLabelOrder<ParetoLabel> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // switched for reversing
+        (other.0.travel_time, other.0.secondary).cmp(&(self.0.travel_time, self.0.secondary))
+    }
+}
+
+/// Parent pointer of a label.
+/// Labels can be removed from the node heaps when they get dominated after being settled,
+/// so these live in a separate arena which is only cleared between queries.
+#[derive(Debug, Clone, Copy)]
+pub struct LabelEntry {
+    node: NodeId,
+    edge: EdgeId,
+    parent: u32,
+}
+
+pub struct ParetoOps<'a, PT, PS> {
+    secondary: &'a [Weight],
+    travel_time_potential: PT,
+    secondary_potential: PS,
+    target: NodeId,
+    entries: Vec<LabelEntry>,
+}
+
+impl<'a, G, PT, PS> MultiCritDijkstraOps<G> for ParetoOps<'a, PT, PS>
+where
+    G: EdgeRandomAccessGraph<Link>,
+    PT: Potential,
+    PS: Potential,
+{
+    type Label = ParetoLabel;
+    type Arc = (NodeIdT, EdgeIdT);
+    type LinkResult = Option<(ParetoLabel, LabelEntry)>;
+    type PredecessorLink = ();
+
+    fn link(
+        &mut self,
+        graph: &G,
+        labels: &TimestampedVector<MultiCritNodeData<Self::Label>>,
+        _parents: &[(NodeId, Self::PredecessorLink)],
+        _tail: NodeIdT,
+        _key: Weight,
+        label: &Self::Label,
+        &(NodeIdT(head), EdgeIdT(edge)): &Self::Arc,
+    ) -> Self::LinkResult {
+        let linked = ParetoLabel {
+            travel_time: label.travel_time + graph.link(edge).weight,
+            secondary: label.secondary + self.secondary[edge as usize],
+            entry: label.entry,
+        };
+        let lower_bound = ParetoLabel {
+            travel_time: linked.travel_time + self.travel_time_potential.potential(head)?,
+            secondary: linked.secondary + self.secondary_potential.potential(head)?,
+            entry: label.entry,
+        };
+        // target labels are final once settled
+        if labels[self.target as usize]
+            .popped()
+            .iter()
+            .any(|NodeQueueLabelOrder(found)| found.dominates(&lower_bound))
+        {
+            return None;
+        }
+
+        Some((
+            linked,
+            LabelEntry {
+                node: head,
+                edge,
+                parent: label.entry,
+            },
+        ))
+    }
+
+    fn merge(&mut self, label: &mut MultiCritNodeData<Self::Label>, linked: Self::LinkResult) -> Option<Weight> {
+        let (mut linked, entry) = linked?;
+        let mut dominated = false;
+        label.retain(|NodeQueueLabelOrder(old_l)| {
+            if old_l.dominates(&linked) {
+                dominated = true;
+            }
+            dominated || !linked.dominates(old_l)
+        });
+
+        if dominated {
+            None
+        } else {
+            linked.entry = self.entries.len() as u32;
+            self.entries.push(entry);
+            label.push(NodeQueueLabelOrder(linked));
+            Some(linked.travel_time)
+        }
+    }
+
+    fn predecessor_link(&self, _link: &Self::Arc) -> Self::PredecessorLink {}
+}
+
+/// One Pareto optimal path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParetoPath {
+    pub travel_time: Weight,
+    pub secondary: Weight,
+    pub node_path: Vec<NodeId>,
+    pub edge_path: Vec<EdgeId>,
+}
+
+/// Pareto optimal paths ordered by increasing travel time (and thus decreasing secondary cost).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParetoFront {
+    pub paths: Vec<ParetoPath>,
+}
+
+impl ParetoFront {
+    fn from_paths(mut paths: Vec<ParetoPath>) -> Self {
+        paths.sort_by_key(|p| (p.travel_time, p.secondary));
+        let mut min_secondary = INFINITY;
+        paths.retain(|p| {
+            let keep = p.secondary < min_secondary;
+            min_secondary = std::cmp::min(min_secondary, p.secondary);
+            keep
+        });
+        Self { paths }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Path with the lowest travel time.
+    pub fn fastest(&self) -> Option<&ParetoPath> {
+        self.paths.first()
+    }
+
+    /// Path with the lowest secondary cost.
+    pub fn cheapest(&self) -> Option<&ParetoPath> {
+        self.paths.last()
+    }
+}
+
+/// Exact bicriteria search.
+/// The travel time is taken from the graph weights, the secondary criterion is indexed by edge id.
+pub struct ParetoServer<'a, G, PT = ZeroPotential, PS = ZeroPotential> {
+    graph: &'a G,
+    data: DijkstraData<ParetoLabel, (), MultiCritNodeData<ParetoLabel>>,
+    ops: ParetoOps<'a, PT, PS>,
+    num_labels: usize,
+}
+
+impl<'a, G: Graph> ParetoServer<'a, G> {
+    pub fn new(graph: &'a G, secondary: &'a [Weight]) -> Self {
+        Self::with_potentials(graph, secondary, ZeroPotential(), ZeroPotential())
+    }
+}
+
+impl<'a, G: Graph, PT, PS> ParetoServer<'a, G, PT, PS> {
+    /// Both potentials have to be lower bounds of their respective criterion, e.g. CH potentials
+    /// on the travel time and on the secondary metric.
+    pub fn with_potentials(graph: &'a G, secondary: &'a [Weight], travel_time_potential: PT, secondary_potential: PS) -> Self {
+        assert_eq!(secondary.len(), graph.num_arcs());
+        Self {
+            graph,
+            data: DijkstraData::new(graph.num_nodes()),
+            ops: ParetoOps {
+                secondary,
+                travel_time_potential,
+                secondary_potential,
+                target: 0,
+                entries: Vec::new(),
+            },
+            num_labels: 0,
+        }
+    }
+
+    /// Number of labels created during the last query.
+    pub fn num_labels(&self) -> usize {
+        self.num_labels
+    }
+}
+
+impl<'a, G, PT, PS> ParetoServer<'a, G, PT, PS>
+where
+    G: LinkIterable<(NodeIdT, EdgeIdT)> + EdgeRandomAccessGraph<Link>,
+    PT: Potential,
+    PS: Potential,
+{
+    pub fn pareto_query(&mut self, Query { from, to }: Query) -> ParetoFront {
+        report!("algo", "Pareto Dijkstra Query");
+
+        self.ops.travel_time_potential.init(to);
+        self.ops.secondary_potential.init(to);
+        self.ops.target = to;
+        self.ops.entries.clear();
+        self.ops.entries.push(LabelEntry {
+            node: from,
+            edge: EdgeId::MAX,
+            parent: u32::MAX,
+        });
+
+        if self.ops.travel_time_potential.potential(from).is_none() || self.ops.secondary_potential.potential(from).is_none() {
+            self.num_labels = 1;
+            return ParetoFront::default();
+        }
+
+        let mut run = MultiCritDijkstraRun::query(
+            self.graph,
+            &mut self.data,
+            &mut self.ops,
+            DijkstraInit {
+                source: NodeIdT(from),
+                initial_state: ParetoLabel {
+                    travel_time: 0,
+                    secondary: 0,
+                    entry: 0,
+                },
+            },
+            |_| Some(Neutral()),
+        );
+        let mut num_queue_pops: usize = 0;
+        while run.next().is_some() {
+            num_queue_pops += 1;
+        }
+        report!("num_queue_pops", num_queue_pops);
+        report!("num_relaxed_arcs", run.num_relaxed_arcs());
+
+        let found: Vec<ParetoLabel> = run.tentative_distance(to).popped().iter().map(|NodeQueueLabelOrder(l)| *l).collect();
+        self.num_labels = self.ops.entries.len();
+        report!("num_labels", self.num_labels);
+
+        let paths = found
+            .into_iter()
+            .map(|label| {
+                let (node_path, edge_path) = self.unpack(label.entry);
+                ParetoPath {
+                    travel_time: label.travel_time,
+                    secondary: label.secondary,
+                    node_path,
+                    edge_path,
+                }
+            })
+            .collect();
+        let front = ParetoFront::from_paths(paths);
+        report!("num_pareto_paths", front.len());
+        front
+    }
+
+    fn unpack(&self, mut entry: u32) -> (Vec<NodeId>, Vec<EdgeId>) {
+        let mut node_path = Vec::new();
+        let mut edge_path = Vec::new();
+        while entry != u32::MAX {
+            let LabelEntry { node, edge, parent } = self.ops.entries[entry as usize];
+            node_path.push(node);
+            if parent != u32::MAX {
+                edge_path.push(edge);
+            }
+            entry = parent;
+        }
+        node_path.reverse();
+        edge_path.reverse();
+        (node_path, edge_path)
+    }
+}
+
+/// Combined weight of a single edge. Both metrics should be scaled to comparable units beforehand.
+pub fn combined_weight(travel_time: Weight, secondary: Weight, alpha: f64) -> Weight {
+    debug_assert!((0.0..=1.0).contains(&alpha));
+    if travel_time >= INFINITY || secondary >= INFINITY {
+        return INFINITY;
+    }
+    (alpha * travel_time as f64 + (1.0 - alpha) * secondary as f64).round() as Weight
+}
+
+/// Heuristic front from one CCH customization per alpha.
+/// Only supported Pareto optimal paths can be found this way, paths in the concave parts of the front are missed.
+pub struct LinearCombinationServer<'a, G> {
+    graph: &'a G,
+    secondary: &'a [Weight],
+    servers: Vec<(f64, CCHServer<CustomizedBasic<'a, CCH>>)>,
+}
+
+impl<'a, G> LinearCombinationServer<'a, G>
+where
+    G: LinkIterable<(NodeIdT, EdgeIdT)> + EdgeRandomAccessGraph<Link> + EdgeIdGraph + Sync,
+{
+    pub fn new(cch: &'a CCH, graph: &'a G, secondary: &'a [Weight], alphas: &[f64]) -> Self {
+        assert_eq!(secondary.len(), graph.num_arcs());
+        let mut first_out = Vec::with_capacity(graph.num_nodes() + 1);
+        let mut head = Vec::with_capacity(graph.num_arcs());
+        first_out.push(0);
+        for node in 0..graph.num_nodes() as NodeId {
+            head.extend(LinkIterable::<(NodeIdT, EdgeIdT)>::link_iter(graph, node).map(|(NodeIdT(head), _)| head));
+            first_out.push(head.len() as EdgeId);
+        }
+
+        let servers = alphas
+            .iter()
+            .map(|&alpha| {
+                let weight = (0..graph.num_arcs())
+                    .map(|edge| combined_weight(graph.link(edge as EdgeId).weight, secondary[edge], alpha))
+                    .collect::<Vec<_>>();
+                let metric = FirstOutGraph::new(&first_out[..], &head[..], &weight[..]);
+                let customized = {
+                    let _blocked = block_reporting();
+                    customize(cch, &metric)
+                };
+                (alpha, CCHServer::new(customized))
+            })
+            .collect();
+
+        Self { graph, secondary, servers }
+    }
+
+    pub fn alphas(&self) -> impl Iterator<Item = f64> + '_ {
+        self.servers.iter().map(|(alpha, _)| *alpha)
+    }
+
+    pub fn pareto_query(&mut self, query: Query) -> ParetoFront {
+        let mut paths = Vec::with_capacity(self.servers.len());
+        for (alpha, server) in &mut self.servers {
+            let mut result = server.query(query);
+            if let Some(node_path) = result.node_path() {
+                let edge_path = Self::edge_path(self.graph, self.secondary, &node_path, *alpha);
+                paths.push(ParetoPath {
+                    travel_time: edge_path.iter().map(|&edge| self.graph.link(edge).weight).sum(),
+                    secondary: edge_path.iter().map(|&edge| self.secondary[edge as usize]).sum(),
+                    node_path,
+                    edge_path,
+                });
+            }
+        }
+        ParetoFront::from_paths(paths)
+    }
+
+    // Parallel edges are resolved by picking the one the customization used, i.e. the one with the minimal combined weight.
+    fn edge_path(graph: &G, secondary: &[Weight], node_path: &[NodeId], alpha: f64) -> Vec<EdgeId> {
+        node_path
+            .windows(2)
+            .map(|w| {
+                graph
+                    .edge_indices(w[0], w[1])
+                    .map(|EdgeIdT(edge)| edge)
+                    .min_by_key(|&edge| combined_weight(graph.link(edge).weight, secondary[edge as usize], alpha))
+                    .unwrap()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::{ch_potentials::BorrowedCCHPot, customizable_contraction_hierarchy::contract};
+    use crate::datastr::node_order::NodeOrder;
+
+    // 0 -> 1 -> 3 is fast but long, 0 -> 2 -> 3 short but slow and 0 -> 1 -> 2 -> 3 in between.
+    // 0 -> 3 is dominated by 0 -> 1 -> 3.
+    fn graph() -> (OwnedGraph, Vec<Weight>) {
+        (
+            OwnedGraph::new(vec![0, 3, 5, 6, 6], vec![1, 2, 3, 2, 3, 3], vec![1, 5, 4, 1, 1, 5]),
+            vec![10, 2, 30, 1, 10, 2],
+        )
+    }
+
+    #[test]
+    fn finds_full_pareto_front() {
+        let (graph, distance) = graph();
+        let mut server = ParetoServer::new(&graph, &distance);
+        let front = server.pareto_query(Query { from: 0, to: 3 });
+
+        let criteria: Vec<_> = front.paths.iter().map(|p| (p.travel_time, p.secondary)).collect();
+        assert_eq!(criteria, vec![(2, 20), (7, 13), (10, 4)]);
+        assert_eq!(front.paths[1].node_path, vec![0, 1, 2, 3]);
+        assert_eq!(front.paths[1].edge_path, vec![0, 3, 5]);
+        assert!(server.pareto_query(Query { from: 3, to: 0 }).is_empty());
+    }
+
+    #[test]
+    fn potentials_prune_without_changing_the_front() {
+        let (graph, distance) = graph();
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let distance_graph = FirstOutGraph::new(graph.first_out(), graph.head(), &distance[..]);
+        let travel_time_customized = customize(&cch, &graph);
+        let distance_customized = customize(&cch, &distance_graph);
+
+        let exact = ParetoServer::new(&graph, &distance).pareto_query(Query { from: 0, to: 3 });
+        let mut server = ParetoServer::with_potentials(
+            &graph,
+            &distance,
+            BorrowedCCHPot::new_from_customized(&travel_time_customized),
+            BorrowedCCHPot::new_from_customized(&distance_customized),
+        );
+        assert_eq!(server.pareto_query(Query { from: 0, to: 3 }), exact);
+    }
+
+    #[test]
+    fn linear_combinations_find_supported_paths() {
+        let (graph, distance) = graph();
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let mut server = LinearCombinationServer::new(&cch, &graph, &distance, &[0.0, 0.5, 1.0]);
+        let front = server.pareto_query(Query { from: 0, to: 3 });
+
+        let criteria: Vec<_> = front.paths.iter().map(|p| (p.travel_time, p.secondary)).collect();
+        assert_eq!(criteria, vec![(2, 20), (10, 4)]);
+        assert_eq!(front.cheapest().unwrap().node_path, vec![0, 2, 3]);
+    }
+}
@@ -161,6 +161,7 @@ impl<'a> crate::io::ReconstructPrepared<MultiMetric<'a>> for &'a CCH {
     }
 }
 
+#[derive(Clone)]
 pub struct MultiMetricPreprocessed<'a> {
     metric_ranges: Vec<(TRange<Timestamp>, u16, bool)>,
     fw_metrics: Vec<Weight>,
@@ -732,7 +733,7 @@ impl<W: Copy> crate::io::Deconstruct for MinMaxPotential<'_, W> {
     }
 }
 
-impl<'a, W: Copy + Default> crate::io::ReconstructPrepared<MinMaxPotential<'a, W>> for &'a CCH {
+impl<'a, W: Copy + Default + 'static> crate::io::ReconstructPrepared<MinMaxPotential<'a, W>> for &'a CCH {
     fn reconstruct_with(self, loader: crate::io::Loader) -> std::io::Result<MinMaxPotential<'a, W>> {
         let _blocked = block_reporting();
         let n = self.num_nodes();
@@ -776,7 +777,7 @@ impl<W: Copy> crate::io::Deconstruct for IntervalMinPotential<'_, W> {
     }
 }
 
-impl<'a, W: Copy + Default> crate::io::ReconstructPrepared<IntervalMinPotential<'a, W>> for &'a CCH {
+impl<'a, W: Copy + Default + 'static> crate::io::ReconstructPrepared<IntervalMinPotential<'a, W>> for &'a CCH {
     fn reconstruct_with(self, loader: crate::io::Loader) -> std::io::Result<IntervalMinPotential<'a, W>> {
         let _blocked = block_reporting();
         let n = self.num_nodes();
@@ -924,7 +925,7 @@ impl<'a> IntervalMinPotential<'a, LiveToPredictedBounds> {
                         let sources_idxs = first_source[edge_idx] as usize..first_source[edge_idx + 1] as usize;
                         let sources = &sources[sources_idxs];
                         if !sources.is_empty() {
-                            let mut cursor = SourceCursor::valid_at(sources, FlTimestamp::new(t_live as f64 / 1000.0));
+                            let mut cursor = SourceCursor::valid_at(sources, FlTimestamp::new(t_live as f64 / 1000.0), catchup.period);
                             while cursor.cur().0.fuzzy_lt(FlTimestamp::new(longest_live as f64 / 1000.0)) {
                                 let source_live_until = match cursor.cur().1.into() {
                                     ShortcutSource::Shortcut(down, up) => {
@@ -15,19 +15,16 @@ pub mod td_live;
 pub mod td_traffic_pots;
 pub mod time_dependent;
 
-/// Maximum time (ms) per query
-#[cfg(not(override_traffic_max_query_time))]
-pub const TRAFFIC_MAX_QUERY_TIME: Option<u128> = Some(10000);
-#[cfg(override_traffic_max_query_time)]
-pub const TRAFFIC_MAX_QUERY_TIME: Option<u128> = parse_max_traffic_query_time(include!(concat!(env!("OUT_DIR"), "/TRAFFIC_MAX_QUERY_TIME")));
-
-#[cfg(override_traffic_max_query_time)]
-const fn parse_max_traffic_query_time(input: u128) -> Option<u128> {
-    if input == 0 {
-        None
-    } else {
-        Some(input)
-    }
+/// Maximum time (ms) per query.
+/// Defaults to 10s and can be overridden at runtime through the TRAFFIC_MAX_QUERY_TIME env var, where 0 disables the limit.
+pub fn traffic_max_query_time() -> Option<u128> {
+    static MAX_QUERY_TIME: std::sync::OnceLock<Option<u128>> = std::sync::OnceLock::new();
+    *MAX_QUERY_TIME.get_or_init(|| {
+        std::env::var("TRAFFIC_MAX_QUERY_TIME").map_or(Some(10000), |max| {
+            let max: u128 = max.parse().expect("TRAFFIC_MAX_QUERY_TIME must be a number of milliseconds");
+            Some(max).filter(|&max| max != 0)
+        })
+    })
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -414,7 +411,7 @@ impl<'a> TrafficAwareServer<'a> {
         let mut total_queue_pops = 0usize;
         let mut iterations_ctxt = push_collection_context("iterations");
         let result = loop {
-            if TRAFFIC_MAX_QUERY_TIME.map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
+            if traffic_max_query_time().map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
                 break None;
             }
 
@@ -552,7 +549,7 @@ impl<'a> HeuristicTrafficAwareServer<'a> {
         let mut i: usize = 0;
         let mut iterations_ctxt = push_collection_context("iterations");
         let result = loop {
-            if TRAFFIC_MAX_QUERY_TIME.map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
+            if traffic_max_query_time().map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
                 break None;
             }
 
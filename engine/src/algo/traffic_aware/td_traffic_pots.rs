@@ -641,7 +641,7 @@ impl<W: Copy> crate::io::Deconstruct for MinMaxPotential<'_, W> {
     }
 }
 
-impl<'a, W: Copy + Default> crate::io::ReconstructPrepared<MinMaxPotential<'a, W>> for &'a CCH {
+impl<'a, W: Copy + Default + 'static> crate::io::ReconstructPrepared<MinMaxPotential<'a, W>> for &'a CCH {
     fn reconstruct_with(self, loader: crate::io::Loader) -> std::io::Result<MinMaxPotential<'a, W>> {
         let _blocked = block_reporting();
         let n = self.num_nodes();
@@ -685,7 +685,7 @@ impl<W: Copy> crate::io::Deconstruct for IntervalMinPotential<'_, W> {
     }
 }
 
-impl<'a, W: Copy + Default> crate::io::ReconstructPrepared<IntervalMinPotential<'a, W>> for &'a CCH {
+impl<'a, W: Copy + Default + 'static> crate::io::ReconstructPrepared<IntervalMinPotential<'a, W>> for &'a CCH {
     fn reconstruct_with(self, loader: crate::io::Loader) -> std::io::Result<IntervalMinPotential<'a, W>> {
         let _blocked = block_reporting();
         let n = self.num_nodes();
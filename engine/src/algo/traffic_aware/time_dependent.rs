@@ -41,7 +41,7 @@ impl<'a, P: TDPotential> TDTrafficAwareServer<'a, P> {
         let mut total_queue_pops = 0usize;
         let mut iterations_ctxt = push_collection_context("iterations");
         let result = loop {
-            if TRAFFIC_MAX_QUERY_TIME.map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
+            if traffic_max_query_time().map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
                 break None;
             }
 
@@ -186,7 +186,7 @@ impl<'a, P: td_astar::TDPotential> HeuristicTDTrafficAwareServer<'a, P> {
         let mut i: usize = 0;
         let mut iterations_ctxt = push_collection_context("iterations");
         let result = loop {
-            if TRAFFIC_MAX_QUERY_TIME.map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
+            if traffic_max_query_time().map(|m| timer.get_passed_ms() > m).unwrap_or(false) {
                 break None;
             }
 
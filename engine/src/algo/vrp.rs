@@ -0,0 +1,497 @@
+//! Capacitated vehicle routing with time windows on road network travel times.
+//!
+//! All vehicles start and end their tours at a single depot.
+//! Travel times between all locations are collected into a `DistanceMatrix`, usually through SSE RPHAST on a customized CCH.
+//! The solver builds initial tours by cheapest insertion and improves them by local search
+//! with 2-opt (reversing a segment of a tour), relocate (moving a single stop) and or-opt (moving a chain of two or three stops) moves.
+//! Moves are applied as soon as they reduce the total travel time and keep all tours feasible.
+//! Stops which can not be served by any vehicle are reported as unassigned.
+
+use super::{
+    customizable_contraction_hierarchy::{query::Server as CCHServer, Customized, CCHT},
+    rphast::*,
+    *,
+};
+use crate::report::*;
+
+/// Travel times between locations, row major.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceMatrix {
+    size: usize,
+    distances: Vec<Weight>,
+}
+
+impl DistanceMatrix {
+    pub fn from_fn(size: usize, mut distance: impl FnMut(usize, usize) -> Weight) -> Self {
+        let mut distances = Vec::with_capacity(size * size);
+        for from in 0..size {
+            for to in 0..size {
+                distances.push(distance(from, to));
+            }
+        }
+        Self { size, distances }
+    }
+
+    /// Many-to-many travel times between `locations` through SSE RPHAST.
+    /// Sources are swept together in batches to keep the memory for the intermediate distances bounded.
+    /// Locations may contain the same node several times, it will only be queried once.
+    pub fn from_customized<C: Customized>(customized: &C, locations: &[NodeId]) -> Self {
+        const BATCH_SIZE: usize = 128;
+
+        let mut unique_nodes = locations.to_vec();
+        unique_nodes.sort_unstable();
+        unique_nodes.dedup();
+
+        // flipped, so the selection contains the sources and the buckets the targets
+        let mut rphast = RPHAST::new(customized.backward_graph(), customized.forward_graph(), customized.cch().node_order().clone());
+        rphast.select(&unique_nodes);
+        let mut query = SSERPHASTQuery::new(&rphast);
+
+        let num_unique = unique_nodes.len();
+        let mut unique_distances = vec![INFINITY; num_unique * num_unique];
+        for (batch_idx, targets) in unique_nodes.chunks(BATCH_SIZE).enumerate() {
+            let result = query.query(targets, &rphast);
+            for (from, &node) in unique_nodes.iter().enumerate() {
+                let offset = from * num_unique + batch_idx * BATCH_SIZE;
+                unique_distances[offset..offset + targets.len()].copy_from_slice(result.distances(node));
+            }
+        }
+        for i in 0..num_unique {
+            unique_distances[i * num_unique + i] = 0;
+        }
+
+        let unique_idxs: Vec<usize> = locations.iter().map(|node| unique_nodes.binary_search(node).unwrap()).collect();
+        Self::from_fn(locations.len(), |from, to| unique_distances[unique_idxs[from] * num_unique + unique_idxs[to]])
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, from: usize, to: usize) -> Weight {
+        self.distances[from * self.size + to]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stop {
+    pub node: NodeId,
+    pub demand: u32,
+    pub service_time: Weight,
+    /// Service has to start within this window. Arriving early means waiting.
+    pub time_window: (Weight, Weight),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vehicle {
+    pub capacity: u32,
+    /// Earliest departure from and latest return to the depot.
+    pub shift: (Weight, Weight),
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub depot: NodeId,
+    pub stops: Vec<Stop>,
+    pub vehicles: Vec<Vehicle>,
+}
+
+impl Problem {
+    /// The depot followed by all stops. This is the index space of the distance matrix.
+    pub fn locations(&self) -> Vec<NodeId> {
+        std::iter::once(self.depot).chain(self.stops.iter().map(|s| s.node)).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tour {
+    pub vehicle: usize,
+    /// Indices into `Problem::stops` in visiting order.
+    pub stops: Vec<usize>,
+    /// Start of service at each stop.
+    pub service_starts: Vec<Weight>,
+    pub departure: Weight,
+    pub return_time: Weight,
+    pub travel_time: Weight,
+    pub load: u32,
+    /// Node path through the road network, empty until unpacked.
+    pub path: Vec<NodeId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    /// Only vehicles which are actually used.
+    pub tours: Vec<Tour>,
+    pub unassigned: Vec<usize>,
+    pub travel_time: Weight,
+}
+
+impl Solution {
+    /// Unpack the road network paths of all tours with a server on the same metric as the distance matrix.
+    pub fn unpack_paths<C: Customized>(&mut self, problem: &Problem, server: &mut CCHServer<C>) {
+        for tour in &mut self.tours {
+            let locations: Vec<NodeId> = std::iter::once(problem.depot)
+                .chain(tour.stops.iter().map(|&s| problem.stops[s].node))
+                .chain(std::iter::once(problem.depot))
+                .collect();
+            tour.path = vec![problem.depot];
+            for leg in locations.windows(2) {
+                if leg[0] == leg[1] {
+                    continue;
+                }
+                let path = server
+                    .query(Query { from: leg[0], to: leg[1] })
+                    .node_path()
+                    .expect("tour legs have to be connected");
+                tour.path.extend_from_slice(&path[1..]);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolverParams {
+    /// Upper bound for the number of improving moves.
+    pub max_moves: usize,
+    /// Longest chain of stops moved in one or-opt move.
+    pub max_or_opt_len: usize,
+}
+
+impl Default for SolverParams {
+    fn default() -> Self {
+        Self {
+            max_moves: 100_000,
+            max_or_opt_len: 3,
+        }
+    }
+}
+
+pub struct Solver<'a> {
+    problem: &'a Problem,
+    matrix: &'a DistanceMatrix,
+    params: SolverParams,
+    routes: Vec<Vec<usize>>,
+    costs: Vec<Weight>,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(problem: &'a Problem, matrix: &'a DistanceMatrix) -> Self {
+        Self::with_params(problem, matrix, SolverParams::default())
+    }
+
+    pub fn with_params(problem: &'a Problem, matrix: &'a DistanceMatrix, params: SolverParams) -> Self {
+        assert_eq!(matrix.size(), problem.stops.len() + 1);
+        Self {
+            problem,
+            matrix,
+            params,
+            routes: vec![Vec::new(); problem.vehicles.len()],
+            costs: vec![0; problem.vehicles.len()],
+        }
+    }
+
+    pub fn solve(mut self) -> Solution {
+        let unassigned = self.construct();
+        report!("num_unassigned", unassigned.len());
+
+        let mut num_moves = 0;
+        while num_moves < self.params.max_moves && (self.two_opt() || (1..=self.params.max_or_opt_len).any(|len| self.move_segment(len))) {
+            num_moves += 1;
+        }
+        report!("num_improving_moves", num_moves);
+
+        let tours: Vec<Tour> = (0..self.routes.len()).filter(|&v| !self.routes[v].is_empty()).map(|v| self.tour(v)).collect();
+        let travel_time = tours.iter().map(|t| t.travel_time).sum();
+        report!("num_tours", tours.len());
+        report!("travel_time", travel_time);
+
+        Solution {
+            tours,
+            unassigned,
+            travel_time,
+        }
+    }
+
+    fn location(stop: usize) -> usize {
+        stop + 1
+    }
+
+    // Travel time of the tour if feasible.
+    fn evaluate(&self, vehicle: usize, route: &[usize]) -> Option<Weight> {
+        if route.is_empty() {
+            return Some(0);
+        }
+        let Vehicle { capacity, shift } = self.problem.vehicles[vehicle];
+        if route.iter().map(|&s| self.problem.stops[s].demand).sum::<u32>() > capacity {
+            return None;
+        }
+
+        let mut time = shift.0;
+        let mut travel_time = 0;
+        let mut prev = 0;
+        for &stop in route {
+            let Stop { service_time, time_window, .. } = self.problem.stops[stop];
+            let leg = self.matrix.get(prev, Self::location(stop));
+            if leg >= INFINITY {
+                return None;
+            }
+            travel_time += leg;
+            time = std::cmp::max(time + leg, time_window.0);
+            if time > time_window.1 {
+                return None;
+            }
+            time += service_time;
+            prev = Self::location(stop);
+        }
+        let leg = self.matrix.get(prev, 0);
+        if leg >= INFINITY || time + leg > shift.1 {
+            return None;
+        }
+        Some(travel_time + leg)
+    }
+
+    fn tour(&self, vehicle: usize) -> Tour {
+        let route = &self.routes[vehicle];
+        let shift = self.problem.vehicles[vehicle].shift;
+        let mut service_starts = Vec::with_capacity(route.len());
+        let mut time = shift.0;
+        let mut prev = 0;
+        for &stop in route {
+            let stop_data = &self.problem.stops[stop];
+            time = std::cmp::max(time + self.matrix.get(prev, Self::location(stop)), stop_data.time_window.0);
+            service_starts.push(time);
+            time += stop_data.service_time;
+            prev = Self::location(stop);
+        }
+
+        Tour {
+            vehicle,
+            stops: route.clone(),
+            service_starts,
+            departure: shift.0,
+            return_time: time + self.matrix.get(prev, 0),
+            travel_time: self.costs[vehicle],
+            load: route.iter().map(|&s| self.problem.stops[s].demand).sum(),
+            path: Vec::new(),
+        }
+    }
+
+    // Cheapest insertion, stops with early closing time windows first.
+    fn construct(&mut self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.problem.stops.len()).collect();
+        order.sort_by_key(|&s| (self.problem.stops[s].time_window.1, s));
+
+        let mut unassigned = Vec::new();
+        for stop in order {
+            let mut best: Option<(Weight, usize, usize)> = None;
+            for vehicle in 0..self.routes.len() {
+                let mut route = self.routes[vehicle].clone();
+                for pos in 0..=route.len() {
+                    route.insert(pos, stop);
+                    if let Some(cost) = self.evaluate(vehicle, &route) {
+                        let delta = cost - self.costs[vehicle];
+                        if best.is_none_or(|(best_delta, _, _)| delta < best_delta) {
+                            best = Some((delta, vehicle, pos));
+                        }
+                    }
+                    route.remove(pos);
+                }
+            }
+
+            if let Some((delta, vehicle, pos)) = best {
+                self.routes[vehicle].insert(pos, stop);
+                self.costs[vehicle] += delta;
+            } else {
+                unassigned.push(stop);
+            }
+        }
+        unassigned
+    }
+
+    // Reverse a segment within a tour.
+    fn two_opt(&mut self) -> bool {
+        for vehicle in 0..self.routes.len() {
+            let len = self.routes[vehicle].len();
+            for i in 0..len {
+                for j in i + 1..len {
+                    let mut route = self.routes[vehicle].clone();
+                    route[i..=j].reverse();
+                    if let Some(cost) = self.evaluate(vehicle, &route) {
+                        if cost < self.costs[vehicle] {
+                            self.routes[vehicle] = route;
+                            self.costs[vehicle] = cost;
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    // Move a chain of `len` consecutive stops to another position in the same or another tour.
+    // `len == 1` is relocate, longer chains are or-opt.
+    fn move_segment(&mut self, len: usize) -> bool {
+        for from_vehicle in 0..self.routes.len() {
+            for start in 0..(self.routes[from_vehicle].len() + 1).saturating_sub(len) {
+                let mut shortened = self.routes[from_vehicle].clone();
+                let segment: Vec<usize> = shortened.drain(start..start + len).collect();
+                let Some(shortened_cost) = self.evaluate(from_vehicle, &shortened) else {
+                    continue;
+                };
+
+                for to_vehicle in 0..self.routes.len() {
+                    let target = if to_vehicle == from_vehicle { &shortened } else { &self.routes[to_vehicle] };
+                    let old_cost = if to_vehicle == from_vehicle {
+                        self.costs[from_vehicle]
+                    } else {
+                        self.costs[from_vehicle] + self.costs[to_vehicle]
+                    };
+
+                    for pos in 0..=target.len() {
+                        if to_vehicle == from_vehicle && pos == start {
+                            continue;
+                        }
+                        let mut extended = target.clone();
+                        extended.splice(pos..pos, segment.iter().copied());
+                        let Some(extended_cost) = self.evaluate(to_vehicle, &extended) else {
+                            continue;
+                        };
+                        let new_cost = if to_vehicle == from_vehicle {
+                            extended_cost
+                        } else {
+                            shortened_cost + extended_cost
+                        };
+
+                        if new_cost < old_cost {
+                            if to_vehicle == from_vehicle {
+                                self.routes[from_vehicle] = extended;
+                                self.costs[from_vehicle] = extended_cost;
+                            } else {
+                                self.routes[from_vehicle] = shortened;
+                                self.costs[from_vehicle] = shortened_cost;
+                                self.routes[to_vehicle] = extended;
+                                self.costs[to_vehicle] = extended_cost;
+                            }
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::{
+        customizable_contraction_hierarchy::{contract, customize},
+        dijkstra::{query::dijkstra::Server as DijkServer, DefaultOps},
+    };
+    use crate::datastr::node_order::NodeOrder;
+
+    // bidirectional ring 0 - 1 - 2 - 3 - 4 - 5 - 0 with unit travel times of 10
+    fn ring() -> OwnedGraph {
+        let n = 6;
+        let mut first_out = vec![0];
+        let mut head = Vec::new();
+        for node in 0..n {
+            head.push((node + n - 1) % n);
+            head.push((node + 1) % n);
+            first_out.push(head.len() as EdgeId);
+        }
+        let m = head.len();
+        OwnedGraph::new(first_out, head, vec![10; m])
+    }
+
+    fn stop(node: NodeId, demand: u32) -> Stop {
+        Stop {
+            node,
+            demand,
+            service_time: 1,
+            time_window: (0, 1000),
+        }
+    }
+
+    #[test]
+    fn rphast_matrix_matches_dijkstra() {
+        let graph = ring();
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let customized = customize(&cch, &graph);
+        let locations = vec![0, 3, 4, 4, 1];
+        let matrix = DistanceMatrix::from_customized(&customized, &locations);
+
+        let mut server = DijkServer::<_, DefaultOps>::new(ring());
+        for (i, &from) in locations.iter().enumerate() {
+            for (j, &to) in locations.iter().enumerate() {
+                assert_eq!(matrix.get(i, j), server.query(Query { from, to }).distance().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn rphast_matrix_respects_edge_directions() {
+        // one way ring 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 0 with shortcuts 0 -> 3 and 4 -> 1
+        let graph = OwnedGraph::new(vec![0, 2, 3, 4, 5, 7, 8], vec![1, 3, 2, 3, 4, 5, 1, 0], vec![10, 5, 10, 10, 10, 10, 7, 10]);
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let customized = customize(&cch, &graph);
+        let locations = vec![3, 0, 5, 1];
+        let matrix = DistanceMatrix::from_customized(&customized, &locations);
+
+        assert_eq!(matrix.get(1, 0), 5);
+        assert_eq!(matrix.get(0, 1), 30);
+        let mut server = DijkServer::<_, DefaultOps>::new(graph.clone());
+        for (i, &from) in locations.iter().enumerate() {
+            for (j, &to) in locations.iter().enumerate() {
+                assert_eq!(matrix.get(i, j), server.query(Query { from, to }).distance().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn capacities_and_time_windows_are_respected() {
+        let graph = ring();
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let customized = customize(&cch, &graph);
+
+        let mut problem = Problem {
+            depot: 0,
+            stops: vec![stop(1, 1), stop(2, 1), stop(4, 1), stop(5, 1)],
+            vehicles: vec![Vehicle { capacity: 2, shift: (0, 1000) }; 3],
+        };
+        // the stop at 2 has to be served after the one at 1 could be
+        problem.stops[1].time_window = (50, 60);
+        let matrix = DistanceMatrix::from_customized(&customized, &problem.locations());
+        let mut solution = Solver::new(&problem, &matrix).solve();
+
+        assert!(solution.unassigned.is_empty());
+        assert_eq!(solution.tours.len(), 2);
+        assert_eq!(solution.travel_time, 80);
+        let to_two = solution.tours.iter().find(|t| t.stops.contains(&1)).unwrap();
+        assert_eq!(to_two.stops, vec![0, 1]);
+        assert_eq!(to_two.service_starts, vec![10, 50]);
+        assert!(solution.tours.iter().all(|t| t.load <= 2));
+
+        assert!(to_two.path.is_empty());
+        solution.unpack_paths(&problem, &mut CCHServer::new(&customized));
+        let to_two = solution.tours.iter().find(|t| t.stops.contains(&1)).unwrap();
+        assert_eq!(to_two.path, vec![0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn infeasible_stops_are_unassigned() {
+        let problem = Problem {
+            depot: 0,
+            stops: vec![stop(3, 1), stop(1, 5)],
+            vehicles: vec![Vehicle { capacity: 2, shift: (0, 1000) }],
+        };
+        let matrix = DistanceMatrix::from_fn(3, |from, to| if from == to { 0 } else { 30 });
+        let solution = Solver::new(&problem, &matrix).solve();
+
+        assert_eq!(solution.unassigned, vec![1]);
+        assert_eq!(solution.tours.len(), 1);
+        assert_eq!(solution.travel_time, 60);
+    }
+}
@@ -6,4 +6,5 @@ pub mod heap;
 pub mod index_heap;
 pub mod node_order;
 pub mod rank_select_map;
+pub mod spatial_index;
 pub mod timestamped_vector;
@@ -0,0 +1,132 @@
+//! Runtime selection of CATCHUp customization and query strategies.
+//!
+//! All the variants used to be selectable only through cargo features, which made comparing them quite tedious.
+//! The features still exist, but now they only determine the `Default` of `CatchupConfig`.
+
+use super::*;
+use std::{fmt, str::FromStr};
+
+/// Strategies used by the CATCHUp customization and query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatchupConfig {
+    /// Compute static bounds for all shortcuts before the actual customization and drop the ones which can never be part of a shortest path.
+    pub precustomization: bool,
+    /// Use the final bounds after the customization to drop additional unnecessary shortcuts.
+    pub postcustomization: bool,
+    /// Process lower triangles ordered by their lower bounds, so that good bounds are found early.
+    pub triangle_sorting: bool,
+    /// Approximate travel time functions with more than this number of points during customization.
+    /// `None` disables approximation.
+    pub approx_threshold: Option<usize>,
+    /// Stall forward elimination tree search nodes by checking downward shortcuts (experimental).
+    pub stall_on_demand: bool,
+    /// Use the bounds from the elimination tree search to determine the corridor and A* potentials instead of only the search space.
+    pub query_corridor: bool,
+    /// Unpack shortcuts lazily during the query and only relax the first original edge of each shortcut.
+    pub query_lazy: bool,
+    /// Use the lower bounds to the target as A* potentials in the query.
+    pub query_astar: bool,
+}
+
+impl Default for CatchupConfig {
+    fn default() -> Self {
+        Self {
+            precustomization: cfg!(feature = "tdcch-precustomization"),
+            postcustomization: cfg!(feature = "tdcch-postcustomization"),
+            triangle_sorting: cfg!(feature = "tdcch-triangle-sorting"),
+            approx_threshold: if cfg!(feature = "tdcch-approx") { Some(APPROX_THRESHOLD) } else { None },
+            stall_on_demand: cfg!(feature = "tdcch-stall-on-demand"),
+            query_corridor: cfg!(feature = "tdcch-query-corridor"),
+            query_lazy: cfg!(feature = "tdcch-query-lazy"),
+            query_astar: cfg!(feature = "tdcch-query-astar"),
+        }
+    }
+}
+
+impl CatchupConfig {
+    /// Apply a single option to this config.
+    /// Options are the names of the flags (`precustomization`, `postcustomization`, `triangle-sorting`, `stall-on-demand`, `corridor`, `lazy`, `astar`),
+    /// optionally prefixed with `no-` to disable them, and `approx=<threshold>` or `no-approx`.
+    pub fn apply(&mut self, option: &str) -> Result<(), String> {
+        let (enable, name) = match option.strip_prefix("no-") {
+            Some(name) => (false, name),
+            None => (true, option),
+        };
+
+        match (name, enable) {
+            ("precustomization", _) => self.precustomization = enable,
+            ("postcustomization", _) => self.postcustomization = enable,
+            ("triangle-sorting", _) => self.triangle_sorting = enable,
+            ("stall-on-demand", _) => self.stall_on_demand = enable,
+            ("corridor", _) => self.query_corridor = enable,
+            ("lazy", _) => self.query_lazy = enable,
+            ("astar", _) => self.query_astar = enable,
+            ("approx", false) => self.approx_threshold = None,
+            ("approx", true) => self.approx_threshold = Some(APPROX_THRESHOLD),
+            _ => match name.split_once('=') {
+                Some(("approx", threshold)) if enable => {
+                    self.approx_threshold = Some(threshold.parse().map_err(|_| format!("invalid approximation threshold: {threshold}"))?)
+                }
+                _ => return Err(format!("unknown CATCHUp option: {option}")),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a comma separated list of options (see `CatchupConfig::apply`) on top of the default config.
+impl FromStr for CatchupConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        for option in s.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+            config.apply(option)?;
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for CatchupConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |enabled: bool| if enabled { "" } else { "no-" };
+        write!(
+            f,
+            "{}precustomization,{}postcustomization,{}triangle-sorting,",
+            flag(self.precustomization),
+            flag(self.postcustomization),
+            flag(self.triangle_sorting)
+        )?;
+        match self.approx_threshold {
+            Some(threshold) => write!(f, "approx={threshold},")?,
+            None => write!(f, "no-approx,")?,
+        }
+        write!(
+            f,
+            "{}stall-on-demand,{}corridor,{}lazy,{}astar",
+            flag(self.stall_on_demand),
+            flag(self.query_corridor),
+            flag(self.query_lazy),
+            flag(self.query_astar)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_roundtrips_through_strings() {
+        let config: CatchupConfig = "no-precustomization, approx=250,lazy,no-astar".parse().unwrap();
+        assert!(!config.precustomization);
+        assert_eq!(config.approx_threshold, Some(250));
+        assert!(config.query_lazy);
+        assert!(!config.query_astar);
+        assert_eq!(config.to_string().parse::<CatchupConfig>().unwrap(), config);
+        assert!("approx=many".parse::<CatchupConfig>().is_err());
+        assert!("no-approx=5".parse::<CatchupConfig>().is_err());
+        assert!("warp-speed".parse::<CatchupConfig>().is_err());
+    }
+}
@@ -64,6 +64,8 @@ pub struct Imai {
     lower_bound: f64,
     upper_absolute: bool,
     lower_absolute: bool,
+    // the approximation covers the same range as the input, which ends at the period for periodic functions
+    end: Timestamp,
 }
 
 impl Imai {
@@ -103,6 +105,7 @@ impl Imai {
             lower_bound,
             upper_absolute,
             lower_absolute,
+            end: points.last().unwrap().at,
         }
     }
 
@@ -180,9 +183,9 @@ impl Imai {
         }
         self.j += 1;
 
-        if approximated_points.last().unwrap().at < period() {
+        if approximated_points.last().unwrap().at < self.end {
             approximated_points.push(TTFPoint {
-                at: period(),
+                at: self.end,
                 val: approximated_points.first().unwrap().val,
             });
         }
@@ -5,7 +5,5 @@ use super::*;
 mod point;
 pub use self::point::*;
 
-#[cfg(feature = "tdcch-approx-imai-iri")]
 mod imai_iri;
-#[cfg(feature = "tdcch-approx-imai-iri")]
 pub use self::imai_iri::*;
@@ -1,6 +1,5 @@
 use super::piecewise_linear_function::cursor::*;
 use super::*;
-use crate::datastr::graph::time_dependent::period as int_period;
 use crate::datastr::graph::Graph as GraphTrait;
 use crate::io::*;
 use crate::report::*;
@@ -9,24 +8,36 @@ pub type IPPIndex = u32;
 
 /// First out based graph data structure for time-dependent graphs.
 /// All data is owned.
+/// The travel time functions of the graph wrap around after the period of its `Horizon`.
 #[derive(Debug, Clone)]
 pub struct Graph {
     pub first_out: Vec<EdgeId>,
     pub head: Vec<NodeId>,
     pub first_ipp_of_arc: Vec<IPPIndex>,
     pub ipps: Vec<TTFPoint>,
+    horizon: Horizon,
 }
 
 impl Graph {
-    /// Create new Graph from raw data.
+    /// Create new Graph from raw data with the default period of two days.
     /// Performs a bit of clean up on the input.
-    pub fn new(
+    pub fn new(first_out: Vec<EdgeId>, head: Vec<NodeId>, first_ipp_of_arc: Vec<IPPIndex>, ipp_departure_time: Vec<u32>, ipp_travel_time: Vec<u32>) -> Graph {
+        Self::new_with_horizon(Horizon::default(), first_out, head, first_ipp_of_arc, ipp_departure_time, ipp_travel_time)
+    }
+
+    /// Create new Graph from raw data with the given time horizon.
+    /// Departure and travel times are in milliseconds.
+    /// Performs a bit of clean up on the input.
+    pub fn new_with_horizon(
+        horizon: Horizon,
         first_out: Vec<EdgeId>,
         head: Vec<NodeId>,
         mut first_ipp_of_arc: Vec<IPPIndex>,
         ipp_departure_time: Vec<u32>,
         ipp_travel_time: Vec<u32>,
     ) -> Graph {
+        let period_ms = (f64::from(horizon.period()) * 1000.0).round() as u32;
+        let open_end_ms = (OPEN_HORIZON_END * 1000.0).round() as u32;
         let mut new_ipp_departure_time = Vec::with_capacity(ipp_departure_time.len() + 2 * head.len());
         let mut new_ipp_travel_time = Vec::with_capacity(ipp_departure_time.len() + 2 * head.len());
 
@@ -34,6 +45,8 @@ impl Graph {
 
         // Make sure all nonconst PLFs have a point at time 0 and one at time `period` and these two have the same value
         // Make sure all const PLFs have exactly one point at time 0.
+        // For open horizons, the last value is kept until the end of the horizon.
+        // Only afterwards, the function returns to its first value over the remaining period.
         for i in 0..head.len() {
             let range = first_ipp_of_arc[i] as usize..first_ipp_of_arc[i + 1] as usize;
             assert_ne!(range.start, range.end);
@@ -48,8 +61,24 @@ impl Graph {
                 }
                 new_ipp_departure_time.extend(ipp_departure_time[range.clone()].iter().cloned());
                 new_ipp_travel_time.extend(ipp_travel_time[range.clone()].iter().cloned());
-                if *new_ipp_departure_time.last().unwrap() != int_period() {
-                    new_ipp_departure_time.push(int_period());
+                if horizon.is_open() {
+                    let last_departure_time = *new_ipp_departure_time.last().unwrap();
+                    let last_travel_time = *new_ipp_travel_time.last().unwrap();
+                    assert!(
+                        last_departure_time < open_end_ms,
+                        "travel time functions have to end before the end of an open horizon"
+                    );
+                    assert!(
+                        last_travel_time.saturating_sub(ipp_travel_time[range.start]) <= period_ms - open_end_ms,
+                        "travel time functions of open horizons may not drop by more than {}s",
+                        OPEN_HORIZON_PERIOD - OPEN_HORIZON_END
+                    );
+                    new_ipp_departure_time.push(open_end_ms);
+                    new_ipp_travel_time.push(last_travel_time);
+                    added += 1;
+                }
+                if *new_ipp_departure_time.last().unwrap() != period_ms {
+                    new_ipp_departure_time.push(period_ms);
                     new_ipp_travel_time.push(ipp_travel_time[range.start]);
                     added += 1;
                 }
@@ -75,13 +104,22 @@ impl Graph {
             head,
             first_ipp_of_arc,
             ipps,
+            horizon,
         }
     }
 
+    /// Time horizon of the travel time functions.
+    pub fn horizon(&self) -> Horizon {
+        self.horizon
+    }
+
     /// Borrow PLF
     pub fn travel_time_function(&self, edge_id: EdgeId) -> PeriodicPiecewiseLinearFunction<'_> {
         let edge_id = edge_id as usize;
-        PeriodicPiecewiseLinearFunction::new(&self.ipps[self.first_ipp_of_arc[edge_id] as usize..self.first_ipp_of_arc[edge_id + 1] as usize])
+        PeriodicPiecewiseLinearFunction::new(
+            &self.ipps[self.first_ipp_of_arc[edge_id] as usize..self.first_ipp_of_arc[edge_id + 1] as usize],
+            self.horizon.period(),
+        )
     }
 
     /// Outgoing edge iterator
@@ -371,7 +409,7 @@ impl LiveGraph {
         if evaled.fuzzy_eq(live) {
             return TTFPoint { at: t_soon, val: live };
         }
-        let mut cursor = Cursor::starting_at_or_after(&plf, t_soon);
+        let mut cursor = Cursor::starting_at_or_after(&plf, t_soon, plf.period());
         let pred_below = evaled.fuzzy_lt(live);
         loop {
             let live_at_cur = if pred_below {
@@ -415,6 +453,8 @@ impl LiveGraph {
 pub trait TDGraphTrait<'a> {
     type TTF: PLF;
     fn travel_time_function(&'a self, edge_id: EdgeId) -> Self::TTF;
+    /// Wraparound value of the travel time functions.
+    fn period(&'a self) -> Timestamp;
 
     fn evaluate_path(&'a self, path: &[EdgeId], t: Timestamp) -> FlWeight {
         let mut tt = FlWeight::ZERO;
@@ -438,6 +478,9 @@ impl<'a> TDGraphTrait<'a> for Graph {
     fn travel_time_function(&'a self, edge_id: EdgeId) -> Self::TTF {
         Graph::travel_time_function(self, edge_id)
     }
+    fn period(&'a self) -> Timestamp {
+        self.horizon.period()
+    }
 }
 
 impl<'a> TDGraphTrait<'a> for LiveGraph {
@@ -445,17 +488,51 @@ impl<'a> TDGraphTrait<'a> for LiveGraph {
     fn travel_time_function(&'a self, edge_id: EdgeId) -> Self::TTF {
         LiveGraph::travel_time_function(self, edge_id)
     }
+    fn period(&'a self) -> Timestamp {
+        self.graph.horizon.period()
+    }
+}
+
+impl Deconstruct for Graph {
+    fn save_each(&self, store: &dyn Fn(&str, &dyn Save) -> std::io::Result<()>) -> std::io::Result<()> {
+        // s to ms
+        let ipp_departure_time: Vec<u32> = self.ipps.iter().map(|p| (f64::from(p.at) * 1000.0).round() as u32).collect();
+        let ipp_travel_time: Vec<u32> = self.ipps.iter().map(|p| (f64::from(p.val) * 1000.0).round() as u32).collect();
+
+        store("first_out", &self.first_out)?;
+        store("head", &self.head)?;
+        store("first_ipp_of_arc", &self.first_ipp_of_arc)?;
+        store("ipp_departure_time", &ipp_departure_time)?;
+        store("ipp_travel_time", &ipp_travel_time)?;
+        store("period", &vec![self.horizon.to_seconds()])?;
+        Ok(())
+    }
 }
 
 impl Reconstruct for Graph {
+    /// Loads the period in seconds from an optional `period` file (infinity for an open horizon).
+    /// Without it, the default period of two days is used. An empty `period` file is an error.
     fn reconstruct_with(loader: Loader) -> std::io::Result<Self> {
         let first_out: Vec<_> = loader.load("first_out")?;
         let head: Vec<_> = loader.load("head")?;
         let ipp_departure_time: Vec<_> = loader.load("ipp_departure_time")?;
+        let horizon = if loader.path().join("period").exists() {
+            let period: Vec<f64> = loader.load("period")?;
+            let &[period] = &period[..] else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("period file must contain exactly one value, got {}", period.len()),
+                ));
+            };
+            Horizon::from_seconds(period)
+        } else {
+            Horizon::default()
+        };
 
-        report!("unprocessed_graph", { "num_nodes": first_out.len() - 1, "num_arcs": head.len(), "num_ipps": ipp_departure_time.len() });
+        report!("unprocessed_graph", { "num_nodes": first_out.len() - 1, "num_arcs": head.len(), "num_ipps": ipp_departure_time.len(), "period": horizon.to_seconds() });
 
-        let graph = Self::new(
+        let graph = Self::new_with_horizon(
+            horizon,
             first_out,
             head,
             loader.load("first_ipp_of_arc")?,
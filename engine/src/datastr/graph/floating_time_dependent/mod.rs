@@ -19,7 +19,7 @@ mod piecewise_linear_function;
 pub use piecewise_linear_function::{PartialPiecewiseLinearFunction, PeriodicPiecewiseLinearFunction, UpdatedPiecewiseLinearFunction, PLF};
 
 mod geometry;
-pub use self::geometry::TTFPoint;
+pub use self::geometry::{Imai, TTFPoint};
 use self::geometry::*;
 
 mod graph;
@@ -40,6 +40,9 @@ pub use self::shortcut_graph::*;
 pub mod travel_time_function;
 pub use travel_time_function::*;
 
+mod config;
+pub use self::config::CatchupConfig;
+
 #[allow(clippy::float_cmp)]
 mod time {
     use std::{
@@ -232,11 +235,8 @@ mod time {
 
         /// Split this value into sum of multiple of `period` (first value) and rest (second value).
         /// Negative values will be handled fine by using euclidian modulo and division.
-        pub fn split_of_period(self) -> (FlWeight, Timestamp) {
-            (
-                FlWeight::new(self.0.div_euclid(super::period().0)),
-                Timestamp::new(self.0.rem_euclid(super::period().0)),
-            )
+        pub fn split_of_period(self, period: Timestamp) -> (FlWeight, Timestamp) {
+            (FlWeight::new(self.0.div_euclid(period.0)), Timestamp::new(self.0.rem_euclid(period.0)))
         }
 
         pub fn from_millis(millis: u32) -> Self {
@@ -299,7 +299,7 @@ mod time {
 }
 pub use self::time::{FlWeight, Timestamp, APPROX, EPSILON};
 
-// Utils to allow tests to override `period` value.
+// Utils to allow tests to set a `period` value which they can pass to the functions under test.
 
 #[cfg(test)]
 thread_local! {
@@ -337,14 +337,78 @@ pub fn period() -> Timestamp {
     TEST_PERIOD_MOCK.with(|period_cell| period_cell.get().expect("period() used but not set"))
 }
 
-/// Travel time functions are periodic. This value is the wraparound value.
-/// Hardcoded to `86400s` * 2, that is 2 days.
-#[cfg(not(test))]
-#[inline]
-pub fn period() -> Timestamp {
-    Timestamp::new(86_400.0 * 2.0)
+mod horizon {
+    use super::Timestamp;
+
+    /// `86400s` * 2, that is 2 days.
+    pub const DEFAULT_PERIOD: f64 = 86_400.0 * 2.0;
+    /// End of open ended horizons, 14 days.
+    pub const OPEN_HORIZON_END: f64 = 86_400.0 * 14.0;
+    /// Period used to represent open ended horizons internally, 28 days.
+    pub const OPEN_HORIZON_PERIOD: f64 = 2.0 * OPEN_HORIZON_END;
+
+    /// Time horizon of the travel time functions of a graph.
+    /// Travel time functions are periodic, the period of the horizon is their wraparound value.
+    /// It is stored in the `TDGraph` and passed explicitly to everything which evaluates, links or merges travel time functions.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Horizon {
+        /// Travel time functions wrap around after the given period.
+        Periodic(Timestamp),
+        /// Travel time functions do not wrap around, the last value holds until `OPEN_HORIZON_END`.
+        /// Times at or after the end are outside of the horizon, queries neither depart nor arrive there.
+        /// Internally, this is a period of `OPEN_HORIZON_PERIOD` where functions return to their first value after the end.
+        Open,
+    }
+
+    impl Default for Horizon {
+        fn default() -> Self {
+            Horizon::Periodic(Timestamp::new(DEFAULT_PERIOD))
+        }
+    }
+
+    impl Horizon {
+        /// The wraparound value of travel time functions with this horizon.
+        pub fn period(self) -> Timestamp {
+            match self {
+                Horizon::Periodic(period) => period,
+                Horizon::Open => Timestamp::new(OPEN_HORIZON_PERIOD),
+            }
+        }
+
+        pub fn is_open(self) -> bool {
+            self == Horizon::Open
+        }
+
+        /// Whether travel time functions are exact at the given time.
+        /// Always true for periodic horizons, open horizons end at `OPEN_HORIZON_END`.
+        pub fn contains(self, t: Timestamp) -> bool {
+            match self {
+                Horizon::Periodic(_) => true,
+                Horizon::Open => t < Timestamp::new(OPEN_HORIZON_END),
+            }
+        }
+
+        /// Inverse of `to_seconds`, infinity denotes an open horizon.
+        pub fn from_seconds(seconds: f64) -> Self {
+            if seconds.is_finite() {
+                Horizon::Periodic(Timestamp::new(seconds))
+            } else {
+                Horizon::Open
+            }
+        }
+
+        /// Period in seconds, infinity for open horizons.
+        pub fn to_seconds(self) -> f64 {
+            match self {
+                Horizon::Periodic(period) => period.into(),
+                Horizon::Open => f64::INFINITY,
+            }
+        }
+    }
 }
 
+pub use horizon::{Horizon, DEFAULT_PERIOD, OPEN_HORIZON_END, OPEN_HORIZON_PERIOD};
+
 use std::sync::atomic::{AtomicIsize, AtomicUsize};
 
 // Stat counters for customization
@@ -508,6 +572,8 @@ pub struct MergeBuffers {
     buffer: Vec<TTFPoint>,
     exact_result_lower: Vec<TTFPoint>,
     exact_result_upper: Vec<TTFPoint>,
+    /// Functions with more points than this get approximated after linking and merging, see `CatchupConfig::approx_threshold`.
+    pub approx_threshold: Option<usize>,
 }
 
 impl Default for MergeBuffers {
@@ -524,6 +590,14 @@ impl MergeBuffers {
             buffer: Vec::new(),
             exact_result_lower: Vec::new(),
             exact_result_upper: Vec::new(),
+            approx_threshold: CatchupConfig::default().approx_threshold,
+        }
+    }
+
+    pub fn with_config(config: &CatchupConfig) -> Self {
+        MergeBuffers {
+            approx_threshold: config.approx_threshold,
+            ..Self::new()
         }
     }
 }
@@ -4,7 +4,6 @@ use super::shortcut::Sources;
 use super::shortcut_source::Sources as _;
 use super::*;
 use std::cmp::{max, min};
-use std::convert::TryFrom;
 use std::sync::atomic::Ordering::Relaxed;
 
 #[derive(Debug)]
@@ -13,6 +12,7 @@ pub struct PartialShortcut {
     cache: Option<ATTFContainer<Box<[TTFPoint]>>>,
     pub start: Timestamp,
     pub end: Timestamp,
+    period: Timestamp,
     pub lower_bound: FlWeight,
     pub upper_bound: FlWeight,
     constant: bool,
@@ -33,6 +33,7 @@ impl PartialShortcut {
                     cache: None,
                     start,
                     end,
+                    period: original_graph.horizon().period(),
                     lower_bound: original_graph.travel_time_function(edge_id).lower_bound(),
                     upper_bound: original_graph.travel_time_function(edge_id).upper_bound(),
                     constant: false,
@@ -44,6 +45,7 @@ impl PartialShortcut {
                 cache: None,
                 start,
                 end,
+                period: original_graph.horizon().period(),
                 lower_bound: FlWeight::INFINITY,
                 upper_bound: FlWeight::INFINITY,
                 constant: false,
@@ -52,7 +54,13 @@ impl PartialShortcut {
         }
     }
 
-    pub fn new_finished(start: Timestamp, end: Timestamp, sources: &[(Timestamp, ShortcutSourceData)], bounds: (FlWeight, FlWeight)) -> Self {
+    pub fn new_finished(
+        start: Timestamp,
+        end: Timestamp,
+        period: Timestamp,
+        sources: &[(Timestamp, ShortcutSourceData)],
+        bounds: (FlWeight, FlWeight),
+    ) -> Self {
         let sources = match sources {
             &[] => Sources::None,
             &[(_, data)] => Sources::One(data),
@@ -62,6 +70,7 @@ impl PartialShortcut {
             cache: None,
             start,
             end,
+            period,
             lower_bound: bounds.0,
             upper_bound: bounds.1,
             constant: bounds.0.fuzzy_eq(bounds.1),
@@ -121,7 +130,7 @@ impl PartialShortcut {
 
                 self.upper_bound = min(self.upper_bound, PartialATTF::from(&linked).static_upper_bound());
                 debug_assert!(
-                    !cfg!(feature = "tdcch-precustomization") || !self.upper_bound.fuzzy_lt(self.lower_bound),
+                    !self.upper_bound.fuzzy_lt(self.lower_bound),
                     "lower {:?} upper {:?}",
                     self.lower_bound,
                     self.upper_bound
@@ -152,12 +161,12 @@ impl PartialShortcut {
                 // new linked function is always better than the current one
                 self.upper_bound = min(self.upper_bound, other_upper_bound);
                 debug_assert!(
-                    !cfg!(feature = "tdcch-precustomization") || !self.upper_bound.fuzzy_lt(self.lower_bound),
+                    !self.upper_bound.fuzzy_lt(self.lower_bound),
                     "lower {:?} upper {:?}",
                     self.lower_bound,
                     self.upper_bound
                 );
-                if cfg!(feature = "tdcch-approx") && linked_ipps.num_points() > APPROX_THRESHOLD {
+                if buffers.approx_threshold.map(|threshold| linked_ipps.num_points() > threshold).unwrap_or(false) {
                     let old = linked_ipps.num_points();
                     if cfg!(feature = "detailed-stats") {
                         CONSIDERED_FOR_APPROX.fetch_add(old, Relaxed);
@@ -187,7 +196,7 @@ impl PartialShortcut {
 
             // this function does exact merging, even when we have only approximate functions by unpacking exact functions for time ranges when bounds overlap.
             // the callback executes exact merging for small time ranges where the bounds overlap, the function takes care of all the rest around that.
-            let (mut merged, intersection_data) = self_plf.merge(&linked, self.start, self.end, buffers, |start, end, buffers| {
+            let (mut merged, intersection_data) = self_plf.merge(&linked, self.start, self.end, self.period, buffers, |start, end, buffers| {
                 let mut self_target = buffers.unpacking_target.push_plf();
                 self.reconstruct_exact_ttf(start, end, shortcut_graph, &mut self_target, &mut buffers.unpacking_tmp);
 
@@ -195,9 +204,15 @@ impl PartialShortcut {
                 ShortcutSource::from(other_data).reconstruct_exact_ttf(start, end, shortcut_graph, &mut other_target, &mut buffers.unpacking_tmp);
 
                 let (self_ipps, other_ipps) = other_target.storage().top_plfs();
-                PartialPiecewiseLinearFunction::new(self_ipps).merge(&PartialPiecewiseLinearFunction::new(other_ipps), start, end, &mut buffers.buffer)
+                PartialPiecewiseLinearFunction::new(self_ipps).merge(
+                    &PartialPiecewiseLinearFunction::new(other_ipps),
+                    start,
+                    end,
+                    self.period,
+                    &mut buffers.buffer,
+                )
             });
-            if cfg!(feature = "tdcch-approx") && merged.num_points() > APPROX_THRESHOLD {
+            if buffers.approx_threshold.map(|threshold| merged.num_points() > threshold).unwrap_or(false) {
                 let old = merged.num_points();
                 if cfg!(feature = "detailed-stats") {
                     CONSIDERED_FOR_APPROX.fetch_add(old, Relaxed);
@@ -215,7 +230,7 @@ impl PartialShortcut {
             // We can only set the lower bound as tight as possible, once we have the final travel time function.
             self.upper_bound = min(self.upper_bound, PartialATTF::from(&merged).static_upper_bound());
             debug_assert!(
-                !cfg!(feature = "tdcch-precustomization") || !self.upper_bound.fuzzy_lt(self.lower_bound),
+                !self.upper_bound.fuzzy_lt(self.lower_bound),
                 "lower {:?} upper {:?}",
                 self.lower_bound,
                 self.upper_bound
@@ -224,7 +239,7 @@ impl PartialShortcut {
             let mut sources = Sources::None;
             std::mem::swap(&mut sources, &mut self.sources);
             // calculate new `ShortcutSource`s.
-            self.sources = sources.combine(intersection_data, other_data, self.start, self.end);
+            self.sources = sources.combine(intersection_data, other_data, self.start, self.end, self.period);
         })();
 
         if cfg!(feature = "detailed-stats") {
@@ -238,8 +253,8 @@ impl PartialShortcut {
 
     pub fn periodic_ttf<'s, 'g: 's>(&'s self, shortcut_graph: &'g impl ShortcutGraphTrt<OriginalGraph = TDGraph>) -> Option<PeriodicATTF<'s>> {
         if let Some(cache) = &self.cache {
-            if self.start.fuzzy_eq(Timestamp::ZERO) && self.end.fuzzy_eq(period()) {
-                return Some(cache.into());
+            if self.start.fuzzy_eq(Timestamp::ZERO) && self.end.fuzzy_eq(self.period) {
+                return Some(PeriodicATTF::new(cache, self.period));
             }
         }
 
@@ -304,14 +319,10 @@ impl PartialShortcut {
             return;
         }
 
-        let new_lower_bound = if cfg!(feature = "tdcch-precustomization") {
-            max(
-                self.lower_bound,
-                self.partial_ttf(shortcut_graph, self.start, self.end).unwrap().static_lower_bound(),
-            )
-        } else {
-            self.partial_ttf(shortcut_graph, self.start, self.end).unwrap().static_lower_bound()
-        };
+        let new_lower_bound = max(
+            self.lower_bound,
+            self.partial_ttf(shortcut_graph, self.start, self.end).unwrap().static_lower_bound(),
+        );
 
         // The final functions lower bound is worse than the upper bound this shortcut got during pre/post/perfect customization
         // Thus, we will never need it.
@@ -323,12 +334,7 @@ impl PartialShortcut {
             return;
         }
 
-        debug_assert!(
-            !cfg!(feature = "tdcch-precustomization") || !new_lower_bound.fuzzy_lt(self.lower_bound),
-            "{:?}, {:?}",
-            new_lower_bound,
-            self
-        );
+        debug_assert!(!new_lower_bound.fuzzy_lt(self.lower_bound), "{:?}, {:?}", new_lower_bound, self);
         debug_assert!(!self.upper_bound.fuzzy_lt(new_lower_bound), "{:?}, {:?}", new_lower_bound, self);
         self.lower_bound = new_lower_bound;
 
@@ -458,7 +464,7 @@ impl PartialShortcut {
     pub fn sources_for<'s>(&'s self, start: Timestamp, end: Timestamp) -> impl Iterator<Item = (Timestamp, ShortcutSourceData)> + 's {
         debug_assert!(self.start.fuzzy_leq(start));
         debug_assert!(end.fuzzy_leq(self.end));
-        self.sources.wrapping_iter_for(start, end)
+        self.sources.wrapping_iter_for(start, end, self.period)
     }
 
     pub fn is_constant(&self) -> bool {
@@ -486,7 +492,7 @@ impl PartialShortcut {
         ShortcutSource::from(*match &self.sources {
             Sources::None => unreachable!("There are no paths for empty shortcuts"),
             Sources::One(source) => source,
-            Sources::Multi(sources) => sources.edge_source_at(t).unwrap(),
+            Sources::Multi(sources) => sources.edge_source_at(t, self.period).unwrap(),
         })
         .unpack_at(t, shortcut_graph, result)
     }
@@ -502,7 +508,7 @@ impl PartialShortcut {
         ShortcutSource::from(*match &self.sources {
             Sources::None => return FlWeight::INFINITY,
             Sources::One(source) => source,
-            Sources::Multi(sources) => sources.edge_source_at(t).unwrap(),
+            Sources::Multi(sources) => sources.edge_source_at(t, self.period).unwrap(),
         })
         .evaluate(t, shortcut_graph)
     }
@@ -525,6 +531,7 @@ impl PartialTrt for PartialShortcut {
         new_sources.dedup_by_key(|(_, source)| *source);
         let lower = a.iter().chain(b.iter()).map(|s| s.lower_bound).min().unwrap();
         let upper = a.iter().chain(b.iter()).map(|s| s.upper_bound).max().unwrap();
+        let period = a[0].period;
 
         let a: Vec<_> = a
             .iter()
@@ -545,7 +552,7 @@ impl PartialTrt for PartialShortcut {
 
         let Partial { start, end, ttf } = Partial::combine(&a, &b);
 
-        let mut s = PartialShortcut::new_finished(start, end, &new_sources, (lower, upper));
+        let mut s = PartialShortcut::new_finished(start, end, period, &new_sources, (lower, upper));
         s.set_cache(Some(ttf.into()));
         s
     }
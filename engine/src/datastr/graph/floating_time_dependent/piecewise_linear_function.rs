@@ -68,14 +68,16 @@ fn append_too_close(points: &mut Vec<TTFPoint>, mut point: TTFPoint) {
 }
 
 /// A struct borrowing a slice of points which implements all sorts of operations and algorithms for PPLFs.
+/// The function wraps around after `period`, usually the period of the `Horizon` of the graph it belongs to.
 #[derive(Debug, Clone, Copy)]
 pub struct PeriodicPiecewiseLinearFunction<'a> {
     ipps: &'a [TTFPoint],
+    period: Timestamp,
 }
 
 impl<'a> PLF for PeriodicPiecewiseLinearFunction<'a> {
     fn evaluate(&self, t: Timestamp) -> FlWeight {
-        let (_, t) = t.split_of_period();
+        let (_, t) = t.split_of_period(self.period);
         PartialPiecewiseLinearFunction { ipps: self.ipps }.eval(t)
     }
     fn append_range(&self, start: Timestamp, end: Timestamp, target: &mut impl PLFTarget) {
@@ -87,7 +89,7 @@ impl<'a> PLF for PeriodicPiecewiseLinearFunction<'a> {
         }
 
         let wrap_val = self.ipps.first().unwrap().val;
-        let (p, t) = (t - wrap_val).split_of_period();
+        let (p, t) = (t - wrap_val).split_of_period(self.period);
         let t = t + wrap_val;
 
         let first = self.first().unwrap();
@@ -131,9 +133,9 @@ impl<'a> std::ops::Deref for PeriodicPiecewiseLinearFunction<'a> {
 }
 
 impl<'a> PeriodicPiecewiseLinearFunction<'a> {
-    /// New PLF from slice of points.
+    /// New PLF from slice of points which wraps around after `period`.
     /// In debug will validate the invariants we need from the function.
-    pub fn new(ipps: &'a [TTFPoint]) -> Self {
+    pub fn new(ipps: &'a [TTFPoint], period: Timestamp) -> Self {
         debug_assert!(ipps.first().unwrap().at == Timestamp::ZERO, "{:?}", ipps);
         debug_assert!(
             ipps.first().unwrap().val.fuzzy_eq(ipps.last().unwrap().val),
@@ -141,7 +143,7 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
             ipps.first(),
             ipps.last()
         );
-        debug_assert!(ipps.len() == 1 || ipps.last().unwrap().at == period(), "{:?}", ipps);
+        debug_assert!(ipps.len() == 1 || ipps.last().unwrap().at == period, "{:?}", ipps);
 
         for points in ipps.windows(2) {
             debug_assert!(points[0].at.fuzzy_lt(points[1].at), "{:?} >= {:?}", points[0], points[1]);
@@ -158,15 +160,24 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
             );
         }
 
-        Self { ipps }
+        Self { ipps, period }
     }
 
-    pub const ZERO: Self = PeriodicPiecewiseLinearFunction {
-        ipps: &[TTFPoint {
-            at: Timestamp::ZERO,
-            val: FlWeight::ZERO,
-        }],
-    };
+    /// The constant zero function.
+    pub fn zero(period: Timestamp) -> Self {
+        PeriodicPiecewiseLinearFunction {
+            ipps: &[TTFPoint {
+                at: Timestamp::ZERO,
+                val: FlWeight::ZERO,
+            }],
+            period,
+        }
+    }
+
+    /// The wraparound value of this function.
+    pub fn period(&self) -> Timestamp {
+        self.period
+    }
 
     pub fn constant(&self) -> bool {
         PartialPiecewiseLinearFunction { ipps: self.ipps }.constant()
@@ -186,7 +197,7 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
     pub(super) fn append_range(&self, start: Timestamp, end: Timestamp, target: &mut impl PLFTarget) {
         debug_assert!(start.fuzzy_lt(end), "{:?} - {:?}", start, end);
 
-        let mut f = Cursor::starting_at_or_after(&self.ipps, start);
+        let mut f = Cursor::starting_at_or_after(&self.ipps, start, self.period);
 
         if target.is_empty() {
             if start.fuzzy_lt(f.cur().at) {
@@ -237,9 +248,11 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
         debug_assert!(!target[target.len() - 1].at.fuzzy_lt(end));
     }
 
-    /// Link two complete and valid PLFs.
+    /// Link two complete and valid PLFs with the same period.
     /// The result is also a complete and valid PLF, but since PLF is just a borrow we return a `Vec<TTFPoint>`
     pub fn link(&self, other: &Self) -> Vec<TTFPoint> {
+        debug_assert_eq!(self.period, other.period);
+        let period = self.period;
         if let [TTFPoint { val, .. }] = &self.ipps {
             if let [TTFPoint { val: other, .. }] = &other.ipps {
                 return vec![TTFPoint {
@@ -248,7 +261,7 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
                 }];
             } else {
                 let zero_val = other.evaluate(val.into());
-                let (_, val_offset) = Timestamp::from(val).split_of_period();
+                let (_, val_offset) = Timestamp::from(val).split_of_period(period);
                 let mut result = std::iter::once(TTFPoint {
                     at: Timestamp::ZERO,
                     val: zero_val + val,
@@ -258,11 +271,11 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
                     val: p.val + val,
                 }))
                 .chain(other.ipps.iter().filter(|p| p.at < val_offset).map(|p| TTFPoint {
-                    at: p.at + FlWeight::from(period()) - FlWeight::from(val_offset),
+                    at: p.at + FlWeight::from(period) - FlWeight::from(val_offset),
                     val: p.val + val,
                 }))
                 .chain(std::iter::once(TTFPoint {
-                    at: period(),
+                    at: period,
                     val: zero_val + val,
                 }))
                 .fold(Vec::with_capacity(other.ipps.len() + 2), |mut acc, p| {
@@ -270,7 +283,7 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
                     acc
                 });
 
-                result.last_mut().unwrap().at = period();
+                result.last_mut().unwrap().at = period;
 
                 return result;
             }
@@ -282,7 +295,7 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
         let mut result: Vec<TTFPoint> = Vec::with_capacity(self.ipps.len() + other.ipps.len() + 1);
 
         let mut f = PartialPlfLinkCursor::new(&self.ipps);
-        let mut g = Cursor::starting_at_or_after(&other.ipps, Timestamp::ZERO + self.ipps[0].val);
+        let mut g = Cursor::starting_at_or_after(&other.ipps, Timestamp::ZERO + self.ipps[0].val, period);
 
         loop {
             let mut x;
@@ -312,12 +325,12 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
                 f.advance();
             }
 
-            if !x.fuzzy_lt(period()) {
+            if !x.fuzzy_lt(period) {
                 break;
             }
             debug_assert!(!x.fuzzy_lt(Timestamp::ZERO), "{:?} {:?}", x, y);
 
-            x = min(x, period());
+            x = min(x, period);
             x = max(x, Timestamp::ZERO);
 
             // Ensure monotonicity - x must be greater than the last point's timestamp
@@ -333,20 +346,22 @@ impl<'a> PeriodicPiecewiseLinearFunction<'a> {
         }
 
         let zero_val = result[0].val;
-        append_point(&mut result, TTFPoint { at: period(), val: zero_val });
-        result.last_mut().unwrap().at = period();
+        append_point(&mut result, TTFPoint { at: period, val: zero_val });
+        result.last_mut().unwrap().at = period;
 
         debug_assert!(result.len() <= self.ipps.len() + other.ipps.len() + 1);
 
         result
     }
 
-    // Merge two complete and valid PLFs in the range between 0 and period and store the result in buffer.
+    // Merge two complete and valid PLFs with the same period in the range between 0 and period and store the result in buffer.
     pub fn merge(&self, other: &Self, buffer: &mut Vec<TTFPoint>) -> (Box<[TTFPoint]>, Vec<(Timestamp, bool)>) {
+        debug_assert_eq!(self.period, other.period);
         PartialPiecewiseLinearFunction { ipps: self.ipps }.merge_in_bounds::<Cursor, True>(
             &PartialPiecewiseLinearFunction { ipps: other.ipps },
             Timestamp::ZERO,
-            period(),
+            self.period,
+            self.period,
             buffer,
         )
     }
@@ -505,18 +520,6 @@ impl<'a> TryFrom<PeriodicPiecewiseLinearFunction<'a>> for PartialPiecewiseLinear
     }
 }
 
-impl<'a> TryFrom<PartialPiecewiseLinearFunction<'a>> for PeriodicPiecewiseLinearFunction<'a> {
-    type Error = ();
-    fn try_from(pplf: PartialPiecewiseLinearFunction<'a>) -> Result<Self, Self::Error> {
-        let pplf = pplf.sub_plf(Timestamp::ZERO, period());
-        if pplf.first().unwrap().at.fuzzy_eq(Timestamp::ZERO) && pplf.last().unwrap().at.fuzzy_eq(period()) {
-            Ok(PeriodicPiecewiseLinearFunction { ipps: pplf.ipps })
-        } else {
-            Err(())
-        }
-    }
-}
-
 impl<'a> std::ops::Deref for PartialPiecewiseLinearFunction<'a> {
     type Target = [TTFPoint];
 
@@ -845,10 +848,28 @@ impl<'a> PartialPiecewiseLinearFunction<'a> {
         debug_assert!(!target[target.len() - 1].at.fuzzy_lt(end));
     }
 
+    /// Restrict to `[0, period]` and view as periodic function, if it covers the complete period.
+    pub fn to_periodic(self, period: Timestamp) -> Option<PeriodicPiecewiseLinearFunction<'a>> {
+        let pplf = self.sub_plf(Timestamp::ZERO, period);
+        if pplf.first().unwrap().at.fuzzy_eq(Timestamp::ZERO) && pplf.last().unwrap().at.fuzzy_eq(period) {
+            Some(PeriodicPiecewiseLinearFunction { ipps: pplf.ipps, period })
+        } else {
+            None
+        }
+    }
+
     // Merge two partial plfs in the range between start and end and store the result in buffer.
-    pub fn merge(self, other: &Self, start: Timestamp, end: Timestamp, buffer: &mut Vec<TTFPoint>) -> (Box<[TTFPoint]>, Vec<(Timestamp, bool)>) {
+    // The functions may not cover times more than one `period` apart.
+    pub fn merge(
+        self,
+        other: &Self,
+        start: Timestamp,
+        end: Timestamp,
+        period: Timestamp,
+        buffer: &mut Vec<TTFPoint>,
+    ) -> (Box<[TTFPoint]>, Vec<(Timestamp, bool)>) {
         debug_assert!(start >= Timestamp::ZERO);
-        self.merge_in_bounds::<PartialPlfMergeCursor, False>(other, start, end, buffer)
+        self.merge_in_bounds::<PartialPlfMergeCursor, False>(other, start, end, period, buffer)
     }
 
     // Actual merging logic. Here be dragons.
@@ -858,6 +879,7 @@ impl<'a> PartialPiecewiseLinearFunction<'a> {
         other: &Self,
         start: Timestamp,
         end: Timestamp,
+        period: Timestamp,
         result: &mut Vec<TTFPoint>,
     ) -> (Box<[TTFPoint]>, Vec<(Timestamp, bool)>) {
         // easy cases
@@ -873,8 +895,8 @@ impl<'a> PartialPiecewiseLinearFunction<'a> {
 
         debug_assert!(!self.ipps.is_empty());
         debug_assert!(!other.ipps.is_empty());
-        let mut f = C::new(&self.ipps);
-        let mut g = C::new(&other.ipps);
+        let mut f = C::new(&self.ipps, period);
+        let mut g = C::new(&other.ipps, period);
 
         let self_start_val = if FullRange::VALUE || f.cur().at.fuzzy_eq(start) {
             f.cur().val
@@ -942,8 +964,8 @@ impl<'a> PartialPiecewiseLinearFunction<'a> {
             return ((Box::from(if better.last().unwrap().1 { self.ipps } else { other.ipps })), better);
         }
 
-        let mut f = C::new(&self.ipps);
-        let mut g = C::new(&other.ipps);
+        let mut f = C::new(&self.ipps, period);
+        let mut g = C::new(&other.ipps, period);
 
         if intersect_fuzzy_on_start {
             append_point(
@@ -1176,7 +1198,7 @@ impl<'a> PartialPiecewiseLinearFunction<'a> {
             debug_assert!(!at.fuzzy_lt(start));
             debug_assert!(!end.fuzzy_lt(at));
         }
-        if !f.cur().val.fuzzy_eq(g.cur().val) && start == Timestamp::ZERO && end == period() {
+        if !f.cur().val.fuzzy_eq(g.cur().val) && start == Timestamp::ZERO && end == period {
             debug_assert_eq!(
                 better.first().map(|(_, better_fn)| better_fn),
                 better.last().map(|(_, better_fn)| better_fn),
@@ -1376,7 +1398,7 @@ mod tests {
                 at: Timestamp::ZERO,
                 val: FlWeight::new(5.0),
             }];
-            let mut cursor = Cursor::new(&ipps);
+            let mut cursor = Cursor::new(&ipps, period());
             assert_eq!(
                 cursor.cur(),
                 TTFPoint {
@@ -1440,7 +1462,7 @@ mod tests {
                     val: FlWeight::new(5.0),
                 },
             ];
-            let mut cursor = Cursor::new(&ipps);
+            let mut cursor = Cursor::new(&ipps, period());
             assert_eq!(
                 cursor.cur(),
                 TTFPoint {
@@ -1542,7 +1564,7 @@ mod tests {
                 },
             ];
 
-            let linked = PeriodicPiecewiseLinearFunction::new(&ipps1).link(&PeriodicPiecewiseLinearFunction::new(&ipps2));
+            let linked = PeriodicPiecewiseLinearFunction::new(&ipps1, period()).link(&PeriodicPiecewiseLinearFunction::new(&ipps2, period()));
             assert_eq!(5, linked.len())
         });
     }
@@ -1570,7 +1592,7 @@ mod tests {
                 },
             ];
 
-            let linked = PeriodicPiecewiseLinearFunction::new(&ipps1).link(&PeriodicPiecewiseLinearFunction::new(&ipps2));
+            let linked = PeriodicPiecewiseLinearFunction::new(&ipps1, period()).link(&PeriodicPiecewiseLinearFunction::new(&ipps2, period()));
             assert_eq!(4, linked.len())
         });
     }
@@ -1583,7 +1605,7 @@ mod tests {
                 val: FlWeight::new(10.0),
             }];
             let mut result = Vec::new();
-            PeriodicPiecewiseLinearFunction::new(&ipps).append_range(Timestamp::new(40.0), Timestamp::new(50.0), &mut result);
+            PeriodicPiecewiseLinearFunction::new(&ipps, period()).append_range(Timestamp::new(40.0), Timestamp::new(50.0), &mut result);
             assert_eq!(
                 result,
                 vec![
@@ -1629,7 +1651,7 @@ mod tests {
                 },
             ];
             let second = PartialPiecewiseLinearFunction::new(&second);
-            let (result, _) = first.merge(&second, Timestamp::new(52079.64118104493), Timestamp::new(52082.0), &mut Vec::new());
+            let (result, _) = first.merge(&second, Timestamp::new(52079.64118104493), Timestamp::new(52082.0), period(), &mut Vec::new());
             assert_eq!(*result.last().unwrap(), second[1]);
         });
     }
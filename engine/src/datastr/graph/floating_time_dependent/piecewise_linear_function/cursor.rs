@@ -1,12 +1,12 @@
 //! Data structures to efficiently iterate over TTFPoints.
-//! Allows to get points valid for times > period().
+//! Allows to get points valid for times > period.
 //! Handling all the ugly shifting and wrapping logic.
 
 use super::*;
 
 /// All the ops we need during merging
 pub trait MergeCursor<'a> {
-    fn new(ipps: &'a [TTFPoint]) -> Self;
+    fn new(ipps: &'a [TTFPoint], period: Timestamp) -> Self;
     fn cur(&self) -> TTFPoint;
     fn next(&self) -> TTFPoint;
     fn prev(&self) -> TTFPoint;
@@ -20,25 +20,29 @@ pub struct Cursor<'a> {
     ipps: &'a [TTFPoint],
     current_index: usize,
     offset: FlWeight,
+    period: FlWeight,
 }
 
 impl<'a> Cursor<'a> {
-    pub fn starting_at_or_after(ipps: &'a [TTFPoint], t: Timestamp) -> Self {
-        let (times_period, t) = t.split_of_period();
-        let offset = times_period * FlWeight::from(period());
+    pub fn starting_at_or_after(ipps: &'a [TTFPoint], t: Timestamp, period: Timestamp) -> Self {
+        let (times_period, t) = t.split_of_period(period);
+        let period = FlWeight::from(period);
+        let offset = times_period * period;
 
         if ipps.len() == 1 {
             return if t > Timestamp::ZERO {
                 Cursor {
                     ipps,
                     current_index: 0,
-                    offset: (period() + offset).into(),
+                    offset: period + offset,
+                    period,
                 }
             } else {
                 Cursor {
                     ipps,
                     current_index: 0,
                     offset,
+                    period,
                 }
             };
         }
@@ -61,24 +65,27 @@ impl<'a> Cursor<'a> {
             Cursor {
                 ipps,
                 current_index: 0,
-                offset: (period() + offset).into(),
+                offset: period + offset,
+                period,
             }
         } else {
             Cursor {
                 ipps,
                 current_index: i,
                 offset,
+                period,
             }
         }
     }
 }
 
 impl<'a> MergeCursor<'a> for Cursor<'a> {
-    fn new(ipps: &'a [TTFPoint]) -> Cursor<'a> {
+    fn new(ipps: &'a [TTFPoint], period: Timestamp) -> Cursor<'a> {
         Cursor {
             ipps,
             current_index: 0,
             offset: FlWeight::new(0.0),
+            period: FlWeight::from(period),
         }
     }
 
@@ -88,7 +95,7 @@ impl<'a> MergeCursor<'a> for Cursor<'a> {
 
     fn next(&self) -> TTFPoint {
         if self.ipps.len() == 1 {
-            self.ipps.first().unwrap().shifted(self.offset + FlWeight::from(period()))
+            self.ipps.first().unwrap().shifted(self.offset + self.period)
         } else {
             self.ipps[self.current_index + 1].shifted(self.offset)
         }
@@ -96,9 +103,9 @@ impl<'a> MergeCursor<'a> for Cursor<'a> {
 
     fn prev(&self) -> TTFPoint {
         if self.ipps.len() == 1 {
-            self.ipps.first().unwrap().shifted(self.offset - FlWeight::from(period()))
+            self.ipps.first().unwrap().shifted(self.offset - self.period)
         } else if self.current_index == 0 {
-            let offset = self.offset - FlWeight::from(period());
+            let offset = self.offset - self.period;
             self.ipps[self.ipps.len() - 2].shifted(offset)
         } else {
             self.ipps[self.current_index - 1].shifted(self.offset)
@@ -108,7 +115,7 @@ impl<'a> MergeCursor<'a> for Cursor<'a> {
     fn advance(&mut self) {
         self.current_index += 1;
         if self.current_index % self.ipps.len() == self.ipps.len() - 1 || self.ipps.len() == 1 {
-            self.offset = self.offset + FlWeight::from(period());
+            self.offset = self.offset + self.period;
             self.current_index = 0;
         }
     }
@@ -126,14 +133,16 @@ pub struct PartialPlfMergeCursor<'a> {
     next: TTFPoint,
     cur: TTFPoint,
     prev: TTFPoint,
+    period: FlWeight,
 }
 
 impl<'a> MergeCursor<'a> for PartialPlfMergeCursor<'a> {
-    fn new(ipps: &'a [TTFPoint]) -> Self {
+    fn new(ipps: &'a [TTFPoint], period: Timestamp) -> Self {
+        let period = FlWeight::from(period);
         let mut iter = ipps.iter();
         let next = iter.next().unwrap().clone();
         let cur = TTFPoint {
-            at: next.at - FlWeight::from(period()),
+            at: next.at - period,
             val: next.val,
         };
         let mut cursor = PartialPlfMergeCursor {
@@ -141,6 +150,7 @@ impl<'a> MergeCursor<'a> for PartialPlfMergeCursor<'a> {
             cur,
             next,
             iter,
+            period,
         };
         cursor.advance();
         cursor
@@ -164,7 +174,7 @@ impl<'a> MergeCursor<'a> for PartialPlfMergeCursor<'a> {
         if let Some(next) = self.iter.next() {
             self.next = next.clone();
         } else {
-            self.next.at = self.next.at + FlWeight::from(period());
+            self.next.at = self.next.at + self.period;
         }
     }
 
@@ -3,11 +3,10 @@
 use super::shortcut_source::Sources as _;
 use super::*;
 use std::cmp::{max, min};
-use std::convert::TryFrom;
 use std::sync::atomic::Ordering::Relaxed;
 
-/// Number of points that a PLF is allowed to have before reduction by approximation is triggered.
-/// Can be overriden through the TDCCH_APPROX_THRESHOLD env var
+/// Default number of points that a PLF is allowed to have before reduction by approximation is triggered.
+/// Can be overriden through the TDCCH_APPROX_THRESHOLD env var at build time or through `CatchupConfig::approx_threshold` at runtime.
 #[cfg(not(override_tdcch_approx_threshold))]
 pub const APPROX_THRESHOLD: usize = 1000;
 #[cfg(override_tdcch_approx_threshold)]
@@ -123,9 +122,9 @@ impl Shortcut {
                 // link functions
                 let linked = first_plf.link(&second_plf);
 
-                self.upper_bound = min(self.upper_bound, PeriodicATTF::from(&linked).static_upper_bound());
+                self.upper_bound = min(self.upper_bound, PeriodicATTF::new(&linked, shortcut_graph.period()).static_upper_bound());
                 debug_assert!(
-                    !cfg!(feature = "tdcch-precustomization") || !self.upper_bound.fuzzy_lt(self.lower_bound),
+                    !self.upper_bound.fuzzy_lt(self.lower_bound),
                     "lower {:?} upper {:?}",
                     self.lower_bound,
                     self.upper_bound
@@ -143,7 +142,7 @@ impl Shortcut {
                 ACTUALLY_LINKED.fetch_add(1, Relaxed);
             }
 
-            let linked = PeriodicATTF::from(&linked_ipps);
+            let linked = PeriodicATTF::new(&linked_ipps, shortcut_graph.period());
             // these bounds are more tight than the previous ones
             let other_lower_bound = linked.static_lower_bound();
             let other_upper_bound = linked.static_upper_bound();
@@ -155,12 +154,12 @@ impl Shortcut {
                 // new linked function is always better than the current one
                 self.upper_bound = min(self.upper_bound, other_upper_bound);
                 debug_assert!(
-                    !cfg!(feature = "tdcch-precustomization") || !self.upper_bound.fuzzy_lt(self.lower_bound),
+                    !self.upper_bound.fuzzy_lt(self.lower_bound),
                     "lower {:?} upper {:?}",
                     self.lower_bound,
                     self.upper_bound
                 );
-                if cfg!(feature = "tdcch-approx") && linked_ipps.num_points() > APPROX_THRESHOLD {
+                if buffers.approx_threshold.map(|threshold| linked_ipps.num_points() > threshold).unwrap_or(false) {
                     let old = linked_ipps.num_points();
                     if cfg!(feature = "detailed-stats") {
                         CONSIDERED_FOR_APPROX.fetch_add(old, Relaxed);
@@ -198,15 +197,21 @@ impl Shortcut {
                 ShortcutSource::from(other_data).reconstruct_exact_ttf(start, end, shortcut_graph, &mut other_target, &mut buffers.unpacking_tmp);
 
                 let (self_ipps, other_ipps) = other_target.storage().top_plfs();
-                PartialPiecewiseLinearFunction::new(self_ipps).merge(&PartialPiecewiseLinearFunction::new(other_ipps), start, end, &mut buffers.buffer)
+                PartialPiecewiseLinearFunction::new(self_ipps).merge(
+                    &PartialPiecewiseLinearFunction::new(other_ipps),
+                    start,
+                    end,
+                    shortcut_graph.period(),
+                    &mut buffers.buffer,
+                )
             });
-            if cfg!(feature = "tdcch-approx") && merged.num_points() > APPROX_THRESHOLD {
+            if buffers.approx_threshold.map(|threshold| merged.num_points() > threshold).unwrap_or(false) {
                 let old = merged.num_points();
                 if cfg!(feature = "detailed-stats") {
                     CONSIDERED_FOR_APPROX.fetch_add(old, Relaxed);
                 }
                 let dbg_res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                    merged = PeriodicATTF::from(&merged).approximate(buffers);
+                    merged = PeriodicATTF::new(&merged, shortcut_graph.period()).approximate(buffers);
                 }));
                 if dbg_res.is_err() {
                     panic!(
@@ -226,9 +231,9 @@ impl Shortcut {
             // We would like to increase the lower bound to make it tighter, but we can't take the max right now,
             // We might find a lower function during a later merge operation.
             // We can only set the lower bound as tight as possible, once we have the final travel time function.
-            self.upper_bound = min(self.upper_bound, PeriodicATTF::from(&merged).static_upper_bound());
+            self.upper_bound = min(self.upper_bound, PeriodicATTF::new(&merged, shortcut_graph.period()).static_upper_bound());
             debug_assert!(
-                !cfg!(feature = "tdcch-precustomization") || !self.upper_bound.fuzzy_lt(self.lower_bound),
+                !self.upper_bound.fuzzy_lt(self.lower_bound),
                 "lower {:?} upper {:?}",
                 self.lower_bound,
                 self.upper_bound
@@ -237,7 +242,7 @@ impl Shortcut {
             let mut sources = Sources::None;
             std::mem::swap(&mut sources, &mut self.sources);
             // calculate new `ShortcutSource`s.
-            self.sources = sources.combine(intersection_data, other_data, Timestamp::ZERO, period());
+            self.sources = sources.combine(intersection_data, other_data, Timestamp::ZERO, shortcut_graph.period(), shortcut_graph.period());
         })();
 
         if cfg!(feature = "detailed-stats") {
@@ -251,7 +256,7 @@ impl Shortcut {
 
     pub fn periodic_ttf<'s, 'g: 's>(&'s self, shortcut_graph: &'g impl ShortcutGraphTrt<OriginalGraph = TDGraph>) -> Option<PeriodicATTF<'s>> {
         if let Some(cache) = &self.cache {
-            return Some(cache.into());
+            return Some(PeriodicATTF::new(cache, shortcut_graph.period()));
         }
 
         match self.sources {
@@ -271,7 +276,7 @@ impl Shortcut {
         start: Timestamp,
         end: Timestamp,
     ) -> Option<PartialATTF<'s>> {
-        if start < Timestamp::ZERO || end > period() {
+        if start < Timestamp::ZERO || end > shortcut_graph.period() {
             return None;
         }
         self.periodic_ttf(shortcut_graph)
@@ -307,11 +312,8 @@ impl Shortcut {
             return;
         }
 
-        let new_lower_bound = if cfg!(feature = "tdcch-precustomization") {
-            max(self.lower_bound, self.periodic_ttf(shortcut_graph).unwrap().static_lower_bound())
-        } else {
-            self.periodic_ttf(shortcut_graph).unwrap().static_lower_bound()
-        };
+        // Without precustomization, the customization resets the lower bound to zero, so this always works.
+        let new_lower_bound = max(self.lower_bound, self.periodic_ttf(shortcut_graph).unwrap().static_lower_bound());
 
         // The final functions lower bound is worse than the upper bound this shortcut got during pre/post/perfect customization
         // Thus, we will never need it.
@@ -325,12 +327,7 @@ impl Shortcut {
             return;
         }
 
-        debug_assert!(
-            !cfg!(feature = "tdcch-precustomization") || !new_lower_bound.fuzzy_lt(self.lower_bound),
-            "{:?}, {:?}",
-            new_lower_bound,
-            self
-        );
+        debug_assert!(!new_lower_bound.fuzzy_lt(self.lower_bound), "{:?}, {:?}", new_lower_bound, self);
         debug_assert!(!self.upper_bound.fuzzy_lt(new_lower_bound), "{:?}, {:?}", new_lower_bound, self);
         self.lower_bound = new_lower_bound;
 
@@ -370,9 +367,9 @@ impl Shortcut {
         }
     }
 
-    pub fn reenable_required(&self, downward: &mut [Shortcut], upward: &mut [Shortcut]) {
+    pub fn reenable_required(&self, downward: &mut [Shortcut], upward: &mut [Shortcut], period: Timestamp) {
         if self.required {
-            for (_, source) in self.sources_iter() {
+            for (_, source) in self.sources_iter(period) {
                 if let ShortcutSource::Shortcut(down, up) = source.into() {
                     downward[down as usize].required = true;
                     upward[up as usize].required = true;
@@ -450,12 +447,12 @@ impl Shortcut {
     }
 
     /// Returns an iterator over all the sources combined with a Timestamp for the time from which the corresponding source becomes valid.
-    pub fn sources_iter(&self) -> impl Iterator<Item = (Timestamp, ShortcutSourceData)> + '_ {
-        self.sources_for(Timestamp::ZERO, period())
+    pub fn sources_iter(&self, period: Timestamp) -> impl Iterator<Item = (Timestamp, ShortcutSourceData)> + '_ {
+        self.sources_for(Timestamp::ZERO, period, period)
     }
 
-    pub fn sources_for(&self, start: Timestamp, end: Timestamp) -> impl Iterator<Item = (Timestamp, ShortcutSourceData)> + '_ {
-        self.sources.wrapping_iter_for(start, end)
+    pub fn sources_for(&self, start: Timestamp, end: Timestamp, period: Timestamp) -> impl Iterator<Item = (Timestamp, ShortcutSourceData)> + '_ {
+        self.sources.wrapping_iter_for(start, end, period)
     }
 
     pub fn is_constant(&self) -> bool {
@@ -479,7 +476,7 @@ impl Shortcut {
         ShortcutSource::from(*match &self.sources {
             Sources::None => unreachable!("There are no paths for empty shortcuts"),
             Sources::One(source) => source,
-            Sources::Multi(sources) => sources.edge_source_at(t).unwrap(),
+            Sources::Multi(sources) => sources.edge_source_at(t, shortcut_graph.period()).unwrap(),
         })
         .unpack_at(t, shortcut_graph, result)
     }
@@ -492,7 +489,7 @@ impl Shortcut {
         ShortcutSource::from(*match &self.sources {
             Sources::None => return FlWeight::INFINITY,
             Sources::One(source) => source,
-            Sources::Multi(sources) => sources.edge_source_at(t).unwrap(),
+            Sources::Multi(sources) => sources.edge_source_at(t, shortcut_graph.period()).unwrap(),
         })
         .evaluate(t, shortcut_graph)
     }
@@ -507,19 +504,19 @@ pub enum Sources {
 }
 
 impl Sources {
-    pub fn wrapping_iter_for(&self, start: Timestamp, end: Timestamp) -> SourcesIter<'_> {
+    pub fn wrapping_iter_for(&self, start: Timestamp, end: Timestamp, period: Timestamp) -> SourcesIter<'_> {
         match self {
             Sources::None => SourcesIter::None,
             Sources::One(source) => SourcesIter::One(start, std::iter::once(*source)),
-            Sources::Multi(sources) => SourcesIter::Multi(sources.wrapping_iter(start, end)),
+            Sources::Multi(sources) => SourcesIter::Multi(sources.wrapping_iter(start, end, period)),
         }
     }
 
-    pub fn iter(&self, start: Timestamp, end: Timestamp) -> SourcesIter<'_> {
+    pub fn iter(&self, start: Timestamp, end: Timestamp, period: Timestamp) -> SourcesIter<'_> {
         match self {
             Sources::None => SourcesIter::None,
             Sources::One(source) => SourcesIter::One(start, std::iter::once(*source)),
-            Sources::Multi(sources) => SourcesIter::Multi(sources.wrapping_iter(start, end)),
+            Sources::Multi(sources) => SourcesIter::Multi(sources.wrapping_iter(start, end, period)),
         }
     }
 
@@ -532,7 +529,14 @@ impl Sources {
     }
 
     // Combine current `Sources` and the result of a merge into new `Sources`
-    pub fn combine(self, intersection_data: Vec<(Timestamp, bool)>, other_data: ShortcutSourceData, start: Timestamp, end: Timestamp) -> Self {
+    pub fn combine(
+        self,
+        intersection_data: Vec<(Timestamp, bool)>,
+        other_data: ShortcutSourceData,
+        start: Timestamp,
+        end: Timestamp,
+        period: Timestamp,
+    ) -> Self {
         // when just one is better all the time
         if let [(_, is_self_better)] = &intersection_data[..] {
             if *is_self_better {
@@ -556,7 +560,7 @@ impl Sources {
         // while self is better we need to copy these over
         // when other becomes better at an intersection we need to insert other_data at the intersection time
         // when self becomes better at an intersection we need to insert the source that was active at that time in the old sources at the new intersection time.
-        for (at, source) in self.iter(start, end) {
+        for (at, source) in self.iter(start, end, period) {
             if intersection_iter.peek().is_none() || at < intersection_iter.peek().unwrap().0 {
                 if self_currently_better {
                     if new_sources.last().map(|&(last_at, _)| last_at.fuzzy_eq(at)).unwrap_or(false) {
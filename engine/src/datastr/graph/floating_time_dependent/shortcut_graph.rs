@@ -86,6 +86,9 @@ pub trait ShortcutGraphTrt {
     fn lower_bound(&self, shortcut_id: ShortcutId) -> FlWeight;
     fn upper_bound(&self, shortcut_id: ShortcutId) -> FlWeight;
     fn original_graph(&self) -> &Self::OriginalGraph;
+    fn period(&self) -> Timestamp {
+        self.original_graph().period()
+    }
     fn reconstruct_exact_ttf(&self, shortcut_id: ShortcutId, start: Timestamp, end: Timestamp, target: &mut MutTopPLF, tmp: &mut ReusablePLFStorage);
     fn get_switchpoints(&self, shortcut_id: ShortcutId, start: Timestamp, end: Timestamp) -> (Vec<(Timestamp, Vec<EdgeId>, FlWeight)>, FlWeight);
     fn unpack_at(&self, shortcut_id: ShortcutId, t: Timestamp, result: &mut Vec<(EdgeId, Timestamp)>);
@@ -188,6 +191,7 @@ pub struct CustomizedGraph<'a> {
 impl<'a> From<ShortcutGraph<'a>> for CustomizedGraph<'a> {
     // cleaning up and compacting preprocessing results.
     fn from(shortcut_graph: ShortcutGraph<'a>) -> Self {
+        let period = shortcut_graph.original_graph.horizon().period();
         let mut outgoing_required = BitVec::new(shortcut_graph.head.len());
         let mut incoming_required = BitVec::new(shortcut_graph.head.len());
 
@@ -280,7 +284,7 @@ impl<'a> From<ShortcutGraph<'a>> for CustomizedGraph<'a> {
                 first_source: degrees_to_first_out(outgoing_iter().map(|shortcut| shortcut.num_sources() as u32)).collect(),
                 sources: outgoing_iter()
                     .flat_map(|shortcut| {
-                        shortcut.sources_iter().map(|(t, s)| {
+                        shortcut.sources_iter(period).map(|(t, s)| {
                             let s = if let ShortcutSource::Shortcut(down, up) = ShortcutSource::from(s) {
                                 ShortcutSource::Shortcut(
                                     mapping_incoming.get(down as usize).unwrap() as EdgeId,
@@ -305,7 +309,7 @@ impl<'a> From<ShortcutGraph<'a>> for CustomizedGraph<'a> {
                 first_source: degrees_to_first_out(incoming_iter().map(|shortcut| shortcut.num_sources() as u32)).collect(),
                 sources: incoming_iter()
                     .flat_map(|shortcut| {
-                        shortcut.sources_iter().map(|(t, s)| {
+                        shortcut.sources_iter(period).map(|(t, s)| {
                             let s = if let ShortcutSource::Shortcut(down, up) = ShortcutSource::from(s) {
                                 ShortcutSource::Shortcut(
                                     mapping_incoming.get(down as usize).unwrap() as EdgeId,
@@ -385,7 +389,7 @@ impl<'a> CustomizedGraph<'a> {
     pub fn evaluate_and_path_length(&self, shortcut_id: ShortcutId, t: Timestamp) -> (FlWeight, usize) {
         ShortcutSource::from(
             *shortcut_id
-                .get_with(&self.incoming, &self.outgoing, |g_dir, id| g_dir.edge_source_at(id, t))
+                .get_with(&self.incoming, &self.outgoing, |g_dir, id| g_dir.edge_source_at(id, t, self.period()))
                 .unwrap(),
         )
         .evaluate_and_path_length(t, self)
@@ -516,7 +520,7 @@ impl CustomizedSingleDirGraph {
         if self.constant.get(edge_idx) {
             debug_assert_eq!(
                 self.bounds[edge_idx].0,
-                self.edge_source_at(edge_id, t)
+                self.edge_source_at(edge_id, t, customized_graph.period())
                     .map(|&source| ShortcutSource::from(source).evaluate(t, customized_graph))
                     .unwrap_or(FlWeight::INFINITY),
                 "{:?}, {:?}, {}",
@@ -527,7 +531,7 @@ impl CustomizedSingleDirGraph {
             return self.bounds[edge_idx].0;
         }
 
-        self.edge_source_at(edge_id, t)
+        self.edge_source_at(edge_id, t, customized_graph.period())
             .map(|&source| ShortcutSource::from(source).evaluate_opt(t, customized_graph, f))
             .unwrap_or(FlWeight::INFINITY)
     }
@@ -561,7 +565,7 @@ impl CustomizedSingleDirGraph {
                 edge_id,
             ));
         }
-        self.edge_source_at(edge_id, t).map(|&source| match source.into() {
+        self.edge_source_at(edge_id, t, customized_graph.period()).map(|&source| match source.into() {
             ShortcutSource::Shortcut(down, up) => {
                 mark_upward(up);
                 let lower_bound_to_middle = customized_graph.outgoing.bounds()[up as usize].0 + lower_bound_target;
@@ -622,13 +626,13 @@ impl CustomizedSingleDirGraph {
 
     /// Recursively unpack the edge with the given id at the given timestamp and add the path to `result`
     pub fn unpack_at(&self, edge_id: EdgeId, t: Timestamp, customized_graph: &CustomizedGraph, result: &mut Vec<(EdgeId, Timestamp)>) {
-        self.edge_source_at(edge_id, t)
+        self.edge_source_at(edge_id, t, customized_graph.period())
             .map(|&source| ShortcutSource::from(source).unpack_at(t, customized_graph, result))
             .expect("can't unpack empty shortcut");
     }
 
-    fn edge_source_at(&self, edge_id: EdgeId, t: Timestamp) -> Option<&ShortcutSourceData> {
-        self.edge_sources(edge_id).edge_source_at(t)
+    fn edge_source_at(&self, edge_id: EdgeId, t: Timestamp, period: Timestamp) -> Option<&ShortcutSourceData> {
+        self.edge_sources(edge_id).edge_source_at(t, period)
     }
 
     /// Borrow slice of all the source of the edge with given id.
@@ -693,7 +697,7 @@ impl<'a> ShortcutGraphTrt for CustomizedGraph<'a> {
     fn unpack_at(&self, shortcut_id: ShortcutId, t: Timestamp, result: &mut Vec<(EdgeId, Timestamp)>) {
         ShortcutSource::from(
             *shortcut_id
-                .get_with(&self.incoming, &self.outgoing, |g_dir, id| g_dir.edge_source_at(id, t))
+                .get_with(&self.incoming, &self.outgoing, |g_dir, id| g_dir.edge_source_at(id, t, self.period()))
                 .unwrap(),
         )
         .unpack_at(t, self, result)
@@ -701,7 +705,7 @@ impl<'a> ShortcutGraphTrt for CustomizedGraph<'a> {
     fn evaluate(&self, shortcut_id: ShortcutId, t: Timestamp) -> FlWeight {
         ShortcutSource::from(
             *shortcut_id
-                .get_with(&self.incoming, &self.outgoing, |g_dir, id| g_dir.edge_source_at(id, t))
+                .get_with(&self.incoming, &self.outgoing, |g_dir, id| g_dir.edge_source_at(id, t, self.period()))
                 .unwrap(),
         )
         .evaluate(t, self)
@@ -726,12 +730,12 @@ impl Default for ReconstructionState {
 }
 
 impl ReconstructionState {
-    fn request_time(times: &mut Vec<(Timestamp, Timestamp)>, mut start: Timestamp, mut end: Timestamp) {
+    fn request_time(times: &mut Vec<(Timestamp, Timestamp)>, mut start: Timestamp, mut end: Timestamp, period: Timestamp) {
         if !cfg!(feature = "tdcch-profiles-with-holes") {
             start = Timestamp::ZERO;
-            end = period();
+            end = period;
         }
-        if times.len() == 1 && times[0].0.fuzzy_eq(Timestamp::ZERO) && times[0].1.fuzzy_eq(period()) {
+        if times.len() == 1 && times[0].0.fuzzy_eq(Timestamp::ZERO) && times[0].1.fuzzy_eq(period) {
             return;
         }
 
@@ -786,9 +790,9 @@ impl ReconstructionState {
         }
 
         // maybe periodic
-        if times.iter().any(|&(start, end)| FlWeight::from(period()).fuzzy_leq(end - start)) {
+        if times.iter().any(|&(start, end)| FlWeight::from(period).fuzzy_leq(end - start)) {
             times.truncate(1);
-            times[0] = (Timestamp::ZERO, period());
+            times[0] = (Timestamp::ZERO, period);
         }
     }
 }
@@ -822,6 +826,7 @@ pub struct ReconstructionGraph<'a> {
 impl<'a> ReconstructionGraph<'a> {
     fn cache(&mut self, shortcut_id: ShortcutId, start: Timestamp, end: Timestamp, buffers: &mut MergeBuffers) {
         debug_assert!(start.fuzzy_lt(end));
+        let period = self.customized_graph.period();
         let inserts = shortcut_id
             .get_from(&self.incoming_cache, &self.outgoing_cache)
             .as_ref()
@@ -846,7 +851,7 @@ impl<'a> ReconstructionGraph<'a> {
                             &self.customized_graph.outgoing,
                             CustomizedSingleDirGraph::edge_sources,
                         );
-                        let mut c = SourceCursor::valid_at(edge_sources, start);
+                        let mut c = SourceCursor::valid_at(edge_sources, start, period);
 
                         while c.cur().0.fuzzy_lt(end) {
                             let mut inner_target = buffers.unpacking_tmp.push_plf();
@@ -868,7 +873,7 @@ impl<'a> ReconstructionGraph<'a> {
 
                         let mut target = buffers.unpacking_target.push_plf();
 
-                        let mut c = SourceCursor::valid_at(edge_sources, start);
+                        let mut c = SourceCursor::valid_at(edge_sources, start, period);
 
                         while c.cur().0.fuzzy_lt(end) {
                             let mut inner_target = buffers.unpacking_tmp.push_plf();
@@ -916,7 +921,7 @@ impl<'a> ReconstructionGraph<'a> {
             .get_mut_from(&mut self.incoming_cache, &mut self.outgoing_cache)
             .as_mut()
             .unwrap()
-            .maybe_to_periodic();
+            .maybe_to_periodic(period);
     }
 
     pub fn cache_iterative_iteration(
@@ -928,6 +933,7 @@ impl<'a> ReconstructionGraph<'a> {
         outgoing_reconstruction_states: &mut [ReconstructionState],
         buffers: &mut MergeBuffers,
     ) {
+        let period = self.customized_graph.period();
         let edge_sources = shortcut_id.get_with(
             &self.customized_graph.incoming,
             &self.customized_graph.outgoing,
@@ -936,7 +942,7 @@ impl<'a> ReconstructionGraph<'a> {
 
         let mut any_down_missing = false;
         for &(start, end) in &state.requested_times {
-            let mut c = SourceCursor::valid_at(edge_sources, start);
+            let mut c = SourceCursor::valid_at(edge_sources, start, period);
 
             while c.cur().0.fuzzy_lt(end) {
                 match ShortcutSource::from(c.cur().1) {
@@ -946,7 +952,12 @@ impl<'a> ReconstructionGraph<'a> {
 
                         if !self.ttf_available(ShortcutId::Incoming(down), first_start, first_end) {
                             any_down_missing = true;
-                            ReconstructionState::request_time(&mut incoming_reconstruction_states[down as usize].requested_times, first_start, first_end);
+                            ReconstructionState::request_time(
+                                &mut incoming_reconstruction_states[down as usize].requested_times,
+                                first_start,
+                                first_end,
+                                period,
+                            );
                             if !incoming_reconstruction_states[down as usize].awaited_by.contains(&shortcut_id) {
                                 incoming_reconstruction_states[down as usize].awaited_by.push(shortcut_id);
                                 state.missing_deps += 1;
@@ -983,7 +994,7 @@ impl<'a> ReconstructionGraph<'a> {
 
         let mut any_up_missing = false;
         for &(start, end) in &state.requested_times {
-            let mut c = SourceCursor::valid_at(edge_sources, start);
+            let mut c = SourceCursor::valid_at(edge_sources, start, period);
 
             while c.cur().0.fuzzy_lt(end) {
                 match ShortcutSource::from(c.cur().1) {
@@ -1004,7 +1015,12 @@ impl<'a> ReconstructionGraph<'a> {
                         if !self.ttf_available(ShortcutId::Outgoing(up), second_start, second_end) {
                             any_up_missing = true;
 
-                            ReconstructionState::request_time(&mut outgoing_reconstruction_states[up as usize].requested_times, second_start, second_end);
+                            ReconstructionState::request_time(
+                                &mut outgoing_reconstruction_states[up as usize].requested_times,
+                                second_start,
+                                second_end,
+                                period,
+                            );
                             if !outgoing_reconstruction_states[up as usize].awaited_by.contains(&shortcut_id) {
                                 outgoing_reconstruction_states[up as usize].awaited_by.push(shortcut_id);
                                 state.missing_deps += 1;
@@ -1111,9 +1127,10 @@ impl<'a> ReconstructionGraph<'a> {
             return;
         }
 
-        if !cfg!(feature = "tdcch-profiles-with-holes") || FlWeight::from(period()).fuzzy_leq(end - start) {
+        let period = self.customized_graph.period();
+        if !cfg!(feature = "tdcch-profiles-with-holes") || FlWeight::from(period).fuzzy_leq(end - start) {
             start = Timestamp::ZERO;
-            end = period();
+            end = period;
         }
 
         let edge_sources = shortcut_id.get_with(
@@ -1122,7 +1139,7 @@ impl<'a> ReconstructionGraph<'a> {
             CustomizedSingleDirGraph::edge_sources,
         );
 
-        let mut c = SourceCursor::valid_at(edge_sources, start);
+        let mut c = SourceCursor::valid_at(edge_sources, start, period);
 
         while c.cur().0.fuzzy_lt(end) {
             match ShortcutSource::from(c.cur().1) {
@@ -1136,7 +1153,7 @@ impl<'a> ReconstructionGraph<'a> {
             c.advance();
         }
 
-        let mut c = SourceCursor::valid_at(edge_sources, start);
+        let mut c = SourceCursor::valid_at(edge_sources, start, period);
 
         while c.cur().0.fuzzy_lt(end) {
             match ShortcutSource::from(c.cur().1) {
@@ -1215,7 +1232,8 @@ impl<'a> ReconstructionGraph<'a> {
     fn periodic_ttf(&self, shortcut_id: ShortcutId) -> Option<PeriodicATTF<'_>> {
         // TODO remove in favor of ReconstructedGraph::periodic_ttf
         if let Some(cache) = shortcut_id.get_from(&self.incoming_cache, &self.outgoing_cache) {
-            return cache.ttf(Timestamp::ZERO, period())?.try_into().ok();
+            let period = self.customized_graph.period();
+            return cache.ttf(Timestamp::ZERO, period)?.to_periodic(period);
         }
 
         match shortcut_id.get_with(
@@ -1272,6 +1290,7 @@ impl<'a> ReconstructedGraph<'a> {
                 CustomizedSingleDirGraph::edge_sources,
             ),
             start,
+            self.period(),
         );
 
         while c.cur().0.fuzzy_lt(end) {
@@ -1313,7 +1332,7 @@ impl<'a> ShortcutGraphTrt for ReconstructedGraph<'a> {
 
     fn periodic_ttf(&self, shortcut_id: ShortcutId) -> Option<PeriodicATTF<'_>> {
         if let Some(cache) = shortcut_id.get_from(&self.incoming_cache, &self.outgoing_cache) {
-            return cache.ttf(Timestamp::ZERO, period())?.try_into().ok();
+            return cache.ttf(Timestamp::ZERO, self.period())?.to_periodic(self.period());
         }
 
         match shortcut_id.get_with(
@@ -1410,7 +1429,7 @@ impl<'a> ShortcutGraphTrt for ReconstructedGraph<'a> {
         ShortcutSource::from(
             *shortcut_id
                 .get_with(&self.customized_graph.incoming, &self.customized_graph.outgoing, |g_dir, id| {
-                    g_dir.edge_source_at(id, t)
+                    g_dir.edge_source_at(id, t, self.period())
                 })
                 .unwrap(),
         )
@@ -1420,7 +1439,7 @@ impl<'a> ShortcutGraphTrt for ReconstructedGraph<'a> {
         ShortcutSource::from(
             *shortcut_id
                 .get_with(&self.customized_graph.incoming, &self.customized_graph.outgoing, |g_dir, id| {
-                    g_dir.edge_source_at(id, t)
+                    g_dir.edge_source_at(id, t, self.period())
                 })
                 .unwrap(),
         )
@@ -1636,13 +1655,14 @@ impl<'a> PartialProfileGraphWrapper<'a> {
         bounds: &mut impl FnMut(ShortcutId) -> (FlWeight, FlWeight),
         lower_and_upper_node: &mut impl FnMut(ShortcutId) -> (NodeId, NodeId),
     ) {
+        let period = self.period();
         let mut any_down_missing = false;
         for &(start, end) in &state.requested_times {
             let mut deps_to_add = 0;
             for_each_lower_triangle_of(shortcut_id, &mut |down, up, middle_node| {
                 if !self.ttf_available(ShortcutId::Incoming(down), start, end) {
                     any_down_missing = true;
-                    ReconstructionState::request_time(&mut incoming_reconstruction_states[down as usize].requested_times, start, end);
+                    ReconstructionState::request_time(&mut incoming_reconstruction_states[down as usize].requested_times, start, end, period);
                     if !incoming_reconstruction_states[down as usize].awaited_by.contains(&shortcut_id) {
                         incoming_reconstruction_states[down as usize].awaited_by.push(shortcut_id);
                         deps_to_add += 1;
@@ -1686,7 +1706,12 @@ impl<'a> PartialProfileGraphWrapper<'a> {
                 if !self.ttf_available(ShortcutId::Outgoing(up), second_start, second_end) {
                     any_up_missing = true;
 
-                    ReconstructionState::request_time(&mut outgoing_reconstruction_states[up as usize].requested_times, second_start, second_end);
+                    ReconstructionState::request_time(
+                        &mut outgoing_reconstruction_states[up as usize].requested_times,
+                        second_start,
+                        second_end,
+                        period,
+                    );
                     if !outgoing_reconstruction_states[up as usize].awaited_by.contains(&shortcut_id) {
                         outgoing_reconstruction_states[up as usize].awaited_by.push(shortcut_id);
                         deps_to_add += 1;
@@ -1724,7 +1749,7 @@ impl<'a> PartialProfileGraphWrapper<'a> {
             let inserts = PartialTrt::missing(self.get(shortcut_id), start, end)
                 .into_iter()
                 .map(|(start, end)| {
-                    let mut shortcut = PartialShortcut::new_finished(start, end, &[], bounds);
+                    let mut shortcut = PartialShortcut::new_finished(start, end, period, &[], bounds);
 
                     let mut triangles = Vec::new();
                     for_each_lower_triangle_of(shortcut_id, &mut |down, up, _| {
@@ -1789,9 +1814,9 @@ impl<'a> ShortcutGraphTrt for PartialProfileGraphWrapper<'a> {
             return self.profile_graph.periodic_ttf(shortcut_id);
         }
         if self.is_dummy(shortcut_id) {
-            Some(PeriodicATTF::Exact(PeriodicPiecewiseLinearFunction::ZERO))
+            Some(PeriodicATTF::Exact(PeriodicPiecewiseLinearFunction::zero(self.period())))
         } else {
-            self.get_for_time_range(shortcut_id, Timestamp::ZERO, period())
+            self.get_for_time_range(shortcut_id, Timestamp::ZERO, self.period())
                 .and_then(|shortcut| shortcut.periodic_ttf(self))
         }
     }
@@ -511,9 +511,9 @@ pub trait Sources {
     fn get_switchpoints(&self, start: Timestamp, end: Timestamp, shortcut_graph: &impl ShortcutGraphTrt)
         -> (Vec<(Timestamp, Vec<EdgeId>, FlWeight)>, FlWeight);
 
-    fn edge_source_at(&self, t: Timestamp) -> Option<&ShortcutSourceData>;
+    fn edge_source_at(&self, t: Timestamp, period: Timestamp) -> Option<&ShortcutSourceData>;
 
-    fn wrapping_iter(&self, start: Timestamp, end: Timestamp) -> WrappingSourceIter<'_>;
+    fn wrapping_iter(&self, start: Timestamp, end: Timestamp, period: Timestamp) -> WrappingSourceIter<'_>;
 }
 
 use std::cmp::{max, min};
@@ -527,7 +527,7 @@ impl Sources for [(Timestamp, ShortcutSourceData)] {
         tmp: &mut ReusablePLFStorage,
     ) {
         // when we have multiple source, we need to do unpacking (and append the results) for all sources which are relevant for the given time range.
-        let mut c = SourceCursor::valid_at(self, start);
+        let mut c = SourceCursor::valid_at(self, start, shortcut_graph.period());
         while c.cur().0.fuzzy_lt(end) {
             let mut inner_target = tmp.push_plf();
             ShortcutSource::from(c.cur().1).reconstruct_exact_ttf(
@@ -555,7 +555,7 @@ impl Sources for [(Timestamp, ShortcutSourceData)] {
         end: Timestamp,
         shortcut_graph: &impl ShortcutGraphTrt,
     ) -> (Vec<(Timestamp, Vec<EdgeId>, FlWeight)>, FlWeight) {
-        let mut c = SourceCursor::valid_at(self, start);
+        let mut c = SourceCursor::valid_at(self, start, shortcut_graph.period());
 
         let (mut switchpoints, mut last_weight) = ShortcutSource::from(c.cur().1).get_switchpoints(max(start, c.cur().0), min(end, c.next().0), shortcut_graph);
 
@@ -578,7 +578,7 @@ impl Sources for [(Timestamp, ShortcutSourceData)] {
         (switchpoints, last_weight)
     }
 
-    fn edge_source_at(&self, t: Timestamp) -> Option<&ShortcutSourceData> {
+    fn edge_source_at(&self, t: Timestamp, period: Timestamp) -> Option<&ShortcutSourceData> {
         if self.is_empty() {
             return None;
         }
@@ -586,7 +586,7 @@ impl Sources for [(Timestamp, ShortcutSourceData)] {
             return Some(&self[0].1);
         }
 
-        let (_, t_period) = t.split_of_period();
+        let (_, t_period) = t.split_of_period(period);
         debug_assert!(self.first().map(|&(t, _)| t == Timestamp::ZERO).unwrap_or(true), "{:?}", self);
         match self.binary_search_by_key(&t_period, |(t, _)| *t) {
             Ok(i) => self.get(i),
@@ -601,27 +601,29 @@ impl Sources for [(Timestamp, ShortcutSourceData)] {
         .map(|(_, s)| s)
     }
 
-    fn wrapping_iter(&self, start: Timestamp, end: Timestamp) -> WrappingSourceIter<'_> {
+    fn wrapping_iter(&self, start: Timestamp, end: Timestamp, period: Timestamp) -> WrappingSourceIter<'_> {
         WrappingSourceIter {
-            cursor: SourceCursor::valid_at(&self, start),
+            cursor: SourceCursor::valid_at(&self, start, period),
             end,
         }
     }
 }
 
 // Helper struct to iterate over sources.
-// Allows to get sources valid for times > period().
+// Allows to get sources valid for times > period.
 // Handles all the ugly wraparound logic.
 pub struct SourceCursor<'a> {
     sources: &'a [(Timestamp, ShortcutSourceData)],
     current_index: usize,
     offset: FlWeight,
+    period: FlWeight,
 }
 
 impl<'a> SourceCursor<'a> {
-    pub fn valid_at(sources: &'a [(Timestamp, ShortcutSourceData)], t: Timestamp) -> Self {
-        let (times_period, t) = t.split_of_period();
-        let offset = times_period * FlWeight::from(period());
+    pub fn valid_at(sources: &'a [(Timestamp, ShortcutSourceData)], t: Timestamp, period: Timestamp) -> Self {
+        let (times_period, t) = t.split_of_period(period);
+        let period = FlWeight::from(period);
+        let offset = times_period * period;
 
         let pos = sources.binary_search_by(|p| {
             use std::cmp::Ordering;
@@ -639,11 +641,13 @@ impl<'a> SourceCursor<'a> {
                 sources,
                 current_index: i,
                 offset,
+                period,
             },
             Err(i) => Self {
                 sources,
                 current_index: i - 1,
                 offset,
+                period,
             },
         }
     }
@@ -654,7 +658,7 @@ impl<'a> SourceCursor<'a> {
 
     pub fn next(&self) -> (Timestamp, ShortcutSourceData) {
         if self.current_index + 1 == self.sources.len() {
-            (self.sources[0].0 + self.offset + FlWeight::from(period()), self.sources[0].1)
+            (self.sources[0].0 + self.offset + self.period, self.sources[0].1)
         } else {
             (self.sources[self.current_index + 1].0 + self.offset, self.sources[self.current_index + 1].1)
         }
@@ -663,7 +667,7 @@ impl<'a> SourceCursor<'a> {
     pub fn advance(&mut self) {
         self.current_index += 1;
         if self.current_index == self.sources.len() {
-            self.offset = self.offset + FlWeight::from(period());
+            self.offset = self.offset + self.period;
             self.current_index = 0;
         }
     }
@@ -82,18 +82,18 @@ impl ATTFContainer<Vec<TTFPoint>> {
     }
 }
 
-impl<D> TryFrom<ApproxPartialsContainer<D>> for ATTFContainer<D>
+impl<D> ApproxPartialsContainer<D>
 where
     D: std::ops::Deref<Target = [TTFPoint]>,
 {
-    type Error = ();
-    fn try_from(partials: ApproxPartialsContainer<D>) -> Result<Self, Self::Error> {
-        if let [partial] = &partials.partials[..] {
-            if partial.start == Timestamp::ZERO && partial.end == period() {
-                return Ok(partials.partials.into_iter().next().unwrap().ttf);
+    /// Extract the contained function if it consists of a single partial covering exactly `[0, period]`.
+    pub fn into_periodic(self, period: Timestamp) -> Option<ATTFContainer<D>> {
+        if let [partial] = &self.partials[..] {
+            if partial.start == Timestamp::ZERO && partial.end == period {
+                return self.partials.into_iter().next().map(|partial| partial.ttf);
             }
         }
-        Err(())
+        None
     }
 }
 
@@ -133,22 +133,28 @@ pub enum PeriodicATTF<'a> {
     Approx(PeriodicPiecewiseLinearFunction<'a>, PeriodicPiecewiseLinearFunction<'a>),
 }
 
-impl<'a, D> From<&'a ATTFContainer<D>> for PeriodicATTF<'a>
-where
-    D: std::ops::Deref<Target = [TTFPoint]>,
-{
-    fn from(cache: &'a ATTFContainer<D>) -> Self {
+impl<'a> PeriodicATTF<'a> {
+    /// View a stored function as periodic with the given `period`.
+    pub fn new<D>(cache: &'a ATTFContainer<D>, period: Timestamp) -> Self
+    where
+        D: std::ops::Deref<Target = [TTFPoint]>,
+    {
         match cache {
-            ATTFContainer::Exact(ipps) => PeriodicATTF::Exact(PeriodicPiecewiseLinearFunction::new(ipps)),
+            ATTFContainer::Exact(ipps) => PeriodicATTF::Exact(PeriodicPiecewiseLinearFunction::new(ipps, period)),
             ATTFContainer::Approx(lower_ipps, upper_ipps) => PeriodicATTF::Approx(
-                PeriodicPiecewiseLinearFunction::new(lower_ipps),
-                PeriodicPiecewiseLinearFunction::new(upper_ipps),
+                PeriodicPiecewiseLinearFunction::new(lower_ipps, period),
+                PeriodicPiecewiseLinearFunction::new(upper_ipps, period),
             ),
         }
     }
-}
 
-impl<'a> PeriodicATTF<'a> {
+    pub fn period(&self) -> Timestamp {
+        match self {
+            PeriodicATTF::Exact(plf) => plf.period(),
+            PeriodicATTF::Approx(lower_plf, _) => lower_plf.period(),
+        }
+    }
+
     pub fn exact(&self) -> bool {
         use PeriodicATTF::*;
 
@@ -510,7 +516,7 @@ impl<'a> PeriodicATTF<'a> {
 
         // we were not dominating in the end, so we need to merge the rest
         if !dominating {
-            let (_, intersections) = merge_exact(start_of_segment, period(), buffers);
+            let (_, intersections) = merge_exact(start_of_segment, self.period(), buffers);
 
             if intersections.len() > 1
                 || result.last().map(|(_, self_better)| *self_better != intersections[0].1).unwrap_or(true)
@@ -563,7 +569,7 @@ impl<'a> PeriodicATTF<'a> {
 
         debug_assert_eq!(bound_merge_state[0].0, Timestamp::ZERO);
 
-        let mut end_of_segment_iter = bound_merge_state.iter().map(|(t, _)| *t).chain(std::iter::once(period()));
+        let mut end_of_segment_iter = bound_merge_state.iter().map(|(t, _)| *t).chain(std::iter::once(self.period()));
         end_of_segment_iter.next();
 
         // go over all segments, either copy the better one, or merge bounds (this time lower with lower and upper with upper) and append these
@@ -585,6 +591,7 @@ impl<'a> PeriodicATTF<'a> {
                             &PartialPiecewiseLinearFunction::new(&other_lower).sub_plf(start_of_segment, end_of_segment),
                             start_of_segment,
                             end_of_segment,
+                            self.period(),
                             &mut buffers.buffer,
                         );
                     if let &[TTFPoint { val, .. }] = &partial_lower[..] {
@@ -600,6 +607,7 @@ impl<'a> PeriodicATTF<'a> {
                             &PartialPiecewiseLinearFunction::new(&other_upper).sub_plf(start_of_segment, end_of_segment),
                             start_of_segment,
                             end_of_segment,
+                            self.period(),
                             &mut buffers.buffer,
                         );
                     if let &[TTFPoint { val, .. }] = &partial_upper[..] {
@@ -685,17 +693,15 @@ impl<'a> TryFrom<PeriodicATTF<'a>> for PartialATTF<'a> {
     }
 }
 
-impl<'a> TryFrom<PartialATTF<'a>> for PeriodicATTF<'a> {
-    type Error = ();
-    fn try_from(ttf: PartialATTF<'a>) -> Result<Self, Self::Error> {
-        Ok(match ttf {
-            PartialATTF::Exact(plf) => PeriodicATTF::Exact(plf.try_into()?),
-            PartialATTF::Approx(lower_plf, upper_plf) => PeriodicATTF::Approx(lower_plf.try_into()?, upper_plf.try_into()?),
+impl<'a> PartialATTF<'a> {
+    /// Restrict to `[0, period]` and view as periodic function, if it covers the complete period.
+    pub fn to_periodic(self, period: Timestamp) -> Option<PeriodicATTF<'a>> {
+        Some(match self {
+            PartialATTF::Exact(plf) => PeriodicATTF::Exact(plf.to_periodic(period)?),
+            PartialATTF::Approx(lower_plf, upper_plf) => PeriodicATTF::Approx(lower_plf.to_periodic(period)?, upper_plf.to_periodic(period)?),
         })
     }
-}
 
-impl<'a> PartialATTF<'a> {
     pub fn exact(&self) -> bool {
         use PartialATTF::*;
 
@@ -771,6 +777,7 @@ impl<'a> PartialATTF<'a> {
         other: &Self,
         start: Timestamp,
         end: Timestamp,
+        period: Timestamp,
         buffers: &mut MergeBuffers,
         merge_exact: impl Fn(Timestamp, Timestamp, &mut MergeBuffers) -> (Box<[TTFPoint]>, Vec<(Timestamp, bool)>),
     ) -> (ATTFContainer<Box<[TTFPoint]>>, Vec<(Timestamp, bool)>) {
@@ -778,7 +785,7 @@ impl<'a> PartialATTF<'a> {
 
         // easy case, both functions are exact, we can just do actual function mering and are done
         if let (Exact(self_plf), Exact(other)) = (self, other) {
-            let (plf, intersections) = self_plf.merge(other, start, end, &mut buffers.buffer);
+            let (plf, intersections) = self_plf.merge(other, start, end, period, &mut buffers.buffer);
             return (ATTFContainer::Exact(plf), intersections);
         }
 
@@ -788,8 +795,8 @@ impl<'a> PartialATTF<'a> {
 
         // merge lower with upper bounds to check when one function completely dominates the other one
         // and when bounds overlap
-        let (_, self_dominating_intersections) = self_upper.merge(&other_lower, start, end, &mut buffers.buffer);
-        let (_, other_dominating_intersections) = other_upper.merge(&self_lower, start, end, &mut buffers.buffer);
+        let (_, self_dominating_intersections) = self_upper.merge(&other_lower, start, end, period, &mut buffers.buffer);
+        let (_, other_dominating_intersections) = other_upper.merge(&self_lower, start, end, period, &mut buffers.buffer);
 
         let mut dominating = false; // does currently one function completely dominate the other
         let mut start_of_segment = start; // where does the current dominance segment start
@@ -1157,6 +1164,7 @@ impl<'a> PartialATTF<'a> {
                         &other_lower.sub_plf(start_of_segment, end_of_segment),
                         start_of_segment,
                         end_of_segment,
+                        period,
                         &mut buffers.buffer,
                     );
                     if let &[TTFPoint { val, .. }] = &partial_lower[..] {
@@ -1170,6 +1178,7 @@ impl<'a> PartialATTF<'a> {
                         &other_upper.sub_plf(start_of_segment, end_of_segment),
                         start_of_segment,
                         end_of_segment,
+                        period,
                         &mut buffers.buffer,
                     );
                     if let &[TTFPoint { val, .. }] = &partial_upper[..] {
@@ -1228,12 +1237,12 @@ impl<'a> PartialATTF<'a> {
         }
     }
 
-    fn can_crop_to_period(&self) -> bool {
+    fn can_crop_to_period(&self, period: Timestamp) -> bool {
         match &self {
-            Self::Exact(points) => PartialPiecewiseLinearFunction::crop_in_place_possible(points, Timestamp::ZERO, period()),
+            Self::Exact(points) => PartialPiecewiseLinearFunction::crop_in_place_possible(points, Timestamp::ZERO, period),
             Self::Approx(lower, upper) => {
-                PartialPiecewiseLinearFunction::crop_in_place_possible(lower, Timestamp::ZERO, period())
-                    && PartialPiecewiseLinearFunction::crop_in_place_possible(upper, Timestamp::ZERO, period())
+                PartialPiecewiseLinearFunction::crop_in_place_possible(lower, Timestamp::ZERO, period)
+                    && PartialPiecewiseLinearFunction::crop_in_place_possible(upper, Timestamp::ZERO, period)
             }
         }
     }
@@ -1485,44 +1494,44 @@ where
     D: std::ops::DerefMut<Target = [TTFPoint]>,
     Vec<TTFPoint>: Into<D>,
 {
-    pub fn maybe_to_periodic(&mut self) {
+    pub fn maybe_to_periodic(&mut self, period: Timestamp) {
         if let [partial] = &mut self.partials[..] {
             let ttf = PartialATTF::from(&partial.ttf);
-            if ttf.can_crop_to_period() && partial.start.fuzzy_leq(Timestamp::ZERO) && period().fuzzy_leq(partial.end) {
+            if ttf.can_crop_to_period(period) && partial.start.fuzzy_leq(Timestamp::ZERO) && period.fuzzy_leq(partial.end) {
                 match &mut partial.ttf {
                     ATTFContainer::Exact(plf) => {
-                        PartialPiecewiseLinearFunction::crop(plf, Timestamp::ZERO, period());
+                        PartialPiecewiseLinearFunction::crop(plf, Timestamp::ZERO, period);
                     }
                     ATTFContainer::Approx(lower_plf, upper_plf) => {
-                        PartialPiecewiseLinearFunction::crop(lower_plf, Timestamp::ZERO, period());
-                        PartialPiecewiseLinearFunction::crop(upper_plf, Timestamp::ZERO, period());
+                        PartialPiecewiseLinearFunction::crop(lower_plf, Timestamp::ZERO, period);
+                        PartialPiecewiseLinearFunction::crop(upper_plf, Timestamp::ZERO, period);
                         PeriodicPiecewiseLinearFunction::make_lower_bound_periodic(lower_plf);
                         PeriodicPiecewiseLinearFunction::make_upper_bound_periodic(upper_plf);
                     }
                 }
                 partial.start = Timestamp::ZERO;
-                partial.end = period();
+                partial.end = period;
                 return;
             }
         }
 
-        if let Some(full_period_partial) = self.ttf(Timestamp::ZERO, period()) {
+        if let Some(full_period_partial) = self.ttf(Timestamp::ZERO, period) {
             let new_container = match full_period_partial {
                 PartialATTF::Exact(plf) => {
                     let mut target = Vec::with_capacity(plf.len());
                     target.extend_from_slice(&plf);
-                    PartialPiecewiseLinearFunction::crop(&mut target, Timestamp::ZERO, period());
+                    PartialPiecewiseLinearFunction::crop(&mut target, Timestamp::ZERO, period);
                     ATTFContainer::Exact(target.into())
                 }
                 PartialATTF::Approx(lower_plf, upper_plf) => {
                     let mut lower_target = Vec::with_capacity(lower_plf.len());
                     lower_target.extend_from_slice(&lower_plf);
-                    PartialPiecewiseLinearFunction::crop(&mut lower_target, Timestamp::ZERO, period());
+                    PartialPiecewiseLinearFunction::crop(&mut lower_target, Timestamp::ZERO, period);
                     PeriodicPiecewiseLinearFunction::make_lower_bound_periodic(&mut lower_target);
 
                     let mut upper_target = Vec::with_capacity(upper_plf.len());
                     upper_target.extend_from_slice(&upper_plf);
-                    PartialPiecewiseLinearFunction::crop(&mut upper_target, Timestamp::ZERO, period());
+                    PartialPiecewiseLinearFunction::crop(&mut upper_target, Timestamp::ZERO, period);
                     PeriodicPiecewiseLinearFunction::make_upper_bound_periodic(&mut upper_target);
 
                     ATTFContainer::Approx(lower_target.into(), upper_target.into())
@@ -1532,60 +1541,59 @@ where
             self.partials[0] = Partial {
                 ttf: new_container,
                 start: Timestamp::ZERO,
-                end: period(),
+                end: period,
             };
             return;
         }
 
         for partial in &self.partials {
             let ttf = PartialATTF::from(&partial.ttf);
-            if FlWeight::from(period()).fuzzy_leq(partial.end - partial.start) {
-                let (times_period, _) = partial.start.split_of_period();
-                let low_offset = times_period * FlWeight::from(period());
-                let mid_offset = low_offset + period();
+            if FlWeight::from(period).fuzzy_leq(partial.end - partial.start) {
+                let (times_period, _) = partial.start.split_of_period(period);
+                let low_offset = times_period * FlWeight::from(period);
+                let mid_offset = low_offset + period;
 
                 let new_container = match ttf {
                     PartialATTF::Exact(plf) => {
                         let mut target = Vec::with_capacity(plf.len() + 2);
-                        plf.sub_plf(mid_offset, partial.start + FlWeight::from(period()))
-                            .append(mid_offset, &mut target);
+                        plf.sub_plf(mid_offset, partial.start + FlWeight::from(period)).append(mid_offset, &mut target);
                         for p in &mut target {
-                            p.at = p.at - FlWeight::from(period());
+                            p.at = p.at - FlWeight::from(period);
                         }
                         plf.sub_plf(partial.start, mid_offset).append(partial.start, &mut target);
                         for p in &mut target {
                             p.at = p.at - low_offset;
                         }
-                        PartialPiecewiseLinearFunction::crop(&mut target, Timestamp::ZERO, period());
+                        PartialPiecewiseLinearFunction::crop(&mut target, Timestamp::ZERO, period);
                         ATTFContainer::Exact(target.into())
                     }
                     PartialATTF::Approx(lower_plf, upper_plf) => {
                         let mut lower_target = Vec::with_capacity(lower_plf.len() + 2);
                         lower_plf
-                            .sub_plf(mid_offset, partial.start + FlWeight::from(period()))
+                            .sub_plf(mid_offset, partial.start + FlWeight::from(period))
                             .append(mid_offset, &mut lower_target);
                         for p in &mut lower_target {
-                            p.at = p.at - FlWeight::from(period());
+                            p.at = p.at - FlWeight::from(period);
                         }
                         lower_plf.sub_plf(partial.start, mid_offset).append_bound(partial.start, &mut lower_target, min);
                         for p in &mut lower_target {
                             p.at = p.at - low_offset;
                         }
-                        PartialPiecewiseLinearFunction::crop(&mut lower_target, Timestamp::ZERO, period());
+                        PartialPiecewiseLinearFunction::crop(&mut lower_target, Timestamp::ZERO, period);
                         PeriodicPiecewiseLinearFunction::make_lower_bound_periodic(&mut lower_target);
 
                         let mut upper_target = Vec::with_capacity(upper_plf.len() + 2);
                         upper_plf
-                            .sub_plf(mid_offset, partial.start + FlWeight::from(period()))
+                            .sub_plf(mid_offset, partial.start + FlWeight::from(period))
                             .append(mid_offset, &mut upper_target);
                         for p in &mut upper_target {
-                            p.at = p.at - FlWeight::from(period());
+                            p.at = p.at - FlWeight::from(period);
                         }
                         upper_plf.sub_plf(partial.start, mid_offset).append_bound(partial.start, &mut upper_target, max);
                         for p in &mut upper_target {
                             p.at = p.at - low_offset;
                         }
-                        PartialPiecewiseLinearFunction::crop(&mut upper_target, Timestamp::ZERO, period());
+                        PartialPiecewiseLinearFunction::crop(&mut upper_target, Timestamp::ZERO, period);
                         PeriodicPiecewiseLinearFunction::make_upper_bound_periodic(&mut upper_target);
 
                         ATTFContainer::Approx(lower_target.into(), upper_target.into())
@@ -1595,7 +1603,7 @@ where
                 self.partials[0] = Partial {
                     ttf: new_container,
                     start: Timestamp::ZERO,
-                    end: period(),
+                    end: period,
                 };
                 break;
             }
@@ -0,0 +1,599 @@
+//! Grid based spatial indices over the nodes and edges of a graph with node coordinates.
+//!
+//! The bounding box of the data is divided into square cells (in degrees), which are enlarged when there would be too many of them.
+//! Each node is stored in the cell containing it, each edge in all cells its bounding box overlaps.
+//! Edges are straight lines between their endpoints unless an `EdgeGeometry` with intermediate shape points is given.
+//! Distances are computed in meters on a local equirectangular projection around the query point,
+//! which is precise enough for the small radii typically used for snapping.
+
+use super::graph::*;
+use crate::io::*;
+
+/// Mean earth radius in meters
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+/// Default cell size of the grid indices in degrees, roughly 500m in latitude direction
+pub const DEFAULT_CELL_SIZE: f64 = 0.005;
+
+/// Great circle distance between two coordinates in meters.
+pub fn haversine_distance(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (lng2 - lng1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+// Local projection around a reference coordinate into a plane with meters as units.
+#[derive(Debug, Clone, Copy)]
+struct LocalProjection {
+    lat: f64,
+    lng: f64,
+    cos_lat: f64,
+}
+
+impl LocalProjection {
+    fn new(lat: f64, lng: f64) -> Self {
+        Self {
+            lat,
+            lng,
+            cos_lat: lat.to_radians().cos(),
+        }
+    }
+
+    fn project(&self, lat: f64, lng: f64) -> (f64, f64) {
+        (
+            (lng - self.lng).to_radians() * self.cos_lat * EARTH_RADIUS,
+            (lat - self.lat).to_radians() * EARTH_RADIUS,
+        )
+    }
+}
+
+/// Axis aligned bounding box in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+impl BoundingBox {
+    pub fn new(min_lat: f64, min_lng: f64, max_lat: f64, max_lng: f64) -> Self {
+        Self {
+            min_lat,
+            min_lng,
+            max_lat,
+            max_lng,
+        }
+    }
+
+    /// Smallest box containing all points within `radius` meters of the given coordinate.
+    pub fn around(lat: f64, lng: f64, radius: f64) -> Self {
+        let d_lat = (radius / EARTH_RADIUS).to_degrees();
+        let d_lng = d_lat / lat.to_radians().cos().max(1e-6);
+        Self::new(lat - d_lat, lng - d_lng, lat + d_lat, lng + d_lng)
+    }
+
+    pub fn contains(&self, lat: f64, lng: f64) -> bool {
+        lat >= self.min_lat && lat <= self.max_lat && lng >= self.min_lng && lng <= self.max_lng
+    }
+
+    // Liang-Barsky clipping of the segment against the box
+    fn intersects_segment(&self, (lat1, lng1): (f64, f64), (lat2, lng2): (f64, f64)) -> bool {
+        let (d_lat, d_lng) = (lat2 - lat1, lng2 - lng1);
+        let mut t_min = 0.0f64;
+        let mut t_max = 1.0f64;
+        for (p, q) in [
+            (-d_lng, lng1 - self.min_lng),
+            (d_lng, self.max_lng - lng1),
+            (-d_lat, lat1 - self.min_lat),
+            (d_lat, self.max_lat - lat1),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return false;
+                }
+            } else if p < 0.0 {
+                t_min = t_min.max(q / p);
+            } else {
+                t_max = t_max.min(q / p);
+            }
+        }
+        t_min <= t_max
+    }
+}
+
+// The number of grid cells is bounded by this factor times the number of items (but at least `MIN_MAX_CELLS`).
+const MAX_CELLS_PER_ITEM: usize = 4;
+const MIN_MAX_CELLS: usize = 1 << 16;
+
+// Uniform grid with a CSR style mapping of cells to item ids, shared by the node and the edge index.
+#[derive(Debug)]
+struct Grid {
+    min_lat: f64,
+    min_lng: f64,
+    cell_size: f64,
+    rows: usize,
+    cols: usize,
+    first_item_of_cell: Vec<u32>,
+    cell_items: Vec<u32>,
+}
+
+impl Grid {
+    fn new(num_items: usize, cell_size: f64, item_bounds: impl Fn(usize) -> BoundingBox) -> Self {
+        assert!(cell_size > 0.0);
+
+        let bounds = (0..num_items).map(&item_bounds).reduce(|a, b| {
+            BoundingBox::new(
+                a.min_lat.min(b.min_lat),
+                a.min_lng.min(b.min_lng),
+                a.max_lat.max(b.max_lat),
+                a.max_lng.max(b.max_lng),
+            )
+        });
+        let mut cell_size = cell_size;
+        let (min_lat, min_lng, rows, cols) = match bounds {
+            Some(bounds) => {
+                // Coarsen the grid when the bounding box is too large for the number of items, e.g. because of an outlier coordinate.
+                let max_cells = (MAX_CELLS_PER_ITEM * num_items).max(MIN_MAX_CELLS) as f64;
+                let dimensions = |cell_size: f64| {
+                    (
+                        ((bounds.max_lat - bounds.min_lat) / cell_size).floor() + 1.0,
+                        ((bounds.max_lng - bounds.min_lng) / cell_size).floor() + 1.0,
+                    )
+                };
+                let (rows, cols) = loop {
+                    let (rows, cols) = dimensions(cell_size);
+                    if rows * cols <= max_cells {
+                        break (rows, cols);
+                    }
+                    cell_size *= 2.0;
+                };
+                (bounds.min_lat, bounds.min_lng, rows as usize, cols as usize)
+            }
+            None => (0.0, 0.0, 1, 1),
+        };
+
+        let mut grid = Self {
+            min_lat,
+            min_lng,
+            cell_size,
+            rows,
+            cols,
+            first_item_of_cell: Vec::new(),
+            cell_items: Vec::new(),
+        };
+
+        // counting sort of (cell, item) pairs
+        let mut items_per_cell = vec![0u32; rows * cols + 1];
+        for item in 0..num_items {
+            grid.for_each_cell(&item_bounds(item), |cell| items_per_cell[cell + 1] += 1);
+        }
+        for cell in 0..rows * cols {
+            items_per_cell[cell + 1] += items_per_cell[cell];
+        }
+        let mut cell_items = vec![0; items_per_cell[rows * cols] as usize];
+        let mut next = items_per_cell.clone();
+        for item in 0..num_items {
+            grid.for_each_cell(&item_bounds(item), |cell| {
+                cell_items[next[cell] as usize] = item as u32;
+                next[cell] += 1;
+            });
+        }
+        grid.first_item_of_cell = items_per_cell;
+        grid.cell_items = cell_items;
+        grid
+    }
+
+    fn row(&self, lat: f64) -> usize {
+        (((lat - self.min_lat) / self.cell_size).max(0.0) as usize).min(self.rows - 1)
+    }
+
+    fn col(&self, lng: f64) -> usize {
+        (((lng - self.min_lng) / self.cell_size).max(0.0) as usize).min(self.cols - 1)
+    }
+
+    fn for_each_cell(&self, bounds: &BoundingBox, mut f: impl FnMut(usize)) {
+        for row in self.row(bounds.min_lat)..=self.row(bounds.max_lat) {
+            for col in self.col(bounds.min_lng)..=self.col(bounds.max_lng) {
+                f(row * self.cols + col);
+            }
+        }
+    }
+
+    // Ids of all items in cells overlapping the box, sorted and without duplicates.
+    fn items_in(&self, bounds: &BoundingBox) -> Vec<u32> {
+        let mut items = Vec::new();
+        self.for_each_cell(bounds, |cell| {
+            items.extend_from_slice(&self.cell_items[self.first_item_of_cell[cell] as usize..self.first_item_of_cell[cell + 1] as usize])
+        });
+        items.sort_unstable();
+        items.dedup();
+        items
+    }
+
+    // Does the box contain the whole grid, i.e. would a query with it return all items?
+    fn covered_by(&self, bounds: &BoundingBox) -> bool {
+        bounds.min_lat <= self.min_lat
+            && bounds.min_lng <= self.min_lng
+            && bounds.max_lat >= self.min_lat + self.rows as f64 * self.cell_size
+            && bounds.max_lng >= self.min_lng + self.cols as f64 * self.cell_size
+    }
+
+    // Run radius searches with growing radii until at least `k` results were found or the whole grid was searched.
+    // Since each radius search is exact, the first `k` results of the final search are the `k` nearest ones.
+    fn k_nearest<T>(&self, lat: f64, lng: f64, k: usize, within: impl Fn(f64) -> Vec<T>) -> Vec<T> {
+        if k == 0 || self.cell_items.is_empty() {
+            return Vec::new();
+        }
+        let mut radius = self.cell_size.to_radians() * EARTH_RADIUS;
+        loop {
+            let mut results = within(radius);
+            if results.len() >= k || self.covered_by(&BoundingBox::around(lat, lng, radius)) {
+                results.truncate(k);
+                return results;
+            }
+            radius *= 2.0;
+        }
+    }
+}
+
+/// A node close to a query point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeCandidate {
+    pub node: NodeId,
+    /// Distance in meters between the query point and the node.
+    pub distance: f64,
+}
+
+/// Grid index over the nodes of a graph.
+#[derive(Debug)]
+pub struct NodeGridIndex<'a> {
+    latitude: &'a [f32],
+    longitude: &'a [f32],
+    grid: Grid,
+}
+
+impl<'a> NodeGridIndex<'a> {
+    pub fn new(latitude: &'a [f32], longitude: &'a [f32]) -> Self {
+        Self::with_cell_size(latitude, longitude, DEFAULT_CELL_SIZE)
+    }
+
+    pub fn with_cell_size(latitude: &'a [f32], longitude: &'a [f32], cell_size: f64) -> Self {
+        assert_eq!(latitude.len(), longitude.len());
+        let grid = Grid::new(latitude.len(), cell_size, |node| {
+            let (lat, lng) = (latitude[node] as f64, longitude[node] as f64);
+            BoundingBox::new(lat, lng, lat, lng)
+        });
+        Self { latitude, longitude, grid }
+    }
+
+    /// All nodes inside the bounding box, ordered by id.
+    pub fn nodes_in_bbox(&self, bounds: &BoundingBox) -> Vec<NodeId> {
+        self.grid
+            .items_in(bounds)
+            .into_iter()
+            .filter(|&node| bounds.contains(self.latitude[node as usize] as f64, self.longitude[node as usize] as f64))
+            .collect()
+    }
+
+    /// All nodes within `radius` meters of the given coordinate, ordered by distance.
+    pub fn nodes_within(&self, lat: f64, lng: f64, radius: f64) -> Vec<NodeCandidate> {
+        let mut candidates: Vec<_> = self
+            .grid
+            .items_in(&BoundingBox::around(lat, lng, radius))
+            .into_iter()
+            .map(|node| NodeCandidate {
+                node,
+                distance: haversine_distance(lat, lng, self.latitude[node as usize] as f64, self.longitude[node as usize] as f64),
+            })
+            .filter(|candidate| candidate.distance <= radius)
+            .collect();
+        candidates.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap().then(a.node.cmp(&b.node)));
+        candidates
+    }
+
+    /// The `k` nodes closest to the given coordinate, ordered by distance.
+    pub fn k_nearest_nodes(&self, lat: f64, lng: f64, k: usize) -> Vec<NodeCandidate> {
+        self.grid.k_nearest(lat, lng, k, |radius| self.nodes_within(lat, lng, radius))
+    }
+
+    /// The node closest to the given coordinate, `None` if the graph has no nodes.
+    pub fn nearest_node(&self, lat: f64, lng: f64) -> Option<NodeCandidate> {
+        self.k_nearest_nodes(lat, lng, 1).pop()
+    }
+}
+
+/// Intermediate shape points of edges, stored CSR style.
+/// The shape of an edge does not contain the coordinates of its tail and head node.
+/// Stored in the files `first_shape_point`, `shape_latitude` and `shape_longitude`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeGeometry {
+    first_shape_point: Vec<u32>,
+    latitude: Vec<f32>,
+    longitude: Vec<f32>,
+}
+
+impl EdgeGeometry {
+    pub fn new(first_shape_point: Vec<u32>, latitude: Vec<f32>, longitude: Vec<f32>) -> Self {
+        assert!(!first_shape_point.is_empty());
+        assert_eq!(latitude.len(), longitude.len());
+        assert_eq!(*first_shape_point.last().unwrap() as usize, latitude.len());
+        Self {
+            first_shape_point,
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Geometry without any shape points, all edges are straight lines.
+    pub fn straight(num_edges: usize) -> Self {
+        Self::new(vec![0; num_edges + 1], Vec::new(), Vec::new())
+    }
+
+    /// Build from an iterator over the shapes of all edges, each given as `(latitude, longitude)` pairs.
+    pub fn from_shapes<S: IntoIterator<Item = (f32, f32)>>(shapes: impl IntoIterator<Item = S>) -> Self {
+        let mut first_shape_point = vec![0];
+        let mut latitude = Vec::new();
+        let mut longitude = Vec::new();
+        for shape in shapes {
+            for (lat, lng) in shape {
+                latitude.push(lat);
+                longitude.push(lng);
+            }
+            first_shape_point.push(latitude.len() as u32);
+        }
+        Self::new(first_shape_point, latitude, longitude)
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.first_shape_point.len() - 1
+    }
+
+    pub fn num_shape_points(&self) -> usize {
+        self.latitude.len()
+    }
+
+    /// Intermediate shape points of an edge as `(latitude, longitude)` pairs ordered from tail to head.
+    pub fn shape(&self, edge: EdgeId) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let range = self.first_shape_point[edge as usize] as usize..self.first_shape_point[edge as usize + 1] as usize;
+        self.latitude[range.clone()].iter().copied().zip(self.longitude[range].iter().copied())
+    }
+}
+
+impl Deconstruct for EdgeGeometry {
+    fn save_each(&self, store: &dyn Fn(&str, &dyn Save) -> std::io::Result<()>) -> std::io::Result<()> {
+        store("first_shape_point", &self.first_shape_point)?;
+        store("shape_latitude", &self.latitude)?;
+        store("shape_longitude", &self.longitude)?;
+        Ok(())
+    }
+}
+
+impl Reconstruct for EdgeGeometry {
+    fn reconstruct_with(loader: Loader) -> std::io::Result<Self> {
+        Ok(Self::new(
+            loader.load("first_shape_point")?,
+            loader.load("shape_latitude")?,
+            loader.load("shape_longitude")?,
+        ))
+    }
+}
+
+/// An edge close to a query point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeCandidate {
+    pub edge: EdgeId,
+    /// Distance in meters between the query point and its projection onto the edge.
+    pub distance: f64,
+    /// Position of the projection on the edge as fraction in `[0, 1]` from tail to head.
+    pub fraction: f64,
+    /// Distance in meters along the edge geometry from the tail to the projection.
+    pub offset: f64,
+}
+
+/// Grid index over the edges of a graph.
+#[derive(Debug)]
+pub struct EdgeGridIndex<'a> {
+    latitude: &'a [f32],
+    longitude: &'a [f32],
+    tail: Vec<NodeId>,
+    head: &'a [NodeId],
+    geometry: Option<&'a EdgeGeometry>,
+    grid: Grid,
+}
+
+impl<'a> EdgeGridIndex<'a> {
+    pub fn new(first_out: &[EdgeId], head: &'a [NodeId], latitude: &'a [f32], longitude: &'a [f32]) -> Self {
+        Self::with_cell_size(first_out, head, latitude, longitude, DEFAULT_CELL_SIZE)
+    }
+
+    pub fn with_cell_size(first_out: &[EdgeId], head: &'a [NodeId], latitude: &'a [f32], longitude: &'a [f32], cell_size: f64) -> Self {
+        Self::build(first_out, head, latitude, longitude, None, cell_size)
+    }
+
+    /// Index edges along their shape instead of as straight lines.
+    pub fn with_geometry(
+        first_out: &[EdgeId],
+        head: &'a [NodeId],
+        latitude: &'a [f32],
+        longitude: &'a [f32],
+        geometry: &'a EdgeGeometry,
+        cell_size: f64,
+    ) -> Self {
+        assert_eq!(geometry.num_edges(), head.len());
+        Self::build(first_out, head, latitude, longitude, Some(geometry), cell_size)
+    }
+
+    fn build(first_out: &[EdgeId], head: &'a [NodeId], latitude: &'a [f32], longitude: &'a [f32], geometry: Option<&'a EdgeGeometry>, cell_size: f64) -> Self {
+        assert_eq!(latitude.len(), longitude.len());
+        assert_eq!(latitude.len() + 1, first_out.len());
+
+        let tail: Vec<NodeId> = (0..latitude.len())
+            .flat_map(|node| std::iter::repeat_n(node as NodeId, (first_out[node + 1] - first_out[node]) as usize))
+            .collect();
+
+        let mut index = Self {
+            latitude,
+            longitude,
+            tail,
+            head,
+            geometry,
+            grid: Grid::new(0, cell_size, |_| unreachable!()),
+        };
+        index.grid = Grid::new(head.len(), cell_size, |edge| {
+            let mut bounds = BoundingBox::new(f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+            for (lat, lng) in index.polyline(edge as EdgeId) {
+                bounds = BoundingBox::new(
+                    bounds.min_lat.min(lat),
+                    bounds.min_lng.min(lng),
+                    bounds.max_lat.max(lat),
+                    bounds.max_lng.max(lng),
+                );
+            }
+            bounds
+        });
+        index
+    }
+
+    // Coordinates of tail, shape points and head of an edge.
+    fn polyline(&self, edge: EdgeId) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let (tail, head) = (self.tail[edge as usize] as usize, self.head[edge as usize] as usize);
+        let shape = self.geometry.into_iter().flat_map(move |geometry| geometry.shape(edge));
+        std::iter::once((self.latitude[tail], self.longitude[tail]))
+            .chain(shape)
+            .chain(std::iter::once((self.latitude[head], self.longitude[head])))
+            .map(|(lat, lng)| (lat as f64, lng as f64))
+    }
+
+    /// Tail node of an edge
+    pub fn tail(&self, edge: EdgeId) -> NodeId {
+        self.tail[edge as usize]
+    }
+
+    /// Project a coordinate onto an edge.
+    pub fn project_onto_edge(&self, edge: EdgeId, lat: f64, lng: f64) -> EdgeCandidate {
+        let projection = LocalProjection::new(lat, lng);
+        let points: Vec<(f64, f64)> = self.polyline(edge).map(|(lat, lng)| projection.project(lat, lng)).collect();
+
+        let mut length = 0.0;
+        let mut best = (f64::INFINITY, 0.0);
+        for segment in points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (segment[0], segment[1]);
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let len_sq = dx * dx + dy * dy;
+            let t = if len_sq > 0.0 { (-(x1 * dx + y1 * dy) / len_sq).clamp(0.0, 1.0) } else { 0.0 };
+            let (x, y) = (x1 + t * dx, y1 + t * dy);
+            let distance = (x * x + y * y).sqrt();
+            if distance < best.0 {
+                best = (distance, length + t * len_sq.sqrt());
+            }
+            length += len_sq.sqrt();
+        }
+
+        let (distance, offset) = best;
+        EdgeCandidate {
+            edge,
+            distance,
+            fraction: if length > 0.0 { offset / length } else { 0.0 },
+            offset,
+        }
+    }
+
+    /// All edges within `radius` meters of the given coordinate, ordered by distance.
+    pub fn edges_within(&self, lat: f64, lng: f64, radius: f64) -> Vec<EdgeCandidate> {
+        let mut candidates: Vec<_> = self
+            .grid
+            .items_in(&BoundingBox::around(lat, lng, radius))
+            .into_iter()
+            .map(|edge| self.project_onto_edge(edge, lat, lng))
+            .filter(|candidate| candidate.distance <= radius)
+            .collect();
+        candidates.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap().then(a.edge.cmp(&b.edge)));
+        candidates
+    }
+
+    /// The `k` edges closest to the given coordinate, ordered by distance.
+    pub fn k_nearest_edges(&self, lat: f64, lng: f64, k: usize) -> Vec<EdgeCandidate> {
+        self.grid.k_nearest(lat, lng, k, |radius| self.edges_within(lat, lng, radius))
+    }
+
+    /// All edges which are at least partially inside the bounding box, ordered by id.
+    pub fn edges_in_bbox(&self, bounds: &BoundingBox) -> Vec<EdgeId> {
+        self.grid
+            .items_in(bounds)
+            .into_iter()
+            .filter(|&edge| {
+                let points: Vec<_> = self.polyline(edge).collect();
+                points.windows(2).any(|segment| bounds.intersects_segment(segment[0], segment[1]))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_close_edges_with_projection() {
+        // 0 -> 1 -> 2 along the equator, roughly 111m per 0.001 degrees
+        let first_out = [0, 1, 2, 2];
+        let head = [1, 2];
+        let latitude = [0.0, 0.0, 0.0];
+        let longitude = [0.0, 0.001, 0.002];
+        let index = EdgeGridIndex::with_cell_size(&first_out, &head, &latitude, &longitude, 0.0005);
+
+        let candidates = index.edges_within(0.0001, 0.00025, 50.0);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].edge, 0);
+        assert!((candidates[0].fraction - 0.25).abs() < 1e-6);
+        assert!((candidates[0].offset - 27.8).abs() < 0.1);
+        assert!((candidates[0].distance - 11.1).abs() < 0.1);
+
+        let candidates = index.edges_within(0.0, 0.001, 10.0);
+        assert_eq!(candidates.iter().map(|c| c.edge).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(index.edges_within(0.01, 0.001, 100.0).is_empty());
+        assert!((haversine_distance(0.0, 0.0, 0.0, 0.001) - 111.2).abs() < 0.1);
+    }
+
+    #[test]
+    fn snaps_to_nodes_and_shaped_edges() {
+        // edge 0 goes from node 0 to node 1 with a detour north, edge 1 is a straight line from node 1 to node 2
+        let first_out = [0, 1, 2, 2];
+        let head = [1, 2];
+        let latitude = [0.0, 0.0, 0.0];
+        let longitude = [0.0, 0.002, 0.004];
+        let geometry = EdgeGeometry::from_shapes(vec![vec![(0.001, 0.0), (0.001, 0.002)], vec![]]);
+        let index = EdgeGridIndex::with_geometry(&first_out, &head, &latitude, &longitude, &geometry, 0.0005);
+
+        // close to the detour, but far from the straight line between the end points
+        let candidates = index.k_nearest_edges(0.0011, 0.001, 1);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].edge, 0);
+        assert!((candidates[0].fraction - 0.5).abs() < 1e-6);
+        assert!((candidates[0].distance - 11.1).abs() < 0.1);
+        assert_eq!(index.k_nearest_edges(0.0011, 0.001, 5).len(), 2);
+
+        assert_eq!(index.edges_in_bbox(&BoundingBox::new(0.0005, 0.0005, 0.002, 0.0015)), vec![0]);
+        assert_eq!(index.edges_in_bbox(&BoundingBox::new(-0.001, 0.0025, 0.0005, 0.003)), vec![1]);
+        assert!(index.edges_in_bbox(&BoundingBox::new(0.0002, 0.0025, 0.0005, 0.003)).is_empty());
+
+        let nodes = NodeGridIndex::with_cell_size(&latitude, &longitude, 0.0005);
+        assert_eq!(nodes.nearest_node(0.0001, 0.0035).map(|c| c.node), Some(2));
+        assert_eq!(nodes.k_nearest_nodes(0.0, -0.01, 2).iter().map(|c| c.node).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(nodes.nodes_within(0.0, 0.002, 250.0).len(), 3);
+        assert_eq!(nodes.nodes_in_bbox(&BoundingBox::new(-1.0, 0.001, 1.0, 0.005)), vec![1, 2]);
+    }
+
+    #[test]
+    fn outliers_do_not_blow_up_the_grid() {
+        let latitude = [0.0, 0.0, 0.001, 89.0];
+        let longitude = [0.0, 0.001, 0.0, 179.0];
+        let nodes = NodeGridIndex::with_cell_size(&latitude, &longitude, 0.00001);
+        assert!(nodes.grid.rows * nodes.grid.cols <= MIN_MAX_CELLS);
+        assert_eq!(nodes.nearest_node(0.0009, 0.0001).map(|c| c.node), Some(2));
+        assert_eq!(nodes.k_nearest_nodes(88.0, 178.0, 1).iter().map(|c| c.node).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(nodes.nodes_in_bbox(&BoundingBox::new(-0.0001, -0.0001, 0.0001, 0.002)), vec![0, 1]);
+    }
+}
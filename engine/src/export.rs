@@ -4,6 +4,9 @@ use crate::datastr::graph::*;
 use std::fs::File;
 use std::io::{Result, Write};
 
+pub mod geojson;
+pub mod gpx;
+
 /// Export graph in DIMACs .gr format.
 pub fn write_graph_to_gr<G: LinkIterGraph>(graph: &G, filename: &str) -> Result<()> {
     let mut file = File::create(filename)?;
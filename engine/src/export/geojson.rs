@@ -0,0 +1,319 @@
+//! Export paths, search spaces, isochrones and subgraphs as GeoJSON `FeatureCollection`s.
+//!
+//! Coordinates are written in GeoJSON order, that is `[longitude, latitude]`.
+//! The resulting files can be loaded directly into QGIS or kepler.gl.
+
+use super::*;
+use crate::algo::{customizable_contraction_hierarchy::CCHT, isochrone::Isochrone};
+use crate::datastr::spatial_index::{BoundingBox, EdgeGeometry, EdgeGridIndex};
+use serde_json::{json, Map, Value};
+
+/// Builder for a GeoJSON `FeatureCollection`.
+/// Each `add_*` method appends one or more features with the given properties.
+/// Properties are passed as JSON objects, usually built with the `json!` macro.
+pub struct FeatureCollection<'a> {
+    latitude: &'a [f32],
+    longitude: &'a [f32],
+    geometry: Option<&'a EdgeGeometry>,
+    features: Vec<Value>,
+}
+
+impl<'a> FeatureCollection<'a> {
+    pub fn new(latitude: &'a [f32], longitude: &'a [f32]) -> Self {
+        assert_eq!(latitude.len(), longitude.len());
+        Self {
+            latitude,
+            longitude,
+            geometry: None,
+            features: Vec::new(),
+        }
+    }
+
+    /// Draw edges along their shape points instead of as straight lines.
+    pub fn with_edge_geometry(mut self, geometry: &'a EdgeGeometry) -> Self {
+        self.geometry = Some(geometry);
+        self
+    }
+
+    fn coords(&self, node: NodeId) -> Value {
+        json!([self.longitude[node as usize], self.latitude[node as usize]])
+    }
+
+    fn push(&mut self, geometry: Value, properties: Map<String, Value>) {
+        self.features.push(json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": properties,
+        }));
+    }
+
+    /// Add a single point feature for a node.
+    pub fn add_node(&mut self, node: NodeId, properties: Value) {
+        let mut properties = into_map(properties);
+        properties.insert("node_id".to_string(), json!(node));
+        self.push(json!({ "type": "Point", "coordinates": self.coords(node) }), properties);
+    }
+
+    /// Add a path given as a sequence of nodes (as returned by `QueryResult::node_path`) as one `LineString`.
+    pub fn add_node_path(&mut self, path: &[NodeId], properties: Value) {
+        let coordinates: Vec<Value> = path.iter().map(|&node| self.coords(node)).collect();
+        self.push(json!({ "type": "LineString", "coordinates": coordinates }), into_map(properties));
+    }
+
+    /// Add a path given as a sequence of edge ids (as returned by `QueryResult::edge_path`).
+    /// Each edge becomes its own `LineString` feature, so per edge data like departure and travel times can be attached.
+    /// The callback receives the index of the edge in the path and the edge id and may add additional properties.
+    pub fn add_edge_path(
+        &mut self,
+        first_out: &[EdgeId],
+        head: &[NodeId],
+        path: &[EdgeId],
+        mut properties: impl FnMut(usize, EdgeId, &mut Map<String, Value>),
+    ) {
+        for (idx, &edge) in path.iter().enumerate() {
+            let tail = link_id_to_tail_mapper::link_id_to_tail(first_out, edge);
+            self.add_edge(tail, head[edge as usize], edge, |props| properties(idx, edge, props));
+        }
+    }
+
+    /// Add a single edge as `LineString` feature.
+    pub fn add_edge(&mut self, tail: NodeId, head: NodeId, edge: EdgeId, properties: impl FnOnce(&mut Map<String, Value>)) {
+        let mut props = Map::new();
+        props.insert("edge_id".to_string(), json!(edge));
+        properties(&mut props);
+        let mut coordinates = vec![self.coords(tail)];
+        if let Some(geometry) = self.geometry {
+            coordinates.extend(geometry.shape(edge).map(|(lat, lng)| json!([lng, lat])));
+        }
+        coordinates.push(self.coords(head));
+        self.push(json!({ "type": "LineString", "coordinates": coordinates }), props);
+    }
+
+    /// Add all edges of a graph where both end points satisfy the predicate, for example all edges in a bounding box.
+    /// Edge weights are exported as `weight` property.
+    pub fn add_subgraph<G>(&mut self, graph: &G, mut node_predicate: impl FnMut(NodeId) -> bool)
+    where
+        G: LinkIterable<(NodeIdT, Weight, EdgeIdT)>,
+    {
+        for node in 0..graph.num_nodes() as NodeId {
+            if !node_predicate(node) {
+                continue;
+            }
+            for (NodeIdT(head), weight, EdgeIdT(edge)) in graph.link_iter(node) {
+                if node_predicate(head) {
+                    self.add_edge(node, head, edge, |props| {
+                        props.insert("weight".to_string(), json!(weight));
+                    });
+                }
+            }
+        }
+    }
+
+    /// Add all edges which are at least partially inside the bounding box.
+    /// Only the edges found through the spatial index are inspected, so this is much faster than `add_subgraph` for small boxes.
+    /// Edge weights are exported as `weight` property.
+    pub fn add_edges_in_bbox(&mut self, index: &EdgeGridIndex, head: &[NodeId], weight: &[Weight], bounds: &BoundingBox) {
+        for edge in index.edges_in_bbox(bounds) {
+            self.add_edge(index.tail(edge), head[edge as usize], edge, |props| {
+                props.insert("weight".to_string(), json!(weight[edge as usize]));
+            });
+        }
+    }
+
+    /// Add the search space of a CCH query from or to `node`, that is all nodes on the path to the root in the elimination tree.
+    /// Nodes are exported as a `MultiPoint` with their ranks in the `ranks` property.
+    pub fn add_cch_search_space(&mut self, cch: &impl CCHT, node: NodeId, properties: Value) {
+        let mut properties = into_map(properties);
+        let order = cch.node_order();
+        let mut ranks = Vec::new();
+        let mut cur = Some(order.rank(node));
+        while let Some(rank) = cur {
+            ranks.push(rank);
+            cur = cch.elimination_tree()[rank as usize].value();
+        }
+
+        let coordinates: Vec<Value> = ranks.iter().map(|&rank| self.coords(order.node(rank))).collect();
+        properties.insert("node_id".to_string(), json!(node));
+        properties.insert("ranks".to_string(), json!(ranks));
+        self.push(json!({ "type": "MultiPoint", "coordinates": coordinates }), properties);
+    }
+
+    /// Add an isochrone as outline polygon, reachable nodes and partially covered boundary edges.
+    /// Features are tagged with a `kind` property to distinguish them.
+    /// Degenerate outlines are exported as `Point` or `LineString`, an empty isochrone has no outline.
+    pub fn add_isochrone(&mut self, isochrone: &Isochrone, properties: Value) {
+        let properties = into_map(properties);
+        let outline: Vec<Value> = isochrone
+            .polygon(self.latitude, self.longitude)
+            .into_iter()
+            .map(|(lat, lng)| json!([lng, lat]))
+            .collect();
+        let geometry = match outline.len() {
+            0 => None,
+            1 => Some(json!({ "type": "Point", "coordinates": outline[0] })),
+            2 => Some(json!({ "type": "LineString", "coordinates": outline })),
+            _ => Some(json!({ "type": "Polygon", "coordinates": [outline] })),
+        };
+        if let Some(geometry) = geometry {
+            let mut props = properties.clone();
+            props.insert("kind".to_string(), json!("outline"));
+            self.push(geometry, props);
+        }
+
+        let coordinates: Vec<Value> = isochrone.reachable.iter().map(|&node| self.coords(node)).collect();
+        let mut props = properties.clone();
+        props.insert("kind".to_string(), json!("reachable"));
+        props.insert("num_nodes".to_string(), json!(isochrone.reachable.len()));
+        self.push(json!({ "type": "MultiPoint", "coordinates": coordinates }), props);
+
+        for edge in &isochrone.boundary_edges {
+            self.add_edge(edge.tail, edge.head, edge.edge, |props| {
+                props.extend(properties.clone());
+                props.insert("kind".to_string(), json!("boundary"));
+                props.insert("coverage".to_string(), json!(edge.coverage));
+            });
+        }
+    }
+
+    pub fn num_features(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "type": "FeatureCollection",
+            "features": self.features,
+        })
+    }
+
+    /// Write the collection to a `.geojson` file.
+    pub fn write_to(&self, filename: &str) -> Result<()> {
+        let file = File::create(filename)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), &self.to_json())?;
+        Ok(())
+    }
+}
+
+fn into_map(properties: Value) -> Map<String, Value> {
+    match properties {
+        Value::Object(map) => map,
+        Value::Null => Map::new(),
+        other => panic!("GeoJSON properties have to be an object, got {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::{customizable_contraction_hierarchy::contract, isochrone::BoundaryEdge};
+    use crate::datastr::node_order::NodeOrder;
+
+    // bidirectional path 0 - 1 - 2 along a meridian
+    fn path_graph() -> OwnedGraph {
+        OwnedGraph::new(vec![0, 1, 3, 4], vec![1, 0, 2, 1], vec![10, 10, 20, 20])
+    }
+
+    const LAT: [f32; 3] = [49.0, 49.1, 49.2];
+    const LNG: [f32; 3] = [8.4, 8.5, 8.6];
+
+    #[test]
+    fn route_round_trip() {
+        let graph = path_graph();
+        let mut collection = FeatureCollection::new(&LAT, &LNG);
+        collection.add_node_path(&[0, 1, 2], json!({ "name": "route" }));
+        collection.add_edge_path(graph.first_out(), graph.head(), &[0, 2], |idx, _, props| {
+            props.insert("index".to_string(), json!(idx));
+        });
+
+        let filename = std::env::temp_dir().join(format!("rust_road_router_route_{}.geojson", std::process::id()));
+        collection.write_to(filename.to_str().unwrap()).unwrap();
+        let read: Value = serde_json::from_reader(File::open(&filename).unwrap()).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        assert_eq!(read, collection.to_json());
+
+        let features = read["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features[0]["geometry"]["type"], "LineString");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            json!([[8.4f32, 49.0f32], [8.5f32, 49.1f32], [8.6f32, 49.2f32]])
+        );
+        assert_eq!(features[0]["properties"], json!({ "name": "route" }));
+        assert_eq!(features[2]["geometry"]["coordinates"], json!([[8.5f32, 49.1f32], [8.6f32, 49.2f32]]));
+        assert_eq!(features[2]["properties"], json!({ "edge_id": 2, "index": 1 }));
+    }
+
+    #[test]
+    fn isochrone_features() {
+        let isochrone = Isochrone {
+            reachable: vec![0, 1],
+            boundary_edges: vec![BoundaryEdge {
+                tail: 1,
+                head: 2,
+                edge: 2,
+                coverage: 0.5,
+            }],
+        };
+        let mut collection = FeatureCollection::new(&LAT, &LNG);
+        collection.add_isochrone(&isochrone, json!({ "budget": 15 }));
+
+        let json = collection.to_json();
+        let features = json["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features[0]["geometry"]["type"], "Polygon");
+        assert_eq!(features[0]["properties"], json!({ "budget": 15, "kind": "outline" }));
+        assert_eq!(features[1]["geometry"]["coordinates"], json!([[8.4f32, 49.0f32], [8.5f32, 49.1f32]]));
+        assert_eq!(features[1]["properties"], json!({ "budget": 15, "kind": "reachable", "num_nodes": 2 }));
+        assert_eq!(
+            features[2]["properties"],
+            json!({ "edge_id": 2, "budget": 15, "kind": "boundary", "coverage": 0.5 })
+        );
+    }
+
+    #[test]
+    fn degenerate_isochrone_outlines() {
+        let outline = |reachable: Vec<NodeId>| {
+            let isochrone = Isochrone {
+                reachable,
+                boundary_edges: vec![],
+            };
+            let mut collection = FeatureCollection::new(&LAT, &LNG);
+            collection.add_isochrone(&isochrone, Value::Null);
+            let json = collection.to_json();
+            json["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|f| f["properties"]["kind"] == "outline")
+                .cloned()
+        };
+
+        assert_eq!(outline(vec![]), None);
+
+        let point = outline(vec![1]).unwrap();
+        assert_eq!(point["geometry"], json!({ "type": "Point", "coordinates": [8.5f32, 49.1f32] }));
+
+        let line = outline(vec![0, 2]).unwrap();
+        assert_eq!(
+            line["geometry"],
+            json!({ "type": "LineString", "coordinates": [[8.4f32, 49.0f32], [8.6f32, 49.2f32]] })
+        );
+    }
+
+    #[test]
+    fn search_space_follows_elimination_tree() {
+        let graph = path_graph();
+        let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+        let mut collection = FeatureCollection::new(&LAT, &LNG);
+        collection.add_cch_search_space(&cch, 0, Value::Null);
+
+        let json = collection.to_json();
+        let feature = &json["features"][0];
+        assert_eq!(feature["geometry"]["type"], "MultiPoint");
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            json!([[8.4f32, 49.0f32], [8.5f32, 49.1f32], [8.6f32, 49.2f32]])
+        );
+        assert_eq!(feature["properties"], json!({ "node_id": 0, "ranks": [0, 1, 2] }));
+    }
+}
@@ -0,0 +1,133 @@
+//! Export paths as GPX 1.1 tracks.
+
+use super::*;
+use chrono::{DateTime, Duration, SecondsFormat};
+
+struct Track {
+    name: String,
+    points: Vec<(f32, f32, Option<f64>)>,
+}
+
+/// Builder for GPX files with one or more tracks.
+/// Track point times are given in seconds relative to a point in time passed to `write_to`.
+pub struct GpxTracks<'a> {
+    latitude: &'a [f32],
+    longitude: &'a [f32],
+    tracks: Vec<Track>,
+}
+
+impl<'a> GpxTracks<'a> {
+    pub fn new(latitude: &'a [f32], longitude: &'a [f32]) -> Self {
+        assert_eq!(latitude.len(), longitude.len());
+        Self {
+            latitude,
+            longitude,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Add a track along a node path without time information.
+    pub fn add_node_path(&mut self, name: &str, path: &[NodeId]) {
+        let points = path
+            .iter()
+            .map(|&node| (self.latitude[node as usize], self.longitude[node as usize], None))
+            .collect();
+        self.tracks.push(Track {
+            name: name.to_string(),
+            points,
+        });
+    }
+
+    /// Add a track along a node path with the time in seconds at which each node is reached,
+    /// for example the result of a time-dependent query.
+    pub fn add_timed_node_path(&mut self, name: &str, path: impl IntoIterator<Item = (NodeId, f64)>) {
+        let points = path
+            .into_iter()
+            .map(|(node, t)| (self.latitude[node as usize], self.longitude[node as usize], Some(t)))
+            .collect();
+        self.tracks.push(Track {
+            name: name.to_string(),
+            points,
+        });
+    }
+
+    /// Write all tracks to a `.gpx` file.
+    /// Times are offsets to `time_origin` (in seconds since the unix epoch), usually midnight of the day the data belongs to.
+    pub fn write_to(&self, filename: &str, time_origin: i64) -> Result<()> {
+        let reference = DateTime::from_timestamp(time_origin, 0).expect("time origin out of range");
+        let mut file = std::io::BufWriter::new(File::create(filename)?);
+        writeln!(file, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            file,
+            r#"<gpx version="1.1" creator="rust_road_router" xmlns="http://www.topografix.com/GPX/1/1">"#
+        )?;
+        for track in &self.tracks {
+            writeln!(file, "  <trk>")?;
+            writeln!(file, "    <name>{}</name>", escape(&track.name))?;
+            writeln!(file, "    <trkseg>")?;
+            for &(lat, lng, t) in &track.points {
+                if let Some(t) = t {
+                    let time = reference + Duration::milliseconds((t * 1000.0).round() as i64);
+                    writeln!(
+                        file,
+                        r#"      <trkpt lat="{}" lon="{}"><time>{}</time></trkpt>"#,
+                        lat,
+                        lng,
+                        time.to_rfc3339_opts(SecondsFormat::Millis, true)
+                    )?;
+                } else {
+                    writeln!(file, r#"      <trkpt lat="{}" lon="{}"/>"#, lat, lng)?;
+                }
+            }
+            writeln!(file, "    </trkseg>")?;
+            writeln!(file, "  </trk>")?;
+        }
+        writeln!(file, "</gpx>")?;
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_tracks_with_times() {
+        let lat = [49.0, 49.1];
+        let lng = [8.4, 8.5];
+        let mut tracks = GpxTracks::new(&lat, &lng);
+        tracks.add_node_path("a & b", &[0, 1]);
+        tracks.add_timed_node_path("timed", vec![(1, 0.0), (0, 61.5)]);
+
+        let filename = std::env::temp_dir().join(format!("rust_road_router_tracks_{}.gpx", std::process::id()));
+        tracks.write_to(filename.to_str().unwrap(), 86_400).unwrap();
+        let written = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(
+            written,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="rust_road_router" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk>
+    <name>a &amp; b</name>
+    <trkseg>
+      <trkpt lat="49" lon="8.4"/>
+      <trkpt lat="49.1" lon="8.5"/>
+    </trkseg>
+  </trk>
+  <trk>
+    <name>timed</name>
+    <trkseg>
+      <trkpt lat="49.1" lon="8.5"><time>1970-01-02T00:00:00.000Z</time></trkpt>
+      <trkpt lat="49" lon="8.4"><time>1970-01-02T00:01:01.500Z</time></trkpt>
+    </trkseg>
+  </trk>
+</gpx>
+"#
+        );
+    }
+}
@@ -15,16 +15,20 @@
 //! head.write_to(&"output_file")?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
+//!
+//! Besides raw dumps, `load_from` also reads the self describing files of the `container` module.
 
 use std::{
     ffi::OsStr,
     fs::{metadata, File},
-    io::{prelude::*, Result},
+    io::{prelude::*, Error, ErrorKind, Result},
     mem,
     path::Path,
     slice,
 };
 
+pub mod container;
+
 /// A trait which allows accessing the data of an object as a slice of bytes.
 /// The bytes should represent a serialization of the object and allow
 /// recreating it when reading these bytes again from the disk.
@@ -106,11 +110,38 @@ pub trait Load: DataBytesMut + Sized {
     /// It should not be necessary to call this method directly.
     fn new_with_bytes(num_bytes: usize) -> Self;
 
+    /// Whether a container file with the given element type may be loaded into this object.
+    /// Flat arrays use `container::accepts` to reject containers with a different element type.
+    fn accepts_container(_element_type: container::ElementType) -> bool {
+        true
+    }
+
     /// This method will load serialized data from the disk, create an object of the appropriate size,
     /// deserialize the bytes into the object and return the object.
+    /// Container files are decoded transparently, see the `container` module.
+    /// Containers with elements of a different type than the elements of `Self` are rejected with `InvalidData`.
     fn load_from<P: AsRef<Path>>(path: P) -> Result<Self> {
         let metadata = metadata(path.as_ref())?;
-        let mut file = File::open(path)?;
+        let mut file = File::open(path.as_ref())?;
+
+        if let Some(header) = container::Header::probe(&mut file)? {
+            if !Self::accepts_container(header.element_type) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{} contains {} but {} was expected",
+                        path.as_ref().display(),
+                        header.element_type.name(),
+                        std::any::type_name::<Self>()
+                    ),
+                ));
+            }
+            let data = container::read_payload(&header, &mut file)?;
+            let mut object = Self::new_with_bytes(data.len());
+            assert_eq!(data.len(), object.data_bytes_mut().len());
+            object.data_bytes_mut().copy_from_slice(&data);
+            return Ok(object);
+        }
 
         let mut object = Self::new_with_bytes(metadata.len() as usize);
         assert_eq!(metadata.len() as usize, object.data_bytes_mut().len());
@@ -120,20 +151,28 @@ pub trait Load: DataBytesMut + Sized {
     }
 }
 
-impl<T: Default + Copy> Load for Vec<T> {
+impl<T: Default + Copy + 'static> Load for Vec<T> {
     fn new_with_bytes(num_bytes: usize) -> Self {
         assert_eq!(num_bytes % mem::size_of::<T>(), 0);
         let num_elements = num_bytes / mem::size_of::<T>();
         (0..num_elements).map(|_| T::default()).collect()
     }
+
+    fn accepts_container(element_type: container::ElementType) -> bool {
+        container::accepts::<T>(element_type)
+    }
 }
 
-impl<T: Default + Copy> Load for Box<[T]> {
+impl<T: Default + Copy + 'static> Load for Box<[T]> {
     fn new_with_bytes(num_bytes: usize) -> Self {
         assert_eq!(num_bytes % mem::size_of::<T>(), 0);
         let num_elements = num_bytes / mem::size_of::<T>();
         (0..num_elements).map(|_| T::default()).collect()
     }
+
+    fn accepts_container(element_type: container::ElementType) -> bool {
+        container::accepts::<T>(element_type)
+    }
 }
 
 /// A trait to allow serializing more complex objects
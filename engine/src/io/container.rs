@@ -0,0 +1,554 @@
+//! A self describing container format for flat arrays.
+//!
+//! Raw dumps carry no information about what they contain, so loading a `Vec<u32>` file as `Vec<f32>` silently succeeds.
+//! Container files start with a fixed size header which records the format version, the element type,
+//! the byte order of the writer, the number of elements and optionally a checksum of the payload.
+//! Integer arrays may additionally be stored with varint or delta + varint compression,
+//! which pays off for `first_out`, `head` and IPP arrays.
+//!
+//! `Load::load_from` transparently decodes container files, so existing code keeps working on converted directories
+//! and raw files stay readable.
+//! It rejects containers whose element type does not match the requested one, so a `u32` container can't be loaded as `Vec<f32>`.
+//! Arrays of composite elements (tuples, structs) only check that the element size is the same.
+//! `read_bytes` gives access to the decoded bytes regardless of the element type, for tools which copy data around.
+//!
+//! Header layout, all fields little endian:
+//!
+//! | offset | size | field |
+//! |--------|------|-------|
+//! | 0      | 8    | magic bytes |
+//! | 8      | 2    | format version |
+//! | 10     | 1    | byte order of raw payloads (0 little, 1 big endian) |
+//! | 11     | 1    | element type |
+//! | 12     | 1    | compression |
+//! | 13     | 1    | flags (bit 0: checksum present) |
+//! | 14     | 2    | reserved |
+//! | 16     | 8    | number of elements |
+//! | 24     | 8    | payload length in bytes |
+//! | 32     | 8    | FNV-1a checksum of the payload |
+
+use super::*;
+use std::any::TypeId;
+use std::io::{Error, ErrorKind, SeekFrom};
+
+/// First bytes of every container file.
+/// Chosen so that it is very unlikely to be the start of a raw array.
+pub const MAGIC: [u8; 8] = *b"\x89RRC\r\n\x1a\n";
+/// The version written by this implementation.
+pub const VERSION: u16 = 1;
+/// Size of the header in bytes.
+pub const HEADER_LEN: usize = 40;
+
+const FLAG_CHECKSUM: u8 = 1;
+
+/// Type of the elements stored in a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ElementType {
+    U8 = 0,
+    I8 = 1,
+    U16 = 2,
+    I16 = 3,
+    U32 = 4,
+    I32 = 5,
+    U64 = 6,
+    I64 = 7,
+    F32 = 8,
+    F64 = 9,
+}
+
+impl ElementType {
+    const ALL: [ElementType; 10] = [
+        ElementType::U8,
+        ElementType::I8,
+        ElementType::U16,
+        ElementType::I16,
+        ElementType::U32,
+        ElementType::I32,
+        ElementType::U64,
+        ElementType::I64,
+        ElementType::F32,
+        ElementType::F64,
+    ];
+
+    /// Size of a single element in bytes.
+    pub fn width(self) -> usize {
+        match self {
+            ElementType::U8 | ElementType::I8 => 1,
+            ElementType::U16 | ElementType::I16 => 2,
+            ElementType::U32 | ElementType::I32 | ElementType::F32 => 4,
+            ElementType::U64 | ElementType::I64 | ElementType::F64 => 8,
+        }
+    }
+
+    /// Signed integers get sign extended and zigzag encoded during compression.
+    pub fn is_signed(self) -> bool {
+        matches!(self, ElementType::I8 | ElementType::I16 | ElementType::I32 | ElementType::I64)
+    }
+
+    /// Parse the type names also used by `encode_vector` and friends (`u32`, `uint32`, ...).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "i8" | "int8" => Some(ElementType::I8),
+            "u8" | "uint8" => Some(ElementType::U8),
+            "i16" | "int16" => Some(ElementType::I16),
+            "u16" | "uint16" => Some(ElementType::U16),
+            "i32" | "int32" => Some(ElementType::I32),
+            "u32" | "uint32" => Some(ElementType::U32),
+            "i64" | "int64" => Some(ElementType::I64),
+            "u64" | "uint64" => Some(ElementType::U64),
+            "f32" | "float32" => Some(ElementType::F32),
+            "f64" | "float64" => Some(ElementType::F64),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ElementType::U8 => "u8",
+            ElementType::I8 => "i8",
+            ElementType::U16 => "u16",
+            ElementType::I16 => "i16",
+            ElementType::U32 => "u32",
+            ElementType::I32 => "i32",
+            ElementType::U64 => "u64",
+            ElementType::I64 => "i64",
+            ElementType::F32 => "f32",
+            ElementType::F64 => "f64",
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|t| *t as u8 == tag)
+    }
+}
+
+/// How the payload is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Compression {
+    /// Plain array in the byte order of the writer.
+    None = 0,
+    /// Every element as LEB128 varint, signed types zigzag encoded.
+    Varint = 1,
+    /// Differences of consecutive elements, zigzag and varint encoded.
+    DeltaVarint = 2,
+}
+
+impl Compression {
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Varint),
+            2 => Some(Compression::DeltaVarint),
+            _ => None,
+        }
+    }
+}
+
+/// Options for writing containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContainerOptions {
+    pub compression: Compression,
+    pub checksum: bool,
+}
+
+impl Default for ContainerOptions {
+    fn default() -> Self {
+        Self {
+            compression: Compression::None,
+            checksum: true,
+        }
+    }
+}
+
+/// Primitive types which can be stored in containers.
+pub trait ContainerElement: Copy + Default + 'static {
+    const TYPE: ElementType;
+}
+
+macro_rules! container_element {
+    ($($t:ty => $variant:ident),*) => {
+        $(impl ContainerElement for $t {
+            const TYPE: ElementType = ElementType::$variant;
+        })*
+
+        /// The element type of `T` if it is one of the primitive `ContainerElement` types.
+        pub fn element_type_of<T: 'static>() -> Option<ElementType> {
+            let id = TypeId::of::<T>();
+            $(if id == TypeId::of::<$t>() {
+                return Some(ElementType::$variant);
+            })*
+            None
+        }
+    };
+}
+
+container_element!(u8 => U8, i8 => I8, u16 => U16, i16 => I16, u32 => U32, i32 => I32, u64 => U64, i64 => I64, f32 => F32, f64 => F64);
+
+/// Parsed container header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u16,
+    pub big_endian: bool,
+    pub element_type: ElementType,
+    pub compression: Compression,
+    pub num_elements: u64,
+    pub payload_len: u64,
+    pub checksum: Option<u64>,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[0..8].copy_from_slice(&MAGIC);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10] = self.big_endian as u8;
+        bytes[11] = self.element_type as u8;
+        bytes[12] = self.compression as u8;
+        bytes[13] = if self.checksum.is_some() { FLAG_CHECKSUM } else { 0 };
+        bytes[16..24].copy_from_slice(&self.num_elements.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.checksum.unwrap_or(0).to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<Self> {
+        let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if version == 0 || version > VERSION {
+            return Err(invalid_data(format!("unsupported container version {}", version)));
+        }
+        let element_type = ElementType::from_tag(bytes[11]).ok_or_else(|| invalid_data(format!("unknown element type tag {}", bytes[11])))?;
+        let compression = Compression::from_tag(bytes[12]).ok_or_else(|| invalid_data(format!("unknown compression tag {}", bytes[12])))?;
+
+        Ok(Self {
+            version,
+            big_endian: bytes[10] != 0,
+            element_type,
+            compression,
+            num_elements: u64_at(16),
+            payload_len: u64_at(24),
+            checksum: if bytes[13] & FLAG_CHECKSUM != 0 { Some(u64_at(32)) } else { None },
+        })
+    }
+
+    /// Read the header if the file is a container.
+    /// Raw files yield `None` and the read position is reset to the start of the file.
+    pub fn probe(file: &mut File) -> Result<Option<Self>> {
+        let len = file.metadata()?.len();
+        if len < HEADER_LEN as u64 {
+            return Ok(None);
+        }
+
+        let mut bytes = [0u8; HEADER_LEN];
+        file.read_exact(&mut bytes)?;
+        if bytes[0..8] != MAGIC {
+            file.seek(SeekFrom::Start(0))?;
+            return Ok(None);
+        }
+
+        let header = Self::from_bytes(&bytes)?;
+        if header.payload_len != len - HEADER_LEN as u64 {
+            return Err(invalid_data(format!(
+                "container payload should have {} bytes but file has {}",
+                header.payload_len,
+                len - HEADER_LEN as u64
+            )));
+        }
+        Ok(Some(header))
+    }
+}
+
+/// Read only the header of a file, `None` for raw files.
+pub fn read_header<P: AsRef<Path>>(path: P) -> Result<Option<Header>> {
+    Header::probe(&mut File::open(path)?)
+}
+
+/// Write data as a container file.
+pub fn write_container<T: ContainerElement>(data: &[T], path: &dyn AsRef<Path>, options: ContainerOptions) -> Result<()> {
+    write_container_bytes(data.data_bytes(), T::TYPE, path, options)
+}
+
+/// Write the native byte representation of an array of the given type as a container file.
+pub fn write_container_bytes(data: &[u8], element_type: ElementType, path: &dyn AsRef<Path>, options: ContainerOptions) -> Result<()> {
+    if data.len() % element_type.width() != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} bytes are not a multiple of the size of {}", data.len(), element_type.name()),
+        ));
+    }
+
+    let encoded;
+    let payload = if options.compression == Compression::None {
+        data
+    } else {
+        encoded = encode(data, element_type, options.compression);
+        &encoded[..]
+    };
+
+    let header = Header {
+        version: VERSION,
+        big_endian: cfg!(target_endian = "big"),
+        element_type,
+        compression: options.compression,
+        num_elements: (data.len() / element_type.width()) as u64,
+        payload_len: payload.len() as u64,
+        checksum: if options.checksum { Some(fnv1a(payload)) } else { None },
+    };
+
+    let mut file = std::io::BufWriter::new(File::create(path)?);
+    file.write_all(&header.to_bytes())?;
+    file.write_all(payload)?;
+    file.flush()
+}
+
+/// Load an array and verify its element type if the file is a container.
+/// Raw files are loaded as before since they carry no type information.
+pub fn load_checked<T: ContainerElement, P: AsRef<Path>>(path: P) -> Result<Vec<T>> {
+    if let Some(header) = read_header(path.as_ref())? {
+        if header.element_type != T::TYPE {
+            return Err(invalid_data(format!(
+                "{} contains {} but {} was expected",
+                path.as_ref().display(),
+                header.element_type.name(),
+                T::TYPE.name()
+            )));
+        }
+    }
+    Vec::<T>::load_from(path)
+}
+
+/// Check whether a container with the given element type may be loaded as an array of `T`.
+/// Primitive types have to match exactly, other types only need the same size.
+pub fn accepts<T: 'static>(element_type: ElementType) -> bool {
+    match element_type_of::<T>() {
+        Some(expected) => expected == element_type,
+        None => element_type.width() == std::mem::size_of::<T>(),
+    }
+}
+
+/// Read the native byte representation of the elements of a file, decoding containers.
+/// Raw files are returned unchanged with a `None` header.
+pub fn read_bytes<P: AsRef<Path>>(path: P) -> Result<(Option<Header>, Vec<u8>)> {
+    let mut file = File::open(path)?;
+    match Header::probe(&mut file)? {
+        Some(header) => {
+            let data = read_payload(&header, &mut file)?;
+            Ok((Some(header), data))
+        }
+        None => {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            Ok((None, data))
+        }
+    }
+}
+
+/// Decode the payload of a container to the native byte representation of its elements.
+/// Expects the file position right after the header, as left by `Header::probe`.
+pub fn read_payload(header: &Header, file: &mut File) -> Result<Vec<u8>> {
+    let mut payload = vec![0u8; header.payload_len as usize];
+    file.read_exact(&mut payload)?;
+
+    if let Some(expected) = header.checksum {
+        let actual = fnv1a(&payload);
+        if actual != expected {
+            return Err(invalid_data(format!(
+                "container checksum mismatch: expected {:#x}, got {:#x}",
+                expected, actual
+            )));
+        }
+    }
+
+    let width = header.element_type.width();
+    let data = match header.compression {
+        Compression::None => {
+            if header.big_endian != cfg!(target_endian = "big") {
+                for element in payload.chunks_exact_mut(width) {
+                    element.reverse();
+                }
+            }
+            payload
+        }
+        compression => decode(&payload, header.element_type, compression, header.num_elements as usize)?,
+    };
+
+    if data.len() as u64 != header.num_elements * width as u64 {
+        return Err(invalid_data(format!(
+            "container should hold {} elements but payload decoded to {} bytes",
+            header.num_elements,
+            data.len()
+        )));
+    }
+    Ok(data)
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// Elements are handled as u64 during compression.
+// Signed values are sign extended so small negative values and differences stay small.
+fn element_bits(element: &[u8], signed: bool) -> u64 {
+    let width = element.len();
+    let mut bytes = [0u8; 8];
+    if cfg!(target_endian = "big") {
+        bytes[8 - width..].copy_from_slice(element);
+    } else {
+        bytes[..width].copy_from_slice(element);
+    }
+    let bits = u64::from_ne_bytes(bytes);
+    if signed && width < 8 {
+        let shift = 64 - 8 * width as u32;
+        (((bits << shift) as i64) >> shift) as u64
+    } else {
+        bits
+    }
+}
+
+fn write_element_bits(bits: u64, element: &mut [u8]) {
+    let width = element.len();
+    let bytes = bits.to_ne_bytes();
+    if cfg!(target_endian = "big") {
+        element.copy_from_slice(&bytes[8 - width..]);
+    } else {
+        element.copy_from_slice(&bytes[..width]);
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn encode(data: &[u8], element_type: ElementType, compression: Compression) -> Vec<u8> {
+    let signed = element_type.is_signed();
+    let mut out = Vec::with_capacity(data.len() / 2);
+    let mut prev = 0u64;
+
+    for element in data.chunks_exact(element_type.width()) {
+        let bits = element_bits(element, signed);
+        let mut value = match compression {
+            Compression::DeltaVarint => zigzag(bits.wrapping_sub(prev) as i64),
+            _ if signed => zigzag(bits as i64),
+            _ => bits,
+        };
+        prev = bits;
+
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    out
+}
+
+fn decode(payload: &[u8], element_type: ElementType, compression: Compression, num_elements: usize) -> Result<Vec<u8>> {
+    let signed = element_type.is_signed();
+    let width = element_type.width();
+    let mut data = vec![0u8; num_elements * width];
+    let mut bytes = payload.iter();
+    let mut prev = 0u64;
+
+    for element in data.chunks_exact_mut(width) {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = *bytes.next().ok_or_else(|| invalid_data("truncated varint payload".to_string()))?;
+            if shift >= 64 {
+                return Err(invalid_data("varint too long".to_string()));
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let bits = match compression {
+            Compression::DeltaVarint => prev.wrapping_add(unzigzag(value) as u64),
+            _ if signed => unzigzag(value) as u64,
+            _ => value,
+        };
+        prev = bits;
+        write_element_bits(bits, element);
+    }
+
+    if bytes.next().is_some() {
+        return Err(invalid_data("trailing bytes after varint payload".to_string()));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<T: ContainerElement + PartialEq + std::fmt::Debug>(data: &[T], compression: Compression) {
+        let encoded = encode(data.data_bytes(), T::TYPE, compression);
+        let decoded = decode(&encoded, T::TYPE, compression, data.len()).unwrap();
+        assert_eq!(decoded, data.data_bytes());
+    }
+
+    #[test]
+    fn compression_roundtrips() {
+        for compression in [Compression::Varint, Compression::DeltaVarint] {
+            roundtrip::<u32>(&[0, 3, 3, 7, 1, u32::MAX, 0], compression);
+            roundtrip::<i32>(&[-1, 0, i32::MIN, i32::MAX, 5, -7], compression);
+            roundtrip::<u64>(&[u64::MAX, 0, 1 << 63], compression);
+            roundtrip::<i8>(&[-128, 127, 0, -1], compression);
+            roundtrip::<f32>(&[0.5, -3.25, f32::INFINITY], compression);
+        }
+    }
+
+    #[test]
+    fn delta_compression_is_small_for_monotone_arrays() {
+        let first_out: Vec<u32> = (0..1000).map(|i| i * 3).collect();
+        assert_eq!(encode(first_out.data_bytes(), ElementType::U32, Compression::DeltaVarint).len(), 1000);
+    }
+
+    #[test]
+    fn header_roundtrips() {
+        let header = Header {
+            version: VERSION,
+            big_endian: false,
+            element_type: ElementType::F64,
+            compression: Compression::DeltaVarint,
+            num_elements: 42,
+            payload_len: 77,
+            checksum: Some(0xdead_beef),
+        };
+        assert_eq!(Header::from_bytes(&header.to_bytes()).unwrap(), header);
+    }
+    #[test]
+    fn load_from_rejects_other_element_types() {
+        let path = std::env::temp_dir().join(format!("rust_road_router_container_{}", std::process::id()));
+        write_container(&[1u32, 2, 3], &path, ContainerOptions::default()).unwrap();
+
+        assert_eq!(Vec::<u32>::load_from(&path).unwrap(), vec![1, 2, 3]);
+        assert_eq!(Vec::<u64>::load_from(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(Box::<[u16]>::load_from(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(Vec::<f32>::load_from(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(Vec::<i32>::load_from(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(load_checked::<f32, _>(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(read_bytes(&path).unwrap().1, [1u32, 2, 3].data_bytes());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,6 +2,7 @@
 
 mod event_iterator;
 mod link_speed_estimator;
+pub mod speed_profiles;
 
 use self::event_iterator::{Event, EventIterator};
 use self::link_speed_estimator::LinkSpeedEstimator;
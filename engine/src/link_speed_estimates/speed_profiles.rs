@@ -0,0 +1,387 @@
+//! Build historic speed profiles from speed observations and turn them into a periodic floating TD graph.
+//!
+//! Observations are aggregated per edge, day type and time bucket.
+//! Buckets with too few samples are dropped, the rest is smoothed with a moving average over neighboring buckets.
+//! Short gaps get interpolated, longer ones fall back to the free flow speed.
+//! The resulting travel time functions are made FIFO and simplified with Imai-Iri.
+
+use super::LinkSpeedData;
+use crate::datastr::graph::{floating_time_dependent::*, EdgeId, NodeId};
+use std::collections::BTreeMap;
+
+/// Classification of days with distinct traffic patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DayType {
+    /// All days together, used when day types are not distinguished.
+    Any,
+    /// Monday to Friday
+    Workday,
+    Saturday,
+    Sunday,
+}
+
+impl DayType {
+    /// Day type of a unix timestamp in ms, shifted by `utc_offset` seconds into local time.
+    pub fn of_timestamp(timestamp: u64, utc_offset: i64) -> Self {
+        let days = (timestamp as i64 / 1000 + utc_offset).div_euclid(86_400);
+        // 1970-01-01 was a thursday
+        match (days + 3).rem_euclid(7) {
+            5 => DayType::Saturday,
+            6 => DayType::Sunday,
+            _ => DayType::Workday,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DayType::Any => "any",
+            DayType::Workday => "workday",
+            DayType::Saturday => "saturday",
+            DayType::Sunday => "sunday",
+        }
+    }
+}
+
+/// How violations of the FIFO property get repaired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoRepair {
+    /// Raise travel times after steep drops (pessimistic, see `PeriodicPiecewiseLinearFunction::fifoize_up`).
+    Up,
+    /// Lower travel times before steep drops (optimistic, see `PeriodicPiecewiseLinearFunction::fifoize_down`).
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedProfileParams {
+    /// Period of the resulting travel time functions in seconds.
+    pub period: f64,
+    /// Number of buckets per period.
+    pub num_buckets: usize,
+    /// Buckets with fewer observations are treated as missing.
+    pub min_num_samples: usize,
+    /// Observations faster than this factor times the free flow speed are discarded.
+    pub too_fast_factor: f64,
+    /// Lower bound for aggregated speeds in km/h.
+    pub min_speed: f64,
+    /// Number of buckets on each side included in the moving average.
+    pub smoothing_radius: usize,
+    /// Longest run of missing buckets which gets interpolated, longer gaps use the free flow speed.
+    pub max_gap_buckets: usize,
+    pub fifo_repair: FifoRepair,
+    /// Absolute error in seconds allowed when simplifying travel time functions, zero disables simplification.
+    pub approx_epsilon: f64,
+    /// Build separate profiles for workdays, saturdays and sundays.
+    pub split_day_types: bool,
+    /// Offset of the local time zone in seconds, applied to timestamps before bucketing.
+    pub utc_offset: i64,
+}
+
+impl Default for SpeedProfileParams {
+    fn default() -> Self {
+        Self {
+            period: 86_400.0,
+            num_buckets: 24 * 4,
+            min_num_samples: 3,
+            too_fast_factor: 1.5,
+            min_speed: 1.0,
+            smoothing_radius: 1,
+            max_gap_buckets: 4,
+            fifo_repair: FifoRepair::Up,
+            approx_epsilon: 1.0,
+            split_day_types: false,
+            utc_offset: 0,
+        }
+    }
+}
+
+/// Counters for the observations passed to a `SpeedProfileBuilder`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpeedProfileStats {
+    pub total: usize,
+    pub applied: usize,
+    pub too_fast: usize,
+    pub invalid: usize,
+    pub unmapped: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    count: usize,
+    weight: f64,
+    // sum of weight / speed, the aggregate is the weighted harmonic mean which corresponds to averaging travel times
+    inverse_speed_sum: f64,
+}
+
+/// Aggregates speed observations and builds periodic travel time functions from them.
+pub struct SpeedProfileBuilder {
+    params: SpeedProfileParams,
+    // in m
+    length: Vec<f64>,
+    // in s
+    free_flow_travel_time: Vec<f64>,
+    buckets: Vec<BTreeMap<(DayType, usize), Bucket>>,
+    stats: SpeedProfileStats,
+}
+
+impl SpeedProfileBuilder {
+    /// Edge lengths in meters and free flow travel times in seconds, which are also used for edges without observations.
+    pub fn new(length: Vec<f64>, free_flow_travel_time: Vec<f64>, params: SpeedProfileParams) -> Self {
+        assert_eq!(length.len(), free_flow_travel_time.len());
+        assert!(params.num_buckets > 0);
+        assert!(params.period > 0.0);
+        Self {
+            params,
+            buckets: vec![BTreeMap::new(); length.len()],
+            length,
+            free_flow_travel_time,
+            stats: SpeedProfileStats::default(),
+        }
+    }
+
+    pub fn params(&self) -> &SpeedProfileParams {
+        &self.params
+    }
+
+    pub fn stats(&self) -> SpeedProfileStats {
+        self.stats
+    }
+
+    /// The day types profiles get built for.
+    pub fn day_types(&self) -> &'static [DayType] {
+        if self.params.split_day_types {
+            &[DayType::Workday, DayType::Saturday, DayType::Sunday]
+        } else {
+            &[DayType::Any]
+        }
+    }
+
+    pub fn horizon(&self) -> Horizon {
+        Horizon::Periodic(Timestamp::new(self.params.period))
+    }
+
+    fn bucket_len(&self) -> f64 {
+        self.params.period / self.params.num_buckets as f64
+    }
+
+    // free flow speed in km/h
+    fn free_flow_speed(&self, edge: usize) -> f64 {
+        if self.free_flow_travel_time[edge] > 0.0 {
+            self.length[edge] / self.free_flow_travel_time[edge] * 3.6
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Add a single observation with a unix timestamp in ms and a speed in km/h.
+    /// Returns whether the observation was used.
+    pub fn add(&mut self, edge: EdgeId, timestamp: u64, speed: f64, weight: f64) -> bool {
+        self.stats.total += 1;
+        let edge = edge as usize;
+        if !speed.is_finite() || speed <= 0.0 || weight.is_nan() || weight <= 0.0 {
+            self.stats.invalid += 1;
+            return false;
+        }
+        if speed > self.free_flow_speed(edge) * self.params.too_fast_factor {
+            self.stats.too_fast += 1;
+            return false;
+        }
+
+        let day_type = if self.params.split_day_types {
+            DayType::of_timestamp(timestamp, self.params.utc_offset)
+        } else {
+            DayType::Any
+        };
+        let period_ms = (self.params.period * 1000.0).round() as i64;
+        let time_of_day = (timestamp as i64 + self.params.utc_offset * 1000).rem_euclid(period_ms) as f64 / 1000.0;
+        let bucket_idx = ((time_of_day / self.bucket_len()) as usize).min(self.params.num_buckets - 1);
+
+        let bucket = self.buckets[edge].entry((day_type, bucket_idx)).or_default();
+        bucket.count += 1;
+        bucket.weight += weight;
+        bucket.inverse_speed_sum += weight / speed;
+        self.stats.applied += 1;
+        true
+    }
+
+    /// Add the output of `estimate_iter`, weighted by the estimate quality.
+    /// `edge_of_link` maps link ids to edges, estimates for unmapped links are skipped.
+    pub fn add_estimates(&mut self, estimates: impl IntoIterator<Item = LinkSpeedData>, mut edge_of_link: impl FnMut(u64) -> Option<EdgeId>) {
+        for estimate in estimates {
+            if let Some(edge) = edge_of_link(estimate.link_id) {
+                self.add(
+                    edge,
+                    estimate.link_entered_timestamp,
+                    f64::from(estimate.velocity),
+                    f64::from(estimate.estimate_quality),
+                );
+            } else {
+                self.stats.total += 1;
+                self.stats.unmapped += 1;
+            }
+        }
+    }
+
+    // aggregated speeds per bucket after smoothing and gap filling, `None` if there are no usable observations
+    fn speed_profile(&self, edge: usize, day_type: DayType) -> Option<Vec<f64>> {
+        let n = self.params.num_buckets;
+        let free_flow_speed = self.free_flow_speed(edge);
+        let mut speeds = vec![None; n];
+        for (&(bucket_day_type, idx), bucket) in &self.buckets[edge] {
+            if bucket_day_type == day_type && bucket.count >= self.params.min_num_samples {
+                speeds[idx] = Some((bucket.weight / bucket.inverse_speed_sum).max(self.params.min_speed).min(free_flow_speed));
+            }
+        }
+        if speeds.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let radius = self.params.smoothing_radius.min(n / 2) as isize;
+        let smoothed: Vec<Option<f64>> = (0..n)
+            .map(|idx| {
+                speeds[idx]?;
+                let neighbors = (-radius..=radius).filter_map(|offset| speeds[(idx as isize + offset).rem_euclid(n as isize) as usize]);
+                let (sum, count) = neighbors.fold((0.0, 0), |(sum, count), speed| (sum + speed, count + 1));
+                Some(sum / count as f64)
+            })
+            .collect();
+
+        Some(
+            (0..n)
+                .map(|idx| {
+                    if let Some(speed) = smoothed[idx] {
+                        return speed;
+                    }
+                    let prev = (1..n).find(|&d| smoothed[(idx + n - d) % n].is_some()).unwrap();
+                    let next = (1..n).find(|&d| smoothed[(idx + d) % n].is_some()).unwrap();
+                    if prev + next - 1 > self.params.max_gap_buckets {
+                        return free_flow_speed;
+                    }
+                    let (prev_speed, next_speed) = (smoothed[(idx + n - prev) % n].unwrap(), smoothed[(idx + next) % n].unwrap());
+                    prev_speed + (next_speed - prev_speed) * prev as f64 / (prev + next) as f64
+                })
+                .collect(),
+        )
+    }
+
+    /// Periodic travel time function of an edge for a day type.
+    /// Edges without enough observations get a constant function with the free flow travel time.
+    pub fn travel_time_function(&self, edge: EdgeId, day_type: DayType) -> Vec<TTFPoint> {
+        let edge = edge as usize;
+        let constant = |travel_time: f64| {
+            vec![TTFPoint {
+                at: Timestamp::ZERO,
+                val: FlWeight::new(travel_time),
+            }]
+        };
+        let speeds = match self.speed_profile(edge, day_type) {
+            Some(speeds) if self.length[edge] > 0.0 => speeds,
+            _ => return constant(self.free_flow_travel_time[edge]),
+        };
+
+        let travel_times: Vec<f64> = speeds.iter().map(|speed| self.length[edge] / (speed / 3.6)).collect();
+        if travel_times.iter().all(|&tt| FlWeight::new(tt).fuzzy_eq(FlWeight::new(travel_times[0]))) {
+            return constant(travel_times[0]);
+        }
+
+        // one point at the center of each bucket and the wraparound points at 0 and the period
+        let wrap = (travel_times[0] + travel_times[travel_times.len() - 1]) / 2.0;
+        let mut ttf = Vec::with_capacity(travel_times.len() + 2);
+        ttf.push(TTFPoint {
+            at: Timestamp::ZERO,
+            val: FlWeight::new(wrap),
+        });
+        ttf.extend(travel_times.iter().enumerate().map(|(idx, &tt)| TTFPoint {
+            at: Timestamp::new((idx as f64 + 0.5) * self.bucket_len()),
+            val: FlWeight::new(tt),
+        }));
+        ttf.push(TTFPoint {
+            at: self.horizon().period(),
+            val: FlWeight::new(wrap),
+        });
+        self.fifoize(&mut ttf);
+
+        if self.params.approx_epsilon > 0.0 {
+            ttf = Imai::new(&ttf, self.params.approx_epsilon, self.params.approx_epsilon, true, true).compute();
+            let first = ttf[0].val;
+            ttf.last_mut().unwrap().val = first;
+            self.fifoize(&mut ttf);
+        }
+
+        if ttf.iter().all(|p| p.val.fuzzy_eq(ttf[0].val)) {
+            ttf.truncate(1);
+        }
+        ttf
+    }
+
+    fn fifoize(&self, ttf: &mut [TTFPoint]) {
+        match self.params.fifo_repair {
+            FifoRepair::Up => PeriodicPiecewiseLinearFunction::fifoize_up(ttf),
+            FifoRepair::Down => PeriodicPiecewiseLinearFunction::fifoize_down(ttf),
+        }
+    }
+
+    /// Build a floating TD graph for the given topology and day type.
+    pub fn build(&self, first_out: Vec<EdgeId>, head: Vec<NodeId>, day_type: DayType) -> TDGraph {
+        assert_eq!(head.len(), self.length.len());
+        let mut first_ipp_of_arc = Vec::with_capacity(head.len() + 1);
+        first_ipp_of_arc.push(0);
+        let mut ipp_departure_time = Vec::new();
+        let mut ipp_travel_time = Vec::new();
+
+        for edge in 0..head.len() {
+            for point in self.travel_time_function(edge as EdgeId, day_type) {
+                // s to ms
+                ipp_departure_time.push((f64::from(point.at) * 1000.0).round() as u32);
+                ipp_travel_time.push((f64::from(point.val) * 1000.0).round() as u32);
+            }
+            first_ipp_of_arc.push(ipp_departure_time.len() as IPPIndex);
+        }
+
+        TDGraph::new_with_horizon(self.horizon(), first_out, head, first_ipp_of_arc, ipp_departure_time, ipp_travel_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_fifo_profiles_with_fallback() {
+        let params = SpeedProfileParams {
+            num_buckets: 4,
+            min_num_samples: 2,
+            smoothing_radius: 0,
+            max_gap_buckets: 1,
+            approx_epsilon: 0.0,
+            split_day_types: true,
+            ..SpeedProfileParams::default()
+        };
+        // 1km at 100km/h free flow, 36s
+        let mut builder = SpeedProfileBuilder::new(vec![1000.0, 1000.0], vec![36.0, 36.0], params);
+        let quarter_day = 6 * 3600 * 1000;
+        // 1970-01-01 is a thursday
+        assert_eq!(DayType::of_timestamp(0, 0), DayType::Workday);
+        assert_eq!(DayType::of_timestamp(2 * 4 * quarter_day, 0), DayType::Saturday);
+
+        // slow traffic in the second quarter of workdays
+        assert!(builder.add(0, quarter_day + 1000, 36.0, 1.0));
+        assert!(builder.add(0, quarter_day + 2000, 36.0, 1.0));
+        assert!(!builder.add(0, quarter_day + 3000, 500.0, 1.0));
+        // only a single sample on saturday
+        assert!(builder.add(0, 2 * 4 * quarter_day, 36.0, 1.0));
+        assert_eq!(builder.stats().too_fast, 1);
+        assert_eq!(builder.stats().applied, 3);
+
+        let ttf = builder.travel_time_function(0, DayType::Workday);
+        // gap of two buckets after the observed one is too long and uses free flow
+        assert_eq!(ttf.len(), 6);
+        assert!(ttf[2].val.fuzzy_eq(FlWeight::new(100.0)));
+        assert!(ttf[4].val.fuzzy_eq(FlWeight::new(36.0)));
+        assert!(ttf.windows(2).all(|p| (p[0].at + p[0].val).fuzzy_leq(p[1].at + p[1].val)));
+
+        assert_eq!(builder.travel_time_function(0, DayType::Saturday).len(), 1);
+        assert_eq!(builder.travel_time_function(1, DayType::Workday).len(), 1);
+        let graph = builder.build(vec![0, 2, 2], vec![1, 0], DayType::Workday);
+        assert_eq!(graph.num_ipps(), 7);
+    }
+}
@@ -8,7 +8,7 @@ use rust_road_router::{
         },
         *,
     },
-    datastr::graph::*,
+    datastr::{graph::*, node_order::NodeOrder},
 };
 
 fn graph() -> OwnedGraph {
@@ -64,3 +64,135 @@ fn bidir_dijkstra_stopping_crit() {
 
     assert_eq!(server.query(Query { from: 0, to: 4 }).distance(), Some(12));
 }
+
+#[test]
+fn cch_isochrone_matches_dijkstra_distances() {
+    use rust_road_router::algo::{customizable_contraction_hierarchy::*, isochrone::*};
+
+    let graph = graph();
+    let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+    let customized = customize(&cch, &graph);
+    let mut isochrone = CCHIsochrone::new(&customized);
+
+    let result = isochrone.query(&graph, 0, 3);
+    assert_eq!(result.reachable, vec![0, 1, 3]);
+    assert_eq!(isochrone.distances()[4], 5);
+
+    let to_two = result.boundary_edges.iter().find(|e| e.tail == 0 && e.head == 2).unwrap();
+    assert!((to_two.coverage - 0.3).abs() < 1e-9);
+    assert!(result.boundary_edges.iter().all(|e| e.coverage < 1.0));
+    assert!(!result.boundary_edges.iter().any(|e| e.tail == 0 && e.head == 1));
+}
+
+#[test]
+fn td_graph_horizon_controls_wraparound() {
+    use rust_road_router::datastr::graph::floating_time_dependent::*;
+
+    // a single edge from 0 to 1 with 2s travel time at 0s and 4s at 5s
+    let periodic = TDGraph::new_with_horizon(
+        Horizon::Periodic(Timestamp::new(10.0)),
+        vec![0, 1, 1],
+        vec![1],
+        vec![0, 2],
+        vec![0, 5000],
+        vec![2000, 4000],
+    );
+    assert!(periodic.travel_time_function(0).evaluate(Timestamp::new(12.5)).fuzzy_eq(FlWeight::new(3.0)));
+
+    let open = TDGraph::new_with_horizon(Horizon::Open, vec![0, 1, 1], vec![1], vec![0, 2], vec![0, 5000], vec![2000, 4000]);
+    assert_eq!(open.ipps.len(), 4);
+    assert!(open.travel_time_function(0).evaluate(Timestamp::new(2.5)).fuzzy_eq(FlWeight::new(3.0)));
+    assert!(open.travel_time_function(0).evaluate(Timestamp::new(86_400.0)).fuzzy_eq(FlWeight::new(4.0)));
+    // graphs with different horizons can be used side by side
+    assert!(periodic.travel_time_function(0).evaluate(Timestamp::new(22.5)).fuzzy_eq(FlWeight::new(3.0)));
+}
+
+#[test]
+fn open_horizon_holds_last_value_and_rejects_times_beyond_its_end() {
+    use rust_road_router::algo::{
+        catchup::Server as CatchupServer, customizable_contraction_hierarchy::*, dijkstra::query::floating_td_dijkstra::Server as DijkServer,
+    };
+    use rust_road_router::datastr::graph::floating_time_dependent::*;
+
+    // 0 -> 1 with 2s travel time at 0s and 4s at 5s, then 1 -> 2 with a constant 1s
+    let graph = TDGraph::new_with_horizon(
+        Horizon::Open,
+        vec![0, 1, 2, 2],
+        vec![1, 2],
+        vec![0, 2, 3],
+        vec![0, 5000, 0],
+        vec![2000, 4000, 1000],
+    );
+    let end = Timestamp::new(OPEN_HORIZON_END);
+
+    // past the last breakpoint, the travel time stays constant until the end of the horizon
+    for t in [10.0, 86_400.0, 10.0 * 86_400.0, OPEN_HORIZON_END - 1.0] {
+        assert!(graph.travel_time_function(0).evaluate(Timestamp::new(t)).fuzzy_eq(FlWeight::new(4.0)), "{}", t);
+    }
+    assert!(!graph.horizon().contains(end));
+
+    let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+    let customized = customization::ftd::customize(&cch, &graph);
+    let mut dijkstra = DijkServer::new(&graph);
+    let mut catchup = CatchupServer::new(&cch, &customized);
+
+    let query = |departure: f64| TDQuery {
+        from: 0,
+        to: 2,
+        departure: Timestamp::new(departure),
+    };
+    for departure in [100.0, 10.0 * 86_400.0] {
+        assert!(dijkstra.td_query(query(departure)).distance().unwrap().fuzzy_eq(FlWeight::new(5.0)));
+        assert!(catchup.td_query(query(departure)).distance().unwrap().fuzzy_eq(FlWeight::new(5.0)));
+    }
+    // departures at the end of the horizon and arrivals beyond it are rejected instead of wrapping around
+    for departure in [OPEN_HORIZON_END, OPEN_HORIZON_END - 2.0, OPEN_HORIZON_END + 86_400.0] {
+        assert_eq!(dijkstra.td_query(query(departure)).distance(), None, "{}", departure);
+        assert_eq!(catchup.td_query(query(departure)).distance(), None, "{}", departure);
+    }
+}
+
+#[test]
+fn td_graph_rejects_empty_period_file() {
+    use rust_road_router::{datastr::graph::floating_time_dependent::*, io::*};
+
+    let dir = std::env::temp_dir().join(format!("rust_road_router_td_graph_period_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let graph = TDGraph::new_with_horizon(
+        Horizon::Periodic(Timestamp::new(10.0)),
+        vec![0, 1, 1],
+        vec![1],
+        vec![0, 2],
+        vec![0, 5000],
+        vec![2000, 4000],
+    );
+    graph.deconstruct_to(&dir).unwrap();
+    assert_eq!(TDGraph::reconstruct_from(&dir).unwrap().horizon(), graph.horizon());
+
+    Vec::<f64>::new().write_to(&dir.join("period")).unwrap();
+    let err = TDGraph::reconstruct_from(&dir).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn cch_exclusion_server_avoids_excluded_links() {
+    use rust_road_router::algo::{customizable_contraction_hierarchy::*, edge_exclusion::*};
+
+    let graph = graph();
+    let cch = contract(&graph, NodeOrder::identity(graph.num_nodes()));
+    let customized = customize(&cch, &graph);
+    let mut server = CCHExclusionServer::new(customized, &graph);
+    assert_eq!(server.query(Query { from: 0, to: 4 }).distance(), Some(5));
+
+    let mut excluded = ExcludedLinks::new();
+    excluded.exclude_link(3, 4);
+    server.set_excluded_links(&excluded);
+    let mut result = server.query(Query { from: 0, to: 4 }).found().unwrap();
+    assert_eq!(result.distance(), 11);
+    assert_eq!(result.node_path(), vec![0, 2, 4]);
+    drop(result);
+
+    server.set_excluded_links(&ExcludedLinks::new());
+    assert_eq!(server.query(Query { from: 0, to: 4 }).distance(), Some(5));
+}
@@ -20,6 +20,7 @@ use rust_road_router::{
 use crate::choice::ChoiceAlgorithm;
 use crate::gawron::gawron;
 use crate::logit::logit;
+use crate::user_classes::UserClasses;
 
 #[derive(Debug, Clone)]
 pub struct AlternativePathsForDTA {
@@ -121,11 +122,85 @@ impl AlternativePaths {
         }
     }
 
+    /// keeps the paths for which `keep` returns true, but never removes all of them.
+    /// Probabilities are rescaled to sum to 1 and if the chosen path was removed, the most probable remaining one is chosen.
+    pub fn retain_paths(&mut self, keep: impl Fn(usize, &AlternativePaths) -> bool) {
+        let keep: Vec<bool> = (0..self.paths.len()).map(|j| keep(j, self)).collect();
+
+        if keep.iter().all(|&k| k) || keep.iter().all(|&k| !k) {
+            return;
+        }
+
+        let chosen_path_is_kept = keep[self.choice];
+        let choice = keep[..self.choice].iter().filter(|&&k| k).count();
+
+        let mut new_paths = Vec::new();
+        let mut new_costs = Vec::new();
+        let mut new_probabilities = Vec::new();
+
+        for (i, path) in self.paths.iter().enumerate() {
+            if keep[i] {
+                new_paths.push(path.clone());
+                new_costs.push(self.costs[i]);
+                new_probabilities.push(self.probabilities[i]);
+            }
+        }
+
+        self.paths = new_paths;
+        self.costs = new_costs;
+        self.probabilities = new_probabilities;
+
+        let prob_sum: f64 = self.probabilities.iter().sum();
+        if prob_sum > 0.0 {
+            for prob in self.probabilities.iter_mut() {
+                *prob /= prob_sum;
+            }
+        }
+
+        self.choice = if chosen_path_is_kept {
+            choice
+        } else {
+            self.probabilities
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .map(|(j, _)| j)
+                .unwrap()
+        };
+    }
+
     pub fn scale_probabilities(&mut self, scale: f64) {
         for prob in &mut self.probabilities {
             *prob *= scale;
         }
     }
+
+    /// the mean overlap of all pairs of alternatives, the overlap of two paths is the share of the edges of the shorter one
+    /// which are also part of the other one. None if there are less than two alternatives
+    pub fn get_mean_overlap(&self) -> Option<f64> {
+        let n = self.paths.len();
+        if n < 2 {
+            return None;
+        }
+
+        let mut overlap_sum = 0.0;
+        for i in 0..n {
+            for j in i + 1..n {
+                let (shorter, longer) = if self.paths[i].edges.len() <= self.paths[j].edges.len() {
+                    (&self.paths[i].edges, &self.paths[j].edges)
+                } else {
+                    (&self.paths[j].edges, &self.paths[i].edges)
+                };
+
+                if !shorter.is_empty() {
+                    let common_edges = shorter.iter().filter(|edge| longer.contains(edge)).count();
+                    overlap_sum += common_edges as f64 / shorter.len() as f64;
+                }
+            }
+        }
+
+        Some(overlap_sum / (n * (n - 1) / 2) as f64)
+    }
 }
 
 /// path represented by a sequence of edge id used in TDGraph
@@ -142,13 +217,45 @@ impl AlternativePathsForDTA {
         max_alternatives: u32,
         keep_routes: &Vec<bool>,
         seed: i32,
+    ) {
+        self.perform_choice_model_with(previous_alternatives, |_| choice_algorithm, max_alternatives, keep_routes, seed);
+    }
+
+    /// replaces the travel times in the costs by the generalized costs of each query's user class
+    /// and applies the choice model of the user class
+    pub fn perform_choice_model_for_user_classes(
+        &mut self,
+        previous_alternatives: &Self,
+        choice_algorithm: &ChoiceAlgorithm,
+        user_classes: &UserClasses,
+        max_alternatives: u32,
+        keep_routes: &Vec<bool>,
+        seed: i32,
+    ) {
+        user_classes.apply_generalized_costs(self);
+        self.perform_choice_model_with(
+            previous_alternatives,
+            |i| user_classes.get_choice_algorithm(i, choice_algorithm),
+            max_alternatives,
+            keep_routes,
+            seed,
+        );
+    }
+
+    fn perform_choice_model_with<'a>(
+        &mut self,
+        previous_alternatives: &Self,
+        choice_algorithm_of_query: impl Fn(usize) -> &'a ChoiceAlgorithm,
+        max_alternatives: u32,
+        keep_routes: &Vec<bool>,
+        seed: i32,
     ) {
         let mut rng: StdRng = StdRng::seed_from_u64(seed.abs() as u64);
 
         for (i, alternative_paths) in self.alternatives_in_query.iter_mut().enumerate() {
             let previous_costs = &previous_alternatives.alternatives_in_query[i].costs;
 
-            alternative_paths.perform_choice_model(choice_algorithm, max_alternatives, previous_costs);
+            alternative_paths.perform_choice_model(choice_algorithm_of_query(i), max_alternatives, previous_costs);
 
             // decide if the route may change or not by checking "keep_route_probabilities[i]"
             if keep_routes[i] {
@@ -205,6 +312,29 @@ impl AlternativePathsForDTA {
         new_paths_tt: &Vec<FlWeight>,
         departures: &Vec<SerializedTimestamp>,
         graph: &TDGraph,
+    ) -> AlternativePathsForDTA {
+        self.merge_alternatives_with_new_paths(new_paths, new_paths_tt, departures, graph, false)
+    }
+
+    /// updates the costs of the previous alternatives like duarouter's `--skip-new-routes`:
+    /// the current shortest paths are only added for queries without any alternative
+    pub fn update_alternatives_skipping_new_paths(
+        &self,
+        new_paths: &Vec<Vec<EdgeId>>,
+        new_paths_tt: &Vec<FlWeight>,
+        departures: &Vec<SerializedTimestamp>,
+        graph: &TDGraph,
+    ) -> AlternativePathsForDTA {
+        self.merge_alternatives_with_new_paths(new_paths, new_paths_tt, departures, graph, true)
+    }
+
+    fn merge_alternatives_with_new_paths(
+        &self,
+        new_paths: &Vec<Vec<EdgeId>>,
+        new_paths_tt: &Vec<FlWeight>,
+        departures: &Vec<SerializedTimestamp>,
+        graph: &TDGraph,
+        skip_new_paths: bool,
     ) -> AlternativePathsForDTA {
         let mut merged_alternative_paths = self.clone();
 
@@ -235,6 +365,10 @@ impl AlternativePathsForDTA {
                 }
             }
 
+            if skip_new_paths && !alternatives.paths.is_empty() {
+                continue;
+            }
+
             // Add new shortest path if it's not among existing alternatives
             if !is_new_path_among_alternatives {
                 alternatives.paths.push(AlternativePath {
@@ -537,6 +671,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_mean_overlap() {
+        let alternatives = AlternativePaths {
+            paths: vec![
+                AlternativePath { edges: vec![1, 2, 3, 4] },
+                AlternativePath { edges: vec![1, 5] },
+                AlternativePath { edges: vec![6, 7] },
+            ],
+            costs: vec![10.0, 12.0, 14.0],
+            probabilities: vec![0.4, 0.3, 0.3],
+            choice: 0,
+        };
+
+        // the pairs overlap by 1/2, 0 and 0
+        assert!((alternatives.get_mean_overlap().unwrap() - 1.0 / 6.0).abs() < 1e-10);
+
+        let single = AlternativePaths {
+            paths: vec![AlternativePath { edges: vec![1] }],
+            costs: vec![10.0],
+            probabilities: vec![1.0],
+            choice: 0,
+        };
+        assert_eq!(single.get_mean_overlap(), None);
+    }
+
     #[test]
     fn test_perform_choice_model_without_new_route() {
         let choice_algorithm = ChoiceAlgorithm::Gawron { a: 0.1, beta: 0.7 };
@@ -1,16 +1,19 @@
 use std::path::Path;
 
-use fastdta::calibrate_traffic_model::calibrate_traffic_models;
+use conversion::DIR_DTA;
+use fastdta::alternative_paths::AlternativePathsForDTA;
+use fastdta::calibrate_traffic_model::{RoadClasses, calibrate_traffic_models, write_calibration_diagnostics};
 use fastdta::cli;
 use fastdta::cli::Parser;
 use fastdta::customize::customize;
+use fastdta::departure_choice::apply_departure_choice;
 use fastdta::logger::Logger;
-use fastdta::postprocess::{prepare_next_iteration_for_sampled_routing, set_relative_gap_with_previous_paths};
+use fastdta::postprocess::{prepare_next_iteration_for_sampled_routing, set_relative_gap_with_previous_paths, write_convergence_report};
 use fastdta::preprocess_routes::{get_graph_data_for_cch, get_graph_data_for_fast_dta};
 use fastdta::query::get_paths_with_cch;
-use fastdta::relative_gap::{EPSILON_TRAVEL_TIME, append_relative_gap_to_file};
-use fastdta::sampled_queries::{get_paths_by_samples, get_paths_by_samples_with_keep_routes};
-use fastdta::sampler::sample;
+use fastdta::relative_gap::{EPSILON_TRAVEL_TIME, append_relative_gap_of_class_to_file, append_relative_gap_to_file};
+use fastdta::sampled_assignment::{SampledAssignment, WeightUpdater};
+use fastdta::sampler::{BatchSampler, get_strata};
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use rayon::slice::ParallelSliceMut;
 use rust_road_router::report::measure;
@@ -23,8 +26,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let iteration = args.router_args.iteration;
 
     let choice_algorithm = args.router_args.get_choice_algorithm();
+    let router_options = args.router_args.get_router_options()?;
+    let write_sumo_alternatives = args.router_args.get_write_sumo_alternatives()?;
+    if router_options.user_classes.as_ref().is_some_and(|user_classes| user_classes.has_own_metrics()) {
+        println!("Warning: user classes with their own metric are not supported by sumo-fastdta-router, they are routed by travel time");
+    }
+    let departure_choice = args.router_args.get_departure_choice();
     let traffic_model_type = args.get_traffic_model();
     let samples = args.get_samples();
+    let stratification = args.get_stratification();
+    let adaptive_sampling = args.get_adaptive_sampling(&samples);
     let keep_route_probability = args.router_args.keep_route_probability.unwrap_or(0.0);
 
     let routing_threads = args.router_args.routing_threads as usize;
@@ -34,48 +45,74 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let logger = Logger::new("sumo-fastdta-router", &input_dir.display().to_string(), iteration as i32);
 
-    let ((edge_ids, query_data, mut meandata, alternative_paths_from_dta, mut traffic_model_data, keep_routes), duration) =
+    let ((edge_ids, mut query_data, mut meandata, alternative_paths_from_dta, mut traffic_model_data, keep_routes), duration) =
         measure(|| get_graph_data_for_fast_dta(input_dir, iteration, traffic_model_type, keep_route_probability));
 
     logger.log("preprocessing", duration.as_nanos());
 
     let (_, duration) = measure(|| {
-        calibrate_traffic_models(&mut traffic_model_data, &mut meandata, &edge_ids, args.calibration_data_points_threshold);
+        let road_classes = RoadClasses::load(input_dir);
+        let sources = calibrate_traffic_models(
+            &mut traffic_model_data,
+            &mut meandata,
+            &edge_ids,
+            &road_classes,
+            args.calibration_data_points_threshold,
+        );
+        write_calibration_diagnostics(
+            input_dir,
+            &traffic_model_data,
+            &edge_ids,
+            &road_classes,
+            &sources,
+            args.export_speed_density_scatter,
+        )
+        .expect("Failed to write the calibration diagnostics");
     });
 
     logger.log("calibration", duration.as_nanos());
 
-    let (samples, duration) = measure(|| sample(&samples, query_data.0.len(), args.router_args.seed.unwrap_or(rand::random::<i32>())));
+    if let Some(departure_choice) = &departure_choice {
+        let (_, duration) = measure(|| {
+            let (_, graph, cch) = get_graph_data_for_cch(input_dir, iteration, &router_options);
+            let customized_graph = customize(&cch, &graph);
+            apply_departure_choice(
+                input_dir,
+                iteration,
+                departure_choice,
+                &cch,
+                &customized_graph,
+                &graph,
+                &mut query_data,
+                args.router_args.seed.unwrap_or(rand::random::<i32>()),
+                routing_threads,
+            );
+        });
+        logger.log("departure time choice", duration.as_nanos());
+    }
+
+    let (mut sampler, duration) = measure(|| {
+        let strata = get_strata(stratification, input_dir, &query_data);
+        BatchSampler::new(&samples, &strata, adaptive_sampling, args.router_args.seed.unwrap_or(rand::random::<i32>()))
+    });
     logger.log("sample", duration.as_nanos());
 
     let previous_paths = alternative_paths_from_dta.get_chosen_paths();
 
+    let mut assignment = SampledAssignment::new(
+        args.get_sample_router(),
+        WeightUpdater::traffic_model(input_dir, &traffic_model_data.traffic_models, &mut meandata),
+        args.keep_route_in_sampling.then_some(&keep_routes),
+    );
     let ((graph, paths, travel_times, departures), duration) = measure(|| {
-        if !args.keep_route_in_sampling {
-            return get_paths_by_samples(
-                &input_dir,
-                iteration,
-                &logger,
-                &query_data,
-                &samples,
-                &traffic_model_data.traffic_models,
-                &previous_paths,
-                &mut meandata,
-                &edge_ids,
-                routing_threads,
-            );
-        }
-        get_paths_by_samples_with_keep_routes(
+        assignment.run(
             &input_dir,
             iteration,
             &logger,
             &query_data,
-            &samples,
-            &traffic_model_data.traffic_models,
+            &mut sampler,
             &previous_paths,
-            &mut meandata,
             &edge_ids,
-            &keep_routes,
             routing_threads,
         )
     });
@@ -91,10 +128,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &graph,
             choice_algorithm,
             args.router_args.max_alternatives,
-            args.router_args.get_write_sumo_alternatives(),
+            write_sumo_alternatives,
             args.router_args.seed.unwrap_or(rand::random::<i32>()),
             &edge_ids,
             &keep_routes,
+            &router_options,
         );
 
         traffic_model_data.deconstruct(&input_dir).unwrap();
@@ -102,9 +140,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         if iteration == 0 {
             // initialize relative gap file with 0.0 for the first iteration
             append_relative_gap_to_file(0.0, &input_dir);
+            if let Some(user_classes) = &router_options.user_classes {
+                for class in &user_classes.classes {
+                    append_relative_gap_of_class_to_file(&class.name, 0.0, &input_dir);
+                }
+            }
         } else {
             // get graph from previous iteration
-            let (_, graph, cch) = get_graph_data_for_cch(input_dir, iteration);
+            let (_, graph, cch) = get_graph_data_for_cch(input_dir, iteration, &router_options);
             let customized_graph = customize(&cch, &graph);
 
             let (_shortest_paths, shortest_travel_times, departures) = get_paths_with_cch(&cch, &customized_graph, input_dir, &graph, routing_threads);
@@ -120,7 +163,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             //     &edge_ids,
             // );
 
-            set_relative_gap_with_previous_paths(&previous_paths, &graph, &input_dir, &shortest_travel_times, &departures);
+            let experienced_tts =
+                set_relative_gap_with_previous_paths(&previous_paths, &graph, &input_dir, &shortest_travel_times, &departures, &router_options);
+
+            let alternative_paths = AlternativePathsForDTA::reconstruct(&input_dir.join(format!("{:0>3}", iteration)).join(DIR_DTA));
+            write_convergence_report(
+                &input_dir,
+                iteration,
+                &shortest_travel_times,
+                &experienced_tts,
+                &departures,
+                Some(&alternative_paths),
+                Some(&alternative_paths_from_dta),
+            );
         }
     });
 
@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use fastdta::calibrate_traffic_model::calibrate_traffic_models;
+use fastdta::calibrate_traffic_model::{RoadClasses, calibrate_traffic_models, write_calibration_diagnostics};
 use fastdta::cli;
 use fastdta::cli::Parser;
 use fastdta::customize::customize;
@@ -53,6 +53,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let iteration = args.router_args.iteration;
 
     let choice_algorithm = args.router_args.get_choice_algorithm();
+    let router_options = args.router_args.get_router_options()?;
+    let write_sumo_alternatives = args.router_args.get_write_sumo_alternatives()?;
+    if router_options.user_classes.as_ref().is_some_and(|user_classes| user_classes.has_own_metrics()) {
+        println!("Warning: user classes with their own metric are not supported by sumo-fastdta2-router, they are routed by travel time");
+    }
+    if router_options.remove_loops || router_options.skip_new_routes {
+        println!("Warning: --remove-loops and --skip-new-routes are not supported by sumo-fastdta2-router and will be ignored");
+    }
+    if args.router_args.departure_window.is_some() {
+        println!("Warning: --departure-window is not supported by sumo-fastdta2-router, departures are kept fixed");
+    }
     let traffic_model_type = args.get_traffic_model();
     let keep_route_probability = args.router_args.keep_route_probability.unwrap_or(0.0);
 
@@ -88,7 +99,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             get_graph_data_for_fastdta2(input_dir, iteration, traffic_model_type, keep_route_probability);
 
         // Reconstruct the graph with travel times from the previous iteration
-        let (_, graph, cch) = get_graph_data_for_cch(input_dir, iteration);
+        let (_, graph, cch) = get_graph_data_for_cch(input_dir, iteration, &router_options);
 
         // Create a copy of the graph for later use
         let original_graph_copy = graph.clone();
@@ -113,7 +124,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Calibrate traffic models using meandata from the previous simulation
     let (_, duration) = measure(|| {
-        calibrate_traffic_models(&mut traffic_model_data, &mut meandata, &edge_ids, args.calibration_data_points_threshold);
+        let road_classes = RoadClasses::load(input_dir);
+        let sources = calibrate_traffic_models(
+            &mut traffic_model_data,
+            &mut meandata,
+            &edge_ids,
+            &road_classes,
+            args.calibration_data_points_threshold,
+        );
+        write_calibration_diagnostics(
+            input_dir,
+            &traffic_model_data,
+            &edge_ids,
+            &road_classes,
+            &sources,
+            args.export_speed_density_scatter,
+        )
+        .expect("Failed to write the calibration diagnostics");
     });
 
     logger.log("calibration", duration.as_nanos());
@@ -146,13 +173,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // Perform choice model to get preferred paths P
         // choice model will modify costs inside alternative_paths
-        alternative_paths.perform_choice_model(
-            &previous_alternative_paths,
-            &choice_algorithm,
-            args.router_args.max_alternatives,
-            &keep_routes,
-            seed,
-        );
+        match &router_options.user_classes {
+            Some(user_classes) => alternative_paths.perform_choice_model_for_user_classes(
+                &previous_alternative_paths,
+                &choice_algorithm,
+                user_classes,
+                args.router_args.max_alternatives,
+                &keep_routes,
+                seed,
+            ),
+            None => alternative_paths.perform_choice_model(
+                &previous_alternative_paths,
+                &choice_algorithm,
+                args.router_args.max_alternatives,
+                &keep_routes,
+                seed,
+            ),
+        }
 
         alternative_paths
     });
@@ -261,13 +298,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // STEP 8: Perform choice model again with updated alternatives
     let (_, duration) = measure(|| {
         // For the second choice model, we use the state before adding FastDTA2 as "previous"
-        alternative_paths.perform_choice_model(
-            &previous_alternative_paths,
-            &choice_algorithm,
-            args.router_args.max_alternatives,
-            &keep_routes,
-            seed,
-        );
+        match &router_options.user_classes {
+            Some(user_classes) => alternative_paths.perform_choice_model_for_user_classes(
+                &previous_alternative_paths,
+                &choice_algorithm,
+                user_classes,
+                args.router_args.max_alternatives,
+                &keep_routes,
+                seed,
+            ),
+            None => alternative_paths.perform_choice_model(
+                &previous_alternative_paths,
+                &choice_algorithm,
+                args.router_args.max_alternatives,
+                &keep_routes,
+                seed,
+            ),
+        }
     });
 
     logger.log("second choice model", duration.as_nanos());
@@ -284,8 +331,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &sp_travel_times,
             &departures,
             &original_graph_copy,
-            args.router_args.get_write_sumo_alternatives(),
+            write_sumo_alternatives,
             &edge_ids,
+            &router_options,
         );
 
         traffic_model_data.deconstruct(&input_dir).unwrap();
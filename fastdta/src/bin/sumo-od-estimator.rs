@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use conversion::{
+    DIR_OD_ESTIMATION, FILE_EDGE_INDICES_TO_ID, FILE_QUERY_IDS,
+    sumo::{FileReader, FileWriter, meandata_reader::SumoMeandataReader, trips_reader::SumoTripsReader, trips_writer::SumoTripsWriter},
+};
+use fastdta::cli::{self, Parser};
+use fastdta::logger::Logger;
+use fastdta::od_estimation::{estimate_od_matrix, get_estimated_trips, get_observed_counts};
+use fastdta::sumo_runner::Simulator;
+use rust_road_router::io::read_strings_from_file;
+use rust_road_router::report::measure;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = cli::OdEstimationArgs::parse();
+
+    let input_dir = Path::new(&args.input_dir);
+    let output_dir = input_dir.join(DIR_OD_ESTIMATION);
+    std::fs::create_dir_all(&output_dir)?;
+
+    let config = args.get_config();
+    let simulator = args.get_simulator();
+    let net_file = args.net_file.as_ref().map(Path::new).unwrap_or(Path::new(""));
+    if matches!(simulator, Simulator::Sumo) {
+        assert!(args.net_file.is_some(), "--net-file is required when SUMO is used as simulator");
+    }
+
+    let routing_threads = args.routing_threads as usize;
+    println!("[sumo-od-estimator] Using {} routing threads", routing_threads);
+
+    let logger = Logger::new("sumo-od-estimator", &input_dir.display().to_string(), 0);
+
+    let (trips, duration) = measure(|| SumoTripsReader::read(Path::new(&args.trips_file)).expect("Failed to read the trips file"));
+    logger.log("read trips", duration.as_nanos());
+
+    // the queries of the input directory have to be the trips of the trips file in the same order
+    let query_ids: Vec<String> = read_strings_from_file(&input_dir.join(FILE_QUERY_IDS))?;
+    assert!(
+        trips.trips.len() == query_ids.len() && trips.trips.iter().zip(query_ids.iter()).all(|(trip, id)| &trip.id == id),
+        "The trips file does not match the queries in {}",
+        input_dir.display()
+    );
+
+    let edge_ids: Vec<String> = read_strings_from_file(&input_dir.join(FILE_EDGE_INDICES_TO_ID))?;
+    let ((observed_counts, aggregation), duration) = measure(|| {
+        get_observed_counts(
+            &SumoMeandataReader::read(Path::new(&args.counts_file)).expect("Failed to read the counts file"),
+            &edge_ids,
+        )
+    });
+    logger.log("read counts", duration.as_nanos());
+
+    let ((realized_trips, statistics), duration) = measure(|| {
+        estimate_od_matrix(
+            input_dir,
+            &output_dir,
+            net_file,
+            &config,
+            &observed_counts,
+            aggregation,
+            args.begin,
+            args.end,
+            &simulator,
+            &logger,
+            routing_threads,
+        )
+    });
+    logger.log("od matrix estimation", duration.as_nanos());
+
+    println!(
+        "Estimated demand: {} trips (seed: {}), rmse={:.3}, r²={:.4}, GEH<5={:.1}%, observed={}, simulated={}",
+        realized_trips.len(),
+        trips.trips.len(),
+        statistics.rmse,
+        statistics.r_squared,
+        100.0 * statistics.geh_below_5,
+        statistics.observed_total,
+        statistics.simulated_total
+    );
+
+    SumoTripsWriter::write(Path::new(&args.output_file), &get_estimated_trips(&trips, &realized_trips))?;
+
+    Ok(())
+}
@@ -1,27 +1,21 @@
 use clap::Parser;
 use conversion::{
-    FILE_EDGE_INDICES_TO_ID, FILE_QUERY_IDS,
+    DIR_DTA, FILE_EDGE_INDICES_TO_ID, FILE_QUERY_IDS,
     sumo::{
-        FileReader, SumoTravelTime,
-        routes::{RoutesDocumentRoot, Vehicle},
-        routes_reader::SumoRoutesReader,
-        sumo_find_file::get_routes_file_name_in_iteration,
-        sumo_to_new_graph_weights::extract_travel_times_from_iteration_directory,
-        sumo_to_td_graph_converter::convert_sumo_to_routing_kit_and_queries,
+        SumoTravelTime, routes::Vehicle, sumo_find_file::get_routes_file_name_in_iteration, sumo_to_td_graph_converter::convert_sumo_to_routing_kit_and_queries,
     },
 };
-use std::{collections::HashMap, env, fs::OpenOptions, path::Path};
+use std::{env, fs::OpenOptions, path::Path};
 use std::{fs::remove_dir_all, io::Write};
 
 use fastdta::{
-    customize::customize,
-    preprocess::{get_cch, preprocess, run_inertial_flow_cutter},
-    query::get_paths_with_cch,
+    alternative_paths::AlternativePathsForDTA,
+    convergence::{ConvergenceReport, DEFAULT_DEPARTURE_INTERVAL, get_travel_times_of_sumo_iteration},
+    preprocess::{preprocess, run_inertial_flow_cutter},
     relative_gap::{EPSILON_TRAVEL_TIME, get_relative_gap},
 };
-use rayon::{iter, prelude::*};
-use rust_road_router::{datastr::graph::floating_time_dependent::TDGraph, io::read_strings_from_file};
-use rust_road_router::{datastr::graph::floating_time_dependent::Timestamp, io::Reconstruct};
+use rayon::prelude::*;
+use rust_road_router::io::read_strings_from_file;
 
 fn main() {
     let time_start = std::time::Instant::now();
@@ -57,7 +51,7 @@ fn main() {
         )
     });
 
-    let edge_id_to_index: HashMap<&String, usize> = edge_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    let routing_threads = std::thread::available_parallelism().unwrap().get();
 
     let mut rel_gaps: Vec<f64> = Vec::new();
 
@@ -66,32 +60,9 @@ fn main() {
     let mut dta_iteration_dir = dta_dir.join(format!("{:0>3}", iteration));
 
     while dta_iteration_dir.exists() {
-        extract_travel_times_from_iteration_directory(&dta_iteration_dir, &temp_cch_dir, &edge_ids);
-        let graph = TDGraph::reconstruct_from(&temp_cch_dir).unwrap();
-        let cch = get_cch(&temp_cch_dir, &graph);
-        let customized_graph = customize(&cch, &graph);
-        let (best_paths, _best_travel_times, departures) = get_paths_with_cch(
-            &cch,
-            &customized_graph,
-            &temp_cch_dir,
-            &graph,
-            std::thread::available_parallelism().unwrap().get(),
-        );
-
         let routes_path = dta_iteration_dir.join(get_routes_file_name_in_iteration(&trips_file, iteration));
-        println!("Reading routes from file {}", routes_path.display());
-        let routes_document_root = SumoRoutesReader::read(&routes_path).unwrap();
-
-        let experienced_tt = get_experienced_travel_times_from_routes(&routes_document_root, &query_ids, &graph, &edge_id_to_index);
-
-        let best_tt: Vec<SumoTravelTime> = best_paths
-            .par_iter()
-            .enumerate()
-            .map(|(i, path)| {
-                let tt = graph.get_travel_time_along_path(Timestamp::from_millis(departures[i]), path); // departure time does not matter for best path travel time
-                tt.into()
-            })
-            .collect();
+        let (best_tt, experienced_tt, departures) =
+            get_travel_times_of_sumo_iteration(&temp_cch_dir, &dta_iteration_dir, &routes_path, &query_ids, &edge_ids, routing_threads);
 
         // print_network_travel_time(&experienced_tt);
         // print_highest_differences(&best_tt, &experienced_tt, &best_paths, &routes_document_root.vehicles, &query_ids, &edge_ids);
@@ -100,6 +71,19 @@ fn main() {
 
         rel_gaps.push(rel_gap);
 
+        if args.report {
+            write_convergence_report(
+                dta_dir,
+                &dta_iteration_dir,
+                iteration,
+                &best_tt,
+                &experienced_tt,
+                &departures,
+                &query_ids,
+                args.departure_interval,
+            );
+        }
+
         if single_iteration {
             break;
         }
@@ -153,6 +137,49 @@ pub struct Args {
     /// If specified, only the files for that iteration will be read
     #[arg(long = "iteration")]
     pub iteration: Option<u32>,
+
+    /// additionally write a convergence report for each iteration, see `fastdta::convergence`
+    #[arg(long = "report", default_value_t = false)]
+    pub report: bool,
+
+    /// length of the departure intervals the relative gap is split by in the convergence report, in seconds
+    #[arg(long = "departure-interval", default_value_t = DEFAULT_DEPARTURE_INTERVAL)]
+    pub departure_interval: f64,
+}
+
+/// writes the convergence report of an iteration, the route statistics are included if the router stored its alternatives
+fn write_convergence_report(
+    dta_dir: &Path,
+    dta_iteration_dir: &Path,
+    iteration: u32,
+    best_tts: &Vec<f64>,
+    experienced_tts: &Vec<f64>,
+    departures: &Vec<u32>,
+    query_ids: &Vec<String>,
+    departure_interval: f64,
+) {
+    let alternatives = dta_iteration_dir
+        .join(DIR_DTA)
+        .exists()
+        .then(|| AlternativePathsForDTA::reconstruct(&dta_iteration_dir.join(DIR_DTA)));
+    let previous_alternatives = iteration
+        .checked_sub(1)
+        .map(|previous_iteration| dta_dir.join(format!("{:0>3}", previous_iteration)).join(DIR_DTA))
+        .filter(|previous_dir| previous_dir.exists())
+        .map(|previous_dir| AlternativePathsForDTA::reconstruct(&previous_dir));
+
+    ConvergenceReport::new(
+        iteration,
+        best_tts,
+        experienced_tts,
+        departures,
+        query_ids,
+        departure_interval,
+        alternatives.as_ref(),
+        previous_alternatives.as_ref(),
+    )
+    .write(dta_dir)
+    .unwrap();
 }
 
 fn get_path_ids_from_indices(edge_ids: &Vec<String>, indices: &Vec<u32>) -> Vec<String> {
@@ -203,42 +230,3 @@ fn print_highest_differences(
         }
     }
 }
-
-pub fn get_experienced_travel_times_from_routes(
-    routes_document_root: &RoutesDocumentRoot,
-    query_ids: &Vec<String>,
-    graph: &TDGraph,
-    edge_id_to_index: &HashMap<&String, usize>,
-) -> Vec<SumoTravelTime> {
-    let vehicle_id_to_vehicle: HashMap<&String, &Vehicle> = routes_document_root.vehicles.iter().map(|v| (&v.id, v)).collect();
-
-    query_ids
-        .par_iter()
-        .enumerate()
-        .map(|(_i, id)| {
-            if let Some(v) = vehicle_id_to_vehicle.get(id) {
-                let experienced_path: Vec<u32> = if let Some(route) = &v.route {
-                    route
-                        .edges
-                        .split_ascii_whitespace()
-                        .map(|edge_id| {
-                            if let Some(&index) = edge_id_to_index.get(&edge_id.to_string()) {
-                                index as u32
-                            } else {
-                                panic!("Edge id {} not found in edge_id_to_index map", edge_id);
-                            }
-                        })
-                        .collect()
-                } else {
-                    panic!("No route found for vehicle id {}", id);
-                };
-
-                let experienced_time = graph.get_travel_time_along_path(Timestamp::new(v.depart), &experienced_path);
-                experienced_time.into()
-            } else {
-                // negative number will be ignored in the relative gap calculation.
-                panic!("No route found for query id {}", id);
-            }
-        })
-        .collect()
-}
@@ -2,10 +2,13 @@ use std::path::Path;
 
 use fastdta::alternative_paths::AlternativePathsForDTA;
 use fastdta::cli::Parser;
+use fastdta::customize::customize;
+use fastdta::departure_choice::apply_departure_choice;
 use fastdta::logger::Logger;
 use fastdta::postprocess::prepare_next_iteration_for_sampled_routing;
-use fastdta::sampled_queries_sumo::{get_paths_by_samples_with_sumo, get_paths_by_samples_with_sumo_keep_routes};
-use fastdta::sampler::sample;
+use fastdta::preprocess_routes::get_graph_data_for_cch;
+use fastdta::sampled_assignment::{SampledAssignment, WeightUpdater};
+use fastdta::sampler::{BatchSampler, get_strata};
 use fastdta::{calculate_keep_routes, cli};
 use rust_road_router::io::read_strings_from_file;
 use rust_road_router::report::measure;
@@ -24,7 +27,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let keep_route_probability = router_args.keep_route_probability.unwrap_or(0.0);
 
     let choice_algorithm = router_args.get_choice_algorithm();
+    let router_options = router_args.get_router_options()?;
+    let write_sumo_alternatives = router_args.get_write_sumo_alternatives()?;
+    if router_options.user_classes.as_ref().is_some_and(|user_classes| user_classes.has_own_metrics()) {
+        println!("Warning: user classes with their own metric are not supported by sumo-sample-router, they are routed by travel time");
+    }
+    let departure_choice = router_args.get_departure_choice();
     let samples = args.get_samples();
+    let stratification = args.get_stratification();
+    let adaptive_sampling = args.get_adaptive_sampling(&samples);
+    let simulator = args.get_simulator();
 
     let routing_threads = router_args.routing_threads as usize;
     println!("[sumo-sample-router] Using {} routing threads", routing_threads);
@@ -37,7 +49,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (edge_ids, duration) = measure(|| read_strings_from_file(&input_dir.join(FILE_EDGE_INDICES_TO_ID)).expect("Failed to read edge IDs"));
     logger.log("read edge ids", duration.as_nanos());
 
-    let (query_data, duration) = measure(|| fastdta::query::read_queries(input_dir));
+    let (mut query_data, duration) = measure(|| fastdta::query::read_queries(input_dir));
     logger.log("read queries", duration.as_nanos());
 
     let (keep_routes, duration) = measure(|| calculate_keep_routes(query_data.0.len(), keep_route_probability, rand::random::<i32>()));
@@ -59,41 +71,50 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     logger.log("extract previous paths", duration.as_nanos());
 
+    if let Some(departure_choice) = &departure_choice {
+        let (_, duration) = measure(|| {
+            let (_, graph, cch) = get_graph_data_for_cch(input_dir, iteration, &router_options);
+            let customized_graph = customize(&cch, &graph);
+            apply_departure_choice(
+                input_dir,
+                iteration,
+                departure_choice,
+                &cch,
+                &customized_graph,
+                &graph,
+                &mut query_data,
+                router_args.seed.unwrap_or(rand::random::<i32>()),
+                routing_threads,
+            );
+        });
+        logger.log("departure time choice", duration.as_nanos());
+    }
+
     // Generate samples
-    let (samples, duration) = measure(|| sample(&samples, query_data.0.len(), args.router_args.seed.unwrap_or(rand::random::<i32>())));
+    let (mut sampler, duration) = measure(|| {
+        let strata = get_strata(stratification, input_dir, &query_data);
+        BatchSampler::new(&samples, &strata, adaptive_sampling, args.router_args.seed.unwrap_or(rand::random::<i32>()))
+    });
     logger.log("sample", duration.as_nanos());
 
     // Route all queries using samples with SUMO simulation
+    let begin = router_args.begin.unwrap_or(0.0);
+    let end = router_args.end.unwrap_or(86400.0);
+    let previous_paths = alternative_paths.get_chosen_paths();
+    let mut assignment = SampledAssignment::new(
+        args.get_sample_router(),
+        WeightUpdater::simulation(input_dir, &simulator, net_file, aggregation, begin, end),
+        args.keep_route_in_sampling.then_some(&keep_routes),
+    );
     let ((graph, paths, travel_times, departures), duration) = measure(|| {
-        if !args.keep_route_in_sampling {
-            return get_paths_by_samples_with_sumo(
-                &input_dir,
-                &net_file,
-                iteration,
-                aggregation,
-                router_args.begin.unwrap_or(0.0),
-                router_args.end.unwrap_or(86400.0),
-                &logger,
-                &query_data,
-                &samples,
-                &edge_ids,
-                routing_threads,
-            );
-        }
-
-        get_paths_by_samples_with_sumo_keep_routes(
+        assignment.run(
             &input_dir,
-            &net_file,
             iteration,
-            aggregation,
-            args.router_args.begin.unwrap_or(0.0),
-            args.router_args.end.unwrap_or(86400.0),
             &logger,
             &query_data,
-            &samples,
-            &alternative_paths.get_chosen_paths(),
+            &mut sampler,
+            &previous_paths,
             &edge_ids,
-            &keep_routes,
             routing_threads,
         )
     });
@@ -112,10 +133,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &graph,
             choice_algorithm,
             args.router_args.max_alternatives,
-            args.router_args.get_write_sumo_alternatives(),
+            write_sumo_alternatives,
             args.router_args.seed.unwrap_or(rand::random::<i32>()),
             &edge_ids,
             &keep_routes,
+            &router_options,
         );
     });
 
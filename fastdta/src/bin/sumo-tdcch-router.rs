@@ -1,10 +1,11 @@
 use fastdta::cli;
 use fastdta::cli::Parser;
 use fastdta::customize::customize;
+use fastdta::departure_choice::apply_departure_choice;
 use fastdta::logger::Logger;
 use fastdta::postprocess::prepare_next_iteration;
 use fastdta::preprocess_routes::get_graph_data_for_cch;
-use fastdta::query::get_paths_with_cch;
+use fastdta::query::{get_paths_with_cch_queries, read_queries};
 use rust_road_router::report::measure;
 use std::path::Path;
 
@@ -19,29 +20,67 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let logger = Logger::new("sumo-tdcch-router", &input_dir.display().to_string(), iteration as i32);
 
     let choice_algorithm = args.get_choice_algorithm();
+    let router_options = args.get_router_options()?;
+    let departure_choice = args.get_departure_choice();
 
     let routing_threads = args.routing_threads as usize;
     println!("[sumo-tdcch-router] Using {} routing threads", routing_threads);
 
     assert!(args.max_alternatives > 0, "max_alternatives must be greater than 0");
 
-    let ((edge_ids, graph, cch), duration) = measure(|| get_graph_data_for_cch(input_dir, iteration));
+    let ((edge_ids, graph, cch), duration) = measure(|| get_graph_data_for_cch(input_dir, iteration, &router_options));
     logger.log("preprocessing", duration.as_nanos());
 
     let (customized_graph, duration) = measure(|| customize(&cch, &graph));
     logger.log("cch customization", duration.as_nanos());
 
-    let ((shortest_paths, travel_times, departures), duration) = measure(|| get_paths_with_cch(&cch, &customized_graph, &input_dir, &graph, routing_threads));
+    let mut query_data = read_queries(input_dir);
+
+    if let Some(departure_choice) = &departure_choice {
+        let (_, duration) = measure(|| {
+            apply_departure_choice(
+                input_dir,
+                iteration,
+                departure_choice,
+                &cch,
+                &customized_graph,
+                &graph,
+                &mut query_data,
+                args.seed.unwrap_or(rand::random::<i32>()),
+                routing_threads,
+            )
+        });
+        logger.log("departure time choice", duration.as_nanos());
+    }
+
+    let ((mut shortest_paths, mut travel_times, departures), duration) = measure(|| {
+        get_paths_with_cch_queries(
+            &cch,
+            &customized_graph,
+            &query_data.0,
+            &query_data.1,
+            &query_data.2,
+            &query_data.3,
+            &query_data.4,
+            &graph,
+            routing_threads,
+        )
+    });
     logger.log("cch routing", duration.as_nanos());
 
+    if let Some(user_classes) = router_options.user_classes.as_ref().filter(|user_classes| user_classes.has_own_metrics()) {
+        let (_, duration) =
+            measure(|| user_classes.route_classes_with_own_metric(&cch, &graph, &query_data, &mut shortest_paths, &mut travel_times, routing_threads));
+        logger.log("user class routing", duration.as_nanos());
+    }
+
     for shortest_path in &shortest_paths {
         if shortest_path.is_empty() {
             println!("Found an empty path!");
         }
     }
 
-    let write_sumo_alternatives =
-        args.no_write_sumo_alternatives == "false" || args.no_write_sumo_alternatives == "0" || args.no_write_sumo_alternatives == "False";
+    let write_sumo_alternatives = args.get_write_sumo_alternatives()?;
 
     let (_, duration) = measure(|| {
         prepare_next_iteration(
@@ -58,6 +97,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             args.seed.unwrap_or(rand::random::<i32>()),
             &edge_ids,
             keep_route_probability,
+            &router_options,
         )
     });
 
@@ -16,6 +16,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let keep_route_probability = args.keep_route_probability.unwrap_or(0.0);
 
     let choice_algorithm = args.get_choice_algorithm();
+    let router_options = args.get_router_options()?;
+    if router_options.user_classes.as_ref().is_some_and(|user_classes| user_classes.has_own_metrics()) {
+        println!("Warning: user classes with their own metric are not supported by sumo-tddijkstra-router, they are routed by travel time");
+    }
+    if args.departure_window.is_some() {
+        println!("Warning: --departure-window is not supported by sumo-tddijkstra-router, departures are kept fixed");
+    }
 
     let routing_threads = args.routing_threads as usize;
     println!("[sumo-tddijkstra-router] Using {} routing threads", routing_threads);
@@ -24,14 +31,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let logger = Logger::new("sumo-tddijkstra-router", &input_dir.display().to_string(), iteration as i32);
 
-    let ((edge_ids, graph), duration) = measure(|| get_graph_data_for_dijkstra(input_dir, iteration));
+    let ((edge_ids, graph), duration) = measure(|| get_graph_data_for_dijkstra(input_dir, iteration, &router_options));
     logger.log("preprocessing", duration.as_nanos());
 
     let ((shortest_paths, travel_times, departures), duration) = measure(|| get_paths_with_dijkstra(input_dir, &graph, routing_threads));
     logger.log("dijkstra routing", duration.as_nanos());
 
-    let write_sumo_alternatives =
-        args.no_write_sumo_alternatives == "false" || args.no_write_sumo_alternatives == "0" || args.no_write_sumo_alternatives == "False";
+    let write_sumo_alternatives = args.get_write_sumo_alternatives()?;
 
     let (_, duration) = measure(|| {
         prepare_next_iteration(
@@ -48,6 +54,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             args.seed.unwrap_or(rand::random::<i32>()),
             &edge_ids,
             keep_route_probability,
+            &router_options,
         )
     });
 
@@ -1,12 +1,104 @@
 use conversion::sumo::meandata::MeandataDocumentRoot;
-use std::fs::OpenOptions;
-use std::io::Write;
+use conversion::{
+    FILE_EDGE_LANES, FILE_EDGE_PRIORITIES, FILE_EDGE_SPEED_DENSITY_SCATTER, FILE_EDGE_SPEEDS, FILE_EDGE_TRAFFIC_MODEL_FIT, GLOBAL_FREE_FLOW_SPEED_FACTOR,
+};
+use rust_road_router::io::Load;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
+use crate::traffic_model::TrafficModel;
 use crate::traffic_model_data::TrafficModelData;
 
-pub fn calibrate_traffic_models(traffic_model_data: &mut TrafficModelData, meandata: &mut MeandataDocumentRoot, edge_ids: &Vec<String>, threshold: usize) {
-    // for each edge, find data in meandata and add to traffic_model_data
+/// edges whose traffic model parameters are pooled: same priority, speed limit (in km/h) and number of lanes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RoadClass {
+    pub priority: i32,
+    pub speed: u32,
+    pub lanes: u32,
+}
+
+pub struct RoadClasses {
+    pub class_of_edge: Vec<RoadClass>,
+    /// free flow speed of each edge in km/h
+    pub free_flow_speeds: Vec<f64>,
+}
+
+impl RoadClasses {
+    /// `speeds` in m/s
+    pub fn new(priorities: &Vec<i32>, speeds: &Vec<f64>, lanes: &Vec<u32>) -> Self {
+        Self {
+            class_of_edge: (0..speeds.len())
+                .map(|i| RoadClass {
+                    priority: priorities[i],
+                    speed: (speeds[i] * 3.6).round() as u32,
+                    lanes: lanes[i],
+                })
+                .collect(),
+            free_flow_speeds: speeds.iter().map(|speed| speed * 3.6 * GLOBAL_FREE_FLOW_SPEED_FACTOR).collect(),
+        }
+    }
+
+    pub fn load(input_dir: &Path) -> Self {
+        let speeds = Vec::<f64>::load_from(&input_dir.join(FILE_EDGE_SPEEDS)).unwrap();
+        let lanes = Vec::<u32>::load_from(&input_dir.join(FILE_EDGE_LANES)).unwrap();
+        let priorities = if input_dir.join(FILE_EDGE_PRIORITIES).exists() {
+            Vec::<i32>::load_from(&input_dir.join(FILE_EDGE_PRIORITIES)).unwrap()
+        } else {
+            println!("Warning: no edge priorities found, road classes only consider speed and lanes");
+            vec![-1; speeds.len()]
+        };
+
+        Self::new(&priorities, &speeds, &lanes)
+    }
+
+    pub fn get_edges_of_classes(&self) -> HashMap<RoadClass, Vec<usize>> {
+        let mut edges_of_class: HashMap<RoadClass, Vec<usize>> = HashMap::new();
+        for (edge_index, class) in self.class_of_edge.iter().enumerate() {
+            edges_of_class.entry(*class).or_default().push(edge_index);
+        }
+        edges_of_class
+    }
+}
+
+/// where the parameters of the traffic model of an edge come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationSource {
+    /// fitted on the observations of the edge
+    Edge,
+    /// fitted on the pooled observations of all edges of its road class, the edge has too few observations
+    RoadClass,
+    /// not calibrated, the defaults of the traffic model
+    Default,
+}
+
+impl CalibrationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CalibrationSource::Edge => "edge",
+            CalibrationSource::RoadClass => "road-class",
+            CalibrationSource::Default => "default",
+        }
+    }
+}
+
+/// Calibrates the traffic model of each edge with less than `threshold` observations, after adding the observations of the meandata.
+/// Edges which then have at least `threshold` observations are fitted on their own, the others inherit the parameters fitted on
+/// all observations of their road class. If the road class has less than `threshold` observations as well, the edge is fitted on
+/// its own observations, if there are any.
+/// Returns the source of the parameters of each edge
+pub fn calibrate_traffic_models(
+    traffic_model_data: &mut TrafficModelData,
+    meandata: &mut MeandataDocumentRoot,
+    edge_ids: &Vec<String>,
+    road_classes: &RoadClasses,
+    threshold: usize,
+) -> Vec<CalibrationSource> {
+    let mut sources = vec![CalibrationSource::Default; edge_ids.len()];
+    let mut sparse_edges = Vec::new();
 
+    // for each edge, find data in meandata and add to traffic_model_data
     for (edge_index, edge_id) in edge_ids.iter().enumerate() {
         if traffic_model_data.observed_densities[edge_index].len() < threshold {
             let mut densities: Vec<f64> = Vec::new();
@@ -26,11 +118,237 @@ pub fn calibrate_traffic_models(traffic_model_data: &mut TrafficModelData, meand
             traffic_model_data.observed_densities[edge_index].extend_from_slice(&densities);
             traffic_model_data.observed_speeds[edge_index].extend_from_slice(&speeds);
 
-            if traffic_model_data.observed_densities[edge_index].is_empty() {
+            if traffic_model_data.observed_densities[edge_index].len() < threshold {
+                sparse_edges.push(edge_index);
                 continue;
             }
 
             traffic_model_data.calibrate_model(edge_index);
         }
+        sources[edge_index] = CalibrationSource::Edge;
+    }
+
+    let edges_of_class = road_classes.get_edges_of_classes();
+    let mut class_models: HashMap<RoadClass, Option<Box<dyn TrafficModel>>> = HashMap::new();
+
+    for edge_index in sparse_edges {
+        let class = road_classes.class_of_edge[edge_index];
+        let class_model = class_models
+            .entry(class)
+            .or_insert_with(|| calibrate_class_model(traffic_model_data, &edges_of_class[&class], road_classes, threshold));
+
+        if let Some(class_model) = class_model {
+            traffic_model_data.traffic_models[edge_index] = TrafficModelData::with_free_flow_speed(
+                &traffic_model_data.traffic_model_type,
+                class_model.as_ref(),
+                road_classes.free_flow_speeds[edge_index],
+            );
+            sources[edge_index] = CalibrationSource::RoadClass;
+        } else if !traffic_model_data.observed_densities[edge_index].is_empty() {
+            traffic_model_data.calibrate_model(edge_index);
+            sources[edge_index] = CalibrationSource::Edge;
+        } else {
+            sources[edge_index] = CalibrationSource::Default;
+        }
+    }
+
+    sources
+}
+
+/// fits a traffic model on the observations of all given edges, None if there are less than `threshold` observations
+fn calibrate_class_model(
+    traffic_model_data: &TrafficModelData,
+    edges: &Vec<usize>,
+    road_classes: &RoadClasses,
+    threshold: usize,
+) -> Option<Box<dyn TrafficModel>> {
+    let num_observations: usize = edges.iter().map(|&edge_index| traffic_model_data.observed_densities[edge_index].len()).sum();
+    if num_observations < threshold || num_observations == 0 {
+        return None;
+    }
+
+    let densities: Vec<f64> = edges
+        .iter()
+        .flat_map(|&edge_index| traffic_model_data.observed_densities[edge_index].iter().copied())
+        .collect();
+    let speeds: Vec<f64> = edges
+        .iter()
+        .flat_map(|&edge_index| traffic_model_data.observed_speeds[edge_index].iter().copied())
+        .collect();
+    let free_flow_speed = edges.iter().map(|&edge_index| road_classes.free_flow_speeds[edge_index]).sum::<f64>() / edges.len() as f64;
+
+    let mut model = TrafficModelData::new_model(&traffic_model_data.traffic_model_type, free_flow_speed);
+    model.calibrate(&speeds, &densities);
+    Some(model)
+}
+
+/// goodness of fit of a traffic model on the observations of an edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelFit {
+    pub observations: usize,
+    /// root mean squared error of the speeds in km/h
+    pub rmse: f64,
+    /// None if all observed speeds are the same
+    pub r_squared: Option<f64>,
+}
+
+impl ModelFit {
+    /// `observed_speeds` in km/h, None if there are no observations
+    pub fn new(model: &dyn TrafficModel, observed_speeds: &[f64], observed_densities: &[f64]) -> Option<Self> {
+        if observed_speeds.is_empty() {
+            return None;
+        }
+
+        let n = observed_speeds.len() as f64;
+        let mean_speed = observed_speeds.iter().sum::<f64>() / n;
+        let sse: f64 = observed_speeds
+            .iter()
+            .zip(observed_densities.iter())
+            .map(|(speed, &density)| (model.get_speed(density) - speed).powi(2))
+            .sum();
+        let sst: f64 = observed_speeds.iter().map(|speed| (speed - mean_speed).powi(2)).sum();
+
+        Some(Self {
+            observations: observed_speeds.len(),
+            rmse: (sse / n).sqrt(),
+            r_squared: (sst > 0.0).then(|| 1.0 - sse / sst),
+        })
+    }
+}
+
+/// writes the goodness of fit of the traffic model of each edge into `dir`, next to the traffic model parameters,
+/// and, if `export_scatter` is set, all observations of the edges with the speeds of their traffic models
+pub fn write_calibration_diagnostics(
+    dir: &Path,
+    traffic_model_data: &TrafficModelData,
+    edge_ids: &Vec<String>,
+    road_classes: &RoadClasses,
+    sources: &Vec<CalibrationSource>,
+    export_scatter: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = BufWriter::new(File::create(dir.join(FILE_EDGE_TRAFFIC_MODEL_FIT))?);
+    writeln!(file, "edge_id,priority,speed,lanes,source,observations,rmse,r_squared")?;
+
+    for (edge_index, edge_id) in edge_ids.iter().enumerate() {
+        let class = road_classes.class_of_edge[edge_index];
+        let fit = ModelFit::new(
+            traffic_model_data.traffic_models[edge_index].as_ref(),
+            &traffic_model_data.observed_speeds[edge_index],
+            &traffic_model_data.observed_densities[edge_index],
+        );
+
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{}",
+            edge_id,
+            class.priority,
+            class.speed,
+            class.lanes,
+            sources[edge_index].as_str(),
+            fit.map_or(0, |fit| fit.observations),
+            fit.map(|fit| format!("{:.6}", fit.rmse)).unwrap_or_default(),
+            fit.and_then(|fit| fit.r_squared)
+                .map(|r_squared| format!("{:.6}", r_squared))
+                .unwrap_or_default()
+        )?;
+    }
+
+    if !export_scatter {
+        return Ok(());
+    }
+
+    let mut file = BufWriter::new(File::create(dir.join(FILE_EDGE_SPEED_DENSITY_SCATTER))?);
+    writeln!(file, "edge_id,source,lane_density,speed,model_speed")?;
+
+    for (edge_index, edge_id) in edge_ids.iter().enumerate() {
+        let model = &traffic_model_data.traffic_models[edge_index];
+        for (&density, &speed) in traffic_model_data.observed_densities[edge_index]
+            .iter()
+            .zip(traffic_model_data.observed_speeds[edge_index].iter())
+        {
+            writeln!(
+                file,
+                "{},{},{:.6},{:.6},{:.6}",
+                edge_id,
+                sources[edge_index].as_str(),
+                density,
+                speed,
+                model.get_speed(density)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traffic_model::TrafficModelType;
+    use conversion::sumo::meandata::{Edge, Interval};
+
+    fn get_meandata(observations: &Vec<(&str, f64, f64)>) -> MeandataDocumentRoot {
+        let mut meandata = MeandataDocumentRoot::empty();
+        meandata.intervals = observations
+            .iter()
+            .enumerate()
+            .map(|(i, &(edge_id, density, speed))| {
+                Interval::create(
+                    i.to_string(),
+                    60.0 * i as f64,
+                    60.0 * (i + 1) as f64,
+                    vec![Edge {
+                        id: edge_id.to_string(),
+                        lane_density: Some(density),
+                        speed: Some(speed),
+                        ..Default::default()
+                    }],
+                )
+            })
+            .collect();
+        meandata
+    }
+
+    #[test]
+    fn test_sparse_edges_inherit_class_parameters() {
+        // edges 0 and 1 share a road class, edge 2 is the only edge of its class
+        let edge_ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let road_classes = RoadClasses::new(&vec![2, 2, 3], &vec![13.9, 13.9, 27.8], &vec![1, 1, 2]);
+        let mut traffic_model_data = TrafficModelData::init(&road_classes.free_flow_speeds, TrafficModelType::ModifiedLee);
+
+        let mut observations = Vec::new();
+        for i in 0..4 {
+            observations.push(("a", 10.0 * i as f64, 50.0 - 5.0 * i as f64));
+        }
+        observations.push(("b", 15.0, 40.0));
+        observations.push(("c", 20.0, 90.0));
+        let mut meandata = get_meandata(&observations);
+
+        let sources = calibrate_traffic_models(&mut traffic_model_data, &mut meandata, &edge_ids, &road_classes, 4);
+
+        assert_eq!(sources, vec![CalibrationSource::Edge, CalibrationSource::RoadClass, CalibrationSource::Edge]);
+
+        // edge b has the parameters of its class but its own free flow speed
+        let params_b = traffic_model_data.traffic_models[1].get_params_as_vec();
+        assert!((params_b[0] - road_classes.free_flow_speeds[1]).abs() < 1e-9);
+        assert_eq!(traffic_model_data.observed_densities[1], vec![15.0]);
+    }
+
+    #[test]
+    fn test_model_fit() {
+        let model = TrafficModelData::new_model(&TrafficModelType::ModifiedLee, 50.0);
+        let densities = vec![0.0, 20.0, 40.0];
+        let speeds: Vec<f64> = densities.iter().map(|&density| model.get_speed(density)).collect();
+
+        let fit = ModelFit::new(model.as_ref(), &speeds, &densities).unwrap();
+        assert_eq!(fit.observations, 3);
+        assert!(fit.rmse < 1e-9);
+        assert!((fit.r_squared.unwrap() - 1.0).abs() < 1e-9);
+
+        let shifted_speeds: Vec<f64> = speeds.iter().map(|speed| speed + 2.0).collect();
+        let fit = ModelFit::new(model.as_ref(), &shifted_speeds, &densities).unwrap();
+        assert!((fit.rmse - 2.0).abs() < 1e-9);
+
+        assert!(ModelFit::new(model.as_ref(), &[], &[]).is_none());
     }
 }
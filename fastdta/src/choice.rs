@@ -1,6 +1,10 @@
+use serde::Deserialize;
+
 pub const LOGIT: &str = "logit";
 pub const GAWRON: &str = "gawron";
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
 pub enum ChoiceAlgorithm {
     Gawron { a: f64, beta: f64 },
     Logit { beta: f64, gamma: f64, theta: f64 },
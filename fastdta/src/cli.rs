@@ -1,8 +1,23 @@
-use std::env;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 pub use clap::Parser;
-
-use crate::{choice, traffic_model::TrafficModelType};
+use conversion::sumo::sumo_to_new_graph_weights::WeightAttribute;
+
+use crate::{
+    choice,
+    departure_choice::DepartureChoice,
+    meso_simulation::MesoNetwork,
+    od_estimation::OdEstimationConfig,
+    router_options::{RouterOptions, parse_sumo_bool},
+    sampled_assignment::SampleRouter,
+    sampler::{AdaptiveSampling, Stratification},
+    sumo_runner::Simulator,
+    traffic_model::TrafficModelType,
+    user_classes::UserClasses,
+};
 
 /// Command-line arguments for preprocessing
 #[derive(Parser, Debug)]
@@ -399,6 +414,28 @@ pub struct RouterArgs {
     /// if set skips writing the alternative routes file in the sumo format
     #[arg(long = "no-write-sumo-alternatives", default_value_t = String::from("False"))]
     pub no_write_sumo_alternatives: String,
+
+    /// enables departure time choice, departures may be shifted by up to this many seconds before or after the original departure
+    #[arg(long = "departure-window")]
+    pub departure_window: Option<f64>,
+
+    /// the distance in seconds between two candidate departures of the departure time choice
+    #[arg(long = "departure-step", default_value = "300")]
+    pub departure_step: f64,
+
+    /// penalty per second of arriving before the preferred arrival time, relative to one second of travel time
+    #[arg(long = "schedule-delay-early", default_value = "0.5")]
+    pub schedule_delay_early: f64,
+
+    /// penalty per second of arriving after the preferred arrival time, relative to one second of travel time
+    #[arg(long = "schedule-delay-late", default_value = "2.0")]
+    pub schedule_delay_late: f64,
+
+    /// a json file with a list of user classes, each with a name, the vTypes of its trips, a value of time and optionally
+    /// a distance cost, a toll factor, its own route choice and whether it is routed on its generalized cost,
+    /// e.g. `[{"name": "truck", "vtypes": ["truck"], "value_of_time": 40.0, "distance_cost": 0.5, "choice": {"method": "logit", "beta": 0.15, "gamma": 1.0, "theta": 0.01}}]`
+    #[arg(long = "user-classes")]
+    pub user_classes: Option<String>,
 }
 
 impl RouterArgs {
@@ -419,8 +456,80 @@ impl RouterArgs {
         }
     }
 
-    pub fn get_write_sumo_alternatives(&self) -> bool {
-        self.no_write_sumo_alternatives == "false" || self.no_write_sumo_alternatives == "0" || self.no_write_sumo_alternatives == "False"
+    pub fn get_write_sumo_alternatives(&self) -> Result<bool, String> {
+        parse_sumo_bool("no-write-sumo-alternatives", &self.no_write_sumo_alternatives).map(|no_write| !no_write)
+    }
+
+    /// collects the duarouter options honored by the router pipeline and warns about the ones which are accepted but ignored
+    pub fn get_router_options(&self) -> Result<RouterOptions, String> {
+        self.warn_about_unsupported_options()?;
+
+        let weight_attribute = match &self.weight_attribute {
+            Some(name) => WeightAttribute::from_sumo_name(name).unwrap_or_else(|| {
+                println!("Warning: --weight-attribute {} is not supported, using overlapTraveltime instead", name);
+                WeightAttribute::default()
+            }),
+            None => WeightAttribute::default(),
+        };
+
+        // duarouter takes a comma separated list of files
+        let weight_files = match &self.weight_files {
+            Some(files) => files.split(',').filter(|file| !file.is_empty()).map(PathBuf::from).collect(),
+            None => vec![],
+        };
+
+        Ok(RouterOptions {
+            remove_loops: parse_sumo_bool("remove-loops", &self.remove_loops)?,
+            repair: parse_sumo_bool("repair", &self.repair)?,
+            weight_files,
+            weight_attribute,
+            exit_times: parse_sumo_bool("exit-times", &self.exit_times)?,
+            route_length: parse_sumo_bool("route-length", &self.route_length)?,
+            keep_all_routes: parse_sumo_bool("keep-all-routes", &self.keep_all_routes)?,
+            skip_new_routes: parse_sumo_bool("skip-new-routes", &self.skip_new_routes)?,
+            user_classes: self
+                .user_classes
+                .as_ref()
+                .map(|file| UserClasses::load(Path::new(file), Path::new(&self.input_dir))),
+        })
+    }
+
+    pub fn get_departure_choice(&self) -> Option<DepartureChoice> {
+        let window = self.departure_window?;
+        println!(
+            "Using departure time choice within +-{}s in steps of {}s, early penalty={}, late penalty={}",
+            window, self.departure_step, self.schedule_delay_early, self.schedule_delay_late
+        );
+        Some(DepartureChoice::new(
+            window,
+            self.departure_step,
+            self.schedule_delay_early,
+            self.schedule_delay_late,
+        ))
+    }
+
+    fn warn_about_unsupported_options(&self) -> Result<(), String> {
+        let unsupported_options = [
+            ("repair.from", parse_sumo_bool("repair.from", &self.repair_from)?),
+            ("repair.to", parse_sumo_bool("repair.to", &self.repair_to)?),
+            ("randomize-flows", parse_sumo_bool("randomize-flows", &self.randomize_flows)?),
+            ("scale", self.scale.is_some_and(|scale| scale != 1.0)),
+            ("weight-period", self.weight_period.is_some()),
+            ("lane-weight-files", self.lane_weight_files.is_some()),
+            ("weights.interpolate", parse_sumo_bool("weights.interpolate", &self.weights_interpolate)?),
+            ("weights.minor-penalty", self.weights_minor_penalty.is_some()),
+            ("weights.priority-factor", self.weights_priority_factor.is_some()),
+            ("weights.random-factor", self.weights_random_factor.is_some()),
+            ("weights.tls-penalty", self.weights_tls_penalty.is_some()),
+            ("weights.turnaround-penalty", self.weights_turnaround_penalty.is_some()),
+        ];
+
+        for (option, is_set) in unsupported_options {
+            if is_set {
+                println!("Warning: --{} is not supported by this router and will be ignored", option);
+            }
+        }
+        Ok(())
     }
 }
 
@@ -435,11 +544,21 @@ pub struct FastDtaArgs {
     ///sample sizes and number of sample per iteration
     /// e.g. --samples "0.1 0.2 0.3 0.4" will sample 10% in the first iteration, 20% in the second, 30% in the third, and 40% in the fourth,
     /// such that each sample does not intersect with previous samples
-    /// Samples are uniformly distributed over all trips
+    /// Samples are uniformly distributed over all trips, see --sampling for stratified samples
     /// Format: "<f64>[ <f64>]*" - space-separated floating point values
     #[arg(long = "samples")]
     pub samples: Option<String>,
 
+    /// how trips are grouped before they are split into the samples, each sample contains the same share of every group:
+    /// "uniform", "departure:<interval in seconds>", "origin-cell:<depth in the separator tree>" or "trip-length:<number of classes>"
+    #[arg(long = "sampling", default_value = "uniform")]
+    pub sampling: String,
+
+    /// size the samples adaptively such that each one changes the estimated travel times by about the given relative amount,
+    /// the first value of --samples is then used as the relative size of the first sample
+    #[arg(long = "adaptive-sampling")]
+    pub adaptive_sampling: Option<f64>,
+
     /// sets the VDF
     #[arg(long = "traffic-model", default_value = "modified-lee")]
     pub traffic_model: String,
@@ -449,9 +568,18 @@ pub struct FastDtaArgs {
     #[arg(long = "calibration-data-points-threshold", default_value = "1000")]
     pub calibration_data_points_threshold: usize,
 
+    /// write the observed lane densities and speeds of all edges with the speeds of their calibrated traffic models
+    /// to a csv file next to the traffic model parameters
+    #[arg(long = "export-speed-density-scatter", default_value_t = false)]
+    pub export_speed_density_scatter: bool,
+
     /// use "keep_route" during sampling (true) or after sampling (false)
     #[arg(long = "keep-route-in-sampling", default_value_t = true)]
     pub keep_route_in_sampling: bool,
+
+    /// the algorithm used to route the trips of each sample: "catchup" or "dijkstra" (time-dependent Dijkstra)
+    #[arg(long = "sample-router", default_value = "catchup")]
+    pub sample_router: String,
 }
 
 impl FastDtaArgs {
@@ -465,6 +593,10 @@ impl FastDtaArgs {
         }
     }
 
+    pub fn get_sample_router(&self) -> SampleRouter {
+        get_sample_router(&self.sample_router)
+    }
+
     pub fn get_samples(&self) -> Vec<f64> {
         let smpls = match &self.samples {
             Some(s) => {
@@ -479,6 +611,14 @@ impl FastDtaArgs {
         println!("Using samples: {:?}", smpls);
         smpls
     }
+
+    pub fn get_stratification(&self) -> Stratification {
+        parse_stratification(&self.sampling)
+    }
+
+    pub fn get_adaptive_sampling(&self, samples: &Vec<f64>) -> Option<AdaptiveSampling> {
+        get_adaptive_sampling(self.adaptive_sampling, samples)
+    }
 }
 
 /// Command-line arguments for SUMO-based sample routing
@@ -491,11 +631,21 @@ pub struct SumoSampleRouterArgs {
     /// sample sizes and number of sample per iteration
     /// e.g. --samples "0.1 0.2 0.3 0.4" will sample 10% in the first iteration, 20% in the second, 30% in the third, and 40% in the fourth,
     /// such that each sample does not intersect with previous samples
-    /// Samples are uniformly distributed over all trips
+    /// Samples are uniformly distributed over all trips, see --sampling for stratified samples
     /// Format: "<f64>[ <f64>]*" - space-separated floating point values
     #[arg(long = "samples")]
     pub samples: Option<String>,
 
+    /// how trips are grouped before they are split into the samples, each sample contains the same share of every group:
+    /// "uniform", "departure:<interval in seconds>", "origin-cell:<depth in the separator tree>" or "trip-length:<number of classes>"
+    #[arg(long = "sampling", default_value = "uniform")]
+    pub sampling: String,
+
+    /// size the samples adaptively such that each one changes the estimated travel times by about the given relative amount,
+    /// the first value of --samples is then used as the relative size of the first sample
+    #[arg(long = "adaptive-sampling")]
+    pub adaptive_sampling: Option<f64>,
+
     /// aggregation interval for SUMO edgeData output (in seconds)
     #[arg(long = "aggregation", default_value = "60")]
     pub aggregation: u32,
@@ -503,9 +653,26 @@ pub struct SumoSampleRouterArgs {
     /// use "keep_route" during sampling (true) or after sampling (false)
     #[arg(long = "keep-route-in-sampling", default_value_t = false)]
     pub keep_route_in_sampling: bool,
+
+    /// the simulation used to evaluate each sample:
+    /// "sumo" runs `sumo --mesosim`, "meso" uses the in-process mesoscopic model
+    #[arg(long = "simulator", default_value = "sumo")]
+    pub simulator: String,
+
+    /// the algorithm used to route the trips of each sample: "catchup" or "dijkstra" (time-dependent Dijkstra)
+    #[arg(long = "sample-router", default_value = "catchup")]
+    pub sample_router: String,
 }
 
 impl SumoSampleRouterArgs {
+    pub fn get_simulator(&self) -> Simulator {
+        get_simulator(&self.simulator, Path::new(&self.router_args.input_dir))
+    }
+
+    pub fn get_sample_router(&self) -> SampleRouter {
+        get_sample_router(&self.sample_router)
+    }
+
     pub fn get_samples(&self) -> Vec<f64> {
         let smpls = match &self.samples {
             Some(s) => {
@@ -520,4 +687,153 @@ impl SumoSampleRouterArgs {
         println!("Using samples: {:?}", smpls);
         smpls
     }
+
+    pub fn get_stratification(&self) -> Stratification {
+        parse_stratification(&self.sampling)
+    }
+
+    pub fn get_adaptive_sampling(&self, samples: &Vec<f64>) -> Option<AdaptiveSampling> {
+        get_adaptive_sampling(self.adaptive_sampling, samples)
+    }
+}
+
+/// Command-line arguments for estimating the demand per OD pair and time slice from edge counts
+#[derive(Parser, Debug)]
+#[command(version, about = "OD matrix estimation CLI options", long_about = None)]
+pub struct OdEstimationArgs {
+    /// the preprocessed directory of the seed trips containing the cch folder and the queries
+    #[arg(long = "input-dir", default_value_t = String::from(env::current_dir().unwrap().to_str().unwrap()))]
+    pub input_dir: String,
+
+    /// the seed trips file the input directory was preprocessed from
+    #[arg(long = "trips-file")]
+    pub trips_file: String,
+
+    /// an edgeData file with the observed counts as `entered` (or `left`) per edge and interval
+    #[arg(long = "counts-file")]
+    pub counts_file: String,
+
+    /// the trips file to write the estimated demand to
+    #[arg(long = "output-file")]
+    pub output_file: String,
+
+    /// the SUMO network, required if SUMO is used as simulator
+    #[arg(long = "net-file", short = 'n')]
+    pub net_file: Option<String>,
+
+    /// length of the departure time slices in seconds, the demand of each OD pair is adjusted per slice
+    #[arg(long = "slice-length", default_value = "900")]
+    pub slice_length: f64,
+
+    /// the number of SPSA iterations, each one routes the demand once and simulates it three times
+    #[arg(long = "iterations", default_value = "20")]
+    pub iterations: u32,
+
+    /// perturbation of the demand factors in the first iteration
+    #[arg(long = "perturbation", default_value = "0.1")]
+    pub perturbation: f64,
+
+    /// the largest change of a demand factor in the first iteration
+    #[arg(long = "step-size", default_value = "0.2")]
+    pub step_size: f64,
+
+    /// upper bound for the factor by which the demand of an OD pair in a time slice may be scaled
+    #[arg(long = "max-factor", default_value = "5.0")]
+    pub max_factor: f64,
+
+    #[arg(long = "seed", default_value = "42")]
+    pub seed: u64,
+
+    #[arg(long = "begin", default_value = "0")]
+    pub begin: f64,
+
+    #[arg(long = "end", default_value = "86400")]
+    pub end: f64,
+
+    /// the simulation used to evaluate the demand:
+    /// "sumo" runs `sumo --mesosim`, "meso" uses the in-process mesoscopic model
+    #[arg(long = "simulator", default_value = "sumo")]
+    pub simulator: String,
+
+    #[arg(long = "routing-threads", default_value_t = std::thread::available_parallelism().unwrap().get() as i32)]
+    pub routing_threads: i32,
+}
+
+impl OdEstimationArgs {
+    pub fn get_simulator(&self) -> Simulator {
+        get_simulator(&self.simulator, Path::new(&self.input_dir))
+    }
+
+    pub fn get_config(&self) -> OdEstimationConfig {
+        let config = OdEstimationConfig {
+            slice_length: self.slice_length,
+            iterations: self.iterations,
+            perturbation: self.perturbation,
+            step_size: self.step_size,
+            max_factor: self.max_factor,
+            seed: self.seed,
+        };
+        println!("Using OD matrix estimation with {:?}", config);
+        config
+    }
+}
+
+fn get_simulator(simulator: &str, input_dir: &Path) -> Simulator {
+    match simulator {
+        "sumo" => {
+            println!("Using SUMO as simulator");
+            Simulator::Sumo
+        }
+        "meso" => {
+            println!("Using the in-process mesoscopic model as simulator");
+            Simulator::Meso(MesoNetwork::load(input_dir, TrafficModelType::ModifiedLee))
+        }
+        _ => panic!("Unknown simulator: {}", simulator),
+    }
+}
+
+fn get_sample_router(sample_router: &str) -> SampleRouter {
+    match sample_router {
+        "catchup" => {
+            println!("Routing the samples with CATCHUp");
+            SampleRouter::Catchup
+        }
+        "dijkstra" => {
+            println!("Routing the samples with time-dependent Dijkstra");
+            SampleRouter::Dijkstra
+        }
+        _ => panic!("Unknown sample router: {}", sample_router),
+    }
+}
+
+fn parse_stratification(sampling: &str) -> Stratification {
+    let (strategy, parameter) = sampling.split_once(':').unwrap_or((sampling, ""));
+    let parse_parameter = |default: &str| {
+        let value = if parameter.is_empty() { default } else { parameter };
+        value
+            .parse::<f64>()
+            .expect(&format!("Invalid parameter for --sampling {}: {}", strategy, value))
+    };
+
+    let stratification = match strategy {
+        "uniform" => Stratification::None,
+        "departure" => Stratification::Departure(parse_parameter("900")),
+        "origin-cell" => Stratification::OriginCell(parse_parameter("4") as usize),
+        "trip-length" => Stratification::TripLength(parse_parameter("4") as usize),
+        _ => panic!("Unknown sampling strategy: {}", sampling),
+    };
+
+    println!("Using stratification: {:?}", stratification);
+    stratification
+}
+
+fn get_adaptive_sampling(target_change: Option<f64>, samples: &Vec<f64>) -> Option<AdaptiveSampling> {
+    let target_change = target_change?;
+    let initial_fraction = samples[0] / samples.iter().sum::<f64>();
+
+    println!(
+        "Using adaptive sampling with a target travel time change of {} and an initial sample of {}",
+        target_change, initial_fraction
+    );
+    Some(AdaptiveSampling::new(initial_fraction, target_change))
 }
@@ -0,0 +1,397 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use conversion::{
+    FILE_CONVERGENCE_REPORT, FILE_CONVERGENCE_SUMMARY, SerializedTimestamp,
+    sumo::{
+        FileReader, SumoTravelTime,
+        routes::{RoutesDocumentRoot, Vehicle},
+        routes_reader::SumoRoutesReader,
+        sumo_to_new_graph_weights::extract_travel_times_from_iteration_directory,
+    },
+};
+use rayon::prelude::*;
+use rust_road_router::{
+    datastr::graph::floating_time_dependent::{TDGraph, Timestamp},
+    io::Reconstruct,
+};
+use serde::Serialize;
+
+use crate::{alternative_paths::AlternativePathsForDTA, customize::customize, preprocess::get_cch, query::get_paths_with_cch, relative_gap::get_excess_cost};
+
+/// length of the departure intervals the relative gap is split by, in seconds
+pub const DEFAULT_DEPARTURE_INTERVAL: f64 = 3600.0;
+
+/// number of trips with the highest excess cost listed in the report
+pub const NUM_WORST_TRIPS: usize = 20;
+
+/// relative gap of the trips departing within `[begin, end)`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DepartureIntervalGap {
+    pub begin: f64,
+    pub end: f64,
+    pub trips: usize,
+    pub relative_gap: f64,
+    pub average_excess_cost: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TripExcessCost {
+    pub query_id: String,
+    pub departure: f64,
+    pub best_travel_time: f64,
+    pub experienced_travel_time: f64,
+    pub excess_cost: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DistributionStatistics {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DistributionStatistics {
+    pub fn from_values(values: impl Iterator<Item = f64>) -> Option<Self> {
+        let (count, sum, min, max) = values.fold((0, 0.0, f64::INFINITY, f64::NEG_INFINITY), |(count, sum, min, max), value| {
+            (count + 1, sum + value, min.min(value), max.max(value))
+        });
+
+        (count > 0).then(|| Self {
+            mean: sum / count as f64,
+            min,
+            max,
+        })
+    }
+}
+
+/// convergence diagnostics of one DTA iteration, comparing the travel times the trips experienced on their chosen routes
+/// with the best travel times on the same travel time functions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConvergenceReport {
+    pub iteration: u32,
+    pub relative_gap: f64,
+    /// mean excess cost over all trips in seconds
+    pub average_excess_cost: f64,
+    pub relative_gap_by_departure: Vec<DepartureIntervalGap>,
+    /// share of trips whose chosen route differs from the one of the previous iteration
+    pub route_switch_share: Option<f64>,
+    pub alternatives_per_trip: Option<DistributionStatistics>,
+    /// the mean overlap of the alternatives of each trip with more than one alternative, see `AlternativePaths::get_mean_overlap`
+    pub path_overlap: Option<DistributionStatistics>,
+    pub worst_trips: Vec<TripExcessCost>,
+}
+
+impl ConvergenceReport {
+    /// `departures` in milliseconds, `departure_interval` in seconds.
+    /// The route statistics are only reported if the alternatives are given, switches need the alternatives of both iterations
+    pub fn new(
+        iteration: u32,
+        best_tts: &Vec<f64>,
+        experienced_tts: &Vec<f64>,
+        departures: &Vec<SerializedTimestamp>,
+        query_ids: &Vec<String>,
+        departure_interval: f64,
+        alternatives: Option<&AlternativePathsForDTA>,
+        previous_alternatives: Option<&AlternativePathsForDTA>,
+    ) -> Self {
+        assert_eq!(best_tts.len(), experienced_tts.len());
+        assert_eq!(best_tts.len(), departures.len());
+
+        let excess_costs: Vec<f64> = best_tts
+            .par_iter()
+            .zip(experienced_tts.par_iter())
+            .map(|(&best_tt, &experienced_tt)| get_excess_cost(best_tt, experienced_tt))
+            .collect();
+        let total_excess_cost: f64 = excess_costs.iter().sum();
+        let total_best_tt: f64 = best_tts.iter().sum();
+
+        Self {
+            iteration,
+            relative_gap: total_excess_cost / total_best_tt,
+            average_excess_cost: if excess_costs.is_empty() {
+                0.0
+            } else {
+                total_excess_cost / excess_costs.len() as f64
+            },
+            relative_gap_by_departure: get_relative_gap_by_departure(best_tts, &excess_costs, departures, departure_interval),
+            route_switch_share: alternatives
+                .zip(previous_alternatives)
+                .map(|(current, previous)| get_route_switch_share(current, previous)),
+            alternatives_per_trip: alternatives.and_then(|alternatives| {
+                DistributionStatistics::from_values(alternatives.alternatives_in_query.iter().map(|alternatives| alternatives.paths.len() as f64))
+            }),
+            path_overlap: alternatives.and_then(|alternatives| {
+                DistributionStatistics::from_values(
+                    alternatives
+                        .alternatives_in_query
+                        .iter()
+                        .filter_map(|alternatives| alternatives.get_mean_overlap()),
+                )
+            }),
+            worst_trips: get_worst_trips(best_tts, experienced_tts, &excess_costs, departures, query_ids),
+        }
+    }
+
+    /// writes the report as json into the iteration directory and appends its summary to the csv file in `dta_dir`
+    pub fn write(&self, dta_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let iteration_dir = dta_dir.join(format!("{:0>3}", self.iteration));
+        std::fs::create_dir_all(&iteration_dir)?;
+        serde_json::to_writer_pretty(BufWriter::new(File::create(iteration_dir.join(FILE_CONVERGENCE_REPORT))?), self)?;
+
+        let summary_file = dta_dir.join(FILE_CONVERGENCE_SUMMARY);
+        let write_header = !summary_file.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(summary_file)?;
+        if write_header {
+            writeln!(
+                file,
+                "iteration,relative_gap,average_excess_cost,route_switch_share,mean_alternatives,max_alternatives,mean_path_overlap,worst_excess_cost"
+            )?;
+        }
+
+        let optional = |value: Option<f64>| value.map(|value| format!("{:.6}", value)).unwrap_or_default();
+        writeln!(
+            file,
+            "{},{:.9},{:.6},{},{},{},{},{}",
+            self.iteration,
+            self.relative_gap,
+            self.average_excess_cost,
+            optional(self.route_switch_share),
+            optional(self.alternatives_per_trip.map(|statistics| statistics.mean)),
+            optional(self.alternatives_per_trip.map(|statistics| statistics.max)),
+            optional(self.path_overlap.map(|statistics| statistics.mean)),
+            optional(self.worst_trips.first().map(|trip| trip.excess_cost)),
+        )?;
+
+        Ok(())
+    }
+}
+
+fn get_relative_gap_by_departure(
+    best_tts: &Vec<f64>,
+    excess_costs: &Vec<f64>,
+    departures: &Vec<SerializedTimestamp>,
+    departure_interval: f64,
+) -> Vec<DepartureIntervalGap> {
+    // interval -> (trips, total excess cost, total best travel time)
+    let mut intervals: BTreeMap<u32, (usize, f64, f64)> = BTreeMap::new();
+    for i in 0..best_tts.len() {
+        let interval = (departures[i] as f64 / 1000.0 / departure_interval).floor() as u32;
+        let entry = intervals.entry(interval).or_insert((0, 0.0, 0.0));
+        entry.0 += 1;
+        entry.1 += excess_costs[i];
+        entry.2 += best_tts[i];
+    }
+
+    intervals
+        .into_iter()
+        .map(|(interval, (trips, excess_cost, best_tt))| DepartureIntervalGap {
+            begin: interval as f64 * departure_interval,
+            end: (interval + 1) as f64 * departure_interval,
+            trips,
+            relative_gap: if best_tt > 0.0 { excess_cost / best_tt } else { 0.0 },
+            average_excess_cost: excess_cost / trips as f64,
+        })
+        .collect()
+}
+
+fn get_route_switch_share(alternatives: &AlternativePathsForDTA, previous_alternatives: &AlternativePathsForDTA) -> f64 {
+    let chosen_paths = alternatives.get_chosen_paths();
+    let previous_chosen_paths = previous_alternatives.get_chosen_paths();
+    assert_eq!(chosen_paths.len(), previous_chosen_paths.len());
+
+    if chosen_paths.is_empty() {
+        return 0.0;
+    }
+
+    let switched = chosen_paths
+        .iter()
+        .zip(previous_chosen_paths.iter())
+        .filter(|(path, previous)| path != previous)
+        .count();
+    switched as f64 / chosen_paths.len() as f64
+}
+
+fn get_worst_trips(
+    best_tts: &Vec<f64>,
+    experienced_tts: &Vec<f64>,
+    excess_costs: &Vec<f64>,
+    departures: &Vec<SerializedTimestamp>,
+    query_ids: &Vec<String>,
+) -> Vec<TripExcessCost> {
+    let mut trips: Vec<usize> = (0..excess_costs.len()).filter(|&i| excess_costs[i] > 0.0).collect();
+    trips.sort_by(|&a, &b| excess_costs[b].total_cmp(&excess_costs[a]));
+
+    trips
+        .into_iter()
+        .take(NUM_WORST_TRIPS)
+        .map(|i| TripExcessCost {
+            query_id: query_ids[i].clone(),
+            departure: departures[i] as f64 / 1000.0,
+            best_travel_time: best_tts[i],
+            experienced_travel_time: experienced_tts[i],
+            excess_cost: excess_costs[i],
+        })
+        .collect()
+}
+
+/// best and experienced travel times of one iteration of a SUMO based DTA.
+/// The travel times of the iteration are written into `network_dir`, a preprocessed directory of the trips,
+/// the best travel times are the ones of shortest paths on them and the experienced ones those along the routes in `routes_file`.
+/// Returns the best travel times, the experienced travel times and the departures
+pub fn get_travel_times_of_sumo_iteration(
+    network_dir: &Path,
+    dta_iteration_dir: &Path,
+    routes_file: &Path,
+    query_ids: &Vec<String>,
+    edge_ids: &Vec<String>,
+    routing_threads: usize,
+) -> (Vec<f64>, Vec<f64>, Vec<SerializedTimestamp>) {
+    let edge_id_to_index: HashMap<&String, usize> = edge_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+
+    extract_travel_times_from_iteration_directory(dta_iteration_dir, network_dir, edge_ids);
+    let graph = TDGraph::reconstruct_from(&network_dir).unwrap();
+    let cch = get_cch(network_dir, &graph);
+    let customized_graph = customize(&cch, &graph);
+    let (best_paths, _best_travel_times, departures) = get_paths_with_cch(&cch, &customized_graph, network_dir, &graph, routing_threads);
+
+    println!("Reading routes from file {}", routes_file.display());
+    let routes_document_root = SumoRoutesReader::read(routes_file).unwrap();
+
+    let experienced_tts = get_experienced_travel_times_from_routes(&routes_document_root, query_ids, &graph, &edge_id_to_index);
+
+    let best_tts: Vec<f64> = best_paths
+        .par_iter()
+        .enumerate()
+        .map(|(i, path)| graph.get_travel_time_along_path(Timestamp::from_millis(departures[i]), path).into())
+        .collect();
+
+    (best_tts, experienced_tts, departures)
+}
+
+/// the travel times along the routes of the vehicles in a SUMO routes file, in the order of `query_ids`
+pub fn get_experienced_travel_times_from_routes(
+    routes_document_root: &RoutesDocumentRoot,
+    query_ids: &Vec<String>,
+    graph: &TDGraph,
+    edge_id_to_index: &HashMap<&String, usize>,
+) -> Vec<SumoTravelTime> {
+    let vehicle_id_to_vehicle: HashMap<&String, &Vehicle> = routes_document_root.vehicles.iter().map(|v| (&v.id, v)).collect();
+
+    query_ids
+        .par_iter()
+        .map(|id| {
+            let Some(v) = vehicle_id_to_vehicle.get(id) else {
+                panic!("No route found for query id {}", id);
+            };
+            let Some(route) = &v.route else {
+                panic!("No route found for vehicle id {}", id);
+            };
+
+            let experienced_path: Vec<u32> = route
+                .edges
+                .split_ascii_whitespace()
+                .map(|edge_id| {
+                    if let Some(&index) = edge_id_to_index.get(&edge_id.to_string()) {
+                        index as u32
+                    } else {
+                        panic!("Edge id {} not found in edge_id_to_index map", edge_id);
+                    }
+                })
+                .collect();
+
+            graph.get_travel_time_along_path(Timestamp::new(v.depart), &experienced_path).into()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alternative_paths::{AlternativePath, AlternativePaths};
+
+    use super::*;
+
+    fn alternatives(paths: Vec<Vec<u32>>, choice: usize) -> AlternativePaths {
+        let n = paths.len();
+        AlternativePaths {
+            paths: paths.into_iter().map(|edges| AlternativePath { edges }).collect(),
+            costs: vec![0.0; n],
+            probabilities: vec![1.0 / n as f64; n],
+            choice,
+        }
+    }
+
+    #[test]
+    fn test_convergence_report() {
+        let best_tts = vec![100.0, 200.0, 100.0, 400.0];
+        let experienced_tts = vec![100.5, 300.0, 150.0, 400.0];
+        let departures = vec![0, 1_000_000, 4_000_000, 4_500_000];
+        let query_ids = vec!["a", "b", "c", "d"].into_iter().map(String::from).collect();
+
+        let previous = AlternativePathsForDTA {
+            alternatives_in_query: vec![
+                alternatives(vec![vec![1]], 0),
+                alternatives(vec![vec![1, 2], vec![1, 3]], 0),
+                alternatives(vec![vec![4]], 0),
+                alternatives(vec![vec![5]], 0),
+            ],
+        };
+        let current = AlternativePathsForDTA {
+            alternatives_in_query: vec![
+                alternatives(vec![vec![1]], 0),
+                alternatives(vec![vec![1, 2], vec![1, 3]], 1),
+                alternatives(vec![vec![4], vec![6]], 1),
+                alternatives(vec![vec![5]], 0),
+            ],
+        };
+
+        let report = ConvergenceReport::new(
+            3,
+            &best_tts,
+            &experienced_tts,
+            &departures,
+            &query_ids,
+            DEFAULT_DEPARTURE_INTERVAL,
+            Some(&current),
+            Some(&previous),
+        );
+
+        // the difference of trip a is within the epsilon
+        assert!((report.relative_gap - 150.0 / 800.0).abs() < 1e-10);
+        assert!((report.average_excess_cost - 37.5).abs() < 1e-10);
+
+        assert_eq!(report.relative_gap_by_departure.len(), 2);
+        assert_eq!(report.relative_gap_by_departure[0].trips, 2);
+        assert!((report.relative_gap_by_departure[0].relative_gap - 100.0 / 300.0).abs() < 1e-10);
+        assert_eq!(report.relative_gap_by_departure[1].begin, 3600.0);
+        assert!((report.relative_gap_by_departure[1].relative_gap - 50.0 / 500.0).abs() < 1e-10);
+
+        assert_eq!(report.route_switch_share, Some(0.5));
+        let alternatives_per_trip = report.alternatives_per_trip.unwrap();
+        assert_eq!(
+            (alternatives_per_trip.mean, alternatives_per_trip.min, alternatives_per_trip.max),
+            (1.5, 1.0, 2.0)
+        );
+        let path_overlap = report.path_overlap.unwrap();
+        assert_eq!((path_overlap.mean, path_overlap.max), (0.25, 0.5));
+
+        assert_eq!(report.worst_trips.len(), 2);
+        assert_eq!(report.worst_trips[0].query_id, "b");
+        assert_eq!(report.worst_trips[1].query_id, "c");
+        assert_eq!(report.worst_trips[1].departure, 4000.0);
+    }
+
+    #[test]
+    fn test_report_without_alternatives() {
+        let report = ConvergenceReport::new(0, &vec![10.0], &vec![10.0], &vec![0], &vec![String::from("a")], 900.0, None, None);
+
+        assert_eq!(report.relative_gap, 0.0);
+        assert_eq!(report.route_switch_share, None);
+        assert_eq!(report.alternatives_per_trip, None);
+        assert!(report.worst_trips.is_empty());
+    }
+}
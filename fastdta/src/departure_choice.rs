@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use conversion::{FILE_QUERIES_DEPARTURE, SerializedTimestamp};
+use rand::{Rng, SeedableRng, rngs};
+use rust_road_router::{
+    algo::customizable_contraction_hierarchy::CCH,
+    datastr::graph::floating_time_dependent::{CustomizedGraph, FlWeight, TDGraph},
+    io::{Load, Reconstruct, Store},
+};
+
+use crate::{customize::customize, query::get_paths_with_cch_queries};
+
+/// Vickrey style departure time choice: each trip may shift its departure within a window around its original departure
+/// to minimize its travel time plus the penalties for arriving before or after its preferred arrival time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepartureChoice {
+    /// the maximum shift of the departure in either direction in milliseconds
+    pub window: SerializedTimestamp,
+    /// the distance between two candidate departures in milliseconds
+    pub step: SerializedTimestamp,
+    /// penalty per second of arriving early, relative to one second of travel time
+    pub early_penalty: f64,
+    /// penalty per second of arriving late, relative to one second of travel time
+    pub late_penalty: f64,
+}
+
+impl DepartureChoice {
+    pub fn new(window: f64, step: f64, early_penalty: f64, late_penalty: f64) -> Self {
+        assert!(window >= 0.0, "departure window must not be negative");
+        assert!(step > 0.0, "departure step must be positive");
+        Self {
+            window: (window * 1000.0) as SerializedTimestamp,
+            step: (step * 1000.0) as SerializedTimestamp,
+            early_penalty,
+            late_penalty,
+        }
+    }
+
+    /// the generalized cost in seconds of a trip with the given travel time, arrival and preferred arrival (all in seconds)
+    pub fn cost(&self, travel_time: f64, arrival: f64, preferred_arrival: f64) -> f64 {
+        travel_time + self.early_penalty * (preferred_arrival - arrival).max(0.0) + self.late_penalty * (arrival - preferred_arrival).max(0.0)
+    }
+
+    /// the number of steps the departure may be shifted in either direction
+    pub fn num_steps(&self) -> i64 {
+        (self.window / self.step) as i64
+    }
+
+    /// the departure shifted by the given number of steps, if it does not fall before midnight
+    pub fn shift(&self, departure: SerializedTimestamp, steps: i64) -> Option<SerializedTimestamp> {
+        let shifted = departure as i64 + steps * self.step as i64;
+        (shifted >= 0).then_some(shifted as SerializedTimestamp)
+    }
+
+    /// the candidate departures of a trip, the original departure is always a candidate
+    pub fn candidates(&self, original_departure: SerializedTimestamp) -> Vec<SerializedTimestamp> {
+        (-self.num_steps()..=self.num_steps())
+            .filter_map(|k| self.shift(original_departure, k))
+            .collect()
+    }
+}
+
+/// the departures chosen in the previous iteration, or the original ones if there are none
+pub fn get_current_departures(input_dir: &Path, iteration: u32, original_departures: &Vec<SerializedTimestamp>) -> Vec<SerializedTimestamp> {
+    if iteration == 0 {
+        return original_departures.clone();
+    }
+
+    let previous_departures_file = input_dir.join(format!("{:0>3}", iteration - 1)).join(FILE_QUERIES_DEPARTURE);
+    if !previous_departures_file.exists() {
+        return original_departures.clone();
+    }
+
+    let departures = Vec::<SerializedTimestamp>::load_from(&previous_departures_file).unwrap();
+    assert_eq!(
+        departures.len(),
+        original_departures.len(),
+        "Number of departures changed since the previous iteration"
+    );
+    departures
+}
+
+/// the preferred arrival of each trip is its original departure plus its travel time on the empty network
+pub fn get_preferred_arrivals(
+    input_dir: &Path,
+    cch: &CCH,
+    query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+    routing_threads: usize,
+) -> Vec<f64> {
+    let free_flow_graph = TDGraph::reconstruct_from(&input_dir).expect("Failed to reconstruct the time-dependent graph");
+    let customized_graph = customize(cch, &free_flow_graph);
+
+    let (_, travel_times, _) = get_paths_with_cch_queries(
+        cch,
+        &customized_graph,
+        &query_data.0,
+        &query_data.1,
+        &query_data.2,
+        &query_data.3,
+        &query_data.4,
+        &free_flow_graph,
+        routing_threads,
+    );
+
+    query_data
+        .2
+        .iter()
+        .zip(travel_times.iter())
+        .map(|(&departure, &travel_time)| departure as f64 / 1000.0 + f64::from(travel_time).max(0.0))
+        .collect()
+}
+
+/// evaluates all candidate departures of each trip with time-dependent queries on the customized graph
+/// and returns the departure with the lowest generalized cost.
+/// Following the method of successive averages only a share of 1 / (iteration + 1) of the trips reconsiders its departure,
+/// the others keep their current one.
+pub fn choose_departures(
+    choice: &DepartureChoice,
+    cch: &CCH,
+    customized_graph: &CustomizedGraph,
+    graph: &TDGraph,
+    query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+    current_departures: &Vec<SerializedTimestamp>,
+    preferred_arrivals: &Vec<f64>,
+    iteration: u32,
+    seed: i32,
+    routing_threads: usize,
+) -> Vec<SerializedTimestamp> {
+    let num_queries = query_data.0.len();
+    let mut best_departures = current_departures.clone();
+    let mut best_costs = vec![f64::INFINITY; num_queries];
+    let mut current_costs = vec![f64::INFINITY; num_queries];
+
+    for k in -choice.num_steps()..=choice.num_steps() {
+        let candidate_departures: Vec<Option<SerializedTimestamp>> = query_data.2.iter().map(|&departure| choice.shift(departure, k)).collect();
+
+        // trips without a candidate for this shift are queried at their original departure and ignored afterwards
+        let (_, travel_times, _) = get_paths_with_cch_queries(
+            cch,
+            customized_graph,
+            &query_data.0,
+            &query_data.1,
+            &candidate_departures
+                .iter()
+                .zip(query_data.2.iter())
+                .map(|(candidate, &departure)| candidate.unwrap_or(departure))
+                .collect(),
+            &query_data.3,
+            &query_data.4,
+            graph,
+            routing_threads,
+        );
+
+        for i in 0..num_queries {
+            let Some(candidate_departure) = candidate_departures[i] else {
+                continue;
+            };
+            if travel_times[i] == FlWeight::INVALID || travel_times[i] >= FlWeight::INFINITY {
+                continue;
+            }
+
+            let travel_time = f64::from(travel_times[i]);
+            let arrival = candidate_departure as f64 / 1000.0 + travel_time;
+            let cost = choice.cost(travel_time, arrival, preferred_arrivals[i]);
+
+            if candidate_departure == current_departures[i] {
+                current_costs[i] = cost;
+            }
+            if cost < best_costs[i] {
+                best_costs[i] = cost;
+                best_departures[i] = candidate_departure;
+            }
+        }
+    }
+
+    let mut rng: rngs::StdRng = SeedableRng::seed_from_u64(seed.abs() as u64);
+    let reconsider_probability = 1.0 / (iteration as f64 + 1.0);
+
+    (0..num_queries)
+        .map(|i| {
+            // only switch if the new departure is strictly better, to avoid oscillating between equally good ones
+            if rng.random_bool(reconsider_probability) && best_costs[i] < current_costs[i] {
+                best_departures[i]
+            } else {
+                current_departures[i]
+            }
+        })
+        .collect()
+}
+
+/// chooses new departures for all trips, writes them to the iteration directory and replaces the departures in `query_data`
+pub fn apply_departure_choice(
+    input_dir: &Path,
+    iteration: u32,
+    choice: &DepartureChoice,
+    cch: &CCH,
+    customized_graph: &CustomizedGraph,
+    graph: &TDGraph,
+    query_data: &mut (Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+    seed: i32,
+    routing_threads: usize,
+) {
+    let current_departures = get_current_departures(input_dir, iteration, &query_data.2);
+    let preferred_arrivals = get_preferred_arrivals(input_dir, cch, query_data, routing_threads);
+
+    let departures = choose_departures(
+        choice,
+        cch,
+        customized_graph,
+        graph,
+        query_data,
+        &current_departures,
+        &preferred_arrivals,
+        iteration,
+        seed,
+        routing_threads,
+    );
+
+    let shifted = departures
+        .iter()
+        .zip(query_data.2.iter())
+        .filter(|(departure, original)| departure != original)
+        .count();
+    println!("Departure time choice: {} of {} trips depart at a shifted time", shifted, departures.len());
+
+    let current_iteration_dir = input_dir.join(format!("{:0>3}", iteration));
+    std::fs::create_dir_all(&current_iteration_dir).unwrap();
+    departures.write_to(&current_iteration_dir.join(FILE_QUERIES_DEPARTURE)).unwrap();
+
+    query_data.2 = departures;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_delay_cost() {
+        let choice = DepartureChoice::new(1800.0, 300.0, 0.5, 2.0);
+
+        assert_eq!(choice.cost(600.0, 1000.0, 1000.0), 600.0);
+        assert_eq!(choice.cost(600.0, 900.0, 1000.0), 650.0);
+        assert_eq!(choice.cost(600.0, 1100.0, 1000.0), 800.0);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let choice = DepartureChoice::new(600.0, 300.0, 0.5, 2.0);
+
+        assert_eq!(choice.candidates(3_600_000), vec![3_000_000, 3_300_000, 3_600_000, 3_900_000, 4_200_000]);
+        // no departures before midnight
+        assert_eq!(choice.candidates(100_000), vec![100_000, 400_000, 700_000]);
+        // without a window the original departure is the only candidate
+        assert_eq!(DepartureChoice::new(0.0, 300.0, 0.5, 2.0).candidates(3_600_000), vec![3_600_000]);
+    }
+}
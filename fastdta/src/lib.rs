@@ -4,22 +4,28 @@ pub mod alternative_paths;
 pub mod calibrate_traffic_model;
 pub mod choice;
 pub mod cli;
+pub mod convergence;
 pub mod customize;
+pub mod departure_choice;
 pub mod gawron;
 pub mod logger;
 pub mod logit;
+pub mod meso_simulation;
+pub mod od_estimation;
 pub mod path_processor;
 pub mod postprocess;
 pub mod preprocess;
 pub mod preprocess_routes;
 pub mod query;
 pub mod relative_gap;
-pub mod sampled_queries;
+pub mod router_options;
+pub mod sampled_assignment;
 pub mod sampled_queries_sumo;
 pub mod sampler;
 pub mod sumo_runner;
 pub mod traffic_model;
 pub mod traffic_model_data;
+pub mod user_classes;
 
 pub fn calculate_keep_routes(n: usize, keep_route_probability: f64, seed: i32) -> Vec<bool> {
     if keep_route_probability <= 0.0 {
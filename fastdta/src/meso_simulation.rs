@@ -0,0 +1,503 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+use conversion::{
+    FILE_EDGE_INDICES_TO_ID, FILE_EDGE_LANES, FILE_EDGE_LENGTHS, FILE_EDGE_SPEEDS, FILE_EDGE_TRAFFIC_MODEL_PARAMS, GLOBAL_FREE_FLOW_SPEED_FACTOR,
+    SerializedTimestamp,
+    sumo::{
+        meandata::{Edge, Interval, MeandataDocumentRoot},
+        tripinfo::{Tripinfo, TripinfosDocumentRoot},
+    },
+};
+use rust_road_router::{
+    datastr::graph::EdgeId,
+    io::{Load, read_strings_from_file},
+};
+
+use crate::{
+    traffic_model::{TrafficModel, TrafficModelType},
+    traffic_model_data::TrafficModelData,
+};
+
+/// lower bound for the speed on an edge in km/h, so that jammed edges still drain
+const MIN_SPEED: f64 = 1.0;
+
+/// Configuration of the in-process mesoscopic simulation, the defaults follow `sumo --mesosim`
+pub struct MesoConfig {
+    pub begin: f64,
+    pub end: f64,
+    /// simulation step length in seconds
+    pub step_length: f64,
+    /// edgeData aggregation period in seconds
+    pub aggregation: u32,
+    /// minimum time gap in seconds between two vehicles leaving the same lane
+    pub headway: f64,
+    /// space in meters occupied by a vehicle in a jam
+    pub jam_spacing: f64,
+    /// vehicles waiting longer than this at the end of an edge are moved on regardless of the space on the next edge
+    pub time_to_teleport: f64,
+    /// edges with less sampled seconds are excluded from the edgeData output
+    pub min_samples: f64,
+}
+
+impl MesoConfig {
+    pub fn new(begin: f64, end: f64, aggregation: u32) -> Self {
+        Self {
+            begin,
+            end,
+            step_length: 1.0,
+            aggregation,
+            headway: 1.13,
+            jam_spacing: 7.5,
+            time_to_teleport: 300.0,
+            min_samples: 1.0,
+        }
+    }
+}
+
+/// the per edge data used by the simulation, edge `i` is the edge with index `i` in the TDGraph
+pub struct MesoNetwork {
+    pub edge_ids: Vec<String>,
+    /// edge lengths in meters
+    pub lengths: Vec<f64>,
+    pub lanes: Vec<u32>,
+    pub traffic_models: Vec<Box<dyn TrafficModel>>,
+}
+
+impl MesoNetwork {
+    pub fn new(edge_ids: Vec<String>, lengths: Vec<f64>, lanes: Vec<u32>, traffic_models: Vec<Box<dyn TrafficModel>>) -> Self {
+        debug_assert_eq!(edge_ids.len(), lengths.len());
+        debug_assert_eq!(edge_ids.len(), lanes.len());
+        debug_assert_eq!(edge_ids.len(), traffic_models.len());
+
+        Self {
+            edge_ids,
+            lengths,
+            lanes,
+            traffic_models,
+        }
+    }
+
+    /// loads the network from the preprocessed files in `input_dir`,
+    /// uses the calibrated traffic models if available and free flow models otherwise
+    pub fn load(input_dir: &Path, traffic_model_type: TrafficModelType) -> Self {
+        let edge_ids = read_strings_from_file(&input_dir.join(FILE_EDGE_INDICES_TO_ID)).unwrap();
+        let lengths = Vec::<f64>::load_from(&input_dir.join(FILE_EDGE_LENGTHS)).unwrap();
+        let lanes = Vec::<u32>::load_from(&input_dir.join(FILE_EDGE_LANES)).unwrap();
+
+        let traffic_model_data = if input_dir.join(FILE_EDGE_TRAFFIC_MODEL_PARAMS).exists() {
+            TrafficModelData::reconstruct(input_dir, traffic_model_type)
+        } else {
+            let free_flow_speeds: Vec<f64> = Vec::<f64>::load_from(&input_dir.join(FILE_EDGE_SPEEDS))
+                .unwrap()
+                .iter()
+                .map(|ffs| *ffs * 3.6 * GLOBAL_FREE_FLOW_SPEED_FACTOR)
+                .collect();
+            TrafficModelData::init(&free_flow_speeds, traffic_model_type)
+        };
+
+        Self::new(edge_ids, lengths, lanes, traffic_model_data.traffic_models)
+    }
+
+    fn storage_capacity(&self, edge: usize, config: &MesoConfig) -> usize {
+        usize::max(1, (self.lengths[edge] * self.lanes[edge].max(1) as f64 / config.jam_spacing) as usize)
+    }
+
+    /// time in seconds to pass the edge when `vehicles` vehicles are on it
+    fn travel_time(&self, edge: usize, vehicles: usize) -> f64 {
+        let lanes = self.lanes[edge].max(1) as f64;
+        let lane_density = 1000.0 * vehicles as f64 / (self.lengths[edge].max(1.0) * lanes);
+        let speed = f64::max(self.traffic_models[edge].get_speed(lane_density), MIN_SPEED) / 3.6;
+
+        self.lengths[edge] / speed
+    }
+}
+
+/// the outputs of a simulation run in the shapes of SUMO's edgeData and tripinfo outputs
+pub struct SimulationOutput {
+    pub meandata: MeandataDocumentRoot,
+    pub tripinfos: TripinfosDocumentRoot,
+}
+
+struct VehicleOnEdge {
+    vehicle: usize,
+    entered: f64,
+    earliest_exit: f64,
+}
+
+#[derive(Default, Clone)]
+struct EdgeAggregate {
+    sampled_seconds: f64,
+    distance: f64,
+    departed: u32,
+    arrived: u32,
+    entered: u32,
+    left: u32,
+}
+
+/// Queue based mesoscopic network loading.
+/// Each edge is a FIFO queue whose vehicles may leave once they passed the edge with the speed given by the edge's traffic model
+/// for the density at the time they entered. Outflow is limited by a per lane headway and inflow by the storage capacity of the edge.
+///
+/// `paths` are sequences of edge indices, vehicles with empty paths are not simulated.
+pub fn simulate(
+    network: &MesoNetwork,
+    paths: &Vec<Vec<EdgeId>>,
+    trip_ids: &Vec<String>,
+    departures: &Vec<SerializedTimestamp>,
+    config: &MesoConfig,
+) -> SimulationOutput {
+    let mut simulation = Simulation::new(network, paths, config);
+
+    let mut pending: Vec<usize> = (0..paths.len()).filter(|&i| !paths[i].is_empty()).collect();
+    pending.sort_by_key(|&i| departures[i]);
+    let mut pending = VecDeque::from(pending);
+    let mut waiting: Vec<usize> = Vec::new();
+
+    let mut time = config.begin;
+    while time < config.end {
+        while let Some(&vehicle) = pending.front() {
+            if departures[vehicle] as f64 / 1000.0 > time {
+                break;
+            }
+            waiting.push(pending.pop_front().unwrap());
+        }
+
+        // insert vehicles in the order of their departure if there is space on their first edge
+        waiting.retain(|&vehicle| !simulation.try_insert(vehicle, time));
+
+        simulation.step(time);
+
+        if waiting.is_empty() && simulation.vehicles_in_network == 0 {
+            match pending.front() {
+                // skip idle time until the next departure
+                Some(&vehicle) => {
+                    let next_departure = departures[vehicle] as f64 / 1000.0;
+                    time = f64::max(
+                        time + config.step_length,
+                        config.begin + ((next_departure - config.begin) / config.step_length).ceil() * config.step_length,
+                    );
+                    continue;
+                }
+                None => break,
+            }
+        }
+
+        time += config.step_length;
+    }
+
+    simulation.finish(trip_ids)
+}
+
+struct Simulation<'a> {
+    network: &'a MesoNetwork,
+    paths: &'a Vec<Vec<EdgeId>>,
+    config: &'a MesoConfig,
+    queues: Vec<VecDeque<VehicleOnEdge>>,
+    /// the earliest time the next vehicle may leave the edge
+    next_exit: Vec<f64>,
+    active_edges: Vec<usize>,
+    is_active: Vec<bool>,
+    vehicles_in_network: usize,
+    route_index: Vec<usize>,
+    depart: Vec<f64>,
+    arrival: Vec<Option<f64>>,
+    aggregates: Vec<HashMap<usize, EdgeAggregate>>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(network: &'a MesoNetwork, paths: &'a Vec<Vec<EdgeId>>, config: &'a MesoConfig) -> Self {
+        let num_edges = network.lengths.len();
+        let num_intervals = usize::max(1, ((config.end - config.begin) / config.aggregation as f64).ceil() as usize);
+
+        Self {
+            network,
+            paths,
+            config,
+            queues: (0..num_edges).map(|_| VecDeque::new()).collect(),
+            next_exit: vec![f64::NEG_INFINITY; num_edges],
+            active_edges: Vec::new(),
+            is_active: vec![false; num_edges],
+            vehicles_in_network: 0,
+            route_index: vec![0; paths.len()],
+            depart: vec![0.0; paths.len()],
+            arrival: vec![None; paths.len()],
+            aggregates: vec![HashMap::new(); num_intervals],
+        }
+    }
+
+    fn try_insert(&mut self, vehicle: usize, time: f64) -> bool {
+        let edge = self.paths[vehicle][0] as usize;
+        if self.queues[edge].len() >= self.network.storage_capacity(edge, self.config) {
+            return false;
+        }
+
+        self.depart[vehicle] = time;
+        self.vehicles_in_network += 1;
+        self.aggregate_at(time, edge).departed += 1;
+        self.enter(vehicle, edge, time);
+
+        true
+    }
+
+    fn enter(&mut self, vehicle: usize, edge: usize, time: f64) {
+        let travel_time = self.network.travel_time(edge, self.queues[edge].len() + 1);
+        self.queues[edge].push_back(VehicleOnEdge {
+            vehicle,
+            entered: time,
+            earliest_exit: time + travel_time,
+        });
+        self.aggregate_at(time, edge).entered += 1;
+
+        if !self.is_active[edge] {
+            self.is_active[edge] = true;
+            self.active_edges.push(edge);
+        }
+    }
+
+    /// moves all vehicles which can leave their edge until `time`
+    fn step(&mut self, time: f64) {
+        let mut i = 0;
+        // edges activated during this step are appended and processed as well
+        while i < self.active_edges.len() {
+            let edge = self.active_edges[i];
+            self.process_edge(edge, time);
+            i += 1;
+        }
+
+        let queues = &self.queues;
+        let is_active = &mut self.is_active;
+        self.active_edges.retain(|&edge| {
+            let active = !queues[edge].is_empty();
+            is_active[edge] = active;
+            active
+        });
+    }
+
+    fn process_edge(&mut self, edge: usize, time: f64) {
+        let headway = self.config.headway / self.network.lanes[edge].max(1) as f64;
+
+        while let Some(front) = self.queues[edge].front() {
+            let exit_time = f64::max(front.earliest_exit, self.next_exit[edge]);
+            if exit_time > time {
+                break;
+            }
+
+            let vehicle = front.vehicle;
+            let entered = front.entered;
+            let next_index = self.route_index[vehicle] + 1;
+
+            if next_index < self.paths[vehicle].len() {
+                let next_edge = self.paths[vehicle][next_index] as usize;
+                let is_blocked = self.queues[next_edge].len() >= self.network.storage_capacity(next_edge, self.config);
+                if is_blocked && time - front.earliest_exit < self.config.time_to_teleport {
+                    break;
+                }
+
+                self.queues[edge].pop_front();
+                self.record_traversal(edge, entered, exit_time, exit_time);
+                self.route_index[vehicle] = next_index;
+                self.enter(vehicle, next_edge, exit_time);
+            } else {
+                self.queues[edge].pop_front();
+                self.record_traversal(edge, entered, exit_time, exit_time);
+                self.aggregate_at(exit_time, edge).arrived += 1;
+                self.arrival[vehicle] = Some(exit_time);
+                self.vehicles_in_network -= 1;
+            }
+
+            self.aggregate_at(exit_time, edge).left += 1;
+            self.next_exit[edge] = exit_time + headway;
+        }
+    }
+
+    fn interval_of(&self, time: f64) -> usize {
+        let index = ((time - self.config.begin) / self.config.aggregation as f64).floor().max(0.0) as usize;
+        usize::min(index, self.aggregates.len() - 1)
+    }
+
+    fn aggregate_at(&mut self, time: f64, edge: usize) -> &mut EdgeAggregate {
+        let interval = self.interval_of(time);
+        self.aggregates[interval].entry(edge).or_default()
+    }
+
+    /// distributes the time spent on the edge in [entered, until] over the aggregation intervals,
+    /// the distance is assumed to be covered uniformly until `exit`
+    fn record_traversal(&mut self, edge: usize, entered: f64, until: f64, exit: f64) {
+        let length = self.network.lengths[edge];
+        let duration = exit - entered;
+
+        for interval in self.interval_of(entered)..=self.interval_of(until) {
+            let begin = self.config.begin + (interval * self.config.aggregation as usize) as f64;
+            let end = begin + self.config.aggregation as f64;
+            let overlap = f64::min(until, end) - f64::max(entered, begin);
+            if overlap <= 0.0 && duration > 0.0 {
+                continue;
+            }
+
+            let aggregate = self.aggregates[interval].entry(edge).or_default();
+            aggregate.sampled_seconds += overlap.max(0.0);
+            aggregate.distance += if duration > 0.0 { length * overlap / duration } else { length };
+        }
+    }
+
+    fn finish(mut self, trip_ids: &Vec<String>) -> SimulationOutput {
+        // account for the vehicles still in the network at the end of the simulation
+        for edge in 0..self.queues.len() {
+            let vehicles: Vec<(f64, f64)> = self.queues[edge].iter().map(|v| (v.entered, v.earliest_exit)).collect();
+            for (entered, earliest_exit) in vehicles {
+                self.record_traversal(edge, entered, self.config.end, f64::max(earliest_exit, self.config.end));
+            }
+        }
+
+        let intervals = self
+            .aggregates
+            .iter()
+            .enumerate()
+            .map(|(interval, aggregates)| {
+                let begin = self.config.begin + (interval * self.config.aggregation as usize) as f64;
+                let end = f64::min(begin + self.config.aggregation as f64, self.config.end);
+
+                let mut edges: Vec<(&usize, &EdgeAggregate)> = aggregates
+                    .iter()
+                    .filter(|(_, aggregate)| aggregate.sampled_seconds >= self.config.min_samples)
+                    .collect();
+                edges.sort_by_key(|(edge, _)| **edge);
+
+                let edges = edges
+                    .into_iter()
+                    .map(|(&edge, aggregate)| self.to_meandata_edge(edge, aggregate, end - begin))
+                    .collect();
+
+                Interval::create(format!("dump_{}", self.config.aggregation), begin, end, edges)
+            })
+            .collect();
+
+        let tripinfos = (0..self.paths.len())
+            .filter_map(|vehicle| {
+                self.arrival[vehicle].map(|arrival| Tripinfo {
+                    id: trip_ids[vehicle].clone(),
+                    depart: self.depart[vehicle],
+                    duration: arrival - self.depart[vehicle],
+                })
+            })
+            .collect();
+
+        SimulationOutput {
+            meandata: MeandataDocumentRoot { intervals },
+            tripinfos: TripinfosDocumentRoot { tripinfos },
+        }
+    }
+
+    fn to_meandata_edge(&self, edge: usize, aggregate: &EdgeAggregate, period: f64) -> Edge {
+        let length = self.network.lengths[edge];
+        let speed = aggregate.distance / aggregate.sampled_seconds;
+        let density = 1000.0 * aggregate.sampled_seconds / (period * length);
+
+        Edge {
+            id: self.network.edge_ids[edge].clone(),
+            traveltime: Some(length / speed),
+            overlap_traveltime: Some(length / speed),
+            density: Some(density),
+            speed: Some(speed),
+            sampled_seconds: Some(aggregate.sampled_seconds),
+            lane_density: Some(density / self.network.lanes[edge].max(1) as f64),
+            departed: Some(aggregate.departed),
+            arrived: Some(aggregate.arrived),
+            entered: Some(aggregate.entered),
+            left: Some(aggregate.left),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ConstantSpeed(f64);
+
+    impl TrafficModel for ConstantSpeed {
+        fn get_speed(&self, _density: f64) -> f64 {
+            self.0
+        }
+
+        fn calibrate(&mut self, _observed_speed: &[f64], _observed_density: &[f64]) {}
+
+        fn debug(&self) {}
+
+        fn get_params_as_vec(&self) -> Vec<f64> {
+            vec![self.0]
+        }
+
+        fn from_vec(params: &Vec<f64>) -> Self {
+            ConstantSpeed(params[0])
+        }
+    }
+
+    /// two edges of 100m each with a speed of 36 km/h, i.e. 10s per edge
+    fn network(lanes: u32) -> MesoNetwork {
+        MesoNetwork::new(
+            vec!["a".to_string(), "b".to_string()],
+            vec![100.0, 100.0],
+            vec![lanes, lanes],
+            vec![Box::new(ConstantSpeed(36.0)), Box::new(ConstantSpeed(36.0))],
+        )
+    }
+
+    #[test]
+    fn test_free_flow_travel_time() {
+        let network = network(1);
+        let config = MesoConfig::new(0.0, 3600.0, 900);
+
+        let output = simulate(&network, &vec![vec![0, 1]], &vec!["veh".to_string()], &vec![5_000], &config);
+
+        assert_eq!(output.tripinfos.tripinfos.len(), 1);
+        assert_eq!(output.tripinfos.tripinfos[0].depart, 5.0);
+        assert!((output.tripinfos.tripinfos[0].duration - 20.0).abs() < 1e-9);
+
+        let edges = &output.meandata.intervals[0].edges;
+        assert_eq!(edges.len(), 2);
+        assert!((edges[0].overlap_traveltime.unwrap() - 10.0).abs() < 1e-9);
+        assert!((edges[1].sampled_seconds.unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(edges[0].departed, Some(1));
+        assert_eq!(edges[1].arrived, Some(1));
+    }
+
+    #[test]
+    fn test_headway_delays_simultaneous_departures() {
+        let one_lane_network = network(1);
+        let config = MesoConfig::new(0.0, 3600.0, 900);
+
+        let paths = vec![vec![0, 1]; 3];
+        let trip_ids = (0..3).map(|i| i.to_string()).collect();
+        let output = simulate(&one_lane_network, &paths, &trip_ids, &vec![0; 3], &config);
+
+        let durations: Vec<f64> = output.tripinfos.tripinfos.iter().map(|t| t.duration).collect();
+        assert_eq!(durations.len(), 3);
+        assert!((durations[0] - 20.0).abs() < 1e-9);
+        assert!(durations[1] > durations[0]);
+        assert!(durations[2] > durations[1]);
+
+        // a second lane doubles the outflow capacity
+        let output = simulate(&network(2), &paths, &trip_ids, &vec![0; 3], &config);
+        let two_lane_durations: Vec<f64> = output.tripinfos.tripinfos.iter().map(|t| t.duration).collect();
+        assert!(two_lane_durations[2] < durations[2]);
+    }
+
+    #[test]
+    fn test_unfinished_vehicles_have_no_tripinfo() {
+        let network = network(1);
+        let config = MesoConfig::new(0.0, 15.0, 15);
+
+        let output = simulate(&network, &vec![vec![0, 1]], &vec!["veh".to_string()], &vec![0], &config);
+
+        assert!(output.tripinfos.tripinfos.is_empty());
+        // 10s on the first edge and 5s on the second edge
+        let edges = &output.meandata.intervals[0].edges;
+        assert!((edges[0].sampled_seconds.unwrap() - 10.0).abs() < 1e-9);
+        assert!((edges[1].sampled_seconds.unwrap() - 5.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,496 @@
+use std::{collections::HashMap, fs::OpenOptions, io::Write, path::Path};
+
+use conversion::{
+    FILE_EDGE_DEFAULT_TRAVEL_TIMES, FILE_EDGE_INDICES_TO_ID, FILE_OD_ESTIMATION_FIT, FILE_QUERY_IDS, SerializedTimestamp, SerializedTravelTime,
+    sumo::{
+        FileReader,
+        meandata::MeandataDocumentRoot,
+        meandata_reader::SumoMeandataReader,
+        sumo_to_new_graph_weights::extract_interpolation_points_from_meandata,
+        trips::{Trip, TripsDocumentRoot},
+    },
+};
+use rand::{Rng, SeedableRng, rngs};
+use rust_road_router::{
+    datastr::graph::{EdgeId, floating_time_dependent::TDGraph},
+    io::{Load, Reconstruct, read_strings_from_file},
+    report::measure,
+};
+
+use crate::{
+    customize::customize, logger::Logger, preprocess::get_cch, query::get_paths_with_cch_queries, query::read_queries,
+    sampled_queries_sumo::run_sumo_simulation_for_batch, sumo_runner::Simulator,
+};
+
+/// parameters of the simultaneous perturbation stochastic approximation (SPSA) of the demand
+#[derive(Debug, Clone)]
+pub struct OdEstimationConfig {
+    /// length of the departure time slices in seconds
+    pub slice_length: f64,
+    pub iterations: u32,
+    /// perturbation of the demand factors in the first iteration
+    pub perturbation: f64,
+    /// the largest change of a demand factor in the first iteration, later steps decay
+    pub step_size: f64,
+    /// upper bound for the factor by which the demand of an OD pair in a time slice may be scaled
+    pub max_factor: f64,
+    pub seed: u64,
+}
+
+/// the trips grouped by their original origin and destination edges and their departure time slice
+#[derive(Debug, Clone)]
+pub struct OdSlices {
+    pub trips_of_slice: Vec<Vec<usize>>,
+}
+
+impl OdSlices {
+    pub fn new(query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>), slice_length: f64) -> Self {
+        let slice_length = ((slice_length * 1000.0) as SerializedTimestamp).max(1);
+
+        let mut slice_of_key: HashMap<(u32, u32, SerializedTimestamp), usize> = HashMap::new();
+        let mut trips_of_slice: Vec<Vec<usize>> = Vec::new();
+
+        for i in 0..query_data.0.len() {
+            let key = (query_data.3[i], query_data.4[i], query_data.2[i] / slice_length);
+            let slice = *slice_of_key.entry(key).or_insert_with(|| {
+                trips_of_slice.push(vec![]);
+                trips_of_slice.len() - 1
+            });
+            trips_of_slice[slice].push(i);
+        }
+
+        Self { trips_of_slice }
+    }
+
+    pub fn len(&self) -> usize {
+        self.trips_of_slice.len()
+    }
+
+    /// the trips realizing the demand of each slice scaled by its factor,
+    /// the trips of a slice are repeated if its demand grows and dropped from the back if it shrinks
+    pub fn realize(&self, factors: &Vec<f64>) -> Vec<usize> {
+        assert_eq!(factors.len(), self.len());
+
+        self.trips_of_slice
+            .iter()
+            .zip(factors.iter())
+            .flat_map(|(trips, &factor)| {
+                let demand = (factor * trips.len() as f64).round() as usize;
+                (0..demand).map(move |j| trips[j % trips.len()])
+            })
+            .collect()
+    }
+}
+
+/// ids of the realized trips, repeated trips get the number of the copy appended
+pub fn get_realized_trip_ids(realized_trips: &Vec<usize>, trip_ids: &Vec<String>) -> Vec<String> {
+    let mut copies = vec![0; trip_ids.len()];
+
+    realized_trips
+        .iter()
+        .map(|&trip| {
+            let copy = copies[trip];
+            copies[trip] += 1;
+            if copy == 0 {
+                trip_ids[trip].clone()
+            } else {
+                format!("{}.{}", trip_ids[trip], copy)
+            }
+        })
+        .collect()
+}
+
+/// the trips file of the realized demand, sorted by departure
+pub fn get_estimated_trips(trips: &TripsDocumentRoot, realized_trips: &Vec<usize>) -> TripsDocumentRoot {
+    let trip_ids = trips.trips.iter().map(|trip| trip.id.clone()).collect();
+    let realized_trip_ids = get_realized_trip_ids(realized_trips, &trip_ids);
+
+    let mut estimated_trips: Vec<Trip> = realized_trips
+        .iter()
+        .zip(realized_trip_ids)
+        .map(|(&trip, id)| Trip {
+            id,
+            ..trips.trips[trip].clone()
+        })
+        .collect();
+    estimated_trips.sort_by(|a, b| a.depart.total_cmp(&b.depart));
+
+    TripsDocumentRoot { trips: estimated_trips }
+}
+
+/// the number of vehicles entering each edge per aggregation interval, keyed by edge index and interval index.
+/// Uses `entered` and falls back to `left` for edges without it, edges which are not part of the network are skipped.
+pub fn get_edge_counts(meandata: &MeandataDocumentRoot, edge_ids: &Vec<String>, aggregation: f64) -> HashMap<(usize, u32), f64> {
+    let edge_index: HashMap<&String, usize> = edge_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    let mut counts = HashMap::new();
+
+    for interval in &meandata.intervals {
+        let interval_index = (interval.begin / aggregation).floor() as u32;
+        for edge in &interval.edges {
+            let (Some(&index), Some(count)) = (edge_index.get(&edge.id), edge.entered.or(edge.left)) else {
+                continue;
+            };
+            *counts.entry((index, interval_index)).or_insert(0.0) += count as f64;
+        }
+    }
+
+    counts
+}
+
+/// reads the observed counts from an edgeData file, the aggregation period is the length of its first interval
+pub fn get_observed_counts(meandata: &MeandataDocumentRoot, edge_ids: &Vec<String>) -> (HashMap<(usize, u32), f64>, u32) {
+    let first_interval = meandata.intervals.first().expect("The counts file contains no intervals");
+    let aggregation = (first_interval.end - first_interval.begin).round() as u32;
+    assert!(aggregation > 0, "The intervals of the counts file must not be empty");
+
+    let counts = get_edge_counts(meandata, edge_ids, aggregation as f64);
+
+    let edges_in_file: usize = meandata.intervals.iter().map(|interval| interval.edges.len()).sum();
+    let edges_with_counts = counts.len();
+    if edges_with_counts < edges_in_file {
+        println!(
+            "Warning: {} of {} counts are ignored since their edge is unknown or they have neither `entered` nor `left`",
+            edges_in_file - edges_with_counts,
+            edges_in_file
+        );
+    }
+
+    (counts, aggregation)
+}
+
+/// goodness of fit of simulated to observed counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FitStatistics {
+    pub num_counts: usize,
+    pub rmse: f64,
+    /// coefficient of determination of the simulated counts
+    pub r_squared: f64,
+    /// share of counts with a GEH statistic (on hourly flows) below 5
+    pub geh_below_5: f64,
+    pub observed_total: f64,
+    pub simulated_total: f64,
+}
+
+impl FitStatistics {
+    /// `aggregation` is the length of the count intervals in seconds
+    pub fn new(observed: &Vec<f64>, simulated: &Vec<f64>, aggregation: f64) -> Self {
+        assert_eq!(observed.len(), simulated.len());
+        let n = observed.len();
+        if n == 0 {
+            return Self {
+                num_counts: 0,
+                rmse: 0.0,
+                r_squared: 0.0,
+                geh_below_5: 0.0,
+                observed_total: 0.0,
+                simulated_total: 0.0,
+            };
+        }
+
+        let observed_total: f64 = observed.iter().sum();
+        let simulated_total: f64 = simulated.iter().sum();
+        let observed_mean = observed_total / n as f64;
+
+        let residual_sum_of_squares: f64 = observed.iter().zip(simulated).map(|(o, s)| (o - s).powi(2)).sum();
+        let total_sum_of_squares: f64 = observed.iter().map(|o| (o - observed_mean).powi(2)).sum();
+
+        let hourly = 3600.0 / aggregation;
+        let geh_below_5 = observed.iter().zip(simulated).filter(|&(o, s)| geh(o * hourly, s * hourly) < 5.0).count() as f64 / n as f64;
+
+        Self {
+            num_counts: n,
+            rmse: (residual_sum_of_squares / n as f64).sqrt(),
+            r_squared: if total_sum_of_squares > 0.0 {
+                1.0 - residual_sum_of_squares / total_sum_of_squares
+            } else {
+                0.0
+            },
+            geh_below_5,
+            observed_total,
+            simulated_total,
+        }
+    }
+}
+
+/// the GEH statistic of two hourly flows
+pub fn geh(observed: f64, simulated: f64) -> f64 {
+    if observed + simulated <= 0.0 {
+        return 0.0;
+    }
+    (2.0 * (simulated - observed).powi(2) / (simulated + observed)).sqrt()
+}
+
+/// the sum of squared differences between the observed and the simulated counts and the fit statistics
+fn compare_counts(observed_counts: &HashMap<(usize, u32), f64>, simulated_counts: &HashMap<(usize, u32), f64>, aggregation: f64) -> (f64, FitStatistics) {
+    let (observed, simulated): (Vec<f64>, Vec<f64>) = observed_counts
+        .iter()
+        .map(|(key, &count)| (count, simulated_counts.get(key).copied().unwrap_or(0.0)))
+        .unzip();
+
+    let objective = observed.iter().zip(simulated.iter()).map(|(o, s)| (o - s).powi(2)).sum();
+    (objective, FitStatistics::new(&observed, &simulated, aggregation))
+}
+
+fn append_fit_statistics_to_file(output_dir: &Path, iteration: u32, objective: f64, statistics: &FitStatistics, num_trips: usize) {
+    let file_path = output_dir.join(FILE_OD_ESTIMATION_FIT);
+    let write_header = !file_path.exists();
+    let mut file = OpenOptions::new().create(true).append(true).open(file_path).unwrap();
+
+    if write_header {
+        writeln!(file, "iteration,trips,objective,rmse,r_squared,geh_below_5,observed_total,simulated_total").unwrap();
+    }
+    writeln!(
+        file,
+        "{},{},{:.3},{:.6},{:.6},{:.6},{},{}",
+        iteration, num_trips, objective, statistics.rmse, statistics.r_squared, statistics.geh_below_5, statistics.observed_total, statistics.simulated_total
+    )
+    .unwrap();
+}
+
+/// the demand factors moved into `[0, max_factor]`
+fn clamp_factors(factors: Vec<f64>, max_factor: f64) -> Vec<f64> {
+    factors.into_iter().map(|factor| factor.clamp(0.0, max_factor)).collect()
+}
+
+/// Adjusts the number of trips per OD pair and departure time slice such that the simulated edge counts match the observed ones.
+///
+/// The demand of each slice is the number of its seed trips scaled by a factor which is estimated with SPSA:
+/// each iteration routes the seed trips on the travel times of the last simulation of the current demand,
+/// simulates the demand with all factors perturbed in a random direction and against it,
+/// and moves the factors along the resulting gradient estimate of the squared count differences.
+///
+/// Returns the realized trips (indices of seed trips, repeated trips are copies) of the best demand and its fit statistics.
+pub fn estimate_od_matrix(
+    input_dir: &Path,
+    output_dir: &Path,
+    net_file: &Path,
+    config: &OdEstimationConfig,
+    observed_counts: &HashMap<(usize, u32), f64>,
+    aggregation: u32,
+    begin: f64,
+    end: f64,
+    simulator: &Simulator,
+    logger: &Logger,
+    routing_threads: usize,
+) -> (Vec<usize>, FitStatistics) {
+    let edge_ids: Vec<String> = read_strings_from_file(&input_dir.join(FILE_EDGE_INDICES_TO_ID)).unwrap();
+    let trip_ids: Vec<String> = read_strings_from_file(&input_dir.join(FILE_QUERY_IDS)).unwrap();
+    let free_flow_tts_ms = Vec::<SerializedTravelTime>::load_from(&input_dir.join(FILE_EDGE_DEFAULT_TRAVEL_TIMES)).unwrap();
+    let query_data = read_queries(input_dir);
+
+    let slices = OdSlices::new(&query_data, config.slice_length);
+    println!(
+        "OD matrix estimation with {} OD pairs and time slices, {} counts",
+        slices.len(),
+        observed_counts.len()
+    );
+
+    let mut graph = TDGraph::reconstruct_from(&input_dir).expect("Failed to reconstruct the time-dependent graph");
+    let cch = get_cch(input_dir, &graph);
+    let mut rng: rngs::StdRng = SeedableRng::seed_from_u64(config.seed);
+
+    // simulates the demand given by the factors on the paths of the seed trips and compares the simulated counts with the observed ones
+    let evaluate = |factors: &Vec<f64>, paths: &Vec<Vec<EdgeId>>, departures: &Vec<SerializedTimestamp>, iteration: u32, batch: usize| {
+        let realized_trips = slices.realize(factors);
+
+        run_sumo_simulation_for_batch(
+            output_dir,
+            net_file,
+            iteration,
+            batch,
+            aggregation,
+            begin,
+            end,
+            &realized_trips.iter().map(|&trip| paths[trip].clone()).collect(),
+            &get_realized_trip_ids(&realized_trips, &trip_ids),
+            &realized_trips.iter().map(|&trip| departures[trip]).collect(),
+            &edge_ids,
+            simulator,
+        )
+        .expect("Failed to simulate the demand");
+
+        let dump_file = output_dir
+            .join(format!("{:0>3}", iteration))
+            .join(format!("_dump_{}_{:0>3}_{:0>3}.xml", aggregation, iteration, batch));
+        let meandata = SumoMeandataReader::read(&dump_file).unwrap();
+
+        let simulated_counts = get_edge_counts(&meandata, &edge_ids, aggregation as f64);
+        let (objective, statistics) = compare_counts(observed_counts, &simulated_counts, aggregation as f64);
+
+        (objective, statistics, meandata, realized_trips)
+    };
+
+    let route = |graph: &TDGraph| {
+        let customized_graph = customize(&cch, graph);
+        let (paths, _, departures) = get_paths_with_cch_queries(
+            &cch,
+            &customized_graph,
+            &query_data.0,
+            &query_data.1,
+            &query_data.2,
+            &query_data.3,
+            &query_data.4,
+            graph,
+            routing_threads,
+        );
+        (paths, departures)
+    };
+
+    let ((mut paths, mut departures), duration) = measure(|| route(&graph));
+    logger.log("routing (iteration 0)", duration.as_nanos());
+
+    let mut factors = vec![1.0; slices.len()];
+    let ((objective, statistics, mut meandata, realized_trips), duration) = measure(|| evaluate(&factors, &paths, &departures, 0, 0));
+    logger.log("simulation (iteration 0)", duration.as_nanos());
+
+    println!(
+        "Seed demand: objective={:.3}, rmse={:.3}, r²={:.4}",
+        objective, statistics.rmse, statistics.r_squared
+    );
+    append_fit_statistics_to_file(output_dir, 0, objective, &statistics, realized_trips.len());
+    let mut best = (objective, statistics, realized_trips);
+
+    // the stability constant and the gain of the step sizes, the gain is chosen once the first gradient is known
+    let stability = 0.1 * config.iterations as f64;
+    let mut gain: Option<f64> = None;
+
+    for k in 0..config.iterations {
+        let iteration = k + 1;
+
+        // route on the travel times of the current demand
+        let ((), duration) = measure(|| {
+            let (first_ipp_of_arc, ipp_travel_time, ipp_departure_time) = extract_interpolation_points_from_meandata(&meandata, &edge_ids, &free_flow_tts_ms);
+            graph = TDGraph::new(
+                Vec::from(graph.first_out()),
+                Vec::from(graph.head()),
+                first_ipp_of_arc,
+                ipp_departure_time,
+                ipp_travel_time,
+            );
+            (paths, departures) = route(&graph);
+        });
+        logger.log(&format!("routing (iteration {iteration})"), duration.as_nanos());
+
+        let perturbation = config.perturbation / (k as f64 + 1.0).powf(0.101);
+        let direction: Vec<f64> = (0..slices.len()).map(|_| if rng.random_bool(0.5) { 1.0 } else { -1.0 }).collect();
+
+        let factors_plus = clamp_factors(factors.iter().zip(&direction).map(|(f, d)| f + perturbation * d).collect(), config.max_factor);
+        let factors_minus = clamp_factors(factors.iter().zip(&direction).map(|(f, d)| f - perturbation * d).collect(), config.max_factor);
+
+        let ((objective_plus, objective_minus), duration) = measure(|| {
+            let (objective_plus, _, _, _) = evaluate(&factors_plus, &paths, &departures, iteration, 1);
+            let (objective_minus, _, _, _) = evaluate(&factors_minus, &paths, &departures, iteration, 2);
+            (objective_plus, objective_minus)
+        });
+        logger.log(&format!("perturbed simulations (iteration {iteration})"), duration.as_nanos());
+
+        let gradient: Vec<f64> = direction
+            .iter()
+            .map(|d| (objective_plus - objective_minus) / (2.0 * perturbation * d))
+            .collect();
+
+        let largest_gradient = gradient.iter().fold(0.0, |max: f64, g| max.max(g.abs()));
+        if gain.is_none() && largest_gradient > 0.0 {
+            gain = Some(config.step_size * (stability + 1.0).powf(0.602) / largest_gradient);
+        }
+
+        if let Some(gain) = gain {
+            let step = gain / (k as f64 + 1.0 + stability).powf(0.602);
+            factors = clamp_factors(factors.iter().zip(&gradient).map(|(f, g)| f - step * g).collect(), config.max_factor);
+        }
+
+        let ((objective, statistics, current_meandata, realized_trips), duration) = measure(|| evaluate(&factors, &paths, &departures, iteration, 0));
+        logger.log(&format!("simulation (iteration {iteration})"), duration.as_nanos());
+        meandata = current_meandata;
+
+        println!(
+            "Iteration {}: {} trips, objective={:.3}, rmse={:.3}, r²={:.4}, GEH<5={:.1}%",
+            iteration,
+            realized_trips.len(),
+            objective,
+            statistics.rmse,
+            statistics.r_squared,
+            100.0 * statistics.geh_below_5
+        );
+        append_fit_statistics_to_file(output_dir, iteration, objective, &statistics, realized_trips.len());
+
+        if objective < best.0 {
+            best = (objective, statistics, realized_trips);
+        }
+    }
+
+    (best.2, best.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use conversion::sumo::meandata::{Edge, Interval};
+
+    use super::*;
+
+    fn query_data() -> (Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>) {
+        // trips 0, 1 and 3 share the OD pair, trip 3 departs in the next slice
+        (vec![0; 4], vec![0; 4], vec![0, 100_000, 0, 1_000_000], vec![1, 1, 2, 1], vec![5, 5, 5, 5])
+    }
+
+    #[test]
+    fn test_od_slices() {
+        let slices = OdSlices::new(&query_data(), 900.0);
+
+        assert_eq!(slices.trips_of_slice, vec![vec![0, 1], vec![2], vec![3]]);
+        assert_eq!(slices.realize(&vec![1.0, 1.0, 1.0]), vec![0, 1, 2, 3]);
+        // the first slice grows by half, the second vanishes
+        assert_eq!(slices.realize(&vec![1.5, 0.4, 1.0]), vec![0, 1, 0, 3]);
+    }
+
+    #[test]
+    fn test_realized_trip_ids() {
+        let trip_ids = vec![String::from("a"), String::from("b")];
+
+        assert_eq!(get_realized_trip_ids(&vec![0, 1, 0, 0], &trip_ids), vec!["a", "b", "a.1", "a.2"]);
+    }
+
+    #[test]
+    fn test_edge_counts() {
+        let edge = |id: &str, entered: Option<u32>, left: Option<u32>| Edge {
+            id: String::from(id),
+            entered,
+            left,
+            ..Default::default()
+        };
+        let meandata = MeandataDocumentRoot {
+            intervals: vec![
+                Interval::create(String::from("dump"), 0.0, 300.0, vec![edge("e0", Some(3), Some(2)), edge("e1", None, Some(4))]),
+                Interval::create(
+                    String::from("dump"),
+                    300.0,
+                    600.0,
+                    vec![edge("e0", Some(5), None), edge("unknown", Some(1), None)],
+                ),
+            ],
+        };
+        let edge_ids = vec![String::from("e0"), String::from("e1")];
+
+        let (counts, aggregation) = get_observed_counts(&meandata, &edge_ids);
+        assert_eq!(aggregation, 300);
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&(0, 0)], 3.0);
+        assert_eq!(counts[&(1, 0)], 4.0);
+        assert_eq!(counts[&(0, 1)], 5.0);
+    }
+
+    #[test]
+    fn test_fit_statistics() {
+        let statistics = FitStatistics::new(&vec![10.0, 20.0, 30.0], &vec![10.0, 20.0, 30.0], 3600.0);
+        assert_eq!(statistics.rmse, 0.0);
+        assert_eq!(statistics.r_squared, 1.0);
+        assert_eq!(statistics.geh_below_5, 1.0);
+
+        let statistics = FitStatistics::new(&vec![100.0, 200.0], &vec![100.0, 300.0], 3600.0);
+        assert!((statistics.rmse - 5000.0_f64.sqrt()).abs() < 1e-9);
+        assert!((statistics.r_squared - (1.0 - 10_000.0 / 5_000.0)).abs() < 1e-9);
+        // GEH of 200 and 300 veh/h is sqrt(2 * 100² / 500) ≈ 6.3
+        assert_eq!(statistics.geh_below_5, 0.5);
+        assert_eq!(statistics.simulated_total, 400.0);
+    }
+}
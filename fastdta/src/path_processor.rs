@@ -1,5 +1,6 @@
 use conversion::SUMO_MAX_TRAVEL_TIME;
 use conversion::sumo::meandata::{Edge, Interval};
+use rayon::prelude::*;
 use rust_road_router::datastr::graph::floating_time_dependent::{EPSILON, TDGraph, TTFPoint};
 use rust_road_router::datastr::graph::{
     Graph,
@@ -8,10 +9,20 @@ use rust_road_router::datastr::graph::{
 
 use crate::traffic_model::TrafficModel;
 
+/// minimum number of paths evaluated by one rayon task
+const MIN_PATHS_PER_CHUNK: usize = 1024;
+/// number of paths whose travel times are evaluated in parallel before their updates are applied
+const PATHS_PER_ROUND: usize = 64 * MIN_PATHS_PER_CHUNK;
+
 /// given a graph, a set of old paths, a set of new paths, and their respective departure times,
 /// computes the estimated densities on the edges which vehicles are rerouted on
 /// and updates the graph's travel times accordingly.
-pub fn adjust_weights_in_graph_by_following_paths<G: TravelTimeGraph>(
+///
+/// The result is the same as following the old and then the new paths one after another with `follow_paths_sequentially`.
+/// The paths are processed in rounds: first the edge travel times of all paths of a round are evaluated in parallel on the current graph,
+/// then the paths are followed in order with these travel times and the graph is updated after every edge.
+/// Once a path reaches an edge whose travel times were updated during the round, it continues on the current graph.
+pub fn adjust_weights_in_graph_by_following_paths<G: TravelTimeGraph + Sync>(
     graph: &mut G,
     old_paths: &Vec<&Vec<u32>>,
     new_paths: &Vec<Vec<u32>>,
@@ -23,44 +34,88 @@ pub fn adjust_weights_in_graph_by_following_paths<G: TravelTimeGraph>(
     traffic_models: &Vec<Box<dyn TrafficModel>>,
     lanes: &Vec<u32>,
 ) {
-    // Debug assertion: verify periods have no holes (consecutive periods are continuous)
-    debug_assert!(intervals.windows(2).all(|w| w[0].end == w[1].begin), "Periods must be continuous with no gaps");
-
-    // dbg!(&graph.ipps());
-
-    // Process old paths (subtract travel times)
-    for (path_idx, path) in old_paths.iter().enumerate() {
-        process_path(
-            graph,
-            path,
-            departures[path_idx],
-            -1.0,
-            intervals,
-            edge_ids,
-            edge_lengths,
-            edge_free_flow_tts,
-            traffic_models,
-            lanes,
-        );
+    let mut follower = PathFollower::new(graph.num_arcs(), intervals, edge_ids, edge_lengths, edge_free_flow_tts, traffic_models, lanes);
+    follow_paths_in_rounds(graph, old_paths, new_paths, departures, &mut follower, PATHS_PER_ROUND);
+}
+
+/// follows the old paths (subtracting their travel times) and then the new paths (adding their travel times) one after another
+/// and updates the graph after every edge. This is the reference for `adjust_weights_in_graph_by_following_paths`.
+pub fn follow_paths_sequentially<G: TravelTimeGraph>(
+    graph: &mut G,
+    old_paths: &Vec<&Vec<u32>>,
+    new_paths: &Vec<Vec<u32>>,
+    departures: &Vec<Timestamp>,
+    intervals: &mut Vec<Interval>,
+    edge_ids: &Vec<String>,
+    edge_lengths: &Vec<f64>,
+    edge_free_flow_tts: &Vec<f64>,
+    traffic_models: &Vec<Box<dyn TrafficModel>>,
+    lanes: &Vec<u32>,
+) {
+    let mut follower = PathFollower::new(graph.num_arcs(), intervals, edge_ids, edge_lengths, edge_free_flow_tts, traffic_models, lanes);
+    for (path, departure, is_old_path) in ordered_paths(old_paths, new_paths, departures) {
+        follower.follow_path(graph, path, departure, is_old_path, &[]);
     }
+}
 
-    // Process new paths (add travel times)
-    for (path_idx, path) in new_paths.iter().enumerate() {
-        process_path(
-            graph,
-            path,
-            departures[path_idx],
-            1.0,
-            intervals,
-            edge_ids,
-            edge_lengths,
-            edge_free_flow_tts,
-            traffic_models,
-            lanes,
-        );
+fn follow_paths_in_rounds<G: TravelTimeGraph + Sync>(
+    graph: &mut G,
+    old_paths: &Vec<&Vec<u32>>,
+    new_paths: &Vec<Vec<u32>>,
+    departures: &Vec<Timestamp>,
+    follower: &mut PathFollower,
+    paths_per_round: usize,
+) {
+    let paths: Vec<(&Vec<u32>, Timestamp, bool)> = ordered_paths(old_paths, new_paths, departures).collect();
+
+    for round in paths.chunks(paths_per_round) {
+        let graph_ref = &*graph;
+        let intervals = &*follower.intervals;
+        let travel_times: Vec<Vec<FlWeight>> = round
+            .par_iter()
+            .with_min_len(MIN_PATHS_PER_CHUNK)
+            .map(|&(path, departure, _)| edge_travel_times(graph_ref, path, departure, intervals))
+            .collect();
+
+        for (&(path, departure, is_old_path), travel_times) in round.iter().zip(&travel_times) {
+            follower.follow_path(graph, path, departure, is_old_path, travel_times);
+        }
+        follower.reset_updated_edges();
     }
 }
 
+/// the old paths followed by the new paths, the path at index `i` of either set departs at `departures[i]`
+fn ordered_paths<'a>(
+    old_paths: &'a Vec<&Vec<u32>>,
+    new_paths: &'a Vec<Vec<u32>>,
+    departures: &'a Vec<Timestamp>,
+) -> impl Iterator<Item = (&'a Vec<u32>, Timestamp, bool)> + 'a {
+    old_paths
+        .iter()
+        .enumerate()
+        .map(|(path_idx, &path)| (path, departures[path_idx], true))
+        .chain(new_paths.iter().enumerate().map(|(path_idx, path)| (path, departures[path_idx], false)))
+}
+
+/// travel times of the edges of a path on the current graph, until the path leaves the intervals
+fn edge_travel_times<G: TravelTimeGraph>(graph: &G, path: &[u32], departure_time: Timestamp, intervals: &[Interval]) -> Vec<FlWeight> {
+    let (Some(first), Some(last)) = (intervals.first(), intervals.last()) else {
+        return Vec::new();
+    };
+
+    let mut current_time = departure_time;
+    let mut travel_times = Vec::with_capacity(path.len());
+    for &edge_id in path {
+        if f64::from(current_time) < first.begin || f64::from(current_time) >= last.end {
+            break;
+        }
+        let travel_time = graph.get_travel_time_along_path(current_time, &[edge_id]);
+        travel_times.push(travel_time);
+        current_time = current_time + travel_time;
+    }
+    travel_times
+}
+
 /// Trait for graphs that can provide travel time calculations
 /// This allows for mocking in tests
 pub trait TravelTimeGraph {
@@ -89,135 +144,158 @@ impl TravelTimeGraph for TDGraph {
     }
 }
 
-/// Process a single path and update edge occupancy deltas
-fn process_path<G: TravelTimeGraph>(
-    graph: &mut G,
-    path: &Vec<u32>,
-    departure_time: Timestamp,
-    sign: f64,
-    intervals: &mut Vec<Interval>,
-    edge_ids: &Vec<String>,
-    edge_lengths: &Vec<f64>,
-    edge_free_flow_tts: &Vec<f64>,
-    traffic_models: &Vec<Box<dyn TrafficModel>>,
-    lanes: &Vec<u32>,
-) {
-    let mut current_time = departure_time;
-    for &edge_id in path {
-        let travel_time = graph.get_travel_time_along_path(current_time, &[edge_id]);
-        // dbg!(f64::from(travel_time));
-        // println!("Edge ID: {}, Travel Time: {:?}", edge_id, travel_time);
-        let arrival_time = current_time + travel_time;
-        // dbg!(arrival_time);
-        let bin_search_res = match intervals.binary_search_by(|interval| {
-            // if current time is between start and end: return equal:
-            if interval.begin <= f64::from(current_time) && f64::from(current_time) < interval.end {
-                std::cmp::Ordering::Equal
-            } else if f64::from(current_time) < interval.begin {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Less
+/// updates the meandata intervals and the graph while following paths
+struct PathFollower<'a> {
+    intervals: &'a mut Vec<Interval>,
+    edge_ids: &'a Vec<String>,
+    edge_lengths: &'a Vec<f64>,
+    edge_free_flow_tts: &'a Vec<f64>,
+    traffic_models: &'a Vec<Box<dyn TrafficModel>>,
+    lanes: &'a Vec<u32>,
+    /// edges whose travel times were updated since the last reset
+    is_updated: Vec<bool>,
+    updated_edges: Vec<u32>,
+}
+
+impl<'a> PathFollower<'a> {
+    fn new(
+        num_arcs: usize,
+        intervals: &'a mut Vec<Interval>,
+        edge_ids: &'a Vec<String>,
+        edge_lengths: &'a Vec<f64>,
+        edge_free_flow_tts: &'a Vec<f64>,
+        traffic_models: &'a Vec<Box<dyn TrafficModel>>,
+        lanes: &'a Vec<u32>,
+    ) -> Self {
+        // Debug assertion: verify periods have no holes (consecutive periods are continuous)
+        debug_assert!(intervals.windows(2).all(|w| w[0].end == w[1].begin), "Periods must be continuous with no gaps");
+
+        Self {
+            intervals,
+            edge_ids,
+            edge_lengths,
+            edge_free_flow_tts,
+            traffic_models,
+            lanes,
+            is_updated: vec![false; num_arcs],
+            updated_edges: Vec::new(),
+        }
+    }
+
+    fn reset_updated_edges(&mut self) {
+        for edge_id in self.updated_edges.drain(..) {
+            self.is_updated[edge_id as usize] = false;
+        }
+    }
+
+    /// Follow a single path and update the time spent on each edge within each interval.
+    /// `travel_times` are the edge travel times evaluated before the last reset, they are used as long as the path
+    /// does not reach an edge which was updated since then. All later travel times are evaluated on the current graph.
+    fn follow_path<G: TravelTimeGraph>(&mut self, graph: &mut G, path: &[u32], departure_time: Timestamp, is_old_path: bool, travel_times: &[FlWeight]) {
+        let edge_ids = self.edge_ids;
+        let sign = if is_old_path { -1.0 } else { 1.0 };
+        let mut current_time = departure_time;
+        let mut travel_times = travel_times.iter();
+        // the time along the path never decreases, so the search for the entry interval continues from the previous one
+        let mut entry_interval = 0;
+
+        for &edge_id in path {
+            while entry_interval < self.intervals.len() && self.intervals[entry_interval].end <= f64::from(current_time) {
+                entry_interval += 1;
             }
-        }) {
-            Ok(idx) => idx,
-            Err(_) => {
+            if entry_interval == self.intervals.len() || f64::from(current_time) < self.intervals[entry_interval].begin {
                 // no more periods to process
-                // println!("Binary search for current_time {} returned Err({})", f64::from(current_time), idx);
                 return;
             }
-        };
 
-        if let Some(interval) = intervals.get_mut(bin_search_res) {
+            let travel_time = match travel_times.next() {
+                Some(&travel_time) if !self.is_updated[edge_id as usize] => travel_time,
+                _ => {
+                    // the remaining precomputed travel times depend on this one, so they are invalid as well
+                    travel_times = [].iter();
+                    graph.get_travel_time_along_path(current_time, &[edge_id])
+                }
+            };
+            let arrival_time = current_time + travel_time;
+            let edge_name = edge_ids[edge_id as usize].as_str();
+
             // add edge, if not present
-            if interval.get_edge(edge_ids[edge_id as usize].as_str()).is_none() {
+            let interval = &mut self.intervals[entry_interval];
+            if interval.get_edge(edge_name).is_none() {
                 interval.add_edge(Edge {
-                    id: edge_ids[edge_id as usize].clone(),
+                    id: edge_name.to_string(),
                     sampled_seconds: Some(0.0),
                     ..Default::default()
                 });
             }
-
-            // set departed to edge.departed = edge.departed + sign * 1;
-            if let Some(edge) = interval.get_edge_mut(edge_ids[edge_id as usize].as_str()) {
-                edge.dbg_entered = edge.dbg_entered + (sign as u32);
+            if !is_old_path {
+                interval.get_edge_mut(edge_name).unwrap().dbg_entered += 1;
             }
-        }
 
-        for (_interval_idx, interval) in intervals.iter_mut().skip(bin_search_res).enumerate() {
-            // Skip periods that start after or at our travel ends
-            if current_time >= arrival_time || interval.begin >= f64::from(arrival_time) {
-                if let Some(interval) = intervals.get_mut(bin_search_res) {
-                    // set departed to edge.departed = edge.departed + sign * 1;
-                    if let Some(edge) = interval.get_edge_mut(edge_ids[edge_id as usize].as_str()) {
-                        edge.dbg_left = edge.dbg_left + (sign as u32);
+            for interval_idx in entry_interval..self.intervals.len() {
+                let interval = &mut self.intervals[interval_idx];
+                // Skip periods that start after or at our travel ends
+                if current_time >= arrival_time || interval.begin >= f64::from(arrival_time) {
+                    if !is_old_path {
+                        self.intervals[entry_interval].get_edge_mut(edge_name).unwrap().dbg_left += 1;
                     }
+                    break; // No more travel time to distribute
                 }
-                break; // No more travel time to distribute
-            }
 
-            // Calculate the overlap between travel time and this period
-            let overlap_start = f64::from(current_time).max(interval.begin);
-            let overlap_end = f64::from(arrival_time).min(interval.end);
-            let overlap_duration = overlap_end - overlap_start;
-
-            if overlap_duration > 0.0 {
-                let interval_duration = interval.end - interval.begin;
-                let interval_begin = interval.begin;
-
-                interval.get_edge_mut(edge_ids[edge_id as usize].as_str()).map(|edge| {
-                    // let previous_sampled = edge.sampled_seconds.unwrap_or(0.0);
-                    // let previous_tt = edge.overlap_traveltime;
-                    // let previous_density = edge.lane_density.unwrap_or(edge.density.unwrap_or(0.0));
-                    // let previous_estimated_density = edge.get_lane_density(interval_duration, edge_lengths[edge_id as usize], lanes[edge_id as usize]);
-                    edge.sampled_seconds = Some(f64::max(edge.sampled_seconds.unwrap_or(0.0) + sign * overlap_duration, 0.0));
-
-                    let estimated_density = edge.get_lane_density(interval_duration, edge_lengths[edge_id as usize], lanes[edge_id as usize]);
-
-                    edge.lane_density = Some(estimated_density);
-
-                    let estimated_tt = traffic_models.get(edge_id as usize).map_or(edge_free_flow_tts[edge_id as usize], |tm| {
-                        let speed = tm.get_speed(estimated_density) / 3.6;
-
-                        if speed.abs() < EPSILON {
-                            return SUMO_MAX_TRAVEL_TIME;
-                        }
-
-                        edge.speed = Some(speed);
-                        let tt = edge_lengths[edge_id as usize] / speed;
-                        if tt < 0.0 {
-                            return SUMO_MAX_TRAVEL_TIME;
-                        }
-                        f64::min(tt, SUMO_MAX_TRAVEL_TIME)
-                    });
-
-                    // if edge_ids[edge_id as usize] == "a2" && interval_begin == 1550.0 {
-                    //     println!(
-                    //         "Update edge {} (l={}) at time {}: sampled_seconds: {:?} -> {:?}, traveltime: {:?} -> {:?}, density: {} (est.: {}) -> {}",
-                    //         edge_ids[edge_id as usize],
-                    //         edge_lengths[edge_id as usize],
-                    //         interval_begin,
-                    //         previous_sampled,
-                    //         edge.sampled_seconds,
-                    //         previous_tt,
-                    //         estimated_tt,
-                    //         previous_density,
-                    //         previous_estimated_density,
-                    //         estimated_density
-                    //     );
-                    //     if estimated_tt < 0.0 {
-                    //         let tm = traffic_models.get(edge_id as usize).unwrap();
-                    //         tm.debug();
-                    //         panic!("Estimated travel time for edge {} is negative: {}", edge_ids[edge_id as usize], estimated_tt);
-                    //     }
-                    // }
-
-                    graph.set_weight_for_edge_at_time(edge_id, Timestamp::new(interval_begin), FlWeight::new(estimated_tt));
-                    edge.overlap_traveltime = Some(estimated_tt);
-                });
+                // Calculate the overlap between travel time and this period
+                let overlap_start = f64::from(current_time).max(interval.begin);
+                let overlap_end = f64::from(arrival_time).min(interval.end);
+                let overlap_duration = overlap_end - overlap_start;
+
+                if overlap_duration > 0.0 {
+                    self.update_edge(graph, interval_idx, edge_id, sign * overlap_duration);
+                }
+                // Move to the next period boundary for the next iteration
+                current_time = Timestamp::new(overlap_end);
             }
-            // Move to the next period boundary for the next iteration
-            current_time = Timestamp::new(overlap_end);
-            // dbg!(current_time);
+        }
+    }
+
+    /// adds the given seconds to the edge within the interval and updates its travel time with the traffic model of the edge.
+    /// Time spent on an edge which is not present in the interval is dropped.
+    fn update_edge<G: TravelTimeGraph>(&mut self, graph: &mut G, interval_idx: usize, edge_id: u32, seconds: f64) {
+        let interval = &mut self.intervals[interval_idx];
+        let interval_duration = interval.end - interval.begin;
+        let interval_begin = interval.begin;
+        let Some(edge) = interval.get_edge_mut(self.edge_ids[edge_id as usize].as_str()) else {
+            return;
+        };
+
+        edge.sampled_seconds = Some(f64::max(edge.sampled_seconds.unwrap_or(0.0) + seconds, 0.0));
+
+        let estimated_density = edge.get_lane_density(interval_duration, self.edge_lengths[edge_id as usize], self.lanes[edge_id as usize]);
+
+        edge.lane_density = Some(estimated_density);
+
+        let estimated_tt = self
+            .traffic_models
+            .get(edge_id as usize)
+            .map_or(self.edge_free_flow_tts[edge_id as usize], |tm| {
+                let speed = tm.get_speed(estimated_density) / 3.6;
+
+                if speed.abs() < EPSILON {
+                    return SUMO_MAX_TRAVEL_TIME;
+                }
+
+                edge.speed = Some(speed);
+                let tt = self.edge_lengths[edge_id as usize] / speed;
+                if tt < 0.0 {
+                    return SUMO_MAX_TRAVEL_TIME;
+                }
+                f64::min(tt, SUMO_MAX_TRAVEL_TIME)
+            });
+
+        graph.set_weight_for_edge_at_time(edge_id, Timestamp::new(interval_begin), FlWeight::new(estimated_tt));
+        edge.overlap_traveltime = Some(estimated_tt);
+
+        if !self.is_updated[edge_id as usize] {
+            self.is_updated[edge_id as usize] = true;
+            self.updated_edges.push(edge_id);
         }
     }
 }
@@ -737,4 +815,239 @@ mod tests {
             total_sampled
         );
     }
+
+    #[test]
+    fn test_many_paths_accumulate_across_rounds() {
+        // more paths than fit into one round, each spends 4 seconds on edge 0 in period 0 and 6 seconds on edge 1 in period 1
+        let mut mock_graph = MockTravelTimeGraph::new(2);
+        mock_graph.set_travel_time(0, FlWeight::new(4.0));
+        mock_graph.set_travel_time(1, FlWeight::new(6.0));
+
+        let num_paths = 3 * PATHS_PER_ROUND + 7;
+        let old_paths_owned = vec![vec![0]; num_paths];
+        let old_paths: Vec<&Vec<u32>> = old_paths_owned.iter().collect();
+        let new_paths = vec![vec![0, 1]; num_paths];
+        let departures = vec![Timestamp::new(6.0); num_paths];
+
+        let mut intervals = vec![
+            Interval::create(
+                "0".to_string(),
+                0.0,
+                10.0,
+                vec![Edge {
+                    id: "edge0".to_string(),
+                    sampled_seconds: Some(100.0),
+                    ..Default::default()
+                }],
+            ),
+            Interval::create("1".to_string(), 10.0, 20.0, vec![]),
+        ];
+        let edge_ids = vec!["edge0".to_string(), "edge1".to_string()];
+        let traffic_models: Vec<Box<dyn TrafficModel>> = vec![];
+
+        adjust_weights_in_graph_by_following_paths(
+            &mut mock_graph,
+            &old_paths,
+            &new_paths,
+            &departures,
+            &mut intervals,
+            &edge_ids,
+            &vec![100.0, 100.0],
+            &vec![1.0, 1.0],
+            &traffic_models,
+            &vec![1, 1],
+        );
+
+        // removing the old paths clamps edge 0 at zero before the new paths are added again
+        let sampled0 = intervals[0].get_edge("edge0").unwrap().sampled_seconds.unwrap();
+        let sampled1 = intervals[1].get_edge("edge1").unwrap().sampled_seconds.unwrap();
+        assert!((sampled0 - 4.0 * num_paths as f64).abs() < 1e-6, "got {}", sampled0);
+        assert!((sampled1 - 6.0 * num_paths as f64).abs() < 1e-6, "got {}", sampled1);
+        let edge0 = intervals[0].get_edge("edge0").unwrap();
+        assert_eq!((edge0.dbg_entered, edge0.dbg_left), (num_paths as u32, num_paths as u32));
+        assert_eq!(intervals[1].get_edge("edge1").unwrap().dbg_entered, num_paths as u32);
+    }
+
+    /// speed decreasing linearly with the density
+    struct LinearSpeed {
+        free_flow_speed: f64,
+        jam_density: f64,
+    }
+
+    impl TrafficModel for LinearSpeed {
+        fn get_speed(&self, density: f64) -> f64 {
+            self.free_flow_speed * (1.0 - density / self.jam_density)
+        }
+
+        fn calibrate(&mut self, _observed_speed: &[f64], _observed_density: &[f64]) {}
+
+        fn debug(&self) {}
+
+        fn get_params_as_vec(&self) -> Vec<f64> {
+            vec![self.free_flow_speed, self.jam_density]
+        }
+
+        fn from_vec(params: &Vec<f64>) -> Self {
+            Self {
+                free_flow_speed: params[0],
+                jam_density: params[1],
+            }
+        }
+    }
+
+    const TD_EDGE_IDS: [&str; 4] = ["e0", "e1", "e2", "e3"];
+
+    /// two routes from node 0 to node 2, 0 -> 1 -> 2 over edges 0 and 1 and 0 -> 3 -> 2 over edges 2 and 3.
+    /// Every edge takes 20s to cross and has a travel time point at the begin of each of the four 100s intervals.
+    fn td_fixture() -> (TDGraph, Vec<Interval>) {
+        let graph = TDGraph::new(
+            vec![0, 2, 3, 3, 4],
+            vec![1, 3, 2, 2],
+            vec![0, 5, 10, 15, 20],
+            [0, 100_000, 200_000, 300_000, 400_000].repeat(4),
+            vec![20_000; 20],
+        );
+
+        // the first and the third interval already contain some traffic on all edges, the others are empty
+        let intervals = (0..4)
+            .map(|i| {
+                let edges = if i % 2 == 0 {
+                    TD_EDGE_IDS
+                        .iter()
+                        .map(|id| Edge {
+                            id: id.to_string(),
+                            sampled_seconds: Some(50.0),
+                            overlap_traveltime: Some(20.0),
+                            ..Default::default()
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+                Interval::create(i.to_string(), 100.0 * i as f64, 100.0 * (i + 1) as f64, edges)
+            })
+            .collect();
+
+        (graph, intervals)
+    }
+
+    /// follows the paths in rounds of the given size and one after another on copies of the fixture
+    fn run_both(
+        old_paths: &Vec<&Vec<u32>>,
+        new_paths: &Vec<Vec<u32>>,
+        departures: &Vec<Timestamp>,
+        paths_per_round: usize,
+    ) -> ((TDGraph, Vec<Interval>), (TDGraph, Vec<Interval>)) {
+        let edge_ids: Vec<String> = TD_EDGE_IDS.iter().map(|id| id.to_string()).collect();
+        let edge_lengths = vec![500.0; 4];
+        let free_flow_tts = vec![20.0; 4];
+        let lanes = vec![1; 4];
+        let traffic_models: Vec<Box<dyn TrafficModel>> = (0..4)
+            .map(|_| {
+                Box::new(LinearSpeed {
+                    free_flow_speed: 90.0,
+                    jam_density: 100.0,
+                }) as Box<dyn TrafficModel>
+            })
+            .collect();
+
+        let (mut parallel_graph, mut parallel_intervals) = td_fixture();
+        let mut follower = PathFollower::new(
+            TravelTimeGraph::num_arcs(&parallel_graph),
+            &mut parallel_intervals,
+            &edge_ids,
+            &edge_lengths,
+            &free_flow_tts,
+            &traffic_models,
+            &lanes,
+        );
+        follow_paths_in_rounds(&mut parallel_graph, old_paths, new_paths, departures, &mut follower, paths_per_round);
+
+        let (mut sequential_graph, mut sequential_intervals) = td_fixture();
+        follow_paths_sequentially(
+            &mut sequential_graph,
+            old_paths,
+            new_paths,
+            departures,
+            &mut sequential_intervals,
+            &edge_ids,
+            &edge_lengths,
+            &free_flow_tts,
+            &traffic_models,
+            &lanes,
+        );
+
+        ((parallel_graph, parallel_intervals), (sequential_graph, sequential_intervals))
+    }
+
+    fn assert_close(a: Option<f64>, b: Option<f64>, what: &str) {
+        match (a, b) {
+            (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9, "{}: {} != {}", what, a, b),
+            _ => assert_eq!(a, b, "{}", what),
+        }
+    }
+
+    fn assert_same(parallel: &mut (TDGraph, Vec<Interval>), sequential: &mut (TDGraph, Vec<Interval>)) {
+        for (parallel, sequential) in parallel.1.iter_mut().zip(sequential.1.iter_mut()) {
+            assert_eq!(parallel.edges.len(), sequential.edges.len(), "edges of interval {}", parallel.id);
+            for id in TD_EDGE_IDS {
+                let (Some(p), Some(s)) = (parallel.get_edge(id).cloned(), sequential.get_edge(id).cloned()) else {
+                    assert!(
+                        parallel.get_edge(id).is_none() && sequential.get_edge(id).is_none(),
+                        "{} in interval {}",
+                        id,
+                        parallel.id
+                    );
+                    continue;
+                };
+                let what = format!("{} in interval {}", id, parallel.id);
+                assert_close(p.sampled_seconds, s.sampled_seconds, &what);
+                assert_close(p.lane_density, s.lane_density, &what);
+                assert_close(p.speed, s.speed, &what);
+                assert_close(p.overlap_traveltime, s.overlap_traveltime, &what);
+                assert_eq!((p.dbg_entered, p.dbg_left), (s.dbg_entered, s.dbg_left), "{}", what);
+            }
+        }
+
+        for (p, s) in parallel.0.ipps.iter().zip(sequential.0.ipps.iter()) {
+            assert!(p.at.fuzzy_eq(s.at) && p.val.fuzzy_eq(s.val), "{:?} != {:?}", p, s);
+        }
+    }
+
+    #[test]
+    fn test_matches_sequential_following_on_td_graph() {
+        // two trips swap their routes
+        let old_paths_owned = vec![vec![0, 1], vec![2, 3]];
+        let old_paths: Vec<&Vec<u32>> = old_paths_owned.iter().collect();
+        let new_paths = vec![vec![2, 3], vec![0, 1]];
+        let departures = vec![Timestamp::new(10.0), Timestamp::new(250.0)];
+
+        let (mut parallel, mut sequential) = run_both(&old_paths, &new_paths, &departures, PATHS_PER_ROUND);
+        assert_same(&mut parallel, &mut sequential);
+
+        // the rerouted trips actually change the traffic, so the comparison is not trivial
+        assert!(parallel.1[2].get_edge("e0").unwrap().sampled_seconds.unwrap() > 50.0);
+    }
+
+    #[test]
+    fn test_paths_see_updates_of_earlier_paths() {
+        // many trips on the same edges in the same intervals, each one is slowed down by the ones before it
+        let old_paths_owned: Vec<Vec<u32>> = (0..20).map(|i| if i % 2 == 0 { vec![0, 1] } else { vec![2, 3] }).collect();
+        let old_paths: Vec<&Vec<u32>> = old_paths_owned.iter().collect();
+        let new_paths: Vec<Vec<u32>> = (0..20).map(|i| if i % 3 == 0 { vec![2, 3] } else { vec![0, 1] }).collect();
+        let departures: Vec<Timestamp> = (0..20).map(|i| Timestamp::new(5.0 + 17.0 * i as f64)).collect();
+
+        for paths_per_round in [1, 2, 3, 7, PATHS_PER_ROUND] {
+            let (mut parallel, mut sequential) = run_both(&old_paths, &new_paths, &departures, paths_per_round);
+            assert_same(&mut parallel, &mut sequential);
+        }
+
+        // the first trips already slow down the later ones, so following all paths on the initial travel times would differ
+        let new_paths = vec![vec![0], vec![0]];
+        let departures = vec![Timestamp::new(10.0), Timestamp::new(20.0)];
+        let (mut parallel, mut sequential) = run_both(&vec![], &new_paths, &departures, PATHS_PER_ROUND);
+        assert_same(&mut parallel, &mut sequential);
+        let sampled = parallel.1[0].get_edge("e0").unwrap().sampled_seconds.unwrap();
+        assert!(sampled > 90.0 + 1e-6, "got {}", sampled);
+    }
 }
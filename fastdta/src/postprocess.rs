@@ -1,16 +1,24 @@
 use std::path::Path;
 
-use conversion::{DIR_DTA, SerializedTimestamp, sumo::paths_to_sumo_routes_converter::write_paths_as_sumo_routes};
+use conversion::{
+    DIR_DTA, FILE_EDGE_LENGTHS, FILE_QUERY_IDS, SerializedTimestamp,
+    sumo::paths_to_sumo_routes_converter::{RouteDetails, write_paths_as_sumo_routes},
+};
 use rand::{Rng, SeedableRng, rngs};
-use rust_road_router::datastr::graph::{
-    EdgeId,
-    floating_time_dependent::{FlWeight, TDGraph, Timestamp},
+use rust_road_router::{
+    datastr::graph::{
+        EdgeId,
+        floating_time_dependent::{FlWeight, TDGraph, Timestamp},
+    },
+    io::{Load, read_strings_from_file},
 };
 
 use crate::{
     alternative_paths::AlternativePathsForDTA,
     choice::ChoiceAlgorithm,
-    relative_gap::{append_relative_gap_to_file, get_relative_gap},
+    convergence::{ConvergenceReport, DEFAULT_DEPARTURE_INTERVAL},
+    relative_gap::{append_relative_gap_of_class_to_file, append_relative_gap_to_file, get_relative_gap},
+    router_options::{RouterOptions, remove_loops},
 };
 
 pub fn prepare_next_iteration_for_sampled_routing(
@@ -27,6 +35,7 @@ pub fn prepare_next_iteration_for_sampled_routing(
     seed: i32,
     edge_indices_to_id: &Vec<String>,
     keep_routes: &Vec<bool>,
+    options: &RouterOptions,
 ) {
     postprocess(
         input_dir,
@@ -43,6 +52,7 @@ pub fn prepare_next_iteration_for_sampled_routing(
         edge_indices_to_id,
         keep_routes,
         true,
+        options,
     );
 }
 
@@ -68,6 +78,7 @@ pub fn prepare_next_iteration(
     seed: i32,
     edge_indices_to_id: &Vec<String>,
     keep_route_probability: f64,
+    options: &RouterOptions,
 ) {
     let keep_routes: Vec<bool> = if keep_route_probability <= 0.0 {
         vec![false; shortest_paths.len()]
@@ -93,6 +104,7 @@ pub fn prepare_next_iteration(
         edge_indices_to_id,
         &keep_routes,
         false,
+        options,
     );
 }
 
@@ -111,35 +123,80 @@ fn postprocess(
     edge_indices_to_id: &Vec<String>,
     keep_routes: &Vec<bool>,
     skip_relative_gap: bool,
+    options: &RouterOptions,
 ) {
     let current_iteration_dir = input_dir.join(format!("{:0>3}", iteration));
 
+    let loop_free_paths = options
+        .remove_loops
+        .then(|| remove_loops_from_paths(new_paths, new_paths_tt, departures, graph));
+    let (new_paths, new_paths_tt) = match &loop_free_paths {
+        Some((paths, travel_times)) => (paths, travel_times),
+        None => (new_paths, new_paths_tt),
+    };
+
     // init all_routes with the previous alternatives
     let alternative_paths = if iteration > 0 {
         // load previous alternatives from input_dir
         let previous_iteration_dir = input_dir.join(format!("{:0>3}", iteration - 1));
         let old_alternative_paths: AlternativePathsForDTA = AlternativePathsForDTA::reconstruct(&previous_iteration_dir.join(DIR_DTA));
 
-        if !skip_relative_gap {
-            // get choices from old_alternative_paths to calculate relative gap
-            set_relative_gap_with_previous_paths(&old_alternative_paths.get_chosen_paths(), graph, &input_dir, &new_paths_tt, departures);
-        }
+        // get choices from old_alternative_paths to calculate relative gap
+        let experienced_tts = (!skip_relative_gap)
+            .then(|| set_relative_gap_with_previous_paths(&old_alternative_paths.get_chosen_paths(), graph, &input_dir, &new_paths_tt, departures, options));
 
         // merge previous alternatives with current shortest paths
-        let mut new_alternative_paths = old_alternative_paths.update_alternatives_with_new_paths(&new_paths, &new_paths_tt, &departures, &graph);
+        let mut new_alternative_paths = if options.skip_new_routes {
+            old_alternative_paths.update_alternatives_skipping_new_paths(&new_paths, &new_paths_tt, &departures, &graph)
+        } else {
+            old_alternative_paths.update_alternatives_with_new_paths(&new_paths, &new_paths_tt, &departures, &graph)
+        };
 
-        new_alternative_paths.perform_choice_model(&old_alternative_paths, &choice_algorithm, max_alternatives, &keep_routes, seed);
+        match &options.user_classes {
+            Some(user_classes) => new_alternative_paths.perform_choice_model_for_user_classes(
+                &old_alternative_paths,
+                &choice_algorithm,
+                user_classes,
+                max_alternatives,
+                &keep_routes,
+                seed,
+            ),
+            None => new_alternative_paths.perform_choice_model(&old_alternative_paths, &choice_algorithm, max_alternatives, &keep_routes, seed),
+        }
+        options.clean_up_alternatives(&mut new_alternative_paths, graph);
+
+        if let Some(experienced_tts) = experienced_tts {
+            write_convergence_report(
+                input_dir,
+                iteration,
+                new_paths_tt,
+                &experienced_tts,
+                departures,
+                Some(&new_alternative_paths),
+                Some(&old_alternative_paths),
+            );
+        }
 
         new_alternative_paths
     } else {
         if !skip_relative_gap {
             // initialize relative gap file with 0.0 for the first iteration
             append_relative_gap_to_file(0.0, &input_dir);
+            if let Some(user_classes) = &options.user_classes {
+                for class in &user_classes.classes {
+                    append_relative_gap_of_class_to_file(&class.name, 0.0, &input_dir);
+                }
+            }
+        }
+        let mut alternative_paths = AlternativePathsForDTA::init(new_paths, new_paths_tt);
+        if let Some(user_classes) = &options.user_classes {
+            user_classes.apply_generalized_costs(&mut alternative_paths);
         }
-        AlternativePathsForDTA::init(new_paths, new_paths_tt)
+        alternative_paths
     };
 
     let (path_sets, costs, probabilities, choices) = transform_alternative_paths_for_dta_to_vectors(&alternative_paths);
+    let route_details = get_route_details(input_dir, &path_sets, departures, graph, options);
 
     write_paths_as_sumo_routes(
         &input_dir,
@@ -152,11 +209,50 @@ fn postprocess(
         &departures,
         &edge_indices_to_id,
         write_sumo_alternatives,
+        &route_details,
     );
 
     alternative_paths.deconstruct(&current_iteration_dir.join(DIR_DTA)).unwrap();
 }
 
+/// removes loops from the new paths and updates their travel times accordingly
+fn remove_loops_from_paths(
+    paths: &Vec<Vec<EdgeId>>,
+    travel_times: &Vec<FlWeight>,
+    departures: &Vec<SerializedTimestamp>,
+    graph: &TDGraph,
+) -> (Vec<Vec<EdgeId>>, Vec<FlWeight>) {
+    paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let loop_free_path = remove_loops(path, graph);
+            if loop_free_path.len() == path.len() {
+                (loop_free_path, travel_times[i])
+            } else {
+                let travel_time = graph.get_travel_time_along_path(Timestamp::from_millis(departures[i]), &loop_free_path);
+                (loop_free_path, travel_time)
+            }
+        })
+        .unzip()
+}
+
+fn get_route_details(
+    input_dir: &Path,
+    path_sets: &Vec<Vec<Vec<EdgeId>>>,
+    departures: &Vec<SerializedTimestamp>,
+    graph: &TDGraph,
+    options: &RouterOptions,
+) -> Vec<Vec<RouteDetails>> {
+    let edge_lengths = if options.route_length {
+        Vec::<f64>::load_from(&input_dir.join(FILE_EDGE_LENGTHS)).unwrap()
+    } else {
+        vec![]
+    };
+
+    options.get_route_details(path_sets, departures, graph, &edge_lengths)
+}
+
 fn transform_alternative_paths_for_dta_to_vectors(
     alternative_paths: &AlternativePathsForDTA,
 ) -> (Vec<Vec<Vec<u32>>>, Vec<Vec<f64>>, Vec<Vec<f64>>, Vec<usize>) {
@@ -186,16 +282,53 @@ pub fn set_relative_gap_with_previous_paths(
     input_dir: &Path,
     travel_times: &Vec<FlWeight>,
     departures: &Vec<SerializedTimestamp>,
-) {
+    options: &RouterOptions,
+) -> Vec<f64> {
     let simulated_tts: Vec<f64> = previous_paths
         .iter()
         .enumerate()
         .map(|(i, path)| f64::from(graph.get_travel_time_along_path(Timestamp::from_millis(departures[i]), path)))
         .collect();
 
-    let rel_gap = get_relative_gap(&travel_times.iter().map(|tt| f64::from(*tt)).collect(), &simulated_tts);
+    let best_tts: Vec<f64> = travel_times.iter().map(|tt| f64::from(*tt)).collect();
+    let rel_gap = get_relative_gap(&best_tts, &simulated_tts);
 
     append_relative_gap_to_file(rel_gap, &input_dir);
+
+    if let Some(user_classes) = &options.user_classes {
+        for (class_name, class_rel_gap) in user_classes.get_relative_gaps(&best_tts, &simulated_tts) {
+            println!("Relative gap of user class {}: {:.6}", class_name, class_rel_gap);
+            append_relative_gap_of_class_to_file(&class_name, class_rel_gap, &input_dir);
+        }
+    }
+
+    simulated_tts
+}
+
+/// writes the convergence diagnostics of the iteration, see `ConvergenceReport`
+pub fn write_convergence_report(
+    input_dir: &Path,
+    iteration: u32,
+    best_tts: &Vec<FlWeight>,
+    experienced_tts: &Vec<f64>,
+    departures: &Vec<SerializedTimestamp>,
+    alternative_paths: Option<&AlternativePathsForDTA>,
+    previous_alternative_paths: Option<&AlternativePathsForDTA>,
+) {
+    let query_ids: Vec<String> = read_strings_from_file(&input_dir.join(FILE_QUERY_IDS)).unwrap();
+
+    ConvergenceReport::new(
+        iteration,
+        &best_tts.iter().map(|&tt| f64::from(tt)).collect(),
+        experienced_tts,
+        departures,
+        &query_ids,
+        DEFAULT_DEPARTURE_INTERVAL,
+        alternative_paths,
+        previous_alternative_paths,
+    )
+    .write(input_dir)
+    .unwrap();
 }
 
 /// Prepare next iteration for FastDTA2 routing
@@ -212,17 +345,44 @@ pub fn prepare_next_iteration_for_fastdta2(
     graph: &TDGraph,
     write_sumo_alternatives: bool,
     edge_indices_to_id: &Vec<String>,
+    options: &RouterOptions,
 ) {
     let current_iteration_dir = input_dir.join(format!("{:0>3}", iteration));
 
     // Calculate relative gap if iteration > 0
-    if iteration > 0 {
+    let previous_iteration = if iteration > 0 {
         let previous_iteration_dir = input_dir.join(format!("{:0>3}", iteration - 1));
         let old_alternative_paths: AlternativePathsForDTA = AlternativePathsForDTA::reconstruct(&previous_iteration_dir.join(DIR_DTA));
-        set_relative_gap_with_previous_paths(&old_alternative_paths.get_chosen_paths(), graph, &input_dir, &shortest_travel_times, departures);
+        let experienced_tts = set_relative_gap_with_previous_paths(
+            &old_alternative_paths.get_chosen_paths(),
+            graph,
+            &input_dir,
+            &shortest_travel_times,
+            departures,
+            options,
+        );
+        Some((old_alternative_paths, experienced_tts))
+    } else {
+        None
+    };
+
+    let mut alternative_paths = alternative_paths.clone();
+    options.clean_up_alternatives(&mut alternative_paths, graph);
+
+    if let Some((old_alternative_paths, experienced_tts)) = &previous_iteration {
+        write_convergence_report(
+            input_dir,
+            iteration,
+            shortest_travel_times,
+            experienced_tts,
+            departures,
+            Some(&alternative_paths),
+            Some(old_alternative_paths),
+        );
     }
 
     let (path_sets, costs, probabilities, choices) = transform_alternative_paths_for_dta_to_vectors(&alternative_paths);
+    let route_details = get_route_details(input_dir, &path_sets, departures, graph, options);
 
     write_paths_as_sumo_routes(
         &input_dir,
@@ -235,7 +395,8 @@ pub fn prepare_next_iteration_for_fastdta2(
         &departures,
         &edge_indices_to_id,
         write_sumo_alternatives,
+        &route_details,
     );
 
-    alternative_paths.clone().deconstruct(&current_iteration_dir.join(DIR_DTA)).unwrap();
+    alternative_paths.deconstruct(&current_iteration_dir.join(DIR_DTA)).unwrap();
 }
@@ -1,11 +1,14 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use conversion::{
     DIR_DTA, FILE_EDGE_DEFAULT_TRAVEL_TIMES, FILE_EDGE_INDICES_TO_ID, FILE_EDGE_LANES, FILE_EDGE_LENGTHS, FILE_EDGE_SPEEDS, GLOBAL_FREE_FLOW_SPEED_FACTOR,
     SerializedTravelTime,
     sumo::{
-        FileReader, meandata::MeandataDocumentRoot, meandata_reader::SumoMeandataReader, sumo_find_file::get_meandata_file,
-        sumo_to_new_graph_weights::get_graph_with_travel_times_from_previous_iteration,
+        FileReader,
+        meandata::MeandataDocumentRoot,
+        meandata_reader::SumoMeandataReader,
+        sumo_find_file::get_meandata_file,
+        sumo_to_new_graph_weights::{get_graph_with_travel_times_from_previous_iteration, get_graph_with_travel_times_from_weight_files},
     },
 };
 use rust_road_router::{
@@ -15,8 +18,8 @@ use rust_road_router::{
 };
 
 use crate::{
-    alternative_paths::AlternativePathsForDTA, calculate_keep_routes, preprocess::get_cch, query::read_queries, traffic_model::TrafficModelType,
-    traffic_model_data::TrafficModelData,
+    alternative_paths::AlternativePathsForDTA, calculate_keep_routes, preprocess::get_cch, query::read_queries, router_options::RouterOptions,
+    traffic_model::TrafficModelType, traffic_model_data::TrafficModelData,
 };
 
 pub fn get_graph_data_for_fast_dta(
@@ -78,20 +81,49 @@ pub fn get_graph_data_for_fast_dta(
     )
 }
 
-pub fn get_graph_data_for_cch(input_dir: &Path, iteration: u32) -> (Vec<String>, TDGraph, CCH) {
-    let (edge_ids, graph) = get_graph_data_for_dijkstra(input_dir, iteration);
+pub fn get_graph_data_for_cch(input_dir: &Path, iteration: u32, options: &RouterOptions) -> (Vec<String>, TDGraph, CCH) {
+    let (edge_ids, graph) = get_graph_data_for_dijkstra(input_dir, iteration, options);
     let cch = get_cch(input_dir, &graph);
 
     (edge_ids, graph, cch)
 }
 
-pub fn get_graph_data_for_dijkstra(input_dir: &Path, iteration: u32) -> (Vec<String>, TDGraph) {
+pub fn get_graph_data_for_dijkstra(input_dir: &Path, iteration: u32, options: &RouterOptions) -> (Vec<String>, TDGraph) {
     let edge_ids: Vec<String> = get_edge_ids(input_dir);
-    let graph = get_graph_with_travel_times_from_previous_iteration(input_dir, iteration, &edge_ids);
+
+    let weight_files = get_weight_files(input_dir, iteration, options);
+    let graph = if weight_files.is_empty() {
+        get_graph_with_travel_times_from_previous_iteration(input_dir, iteration, &edge_ids)
+    } else {
+        get_graph_with_travel_times_from_weight_files(input_dir, &weight_files, options.weight_attribute, &edge_ids)
+    };
 
     (edge_ids, graph)
 }
 
+/// the files given by `--weight-files`, falling back to the previous iteration's dump.
+/// Relative paths which do not exist in the working directory are looked up in the input directory.
+fn get_weight_files(input_dir: &Path, iteration: u32, options: &RouterOptions) -> Vec<PathBuf> {
+    if options.weight_files.is_empty() {
+        if iteration > 0 {
+            return vec![get_meandata_file(&input_dir.join(format!("{:0>3}", iteration - 1)))];
+        }
+        return vec![];
+    }
+
+    options
+        .weight_files
+        .iter()
+        .map(|file| {
+            if file.is_relative() && !file.exists() {
+                input_dir.join(file)
+            } else {
+                file.clone()
+            }
+        })
+        .collect()
+}
+
 fn get_edge_ids(input_dir: &Path) -> Vec<String> {
     read_strings_from_file(&input_dir.join(FILE_EDGE_INDICES_TO_ID)).unwrap_or_else(|_| {
         panic!(
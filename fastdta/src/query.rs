@@ -531,6 +531,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
                 Edge {
                     id: String::from("e2"),
@@ -542,6 +543,7 @@ mod tests {
                     lanes: vec![],
                     params: vec![],
                     priority: Some(-1),
+                    shape: None,
                 },
             ],
         };
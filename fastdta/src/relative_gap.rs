@@ -20,22 +20,35 @@ pub fn get_relative_gap(best_tts: &Vec<f64>, simulated_tts: &Vec<f64>) -> f64 {
                     "Simulated travel time for trip {} is less than best travel time: {} < {}",
                     i, simulated_tts[i], best_tt
                 );
-                return 0.0;
             }
 
-            if f64::abs(simulated_tts[i] - best_tt) < EPSILON_TRAVEL_TIME {
-                return 0.0;
-            }
-
-            simulated_tts[i] - best_tt
+            get_excess_cost(best_tt, simulated_tts[i])
         })
         .sum::<f64>()
         / best_tts.par_iter().sum::<f64>()
 }
 
+/// the travel time a trip spends on top of the best travel time,
+/// differences within `EPSILON_TRAVEL_TIME` and trips faster than the best travel time have no excess cost
+pub fn get_excess_cost(best_tt: f64, simulated_tt: f64) -> f64 {
+    if simulated_tt - best_tt < EPSILON_TRAVEL_TIME {
+        return 0.0;
+    }
+
+    simulated_tt - best_tt
+}
+
 // append a gap to a file (should be: "rel_gaps.txt") in the dta_dir
 pub fn append_relative_gap_to_file(relative_gap: f64, output_dir: &Path) {
     let mut file = OpenOptions::new().create(true).append(true).open(output_dir.join(FILE_RELATIVE_GAPS)).unwrap();
 
     writeln!(file, "{:.9}", relative_gap).unwrap();
 }
+
+// append the gap of a user class to "rel_gaps_<class>.txt" in the dta_dir
+pub fn append_relative_gap_of_class_to_file(class_name: &str, relative_gap: f64, output_dir: &Path) {
+    let file_name = FILE_RELATIVE_GAPS.replace(".txt", &format!("_{}.txt", class_name));
+    let mut file = OpenOptions::new().create(true).append(true).open(output_dir.join(file_name)).unwrap();
+
+    writeln!(file, "{:.9}", relative_gap).unwrap();
+}
@@ -0,0 +1,240 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use conversion::{
+    SerializedTimestamp,
+    sumo::{paths_to_sumo_routes_converter::RouteDetails, sumo_to_new_graph_weights::WeightAttribute},
+};
+use rust_road_router::datastr::graph::{
+    EdgeId,
+    floating_time_dependent::{TDGraph, Timestamp},
+};
+
+use crate::{alternative_paths::AlternativePathsForDTA, user_classes::UserClasses};
+
+/// duarouter options which change how routes are computed, merged and written
+#[derive(Debug, Clone, Default)]
+pub struct RouterOptions {
+    /// `--remove-loops`: cut cycles out of newly computed routes
+    pub remove_loops: bool,
+    /// `--repair`: drop disconnected or empty alternatives instead of passing them on to the simulation
+    pub repair: bool,
+    /// `--weight-files`: read edge weights from these edgeData files instead of the previous iteration's dump
+    pub weight_files: Vec<PathBuf>,
+    /// `--weight-attribute`: the edgeData attribute used as edge weight
+    pub weight_attribute: WeightAttribute,
+    /// `--exit-times`: write the time at which each edge of a route is left
+    pub exit_times: bool,
+    /// `--route-length`: write the length of each route
+    pub route_length: bool,
+    /// `--keep-all-routes`: keep alternatives whose probability dropped to zero
+    pub keep_all_routes: bool,
+    /// `--skip-new-routes`: only reuse the alternatives of vehicles which already have some
+    pub skip_new_routes: bool,
+    /// `--user-classes`: trips grouped by vType with their own value of time, costs and route choice
+    pub user_classes: Option<UserClasses>,
+}
+
+impl RouterOptions {
+    pub fn writes_route_details(&self) -> bool {
+        self.exit_times || self.route_length
+    }
+
+    /// computes exit times and lengths for each alternative, empty if neither is requested
+    pub fn get_route_details(
+        &self,
+        path_sets: &Vec<Vec<Vec<EdgeId>>>,
+        departures: &Vec<SerializedTimestamp>,
+        graph: &TDGraph,
+        edge_lengths: &Vec<f64>,
+    ) -> Vec<Vec<RouteDetails>> {
+        if !self.writes_route_details() {
+            return vec![];
+        }
+
+        path_sets
+            .iter()
+            .enumerate()
+            .map(|(i, paths)| {
+                paths
+                    .iter()
+                    .map(|path| RouteDetails {
+                        exit_times: self.exit_times.then(|| get_exit_times(path, Timestamp::from_millis(departures[i]), graph)),
+                        length: self.route_length.then(|| path.iter().map(|&edge| edge_lengths[edge as usize]).sum()),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// applies `--keep-all-routes` and `--repair` to the alternatives after the choice model was applied
+    pub fn clean_up_alternatives(&self, alternative_paths: &mut AlternativePathsForDTA, graph: &TDGraph) {
+        if !self.keep_all_routes {
+            for alternatives in alternative_paths.alternatives_in_query.iter_mut() {
+                alternatives.retain_paths(|j, alternatives| alternatives.probabilities[j] > 0.0);
+            }
+        }
+
+        let mut invalid_routes = 0;
+        for alternatives in alternative_paths.alternatives_in_query.iter_mut() {
+            if self.repair {
+                alternatives.retain_paths(|j, alternatives| is_connected(&alternatives.paths[j].edges, graph));
+            }
+            invalid_routes += alternatives.paths.iter().filter(|path| !is_connected(&path.edges, graph)).count();
+        }
+
+        if invalid_routes > 0 {
+            println!(
+                "Warning: {} routes are empty or not connected{}",
+                invalid_routes,
+                if self.repair {
+                    " and could not be repaired"
+                } else {
+                    ", use --repair to drop them"
+                }
+            );
+        }
+    }
+}
+
+/// parses a boolean option value the way SUMO does
+pub fn parse_sumo_bool(option: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" | "x" => Ok(true),
+        "false" | "0" | "no" | "off" | "-" => Ok(false),
+        _ => Err(format!("Invalid boolean value '{value}' for option --{option}")),
+    }
+}
+
+/// removes cycles from a path like duarouter's `--remove-loops`.
+/// As in duarouter, a route which passes the tail of its first edge again starts with the edge before the last such pass,
+/// and a route which reaches the head of its last edge before ends with the edge after the first such visit.
+/// Afterwards, cycles within the route are cut out, keeping the first and the last edge.
+pub fn remove_loops(path: &Vec<EdgeId>, graph: &TDGraph) -> Vec<EdgeId> {
+    if path.len() < 2 {
+        return path.clone();
+    }
+
+    let head = graph.head();
+    let tail = |edge: EdgeId| (graph.first_out().partition_point(|&first| first <= edge) - 1) as u32;
+
+    let mut path = &path[..];
+    let start = tail(path[0]);
+    if let Some(last_start) = (1..path.len()).rev().find(|&i| tail(path[i]) == start) {
+        path = &path[last_start - 1..];
+    }
+    let end = head[path[path.len() - 1] as usize];
+    if let Some(first_end) = (0..path.len() - 1).find(|&i| head[path[i] as usize] == end) {
+        path = &path[..first_end + 2];
+    }
+
+    let mut cleaned = vec![path[0]];
+    // the node reached after the edge at position i - 1 of `cleaned`
+    let mut reached_at: HashMap<u32, usize> = HashMap::from([(head[path[0] as usize], 1)]);
+
+    for &edge in &path[1..path.len() - 1] {
+        let node = head[edge as usize];
+
+        if let Some(&position) = reached_at.get(&node) {
+            // cut the cycle starting and ending at node
+            cleaned.truncate(position);
+            reached_at.retain(|_, &mut p| p <= position);
+        } else {
+            cleaned.push(edge);
+            reached_at.insert(node, cleaned.len());
+        }
+    }
+
+    let last_edge = path[path.len() - 1];
+    match reached_at.get(&head[last_edge as usize]) {
+        // the last edge was already passed before, end the route there
+        Some(&position) if cleaned[position - 1] == last_edge => cleaned.truncate(position),
+        _ => cleaned.push(last_edge),
+    }
+
+    cleaned
+}
+
+/// a path is valid if it is not empty and each edge starts where the previous one ends
+pub fn is_connected(path: &Vec<EdgeId>, graph: &TDGraph) -> bool {
+    let first_out = graph.first_out();
+    let head = graph.head();
+
+    !path.is_empty()
+        && path.windows(2).all(|edges| {
+            let tail = first_out.partition_point(|&first| first <= edges[1]) - 1;
+            head[edges[0] as usize] as usize == tail
+        })
+}
+
+fn get_exit_times(path: &Vec<EdgeId>, departure: Timestamp, graph: &TDGraph) -> Vec<f64> {
+    let mut time = departure;
+
+    path.iter()
+        .map(|&edge| {
+            time = time + graph.get_travel_time_along_path(time, &[edge]);
+            f64::from(time)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> TDGraph {
+        // edges: 0 -> 1, 1 -> 0, 1 -> 2, 1 -> 3, 2 -> 1, 3 -> 1
+        let first_out = vec![0, 1, 4, 5, 6];
+        let head = vec![1, 0, 2, 3, 1, 1];
+        let first_ipp_of_arc = vec![0, 1, 2, 3, 4, 5, 6];
+        let ipp_departure_time = vec![0; 6];
+        let ipp_travel_time = vec![1_000; 6];
+
+        TDGraph::new(first_out, head, first_ipp_of_arc, ipp_departure_time, ipp_travel_time)
+    }
+
+    #[test]
+    fn test_parse_sumo_bool() {
+        assert_eq!(parse_sumo_bool("exit-times", "True"), Ok(true));
+        assert_eq!(parse_sumo_bool("exit-times", "yes"), Ok(true));
+        assert_eq!(parse_sumo_bool("exit-times", "x"), Ok(true));
+        assert_eq!(parse_sumo_bool("exit-times", "False"), Ok(false));
+        assert_eq!(parse_sumo_bool("exit-times", "0"), Ok(false));
+        assert_eq!(parse_sumo_bool("exit-times", "off"), Ok(false));
+        assert_eq!(
+            parse_sumo_bool("exit-times", "maybe"),
+            Err(String::from("Invalid boolean value 'maybe' for option --exit-times"))
+        );
+        assert!(parse_sumo_bool("exit-times", "").is_err());
+    }
+
+    #[test]
+    fn test_remove_loops() {
+        let graph = graph();
+
+        // 0 -> 1 -> 2 -> 1 -> 3
+        let path = vec![0, 2, 4, 3];
+        assert!(is_connected(&path, &graph));
+        assert_eq!(remove_loops(&path, &graph), vec![0, 3]);
+
+        // 0 -> 1 -> 0 -> 1 -> 3 passes the start again, so the route starts with the turnaround 1 -> 0
+        assert_eq!(remove_loops(&vec![0, 1, 0, 3], &graph), vec![1, 0, 3]);
+
+        // 0 -> 1 -> 3 -> 1 reaches the end already after the first edge, so the route ends with the edge after that
+        assert_eq!(remove_loops(&vec![0, 3, 5], &graph), vec![0, 3]);
+        // 0 -> 1 -> 2 -> 1 -> 3 -> 1 ends at the head of the first edge
+        assert_eq!(remove_loops(&vec![0, 2, 4, 3, 5], &graph), vec![0, 2]);
+
+        // loop free paths are not changed
+        assert_eq!(remove_loops(&vec![0, 2], &graph), vec![0, 2]);
+        assert_eq!(remove_loops(&vec![0, 1], &graph), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_is_connected() {
+        let graph = graph();
+
+        assert!(is_connected(&vec![0, 2, 4], &graph));
+        assert!(!is_connected(&vec![0, 4], &graph));
+        assert!(!is_connected(&vec![], &graph));
+    }
+}
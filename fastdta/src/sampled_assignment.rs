@@ -0,0 +1,527 @@
+use std::path::Path;
+
+use conversion::{
+    FILE_EDGE_DEFAULT_TRAVEL_TIMES, FILE_EDGE_LANES, FILE_EDGE_LENGTHS, FILE_QUERY_IDS, SerializedTimestamp, SerializedTravelTime,
+    sumo::{
+        meandata::MeandataDocumentRoot,
+        sumo_to_new_graph_weights::{extract_interpolation_points_from_meandata, get_graph_with_travel_times_from_previous_iteration},
+    },
+};
+use rust_road_router::{
+    algo::{catchup::customize, customizable_contraction_hierarchy::CCH},
+    datastr::graph::floating_time_dependent::{CustomizedGraph, FlWeight, TDGraph, Timestamp},
+    io::{Load, Reconstruct, read_strings_from_file},
+    report::measure,
+};
+
+use crate::{
+    logger::Logger,
+    path_processor::adjust_weights_in_graph_by_following_paths,
+    preprocess::get_cch,
+    query::{get_paths_with_cch_queries, get_paths_with_dijkstra_queries},
+    sampled_queries_sumo::{run_sumo_simulation_for_batch, update_graph_from_sumo_dump},
+    sampler::{BatchSampler, relative_travel_time_change},
+    sumo_runner::Simulator,
+    traffic_model::TrafficModel,
+};
+
+/// the algorithm used to route the trips of a sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleRouter {
+    /// CATCHUp queries, the CCH is customized with the current travel times before each sample
+    Catchup,
+    /// time-dependent Dijkstra queries on the current travel times
+    Dijkstra,
+}
+
+/// how the travel times are updated after the routes of a sample have changed
+pub enum WeightUpdater<'a> {
+    /// estimates the travel times by following the old and new routes of the sample with the traffic models of the edges,
+    /// see `path_processor`
+    TrafficModel {
+        traffic_models: &'a Vec<Box<dyn TrafficModel>>,
+        meandata: &'a mut MeandataDocumentRoot,
+        free_flow_tts_ms: Vec<SerializedTravelTime>,
+        edge_lengths: Vec<f64>,
+        edge_lanes: Vec<u32>,
+    },
+    /// simulates the current routes of all trips and reads the travel times from the edgeData output
+    Simulation {
+        simulator: &'a Simulator,
+        net_file: &'a Path,
+        aggregation: u32,
+        begin: f64,
+        end: f64,
+        free_flow_tts_ms: Vec<SerializedTravelTime>,
+        query_ids: Vec<String>,
+    },
+}
+
+impl<'a> WeightUpdater<'a> {
+    pub fn traffic_model(input_dir: &Path, traffic_models: &'a Vec<Box<dyn TrafficModel>>, meandata: &'a mut MeandataDocumentRoot) -> Self {
+        WeightUpdater::TrafficModel {
+            traffic_models,
+            meandata,
+            free_flow_tts_ms: Vec::<SerializedTravelTime>::load_from(&input_dir.join(FILE_EDGE_DEFAULT_TRAVEL_TIMES)).unwrap(),
+            edge_lengths: Vec::<f64>::load_from(&input_dir.join(FILE_EDGE_LENGTHS)).unwrap(),
+            edge_lanes: Vec::<u32>::load_from(&input_dir.join(FILE_EDGE_LANES)).unwrap(),
+        }
+    }
+
+    pub fn simulation(input_dir: &Path, simulator: &'a Simulator, net_file: &'a Path, aggregation: u32, begin: f64, end: f64) -> Self {
+        WeightUpdater::Simulation {
+            simulator,
+            net_file,
+            aggregation,
+            begin,
+            end,
+            free_flow_tts_ms: Vec::<SerializedTravelTime>::load_from(&input_dir.join(FILE_EDGE_DEFAULT_TRAVEL_TIMES)).unwrap(),
+            query_ids: read_strings_from_file(&input_dir.join(FILE_QUERY_IDS)).unwrap(),
+        }
+    }
+
+    /// the travel times before the first sample: those of the meandata for the traffic model,
+    /// those of the previous iteration for the simulation
+    fn get_graph(&self, input_dir: &Path, iteration: u32, edge_ids: &Vec<String>) -> TDGraph {
+        match self {
+            WeightUpdater::TrafficModel {
+                meandata, free_flow_tts_ms, ..
+            } => {
+                let graph: TDGraph = TDGraph::reconstruct_from(&input_dir).expect("Failed to reconstruct the time-dependent graph");
+                let (first_ipp_of_arc, ipp_travel_time, ipp_departure_time) = extract_interpolation_points_from_meandata(meandata, edge_ids, free_flow_tts_ms);
+
+                TDGraph::new(
+                    Vec::from(graph.first_out()),
+                    Vec::from(graph.head()),
+                    first_ipp_of_arc,
+                    ipp_departure_time,
+                    ipp_travel_time,
+                )
+            }
+            WeightUpdater::Simulation { .. } => get_graph_with_travel_times_from_previous_iteration(input_dir, iteration, edge_ids),
+        }
+    }
+
+    /// updates the travel times of `graph` after the trips of the sample changed from their old to their new routes,
+    /// `routed_paths` and `departures` are the current routes of all trips
+    fn update(
+        &mut self,
+        graph: &mut TDGraph,
+        input_dir: &Path,
+        iteration: u32,
+        batch: usize,
+        logger: &Logger,
+        sampled_old_paths: &Vec<&Vec<u32>>,
+        sampled_new_paths: &Vec<Vec<u32>>,
+        sampled_departures: &Vec<SerializedTimestamp>,
+        routed_paths: &Vec<Vec<u32>>,
+        departures: &Vec<SerializedTimestamp>,
+        edge_ids: &Vec<String>,
+    ) {
+        match self {
+            WeightUpdater::TrafficModel {
+                traffic_models,
+                meandata,
+                free_flow_tts_ms,
+                edge_lengths,
+                edge_lanes,
+            } => {
+                let (_, duration) = measure(|| {
+                    let free_flow_tts: Vec<f64> = free_flow_tts_ms.iter().map(|&tt| tt as f64 / 1000.0).collect();
+
+                    adjust_weights_in_graph_by_following_paths(
+                        graph,
+                        sampled_old_paths,
+                        sampled_new_paths,
+                        &sampled_departures.iter().map(|&d| Timestamp::from_millis(d)).collect(),
+                        &mut meandata.intervals,
+                        edge_ids,
+                        edge_lengths,
+                        &free_flow_tts,
+                        traffic_models,
+                        edge_lanes,
+                    );
+                });
+                logger.log(format!("adjust weights (sample {batch})").as_str(), duration.as_nanos());
+            }
+            WeightUpdater::Simulation {
+                simulator,
+                net_file,
+                aggregation,
+                begin,
+                end,
+                free_flow_tts_ms,
+                query_ids,
+            } => {
+                // simulate all trips which already have a route
+                let mut simulation_paths: Vec<Vec<u32>> = Vec::new();
+                let mut simulation_trip_ids: Vec<String> = Vec::new();
+                let mut simulation_departures: Vec<SerializedTimestamp> = Vec::new();
+
+                for query_i in 0..routed_paths.len() {
+                    if !routed_paths[query_i].is_empty() {
+                        simulation_paths.push(routed_paths[query_i].clone());
+                        simulation_trip_ids.push(query_ids[query_i].clone());
+                        simulation_departures.push(departures[query_i]);
+                    }
+                }
+
+                let (_, duration) = measure(|| {
+                    run_sumo_simulation_for_batch(
+                        input_dir,
+                        net_file,
+                        iteration,
+                        batch,
+                        *aggregation,
+                        *begin,
+                        *end,
+                        &simulation_paths,
+                        &simulation_trip_ids,
+                        &simulation_departures,
+                        edge_ids,
+                        simulator,
+                    )
+                    .expect("Failed to run SUMO simulation");
+                });
+                logger.log(&format!("sumo simulation (batch {batch})"), duration.as_nanos());
+
+                let (_, duration) = measure(|| {
+                    update_graph_from_sumo_dump(input_dir, iteration, batch, *aggregation, graph, edge_ids, free_flow_tts_ms)
+                        .expect("Failed to update graph from SUMO dump");
+                });
+                logger.log(&format!("update graph weights (batch {batch})"), duration.as_nanos());
+            }
+        }
+    }
+}
+
+/// Routes the trips sample by sample and updates the travel times after each sample,
+/// such that later samples are routed on the travel times caused by the earlier ones.
+pub struct SampledAssignment<'a> {
+    pub router: SampleRouter,
+    pub weight_updater: WeightUpdater<'a>,
+    /// trips which keep their previous route when they are sampled, see `calculate_keep_routes`.
+    /// If None, all sampled trips are rerouted
+    pub keep_routes: Option<&'a Vec<bool>>,
+}
+
+impl<'a> SampledAssignment<'a> {
+    pub fn new(router: SampleRouter, weight_updater: WeightUpdater<'a>, keep_routes: Option<&'a Vec<bool>>) -> Self {
+        Self {
+            router,
+            weight_updater,
+            keep_routes,
+        }
+    }
+
+    /// Route all queries using the samples of `sampler`.
+    /// Returns the graph with the travel times before the first sample, the routes, their travel times and the departures
+    pub fn run(
+        &mut self,
+        input_dir: &Path,
+        iteration: u32,
+        logger: &Logger,
+        query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+        sampler: &mut BatchSampler,
+        previous_paths: &Vec<&Vec<u32>>,
+        edge_ids: &Vec<String>,
+        routing_threads: usize,
+    ) -> (TDGraph, Vec<Vec<u32>>, Vec<FlWeight>, Vec<SerializedTimestamp>) {
+        let num_queries = query_data.0.len();
+        // there are no previous paths in the first iteration
+        let empty_path: Vec<u32> = Vec::new();
+        let previous_paths: Vec<&Vec<u32>> = (0..num_queries).map(|i| *previous_paths.get(i).unwrap_or(&&empty_path)).collect();
+
+        let mut routed_paths: Vec<Vec<u32>> = previous_paths.iter().map(|&path| path.clone()).collect();
+        let mut travel_times = vec![FlWeight::INVALID; num_queries];
+        let mut departures = query_data.2.clone();
+
+        let mut graph = self.weight_updater.get_graph(input_dir, iteration, edge_ids);
+        let initial_graph = graph.clone();
+        let cch = (self.router == SampleRouter::Catchup).then(|| get_cch(input_dir, &graph));
+
+        let mut batch = 0;
+        while let Some(sample) = sampler.next_batch() {
+            let sample = &sample;
+
+            let customized_graph = cch.as_ref().map(|cch| {
+                let (customized_graph, duration) = measure(|| customize(cch, &graph));
+                logger.log(format!("cch customization (sample {batch})").as_str(), duration.as_nanos());
+                customized_graph
+            });
+
+            let ((sampled_paths, sampled_travel_times, sampled_departures), duration) = measure(|| {
+                self.route_sample(
+                    &graph,
+                    cch.as_ref().zip(customized_graph.as_ref()),
+                    sample,
+                    query_data,
+                    &previous_paths,
+                    routing_threads,
+                )
+            });
+            logger.log(format!("routing (sample {batch})").as_str(), duration.as_nanos());
+
+            let sampled_old_paths: Vec<&Vec<u32>> = sample.iter().map(|&query_i| previous_paths[query_i]).collect();
+            sample.iter().enumerate().for_each(|(i, &query_i)| {
+                routed_paths[query_i] = sampled_paths[i].clone();
+                travel_times[query_i] = sampled_travel_times[i];
+                departures[query_i] = sampled_departures[i];
+            });
+
+            self.weight_updater.update(
+                &mut graph,
+                input_dir,
+                iteration,
+                batch,
+                logger,
+                &sampled_old_paths,
+                &sampled_paths,
+                &sampled_departures,
+                &routed_paths,
+                &departures,
+                edge_ids,
+            );
+
+            if sampler.is_adaptive() {
+                let travel_times_after = sampled_paths
+                    .iter()
+                    .zip(sampled_departures.iter())
+                    .map(|(path, &departure)| graph.get_travel_time_along_path(Timestamp::from_millis(departure), path))
+                    .collect();
+                sampler.report_travel_time_change(relative_travel_time_change(&sampled_travel_times, &travel_times_after));
+            }
+
+            batch += 1;
+        }
+
+        // the traffic model estimates the travel times of the routes while routing,
+        // the routes of simulated samples are evaluated on the travel times of the previous simulation instead
+        if let WeightUpdater::Simulation { .. } = self.weight_updater {
+            travel_times = routed_paths
+                .iter()
+                .enumerate()
+                .map(|(i, path)| initial_graph.get_travel_time_along_path(Timestamp::from_millis(departures[i]), path))
+                .collect();
+        }
+
+        (initial_graph, routed_paths, travel_times, departures)
+    }
+
+    /// routes the rerouted trips of the sample, the others keep their previous routes
+    fn route_sample(
+        &self,
+        graph: &TDGraph,
+        cch: Option<(&CCH, &CustomizedGraph)>,
+        sample: &Vec<usize>,
+        query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+        previous_paths: &Vec<&Vec<u32>>,
+        routing_threads: usize,
+    ) -> (Vec<Vec<u32>>, Vec<FlWeight>, Vec<SerializedTimestamp>) {
+        let keeps_route = |query_i: usize| self.keep_routes.is_some_and(|keep_routes| keep_routes[query_i]);
+        let rerouted: Vec<usize> = sample.iter().copied().filter(|&query_i| !keeps_route(query_i)).collect();
+        let select = |values: &Vec<u32>| -> Vec<u32> { rerouted.iter().map(|&query_i| values[query_i]).collect() };
+
+        let (rerouted_paths, rerouted_travel_times, rerouted_departures) = match (self.router, cch) {
+            (SampleRouter::Catchup, Some((cch, customized_graph))) => get_paths_with_cch_queries(
+                cch,
+                customized_graph,
+                &select(&query_data.0),
+                &select(&query_data.1),
+                &select(&query_data.2),
+                &select(&query_data.3),
+                &select(&query_data.4),
+                graph,
+                routing_threads,
+            ),
+            (SampleRouter::Catchup, None) => panic!("CATCHUp requires a customized CCH"),
+            (SampleRouter::Dijkstra, _) => get_paths_with_dijkstra_queries(
+                &select(&query_data.0),
+                &select(&query_data.1),
+                &select(&query_data.2),
+                &select(&query_data.3),
+                &select(&query_data.4),
+                graph,
+                routing_threads,
+            ),
+        };
+
+        if rerouted.len() == sample.len() {
+            return (rerouted_paths, rerouted_travel_times, rerouted_departures);
+        }
+
+        // combine the new paths with the previous paths of the trips keeping their routes
+        let mut rerouted_results = rerouted_paths.into_iter().zip(rerouted_travel_times).zip(rerouted_departures);
+        let mut paths = Vec::with_capacity(sample.len());
+        let mut travel_times = Vec::with_capacity(sample.len());
+        let mut departures = Vec::with_capacity(sample.len());
+
+        for &query_i in sample {
+            let ((path, travel_time), departure) = if keeps_route(query_i) {
+                let departure = query_data.2[query_i];
+                let path = previous_paths[query_i].clone();
+                let travel_time = graph.get_travel_time_along_path(Timestamp::from_millis(departure), &path);
+                ((path, travel_time), departure)
+            } else {
+                rerouted_results.next().unwrap()
+            };
+            paths.push(path);
+            travel_times.push(travel_time);
+            departures.push(departure);
+        }
+
+        (paths, travel_times, departures)
+    }
+}
+
+#[cfg(all(test, not(feature = "expand-sumo-nodes")))]
+mod tests {
+    use std::path::PathBuf;
+
+    use conversion::{FILE_FIRST_IPP_OF_ARC, FILE_FIRST_OUT, FILE_HEAD, FILE_IPP_DEPARTURE_TIME, FILE_IPP_TRAVEL_TIME, sumo::meandata::Interval};
+    use rust_road_router::io::{Store, write_strings_to_file};
+
+    use super::*;
+    use crate::{meso_simulation::MesoNetwork, traffic_model::modified_lee::ModifiedLee};
+
+    const NUM_TRIPS: usize = 20;
+
+    /// all trips enter on edge `in` and leave on edge `out`, in between they either take the 500m long edge `top`
+    /// or the two edges `bottom1` and `bottom2` with 600m in total. All edges have a free flow speed of 36 km/h.
+    struct Fixture {
+        input_dir: PathBuf,
+        edge_ids: Vec<String>,
+        query_data: (Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let input_dir = std::env::temp_dir().join(format!("fastdta_sampled_assignment_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&input_dir).unwrap();
+
+            let edge_ids: Vec<String> = ["in", "top", "bottom1", "out", "bottom2"].iter().map(|id| id.to_string()).collect();
+            let lengths = vec![100.0, 500.0, 300.0, 100.0, 300.0];
+            let free_flow_tts_ms: Vec<SerializedTravelTime> = lengths.iter().map(|&length| (length * 100.0) as SerializedTravelTime).collect();
+
+            vec![0u32, 1, 3, 4, 5, 5].write_to(&input_dir.join(FILE_FIRST_OUT)).unwrap();
+            vec![1u32, 2, 3, 4, 2].write_to(&input_dir.join(FILE_HEAD)).unwrap();
+            (0..=5u32).collect::<Vec<_>>().write_to(&input_dir.join(FILE_FIRST_IPP_OF_ARC)).unwrap();
+            vec![0 as SerializedTimestamp; 5].write_to(&input_dir.join(FILE_IPP_DEPARTURE_TIME)).unwrap();
+            free_flow_tts_ms.write_to(&input_dir.join(FILE_IPP_TRAVEL_TIME)).unwrap();
+            free_flow_tts_ms.write_to(&input_dir.join(FILE_EDGE_DEFAULT_TRAVEL_TIMES)).unwrap();
+            lengths.write_to(&input_dir.join(FILE_EDGE_LENGTHS)).unwrap();
+            vec![1u32; 5].write_to(&input_dir.join(FILE_EDGE_LANES)).unwrap();
+            let query_ids: Vec<String> = (0..NUM_TRIPS).map(|i| format!("trip{}", i)).collect();
+            write_strings_to_file(&input_dir.join(FILE_QUERY_IDS), &query_ids.iter().collect()).unwrap();
+
+            // the first half of the trips departs in the first aggregation interval, the second half in the second one
+            let departures = (0..NUM_TRIPS)
+                .map(|i| if i < NUM_TRIPS / 2 { 10_000 } else { 310_000 } + i as u32 * 1_000)
+                .collect();
+            let query_data = (vec![1; NUM_TRIPS], vec![2; NUM_TRIPS], departures, vec![0; NUM_TRIPS], vec![3; NUM_TRIPS]);
+
+            Self {
+                input_dir,
+                edge_ids,
+                query_data,
+            }
+        }
+
+        fn traffic_models(&self) -> Vec<Box<dyn TrafficModel>> {
+            (0..self.edge_ids.len())
+                .map(|_| Box::new(ModifiedLee::new(36.0, 50.0)) as Box<dyn TrafficModel>)
+                .collect()
+        }
+
+        fn run(&self, weight_updater: WeightUpdater) -> (TDGraph, Vec<Vec<u32>>, Vec<FlWeight>, Vec<SerializedTimestamp>) {
+            let mut sampler = BatchSampler::new(&vec![0.5, 0.5], &vec![0; NUM_TRIPS], None, 42);
+            let mut assignment = SampledAssignment::new(SampleRouter::Dijkstra, weight_updater, None);
+            assignment.run(
+                &self.input_dir,
+                0,
+                &Logger::new("test", "sampled_assignment", 0),
+                &self.query_data,
+                &mut sampler,
+                &vec![],
+                &self.edge_ids,
+                2,
+            )
+        }
+
+        fn check_routes(&self, paths: &Vec<Vec<u32>>, departures: &Vec<SerializedTimestamp>) {
+            assert_eq!(departures, &self.query_data.2);
+            for path in paths {
+                assert!(path == &vec![0, 1, 3] || path == &vec![0, 2, 4, 3], "unexpected route {:?}", path);
+            }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.input_dir);
+        }
+    }
+
+    #[test]
+    fn test_traffic_model_updater() {
+        let fixture = Fixture::new("traffic_model");
+        let traffic_models = fixture.traffic_models();
+        let mut meandata = MeandataDocumentRoot {
+            intervals: vec![
+                Interval::create("0".to_string(), 0.0, 300.0, vec![]),
+                Interval::create("1".to_string(), 300.0, 600.0, vec![]),
+            ],
+        };
+
+        let (graph, paths, travel_times, departures) = fixture.run(WeightUpdater::traffic_model(&fixture.input_dir, &traffic_models, &mut meandata));
+
+        fixture.check_routes(&paths, &departures);
+        // the travel times are the ones of the queries, so they are at least the free flow travel times of the routes
+        for (path, &travel_time) in paths.iter().zip(&travel_times) {
+            let free_flow_tt = graph.get_travel_time_along_path(Timestamp::ZERO, path);
+            assert!(
+                travel_time.fuzzy_eq(free_flow_tt) || free_flow_tt.fuzzy_lt(travel_time),
+                "{:?} {:?}",
+                travel_time,
+                free_flow_tt
+            );
+            assert!(travel_time.fuzzy_lt(FlWeight::INFINITY));
+        }
+
+        // the updater follows each trip from its own departure, so both intervals contain the trips departing in them
+        for interval in &mut meandata.intervals {
+            let entering = interval.get_edge("in").map_or(0, |edge| edge.dbg_entered);
+            assert_eq!(entering as usize, NUM_TRIPS / 2, "interval {}", interval.id);
+        }
+    }
+
+    #[test]
+    fn test_meso_simulation_updater() {
+        let fixture = Fixture::new("meso");
+        let simulator = Simulator::Meso(MesoNetwork::new(
+            fixture.edge_ids.clone(),
+            vec![100.0, 500.0, 300.0, 100.0, 300.0],
+            vec![1; 5],
+            fixture.traffic_models(),
+        ));
+
+        let (graph, paths, travel_times, departures) = fixture.run(WeightUpdater::simulation(
+            &fixture.input_dir,
+            &simulator,
+            Path::new("unused.net.xml"),
+            300,
+            0.0,
+            600.0,
+        ));
+
+        fixture.check_routes(&paths, &departures);
+        // one simulation per sample
+        for batch in 0..2 {
+            assert!(fixture.input_dir.join(format!("000/_dump_300_000_{:0>3}.xml", batch)).exists());
+        }
+        // the routes of simulated samples are evaluated on the travel times before the first sample
+        for ((path, &travel_time), &departure) in paths.iter().zip(&travel_times).zip(&departures) {
+            assert!(travel_time.fuzzy_eq(graph.get_travel_time_along_path(Timestamp::from_millis(departure), path)));
+        }
+    }
+}
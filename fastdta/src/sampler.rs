@@ -1,4 +1,17 @@
-use rand::{SeedableRng, seq::IndexedRandom};
+use std::{collections::VecDeque, path::Path};
+
+use conversion::{FILE_LATITUDE, FILE_LONGITUDE, SerializedPosition, SerializedTimestamp};
+use rand::{
+    SeedableRng,
+    seq::{IndexedRandom, SliceRandom},
+};
+use rust_road_router::{
+    algo::customizable_contraction_hierarchy::{CCHT, separator_decomposition::SeparatorTree},
+    datastr::graph::floating_time_dependent::{FlWeight, TDGraph},
+    io::{Load, Reconstruct},
+};
+
+use crate::preprocess::get_cch;
 
 pub fn sample(sample_relative_sizes: &Vec<f64>, n: usize, seed: i32) -> Vec<Vec<usize>> {
     let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
@@ -39,6 +52,260 @@ pub fn sample(sample_relative_sizes: &Vec<f64>, n: usize, seed: i32) -> Vec<Vec<
     samples
 }
 
+/// how trips are grouped into strata before they are distributed over the batches,
+/// each batch then contains the same share of every stratum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stratification {
+    /// all trips form a single stratum
+    None,
+    /// trips departing in the same interval (in seconds) form a stratum
+    Departure(f64),
+    /// trips starting in the same CCH cell form a stratum, cells are the subtrees at the given depth of the separator tree
+    OriginCell(usize),
+    /// trips are split into the given number of quantiles of their beeline distance
+    TripLength(usize),
+}
+
+/// parameters of the adaptive batch sizing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// relative size of the first batch
+    pub initial_fraction: f64,
+    pub min_fraction: f64,
+    pub max_fraction: f64,
+    /// the relative change of the estimated travel times per batch we aim for
+    pub target_change: f64,
+}
+
+impl AdaptiveSampling {
+    pub fn new(initial_fraction: f64, target_change: f64) -> Self {
+        assert!(target_change > 0.0, "target change of the adaptive sampling must be positive");
+        Self {
+            initial_fraction,
+            min_fraction: 0.01,
+            max_fraction: 0.5,
+            target_change,
+        }
+    }
+}
+
+/// computes the stratum of each query
+pub fn get_strata(stratification: Stratification, input_dir: &Path, query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>)) -> Vec<usize> {
+    match stratification {
+        Stratification::None => vec![0; query_data.0.len()],
+        Stratification::Departure(interval) => get_departure_strata(&query_data.2, (interval * 1000.0) as SerializedTimestamp),
+        Stratification::OriginCell(depth) => {
+            let graph = TDGraph::reconstruct_from(&input_dir).expect("Failed to reconstruct the time-dependent graph");
+            let cch = get_cch(input_dir, &graph);
+            get_origin_cell_strata(&query_data.0, &cch, depth)
+        }
+        Stratification::TripLength(classes) => {
+            // the SUMO converter stores the projected coordinates of the nodes in the latitude and longitude files
+            let y = Vec::<SerializedPosition>::load_from(input_dir.join(FILE_LATITUDE)).unwrap();
+            let x = Vec::<SerializedPosition>::load_from(input_dir.join(FILE_LONGITUDE)).unwrap();
+            get_trip_length_strata(&query_data.0, &query_data.1, &x, &y, classes)
+        }
+    }
+}
+
+pub fn get_departure_strata(departures: &Vec<SerializedTimestamp>, interval: SerializedTimestamp) -> Vec<usize> {
+    assert!(interval > 0, "departure interval of the stratification must be positive");
+    departures.iter().map(|&departure| (departure / interval) as usize).collect()
+}
+
+pub fn get_origin_cell_strata<C: CCHT>(origins: &Vec<u32>, cch: &C, depth: usize) -> Vec<usize> {
+    let mut cell_of_rank = vec![0; cch.node_order().len()];
+    let mut num_cells = 0;
+    assign_cells(cch.separators(), depth, &mut cell_of_rank, &mut num_cells);
+
+    origins.iter().map(|&origin| cell_of_rank[cch.node_order().rank(origin) as usize]).collect()
+}
+
+// every subtree at the given depth becomes a cell, separators above get a cell of their own
+fn assign_cells(tree: &SeparatorTree, depth: usize, cell_of_rank: &mut Vec<usize>, num_cells: &mut usize) {
+    if depth == 0 || tree.children.is_empty() {
+        assign_subtree(tree, *num_cells, cell_of_rank);
+        *num_cells += 1;
+        return;
+    }
+
+    if !tree.nodes.is_empty() {
+        tree.nodes.iter().for_each(|rank| cell_of_rank[rank as usize] = *num_cells);
+        *num_cells += 1;
+    }
+
+    for child in &tree.children {
+        assign_cells(child, depth - 1, cell_of_rank, num_cells);
+    }
+}
+
+fn assign_subtree(tree: &SeparatorTree, cell: usize, cell_of_rank: &mut Vec<usize>) {
+    tree.nodes.iter().for_each(|rank| cell_of_rank[rank as usize] = cell);
+    tree.children.iter().for_each(|child| assign_subtree(child, cell, cell_of_rank));
+}
+
+/// splits the trips into quantiles of the euclidean distance between the projected coordinates (in meters) of origin and destination
+pub fn get_trip_length_strata(
+    origins: &Vec<u32>,
+    destinations: &Vec<u32>,
+    x: &Vec<SerializedPosition>,
+    y: &Vec<SerializedPosition>,
+    classes: usize,
+) -> Vec<usize> {
+    assert!(classes > 0, "number of trip length classes must be positive");
+
+    let distances: Vec<f64> = origins
+        .iter()
+        .zip(destinations.iter())
+        .map(|(&from, &to)| {
+            let dx = x[to as usize] as f64 - x[from as usize] as f64;
+            let dy = y[to as usize] as f64 - y[from as usize] as f64;
+            dx.hypot(dy)
+        })
+        .collect();
+
+    // quantiles, such that each class contains roughly the same number of trips
+    let mut order: Vec<usize> = (0..distances.len()).collect();
+    order.sort_by(|&a, &b| distances[a].total_cmp(&distances[b]));
+
+    let mut strata = vec![0; distances.len()];
+    for (position, &query) in order.iter().enumerate() {
+        strata[query] = position * classes / distances.len();
+    }
+    strata
+}
+
+/// like `sample`, but each stratum is split separately by the relative sizes
+pub fn stratified_sample(sample_relative_sizes: &Vec<f64>, strata: &Vec<usize>, seed: i32) -> Vec<Vec<usize>> {
+    let mut samples: Vec<Vec<usize>> = vec![vec![]; sample_relative_sizes.len()];
+
+    for (stratum, members) in group_by_stratum(strata).iter().enumerate() {
+        let stratum_samples = sample(sample_relative_sizes, members.len(), seed.wrapping_add(stratum as i32));
+
+        for (i, stratum_sample) in stratum_samples.iter().enumerate() {
+            samples[i].extend(stratum_sample.iter().map(|&member| members[member]));
+        }
+    }
+
+    samples
+}
+
+fn group_by_stratum(strata: &Vec<usize>) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = vec![vec![]; strata.iter().max().map_or(0, |&max| max + 1)];
+    strata.iter().enumerate().for_each(|(query, &stratum)| groups[stratum].push(query));
+    groups.retain(|group| !group.is_empty());
+    groups
+}
+
+/// sum of the absolute travel time differences relative to the sum of the previous travel times
+pub fn relative_travel_time_change(before: &Vec<FlWeight>, after: &Vec<FlWeight>) -> f64 {
+    let total: f64 = before.iter().map(|&tt| f64::from(tt)).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    before.iter().zip(after.iter()).map(|(&b, &a)| (f64::from(a) - f64::from(b)).abs()).sum::<f64>() / total
+}
+
+/// the batches of trips which are routed one after another
+pub enum BatchSampler {
+    /// batches drawn up front with fixed relative sizes
+    Fixed(VecDeque<Vec<usize>>),
+    /// batches whose size depends on how much the previous batch changed the travel times
+    Adaptive(AdaptiveSampler),
+}
+
+impl BatchSampler {
+    pub fn new(sample_relative_sizes: &Vec<f64>, strata: &Vec<usize>, adaptive_sampling: Option<AdaptiveSampling>, seed: i32) -> Self {
+        match adaptive_sampling {
+            None => BatchSampler::Fixed(stratified_sample(sample_relative_sizes, strata, seed).into()),
+            Some(config) => BatchSampler::Adaptive(AdaptiveSampler::new(strata, config, seed)),
+        }
+    }
+
+    pub fn next_batch(&mut self) -> Option<Vec<usize>> {
+        match self {
+            BatchSampler::Fixed(batches) => batches.pop_front(),
+            BatchSampler::Adaptive(sampler) => sampler.next_batch(),
+        }
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        matches!(self, BatchSampler::Adaptive(_))
+    }
+
+    /// reports the relative change of the travel times caused by the last batch, ignored for fixed batches
+    pub fn report_travel_time_change(&mut self, relative_change: f64) {
+        if let BatchSampler::Adaptive(sampler) = self {
+            sampler.report_travel_time_change(relative_change);
+        }
+    }
+}
+
+pub struct AdaptiveSampler {
+    /// the shuffled trips of each stratum which were not sampled yet
+    remaining: Vec<Vec<usize>>,
+    num_trips: usize,
+    fraction: f64,
+    config: AdaptiveSampling,
+}
+
+impl AdaptiveSampler {
+    pub fn new(strata: &Vec<usize>, config: AdaptiveSampling, seed: i32) -> Self {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let mut remaining = group_by_stratum(strata);
+        remaining.iter_mut().for_each(|members| members.shuffle(&mut rng));
+
+        Self {
+            remaining,
+            num_trips: strata.len(),
+            fraction: config.initial_fraction.clamp(config.min_fraction, config.max_fraction),
+            config,
+        }
+    }
+
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    pub fn next_batch(&mut self) -> Option<Vec<usize>> {
+        let num_remaining: usize = self.remaining.iter().map(|members| members.len()).sum();
+        if num_remaining == 0 {
+            return None;
+        }
+
+        let batch_size = ((self.fraction * self.num_trips as f64).round() as usize).max(1);
+        // do not leave a tail smaller than the minimum batch size
+        let share = if num_remaining - batch_size.min(num_remaining) < (self.config.min_fraction * self.num_trips as f64).ceil() as usize {
+            1.0
+        } else {
+            batch_size as f64 / num_remaining as f64
+        };
+
+        let mut batch = Vec::with_capacity(batch_size);
+        for members in self.remaining.iter_mut() {
+            let take = ((members.len() as f64 * share).round() as usize).min(members.len());
+            batch.extend(members.drain(members.len() - take..));
+        }
+
+        if batch.is_empty() {
+            // all strata are too small for their share to round up, take from the largest one
+            let largest = self.remaining.iter_mut().max_by_key(|members| members.len()).unwrap();
+            batch.push(largest.pop().unwrap());
+        }
+
+        Some(batch)
+    }
+
+    /// grows the next batch if the last one changed the travel times less than targeted and shrinks it otherwise,
+    /// by at most a factor of two per batch
+    pub fn report_travel_time_change(&mut self, relative_change: f64) {
+        let factor = (self.config.target_change / relative_change.max(f64::EPSILON)).clamp(0.5, 2.0);
+        self.fraction = (self.fraction * factor).clamp(self.config.min_fraction, self.config.max_fraction);
+        println!("Relative travel time change {:.4}, next batch fraction {:.4}", relative_change, self.fraction);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     //! Tests for the sample function.
@@ -264,4 +531,75 @@ mod tests {
         verify_complete_sample(&samples, n);
         assert_eq!(samples.len(), 4);
     }
+
+    #[test]
+    fn test_departure_strata() {
+        let departures = vec![0, 899_999, 900_000, 2_700_000];
+        assert_eq!(get_departure_strata(&departures, 900_000), vec![0, 0, 1, 3]);
+    }
+
+    #[test]
+    fn test_trip_length_strata() {
+        // node 0 is the origin of all trips, the others are increasingly far away,
+        // coordinates are in meters, so they must not wrap around like degrees would
+        let x = vec![0.0, 100.0, 200.0, 300.0, 400.0];
+        let y = vec![0.0, 10.0, 0.0, -10.0, 0.0];
+        let origins = vec![0, 0, 0, 0];
+        let destinations = vec![4, 1, 3, 2];
+
+        assert_eq!(get_trip_length_strata(&origins, &destinations, &x, &y, 2), vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_stratified_sample_splits_each_stratum() {
+        let sizes = vec![1.0, 1.0];
+        let strata: Vec<usize> = (0..100).map(|i| if i < 80 { 0 } else { 1 }).collect();
+        let samples = stratified_sample(&sizes, &strata, 42);
+
+        verify_complete_sample(&samples, 100);
+        for sample in &samples {
+            assert_eq!(sample.iter().filter(|&&i| strata[i] == 0).count(), 40);
+            assert_eq!(sample.iter().filter(|&&i| strata[i] == 1).count(), 10);
+        }
+    }
+
+    #[test]
+    fn test_fixed_batch_sampler() {
+        let mut sampler = BatchSampler::new(&vec![0.9, 0.1], &vec![0; 10], None, 42);
+        sampler.report_travel_time_change(1.0);
+
+        assert_eq!(sampler.next_batch().unwrap().len(), 9);
+        assert_eq!(sampler.next_batch().unwrap().len(), 1);
+        assert!(sampler.next_batch().is_none());
+    }
+
+    #[test]
+    fn test_adaptive_batch_sizes() {
+        let n = 1000;
+        let mut sampler = AdaptiveSampler::new(&vec![0; n], AdaptiveSampling::new(0.1, 0.05), 42);
+        let mut batches = vec![sampler.next_batch().unwrap()];
+        assert_eq!(batches[0].len(), 100);
+
+        // a large change shrinks the next batch
+        sampler.report_travel_time_change(0.2);
+        batches.push(sampler.next_batch().unwrap());
+        assert_eq!(batches[1].len(), 50);
+
+        // a small change grows it again
+        sampler.report_travel_time_change(0.01);
+        batches.push(sampler.next_batch().unwrap());
+        assert_eq!(batches[2].len(), 100);
+
+        while let Some(batch) = sampler.next_batch() {
+            batches.push(batch);
+        }
+        verify_complete_sample(&batches, n);
+    }
+
+    #[test]
+    fn test_relative_travel_time_change() {
+        let before = vec![FlWeight::new(10.0), FlWeight::new(30.0)];
+        let after = vec![FlWeight::new(12.0), FlWeight::new(28.0)];
+        assert!((relative_travel_time_change(&before, &after) - 0.1).abs() < 1e-9);
+    }
 }
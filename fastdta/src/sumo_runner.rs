@@ -2,6 +2,16 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::meso_simulation::MesoNetwork;
+
+/// the network loading model used to evaluate routes
+pub enum Simulator {
+    /// spawns `sumo --mesosim`
+    Sumo,
+    /// the in-process queue based model from `meso_simulation`, does not require a SUMO installation
+    Meso(MesoNetwork),
+}
+
 /// Configuration for running SUMO simulation
 pub struct SumoConfig {
     pub net_file: PathBuf,
@@ -116,16 +116,28 @@ impl TrafficModelData {
         self.traffic_models[index].calibrate(&self.observed_speeds[index], &self.observed_densities[index]);
     }
 
-    /// initialize traffic model data with empty observations and traffic models with given free flow speeds in km/h
-    pub fn init(free_flow_speed: &Vec<f64>, traffic_model_type: TrafficModelType) -> Self {
-        let mut traffic_models: Vec<Box<dyn TrafficModel>> = Vec::with_capacity(free_flow_speed.len());
-        for ffs in free_flow_speed {
-            match traffic_model_type {
-                TrafficModelType::ModifiedLee => {
-                    traffic_models.push(Box::new(ModifiedLee::new(*ffs, 0.0)));
-                }
+    /// an uncalibrated traffic model for the given free flow speed in km/h
+    pub fn new_model(traffic_model_type: &TrafficModelType, free_flow_speed: f64) -> Box<dyn TrafficModel> {
+        match traffic_model_type {
+            TrafficModelType::ModifiedLee => Box::new(ModifiedLee::new(free_flow_speed, 0.0)),
+        }
+    }
+
+    /// a traffic model with the calibrated parameters of `model`, but the given free flow speed in km/h
+    pub fn with_free_flow_speed(traffic_model_type: &TrafficModelType, model: &dyn TrafficModel, free_flow_speed: f64) -> Box<dyn TrafficModel> {
+        match traffic_model_type {
+            TrafficModelType::ModifiedLee => {
+                // params[0] = free_flow_speed, see `ModifiedLee::get_params_as_vec`
+                let mut params = model.get_params_as_vec();
+                params[0] = free_flow_speed;
+                Box::new(ModifiedLee::from_vec(&params))
             }
         }
+    }
+
+    /// initialize traffic model data with empty observations and traffic models with given free flow speeds in km/h
+    pub fn init(free_flow_speed: &Vec<f64>, traffic_model_type: TrafficModelType) -> Self {
+        let traffic_models: Vec<Box<dyn TrafficModel>> = free_flow_speed.iter().map(|&ffs| Self::new_model(&traffic_model_type, ffs)).collect();
 
         TrafficModelData {
             traffic_models: traffic_models,
@@ -0,0 +1,336 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use conversion::{DEFAULT_VTYPE, FILE_EDGE_LENGTHS, FILE_EDGE_TOLLS, FILE_QUERY_VTYPES};
+use rust_road_router::{
+    algo::customizable_contraction_hierarchy::CCH,
+    datastr::graph::{
+        EdgeId,
+        floating_time_dependent::{FlWeight, TDGraph, Timestamp},
+    },
+    io::{Load, read_strings_from_file},
+};
+use serde::Deserialize;
+
+use crate::{
+    alternative_paths::AlternativePathsForDTA, choice::ChoiceAlgorithm, customize::customize, query::get_paths_with_cch_queries, relative_gap::get_relative_gap,
+};
+
+/// the value of time of the default class, which makes its generalized cost equal to the travel time in seconds
+const DEFAULT_VALUE_OF_TIME: f64 = 3600.0;
+
+/// a group of trips with its own route choice and generalized cost:
+/// `value_of_time * travel time in hours + distance_cost * length in km + toll_factor * tolls`
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserClass {
+    pub name: String,
+    /// the vTypes of the trips belonging to this class
+    pub vtypes: Vec<String>,
+    /// monetary units per hour of travel time
+    #[serde(default = "default_value_of_time")]
+    pub value_of_time: f64,
+    /// monetary units per kilometer
+    #[serde(default)]
+    pub distance_cost: f64,
+    /// factor applied to the tolls of the edges
+    #[serde(default = "default_toll_factor")]
+    pub toll_factor: f64,
+    /// the route choice of this class, the global one is used if not given
+    #[serde(default)]
+    pub choice: Option<ChoiceAlgorithm>,
+    /// route this class by its generalized cost converted to seconds instead of by travel time
+    #[serde(default)]
+    pub own_metric: bool,
+}
+
+fn default_value_of_time() -> f64 {
+    DEFAULT_VALUE_OF_TIME
+}
+
+fn default_toll_factor() -> f64 {
+    1.0
+}
+
+impl UserClass {
+    /// trips whose vType is not part of any class, their costs are travel times
+    pub fn default_class() -> Self {
+        Self {
+            name: String::from("default"),
+            vtypes: vec![],
+            value_of_time: DEFAULT_VALUE_OF_TIME,
+            distance_cost: 0.0,
+            toll_factor: 0.0,
+            choice: None,
+            own_metric: false,
+        }
+    }
+
+    pub fn generalized_cost(&self, travel_time: f64, length: f64, toll: f64) -> f64 {
+        self.value_of_time * travel_time / 3600.0 + self.distance_cost * length / 1000.0 + self.toll_factor * toll
+    }
+
+    /// the costs of an edge which do not depend on time, converted to seconds of travel time
+    pub fn time_independent_cost_in_seconds(&self, length: f64, toll: f64) -> f64 {
+        (self.distance_cost * length / 1000.0 + self.toll_factor * toll) * 3600.0 / self.value_of_time
+    }
+}
+
+/// the user classes and the class of each query
+#[derive(Debug, Clone)]
+pub struct UserClasses {
+    pub classes: Vec<UserClass>,
+    pub class_of_query: Vec<usize>,
+    edge_lengths: Vec<f64>,
+    edge_tolls: Vec<f64>,
+}
+
+impl UserClasses {
+    pub fn new(classes: Vec<UserClass>, query_vtypes: &Vec<String>, edge_lengths: Vec<f64>, edge_tolls: Vec<f64>) -> Self {
+        let mut classes = classes;
+        for class in &classes {
+            assert!(class.value_of_time > 0.0, "value of time of user class {} must be positive", class.name);
+        }
+
+        let default_class = classes.len();
+        let class_of_query: Vec<usize> = query_vtypes
+            .iter()
+            .map(|vtype| classes.iter().position(|class| class.vtypes.contains(vtype)).unwrap_or(default_class))
+            .collect();
+
+        if class_of_query.contains(&default_class) {
+            classes.push(UserClass::default_class());
+        }
+
+        Self {
+            classes,
+            class_of_query,
+            edge_lengths,
+            edge_tolls,
+        }
+    }
+
+    /// reads the classes from a json file containing a list of classes and assigns the preprocessed queries to them
+    pub fn load(config_file: &Path, input_dir: &Path) -> Self {
+        let file = File::open(config_file).unwrap_or_else(|_| panic!("Failed to open user classes file {}", config_file.display()));
+        let classes: Vec<UserClass> = serde_json::from_reader(BufReader::new(file)).expect("Failed to parse user classes");
+
+        let edge_lengths = Vec::<f64>::load_from(input_dir.join(FILE_EDGE_LENGTHS)).unwrap();
+
+        let edge_tolls = if input_dir.join(FILE_EDGE_TOLLS).exists() {
+            Vec::<f64>::load_from(input_dir.join(FILE_EDGE_TOLLS)).unwrap()
+        } else {
+            println!("Warning: no edge tolls found, rerun the preprocessing to read them from the network");
+            vec![0.0; edge_lengths.len()]
+        };
+
+        let query_vtypes = if input_dir.join(FILE_QUERY_VTYPES).exists() {
+            read_strings_from_file(&input_dir.join(FILE_QUERY_VTYPES)).unwrap()
+        } else {
+            println!("Warning: no vTypes of the trips found, rerun the preprocessing to assign trips to user classes");
+            let num_queries = read_strings_from_file(&input_dir.join(conversion::FILE_QUERY_IDS)).unwrap().len();
+            vec![String::from(DEFAULT_VTYPE); num_queries]
+        };
+
+        let user_classes = Self::new(classes, &query_vtypes, edge_lengths, edge_tolls);
+        for (class_index, class) in user_classes.classes.iter().enumerate() {
+            println!(
+                "User class {}: {} trips, value of time={}, distance cost={}, toll factor={}, own metric={}",
+                class.name,
+                user_classes.get_queries_of_class(class_index).len(),
+                class.value_of_time,
+                class.distance_cost,
+                class.toll_factor,
+                class.own_metric
+            );
+        }
+        user_classes
+    }
+
+    pub fn get_class(&self, query: usize) -> &UserClass {
+        &self.classes[self.class_of_query[query]]
+    }
+
+    pub fn get_queries_of_class(&self, class_index: usize) -> Vec<usize> {
+        (0..self.class_of_query.len()).filter(|&i| self.class_of_query[i] == class_index).collect()
+    }
+
+    pub fn has_own_metrics(&self) -> bool {
+        self.classes.iter().any(|class| class.own_metric)
+    }
+
+    pub fn get_choice_algorithm<'a>(&'a self, query: usize, default: &'a ChoiceAlgorithm) -> &'a ChoiceAlgorithm {
+        self.get_class(query).choice.as_ref().unwrap_or(default)
+    }
+
+    pub fn generalized_cost(&self, query: usize, travel_time: f64, path: &[EdgeId]) -> f64 {
+        let length = path.iter().map(|&edge| self.edge_lengths[edge as usize]).sum();
+        let toll = path.iter().map(|&edge| self.edge_tolls[edge as usize]).sum();
+        self.get_class(query).generalized_cost(travel_time, length, toll)
+    }
+
+    /// expects the costs of the alternatives to be travel times and replaces them by the generalized costs
+    pub fn apply_generalized_costs(&self, alternative_paths: &mut AlternativePathsForDTA) {
+        for (i, alternatives) in alternative_paths.alternatives_in_query.iter_mut().enumerate() {
+            for (j, path) in alternatives.paths.iter().enumerate() {
+                alternatives.costs[j] = self.generalized_cost(i, alternatives.costs[j], &path.edges);
+            }
+        }
+    }
+
+    /// a copy of the graph where the time independent costs of the class are added to each travel time function
+    pub fn get_metric_graph(&self, class_index: usize, graph: &TDGraph) -> TDGraph {
+        let class = &self.classes[class_index];
+        let num_edges = graph.head().len();
+
+        let mut first_ipp_of_arc = Vec::with_capacity(num_edges + 1);
+        let mut ipp_departure_time = Vec::new();
+        let mut ipp_travel_time = Vec::new();
+
+        for edge in 0..num_edges {
+            first_ipp_of_arc.push(ipp_departure_time.len() as u32);
+            let penalty = class.time_independent_cost_in_seconds(self.edge_lengths[edge], self.edge_tolls[edge]);
+
+            for point in graph.travel_time_function(edge as EdgeId).iter() {
+                ipp_departure_time.push((f64::from(point.at) * 1000.0).round() as u32);
+                ipp_travel_time.push(((f64::from(point.val) + penalty) * 1000.0).round() as u32);
+            }
+        }
+        first_ipp_of_arc.push(ipp_departure_time.len() as u32);
+
+        TDGraph::new_with_horizon(
+            graph.horizon(),
+            Vec::from(graph.first_out()),
+            Vec::from(graph.head()),
+            first_ipp_of_arc,
+            ipp_departure_time,
+            ipp_travel_time,
+        )
+    }
+
+    /// reroutes the queries of classes with their own metric on that metric,
+    /// the travel times of the new paths are evaluated on the original graph
+    pub fn route_classes_with_own_metric(
+        &self,
+        cch: &CCH,
+        graph: &TDGraph,
+        query_data: &(Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>),
+        paths: &mut Vec<Vec<EdgeId>>,
+        travel_times: &mut Vec<FlWeight>,
+        routing_threads: usize,
+    ) {
+        for (class_index, class) in self.classes.iter().enumerate() {
+            if !class.own_metric {
+                continue;
+            }
+
+            let queries = self.get_queries_of_class(class_index);
+            if queries.is_empty() {
+                continue;
+            }
+
+            let metric_graph = self.get_metric_graph(class_index, graph);
+            let customized_graph = customize(cch, &metric_graph);
+
+            let (class_paths, _, _) = get_paths_with_cch_queries(
+                cch,
+                &customized_graph,
+                &queries.iter().map(|&i| query_data.0[i]).collect(),
+                &queries.iter().map(|&i| query_data.1[i]).collect(),
+                &queries.iter().map(|&i| query_data.2[i]).collect(),
+                &queries.iter().map(|&i| query_data.3[i]).collect(),
+                &queries.iter().map(|&i| query_data.4[i]).collect(),
+                &metric_graph,
+                routing_threads,
+            );
+
+            for (k, &i) in queries.iter().enumerate() {
+                travel_times[i] = graph.get_travel_time_along_path(Timestamp::from_millis(query_data.2[i]), &class_paths[k]);
+                paths[i] = class_paths[k].clone();
+            }
+        }
+    }
+
+    /// the relative gap of the travel times of the trips in each class, classes without trips have a gap of 0
+    pub fn get_relative_gaps(&self, best_tts: &Vec<f64>, simulated_tts: &Vec<f64>) -> Vec<(String, f64)> {
+        self.classes
+            .iter()
+            .enumerate()
+            .map(|(class_index, class)| {
+                let queries = self.get_queries_of_class(class_index);
+                if queries.is_empty() {
+                    return (class.name.clone(), 0.0);
+                }
+                let relative_gap = get_relative_gap(
+                    &queries.iter().map(|&i| best_tts[i]).collect(),
+                    &queries.iter().map(|&i| simulated_tts[i]).collect(),
+                );
+                (class.name.clone(), relative_gap)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_classes() -> UserClasses {
+        let classes: Vec<UserClass> = serde_json::from_str(
+            r#"[
+                {"name": "commuter", "vtypes": ["car"], "value_of_time": 18.0, "distance_cost": 0.3, "toll_factor": 0.0, "choice": {"method": "logit", "beta": 0.15, "gamma": 1.0, "theta": 0.1}},
+                {"name": "truck", "vtypes": ["truck", "trailer"], "value_of_time": 36.0, "toll_factor": 2.0, "own_metric": true}
+            ]"#,
+        )
+        .unwrap();
+
+        let query_vtypes = vec!["car", "truck", "bus", "trailer"].into_iter().map(String::from).collect();
+        UserClasses::new(classes, &query_vtypes, vec![1000.0, 2000.0], vec![0.0, 5.0])
+    }
+
+    #[test]
+    fn test_class_assignment() {
+        let user_classes = user_classes();
+
+        assert_eq!(user_classes.classes.len(), 3);
+        assert_eq!(user_classes.class_of_query, vec![0, 1, 2, 1]);
+        assert_eq!(user_classes.get_class(2).name, "default");
+        assert_eq!(user_classes.get_queries_of_class(1), vec![1, 3]);
+
+        let global = ChoiceAlgorithm::create_gawron(0.5, 0.9);
+        assert!(matches!(user_classes.get_choice_algorithm(0, &global), ChoiceAlgorithm::Logit { .. }));
+        assert!(matches!(user_classes.get_choice_algorithm(1, &global), ChoiceAlgorithm::Gawron { .. }));
+    }
+
+    #[test]
+    fn test_generalized_cost() {
+        let user_classes = user_classes();
+        let path = vec![0, 1];
+
+        // half an hour at 18 per hour and 3 km at 0.3 per km, commuters ignore tolls
+        assert!((user_classes.generalized_cost(0, 1800.0, &path) - 9.9).abs() < 1e-9);
+        // half an hour at 36 per hour and twice the toll of 5
+        assert!((user_classes.generalized_cost(1, 1800.0, &path) - 28.0).abs() < 1e-9);
+        // the default class pays in seconds of travel time
+        assert!((user_classes.generalized_cost(2, 1800.0, &path) - 1800.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_relative_gaps() {
+        let user_classes = user_classes();
+        let best_tts = vec![100.0, 200.0, 300.0, 200.0];
+        let simulated_tts = vec![150.0, 200.0, 300.0, 300.0];
+
+        let relative_gaps = user_classes.get_relative_gaps(&best_tts, &simulated_tts);
+        assert_eq!(relative_gaps[0], (String::from("commuter"), 0.5));
+        assert_eq!(relative_gaps[1], (String::from("truck"), 0.25));
+        assert_eq!(relative_gaps[2], (String::from("default"), 0.0));
+    }
+
+    #[test]
+    fn test_time_independent_cost_in_seconds() {
+        let user_classes = user_classes();
+
+        // a toll of 5 with factor 2 is worth 10 / 36 hours
+        assert!((user_classes.classes[1].time_independent_cost_in_seconds(2000.0, 5.0) - 1000.0).abs() < 1e-9);
+        assert_eq!(user_classes.classes[2].time_independent_cost_in_seconds(2000.0, 5.0), 0.0);
+    }
+}
@@ -0,0 +1,105 @@
+// Long running live traffic service.
+// Reads live updates from a file (tailed) or a socket (`tcp:<addr>`, `unix:<path>`), periodically customizes
+// the multi metric potentials with them and answers queries read from stdin as `from,to[,departure]` lines.
+// Without departure, queries depart at the current time of the service.
+
+#[macro_use]
+extern crate rust_road_router;
+use rust_road_router::{
+    algo::{customizable_contraction_hierarchy::*, live_traffic::*, td_astar::*, *},
+    cli::CliErr,
+    datastr::{graph::time_dependent::*, node_order::*},
+    io::*,
+    report::*,
+};
+use std::{
+    env,
+    error::Error,
+    io::{stdin, BufRead},
+    path::Path,
+    sync::mpsc::channel,
+    time::Duration,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let _reporter = enable_reporting("live_traffic_service");
+
+    let mut args = env::args().skip(1);
+    let arg = &args.next().ok_or(CliErr("No directory arg given"))?;
+    let path = Path::new(arg);
+    let source: UpdateSource = args.next().ok_or(CliErr("No update source given"))?.parse()?;
+
+    let t_start = args.next().unwrap_or("0".to_string()).parse()?;
+    report!("t_start", t_start);
+    let customization_interval_s = args.next().unwrap_or("60".to_string()).parse()?;
+    report!("customization_interval_s", customization_interval_s);
+    let pre_in = args.next().unwrap_or("multi_metric_pre".to_string());
+
+    let graph = TDGraph::reconstruct_from(&path)?;
+    let order = NodeOrder::from_node_order(Vec::load_from(path.join("cch_perm"))?);
+    let cch = CCH::fix_order_and_build(&graph, order);
+    let mmp: MultiMetricPreprocessed = cch.reconstruct_from(&path.join(pre_in))?;
+
+    let state = if path.join("geo_distance").exists() {
+        LiveTrafficState::with_lengths(graph, Vec::load_from(path.join("geo_distance"))?)
+    } else {
+        LiveTrafficState::new(graph)
+    };
+    let params = LiveTrafficParams {
+        customization_interval: Duration::from_secs(customization_interval_s),
+        ..Default::default()
+    };
+    let mut service = LiveTrafficService::new(state, mmp, params);
+    let mut query_server = service.subscribe();
+
+    let clock = LiveClock::new(t_start);
+    {
+        let _blocked = block_reporting();
+        service.customize(clock.now());
+    }
+
+    let (sender, updates) = channel();
+    // detached on purpose, listeners never terminate on their own
+    source.spawn(sender)?;
+
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            let _blocked = block_reporting();
+            if !query_server.wait_for_generation() {
+                return;
+            }
+            for line in stdin().lock().lines() {
+                let line = line.unwrap();
+                let fields: Vec<_> = line.split(',').map(str::trim).collect();
+                let (from, to, departure) = match fields[..] {
+                    [from, to] => (from.parse(), to.parse(), Ok(clock.now())),
+                    [from, to, departure] => (from.parse(), to.parse(), departure.parse()),
+                    _ => {
+                        eprintln!("expected from,to[,departure] but got {:?}", line);
+                        continue;
+                    }
+                };
+                let (Ok(from), Ok(to), Ok(departure)) = (from, to, departure) else {
+                    eprintln!("invalid query {:?}", line);
+                    continue;
+                };
+                let travel_time = query_server.query(TDQuery { from, to, departure });
+                println!(
+                    "{},{},{},{},{}",
+                    from,
+                    to,
+                    departure,
+                    travel_time.map(|tt| tt.to_string()).unwrap_or_default(),
+                    query_server.generation().unwrap()
+                );
+            }
+        });
+
+        let _blocked = block_reporting();
+        service.run(&updates, &clock);
+    });
+
+    report!("num_generations", service.generation());
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+// Convert a graph directory of raw RoutingKit style dumps into container files (see `rust_road_router::io::container`) or back.
+// Files are copied recursively. The element type of a file is determined by its name, unknown files are copied unchanged.
+
+use rust_road_router::{
+    cli::CliErr,
+    io::{container::*, *},
+};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Element types of the files commonly found in graph directories.
+const KNOWN_FILES: &[(&str, ElementType)] = &[
+    ("first_out", ElementType::U32),
+    ("head", ElementType::U32),
+    ("tail", ElementType::U32),
+    ("travel_time", ElementType::U32),
+    ("geo_distance", ElementType::U32),
+    ("way", ElementType::U32),
+    ("latitude", ElementType::F32),
+    ("longitude", ElementType::F32),
+    ("first_ipp_of_arc", ElementType::U32),
+    ("ipp_departure_time", ElementType::U32),
+    ("ipp_travel_time", ElementType::U32),
+    ("first_constant_hour", ElementType::U32),
+    ("cch_perm", ElementType::U32),
+    ("cch_exp_perm", ElementType::U32),
+    ("order", ElementType::U32),
+    ("rank", ElementType::U32),
+    ("weight", ElementType::U32),
+    ("source", ElementType::U32),
+    ("target", ElementType::U32),
+    ("source_node", ElementType::U32),
+    ("target_node", ElementType::U32),
+    ("source_time", ElementType::U32),
+    ("travel_time_length", ElementType::U32),
+    ("forbidden_turn_from_arc", ElementType::U32),
+    ("forbidden_turn_to_arc", ElementType::U32),
+];
+
+struct Conversion {
+    to_raw: bool,
+    options: ContainerOptions,
+    types: HashMap<String, ElementType>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let (input, output) = match (args.next(), args.next()) {
+        (Some(input), Some(output)) => (PathBuf::from(input), PathBuf::from(output)),
+        _ => {
+            print_usage();
+            return Err(Box::new(CliErr("Invalid arguments")));
+        }
+    };
+
+    let mut conversion = Conversion {
+        to_raw: false,
+        options: ContainerOptions::default(),
+        types: KNOWN_FILES.iter().map(|&(name, element_type)| (name.to_string(), element_type)).collect(),
+    };
+
+    for arg in args {
+        match arg.as_str() {
+            "--to-raw" => conversion.to_raw = true,
+            "--compress" => conversion.options.compression = Compression::DeltaVarint,
+            "--varint" => conversion.options.compression = Compression::Varint,
+            "--no-checksum" => conversion.options.checksum = false,
+            _ => match arg.split_once('=') {
+                Some((name, type_name)) => {
+                    let element_type = ElementType::parse(type_name).ok_or(CliErr("Invalid data type"))?;
+                    conversion.types.insert(name.to_string(), element_type);
+                }
+                None => {
+                    print_usage();
+                    return Err(Box::new(CliErr("Invalid arguments")));
+                }
+            },
+        }
+    }
+
+    if input == output {
+        return Err(Box::new(CliErr("Input and output directory have to differ")));
+    }
+
+    conversion.convert_dir(&input, &output)
+}
+
+impl Conversion {
+    fn convert_dir(&self, input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(output)?;
+
+        for entry in fs::read_dir(input)? {
+            let entry = entry?;
+            let source = entry.path();
+            let destination = output.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                self.convert_dir(&source, &destination)?;
+            } else {
+                self.convert_file(&source, &destination)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_file(&self, source: &Path, destination: &Path) -> Result<(), Box<dyn Error>> {
+        let header = read_header(source)?;
+
+        if self.to_raw {
+            if let Some(header) = header {
+                let (_, data) = read_bytes(source)?;
+                data.write_to(&destination)?;
+                println!("{}: {} -> raw", source.display(), header.element_type.name());
+            } else {
+                fs::copy(source, destination)?;
+            }
+            return Ok(());
+        }
+
+        let name = source.file_name().unwrap().to_string_lossy();
+        match (header, self.types.get(name.as_ref())) {
+            (Some(header), _) => {
+                // Already a container, possibly with different options - rewrite with the requested ones.
+                let (_, data) = read_bytes(source)?;
+                write_container_bytes(&data, header.element_type, &destination, self.options)?;
+            }
+            (None, Some(&element_type)) => {
+                let (_, data) = read_bytes(source)?;
+                write_container_bytes(&data, element_type, &destination, self.options)?;
+                let converted = fs::metadata(destination)?.len();
+                println!(
+                    "{}: {} elements of {}, {} -> {} bytes",
+                    source.display(),
+                    data.len() / element_type.width(),
+                    element_type.name(),
+                    data.len(),
+                    converted
+                );
+            }
+            (None, None) => {
+                eprintln!("{}: unknown element type, copying unchanged", source.display());
+                fs::copy(source, destination)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn print_usage() {
+    eprintln!(
+        "Usage: convert_graph_format input_dir output_dir [--compress | --varint] [--no-checksum] [--to-raw] [file_name=data_type ...]
+
+Converts all raw arrays in input_dir (recursively) into typed container files in output_dir.
+The element type is derived from the file name for the usual RoutingKit files and can be given explicitly with file_name=data_type (e.g. travel_time=u32, speed=f64).
+--compress stores integer arrays delta and varint encoded, --varint only varint encoded.
+--to-raw converts container files back into raw dumps, e.g. for tools which do not understand containers.
+"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversion(to_raw: bool, compression: Compression) -> Conversion {
+        Conversion {
+            to_raw,
+            options: ContainerOptions { compression, checksum: true },
+            types: KNOWN_FILES.iter().map(|&(name, element_type)| (name.to_string(), element_type)).collect(),
+        }
+    }
+
+    #[test]
+    fn raw_container_raw_roundtrip_preserves_bytes() {
+        let base = env::temp_dir().join(format!("convert_graph_format_{}", std::process::id()));
+        let (raw, container, back) = (base.join("raw"), base.join("container"), base.join("back"));
+        fs::create_dir_all(raw.join("sub")).unwrap();
+
+        let files: Vec<(PathBuf, Vec<u8>)> = vec![
+            (raw.join("first_out"), vec![0u32, 2, 3, 3].data_bytes().to_vec()),
+            (raw.join("head"), vec![1u32, 2, 0].data_bytes().to_vec()),
+            (raw.join("latitude"), vec![49.0f32, 49.5, 50.0].data_bytes().to_vec()),
+            (raw.join("sub").join("travel_time"), vec![10u32, 7, 100_000].data_bytes().to_vec()),
+            (raw.join("notes"), b"not an array".to_vec()),
+        ];
+        for (path, bytes) in &files {
+            fs::write(path, bytes).unwrap();
+        }
+
+        for compression in [Compression::None, Compression::Varint, Compression::DeltaVarint] {
+            conversion(false, compression).convert_dir(&raw, &container).unwrap();
+            assert_eq!(read_header(container.join("head")).unwrap().unwrap().element_type, ElementType::U32);
+            assert_eq!(read_header(container.join("latitude")).unwrap().unwrap().element_type, ElementType::F32);
+            assert!(read_header(container.join("notes")).unwrap().is_none());
+
+            conversion(true, Compression::None).convert_dir(&container, &back).unwrap();
+            for (path, bytes) in &files {
+                let converted = back.join(path.strip_prefix(&raw).unwrap());
+                assert_eq!(&fs::read(&converted).unwrap(), bytes, "{}", converted.display());
+            }
+
+            fs::remove_dir_all(&container).unwrap();
+            fs::remove_dir_all(&back).unwrap();
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+// Match raw GPS traces onto a graph with the HMM map matcher.
+// Expects a graph directory (with geo_distance, latitude, longitude and cch_perm), a CSV file with `trace_id,timestamp_ms,latitude,longitude` lines
+// ordered by trace and timestamp and an output directory.
+// For every matched point the output contains `matched_trace` (index of the trace in the input), `matched_segment`, `matched_timestamp`,
+// `matched_link_id` and `matched_fraction`.
+// The matched paths are written as `path_edge` with `first_path_edge` containing the offsets for each segment.
+
+use rust_road_router::{
+    algo::{customizable_contraction_hierarchy::*, map_matching::*},
+    cli::CliErr,
+    datastr::{graph::*, node_order::NodeOrder},
+    io::*,
+};
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let (graph_dir, trace_file, output_dir) = match (args.next(), args.next(), args.next()) {
+        (Some(graph_dir), Some(trace_file), Some(output_dir)) => (graph_dir, trace_file, output_dir),
+        _ => {
+            eprintln!("Usage: map_match graph_dir traces.csv output_dir");
+            return Err(Box::new(CliErr("Invalid arguments")));
+        }
+    };
+    let path = Path::new(&graph_dir);
+
+    let graph = WeightedGraphReconstructor("geo_distance").reconstruct_from(&path)?;
+    let latitude = Vec::<f32>::load_from(path.join("latitude"))?;
+    let longitude = Vec::<f32>::load_from(path.join("longitude"))?;
+    let order = NodeOrder::from_node_order(Vec::load_from(path.join("cch_perm"))?);
+    let cch = CCH::fix_order_and_build(&graph, order);
+    let customized = customize(&cch, &graph);
+
+    let mut matcher = MapMatcher::new(
+        graph.first_out(),
+        graph.head(),
+        graph.weight(),
+        &latitude,
+        &longitude,
+        customized,
+        MapMatchingParams::default(),
+    );
+
+    let mut matched_trace = Vec::<u32>::new();
+    let mut matched_segment = Vec::<u32>::new();
+    let mut matched_timestamp = Vec::<u64>::new();
+    let mut matched_link_id = Vec::<EdgeId>::new();
+    let mut matched_fraction = Vec::<f32>::new();
+    let mut first_path_edge = vec![0 as EdgeId];
+    let mut path_edge = Vec::<EdgeId>::new();
+
+    let mut num_traces = 0;
+    let mut num_points = 0;
+    let mut match_trace = |trace: &[GpsPoint]| {
+        for segment in matcher.match_trace(trace) {
+            for (point, trace_data) in segment.points.iter().zip(segment.trace_data(trace)) {
+                matched_trace.push(num_traces);
+                matched_segment.push(first_path_edge.len() as u32 - 1);
+                matched_timestamp.push(trace_data.timestamp);
+                matched_link_id.push(point.edge);
+                matched_fraction.push(trace_data.traversed_in_travel_direction_fraction);
+            }
+            path_edge.extend_from_slice(&segment.path);
+            first_path_edge.push(path_edge.len() as EdgeId);
+        }
+        num_traces += 1;
+        num_points += trace.len();
+    };
+
+    let mut current_id = None;
+    let mut trace = Vec::new();
+    for line in BufReader::new(File::open(&trace_file)?).lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        let point = match (fields[1].parse(), fields[2].parse(), fields[3].parse()) {
+            (Ok(timestamp), Ok(latitude), Ok(longitude)) => GpsPoint {
+                timestamp,
+                latitude,
+                longitude,
+            },
+            // header or broken line
+            _ => continue,
+        };
+        if current_id.as_deref() != Some(fields[0]) {
+            if !trace.is_empty() {
+                match_trace(&trace);
+                trace.clear();
+            }
+            current_id = Some(fields[0].to_string());
+        }
+        trace.push(point);
+    }
+    if !trace.is_empty() {
+        match_trace(&trace);
+    }
+
+    println!(
+        "matched {} of {} points of {} traces in {} segments",
+        matched_link_id.len(),
+        num_points,
+        num_traces,
+        first_path_edge.len() - 1
+    );
+
+    let output = Path::new(&output_dir);
+    matched_trace.write_to(&output.join("matched_trace"))?;
+    matched_segment.write_to(&output.join("matched_segment"))?;
+    matched_timestamp.write_to(&output.join("matched_timestamp"))?;
+    matched_link_id.write_to(&output.join("matched_link_id"))?;
+    matched_fraction.write_to(&output.join("matched_fraction"))?;
+    first_path_edge.write_to(&output.join("first_path_edge"))?;
+    path_edge.write_to(&output.join("path_edge"))?;
+
+    Ok(())
+}
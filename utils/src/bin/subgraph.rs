@@ -1,8 +1,9 @@
 // Utility to extract a subgraph limited to a geographic bounding box.
+// If the graph has edge shapes (`first_shape_point`, `shape_latitude` and `shape_longitude`), they are carried over.
 
 use rust_road_router::{
     cli::CliErr,
-    datastr::{graph::*, rank_select_map::*},
+    datastr::{graph::*, rank_select_map::*, spatial_index::*},
     io::*,
 };
 use std::{env, error::Error, path::Path};
@@ -30,31 +31,38 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut new_lat = Vec::new();
     let mut new_lng = Vec::new();
 
+    let geometry = if path.join("first_shape_point").exists() {
+        Some(EdgeGeometry::reconstruct_from(&path)?)
+    } else {
+        None
+    };
+    let mut new_shapes = Vec::new();
+
     new_first_out.push(0);
 
-    let in_bounding_box = |node| lat[node] >= min_lat && lat[node] <= max_lat && lng[node] >= min_lon && lng[node] <= max_lon;
+    let bounding_box = BoundingBox::new(min_lat as f64, min_lon as f64, max_lat as f64, max_lon as f64);
+    let nodes = NodeGridIndex::new(&lat, &lng).nodes_in_bbox(&bounding_box);
 
     let mut new_nodes = BitVec::new(graph.num_nodes());
-
-    for node in 0..graph.num_nodes() {
-        if in_bounding_box(node) {
-            new_nodes.set(node);
-        }
+    for &node in &nodes {
+        new_nodes.set(node as usize);
     }
 
     let id_map = RankSelectMap::new(new_nodes);
 
-    for node in 0..graph.num_nodes() {
-        if in_bounding_box(node) {
-            new_first_out.push(*new_first_out.last().unwrap());
-            new_lat.push(lat[node]);
-            new_lng.push(lng[node]);
+    for &node in &nodes {
+        let node = node as usize;
+        new_first_out.push(*new_first_out.last().unwrap());
+        new_lat.push(lat[node]);
+        new_lng.push(lng[node]);
 
-            for link in LinkIterable::<Link>::link_iter(&graph, node as NodeId) {
-                if in_bounding_box(link.node as usize) {
-                    *new_first_out.last_mut().unwrap() += 1;
-                    new_head.push(id_map.get(link.node as usize).unwrap() as u32);
-                    new_travel_time.push(link.weight);
+        for (NodeIdT(head), weight, EdgeIdT(edge)) in LinkIterable::<(NodeIdT, Weight, EdgeIdT)>::link_iter(&graph, node as NodeId) {
+            if let Some(new_head_id) = id_map.get(head as usize) {
+                *new_first_out.last_mut().unwrap() += 1;
+                new_head.push(new_head_id as u32);
+                new_travel_time.push(weight);
+                if let Some(geometry) = &geometry {
+                    new_shapes.push(geometry.shape(edge).collect::<Vec<_>>());
                 }
             }
         }
@@ -65,6 +73,9 @@ fn main() -> Result<(), Box<dyn Error>> {
     new_travel_time.write_to(&out_path.join("travel_time"))?;
     new_lat.write_to(&out_path.join("latitude"))?;
     new_lng.write_to(&out_path.join("longitude"))?;
+    if geometry.is_some() {
+        EdgeGeometry::from_shapes(new_shapes).deconstruct_to(&out_path)?;
+    }
 
     Ok(())
 }
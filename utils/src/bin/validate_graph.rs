@@ -0,0 +1,88 @@
+// Check a graph directory for broken invariants and optionally write a repaired copy.
+// The repaired graph has no zero travel times, FIFO travel time functions and only contains the largest strongly connected component.
+// The original ids of the remaining nodes and edges are written to `node_mapping` and `edge_mapping`.
+
+use rust_road_router::{cli::CliErr, datastr::graph::time_dependent::period, io::*};
+use std::{env, error::Error, path::Path};
+use utils::validation::*;
+
+// How many issues of each kind get printed in detail.
+const MAX_PRINTED_PER_KIND: usize = 10;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let arg = &args.next().ok_or(CliErr("No graph directory arg given"))?;
+    let path = Path::new(arg);
+    let output = args.next();
+    let period = match args.next() {
+        Some(period) => period.parse()?,
+        None => period(),
+    };
+
+    let mut graph = GraphData::load_from_dir(path)?;
+    println!("{} nodes, {} arcs", graph.num_nodes(), graph.num_arcs());
+
+    let report = validate(&graph, period);
+    print_report(&report);
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            return if report.is_valid() {
+                Ok(())
+            } else {
+                Err(Box::new(CliErr("Graph is invalid")))
+            };
+        }
+    };
+
+    if report.issues.iter().any(Issue::is_structural) {
+        return Err(Box::new(CliErr("Structural issues can not be repaired")));
+    }
+    if report.issues.iter().any(|issue| {
+        matches!(
+            issue,
+            Issue::IppNotSorted { .. } | Issue::IppOutsidePeriod { .. } | Issue::InvalidCoordinate { .. }
+        )
+    }) {
+        eprintln!("Warning: unsorted ipps, ipps outside of the period and invalid coordinates are not repaired");
+    }
+
+    println!("fixed {} zero travel times", graph.fix_zero_travel_times());
+    println!("fifoized {} travel time functions", graph.fifoize(period));
+
+    let components = report.components.as_ref().unwrap();
+    let (graph, node_mapping, edge_mapping) = graph.largest_scc_subgraph(components);
+    println!(
+        "kept {} nodes and {} arcs of the largest strongly connected component",
+        graph.num_nodes(),
+        graph.num_arcs()
+    );
+
+    let output = Path::new(&output);
+    graph.write_to_dir(output)?;
+    node_mapping.write_to(&output.join("node_mapping"))?;
+    edge_mapping.write_to(&output.join("edge_mapping"))?;
+
+    let report = validate(&graph, period);
+    print_report(&report);
+
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    if report.is_valid() {
+        println!("no issues found");
+        return;
+    }
+
+    for (kind, count) in report.summary() {
+        println!("{}: {} issues", kind, count);
+        for issue in report.issues.iter().filter(|issue| issue.kind() == kind).take(MAX_PRINTED_PER_KIND) {
+            println!("  {}", issue);
+        }
+        if count > MAX_PRINTED_PER_KIND {
+            println!("  ...");
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod validation;
+
 #[cfg(test)]
 mod tests {
     #[test]
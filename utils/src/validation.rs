@@ -0,0 +1,697 @@
+//! Validation and repair of graph directories.
+//!
+//! Broken input data usually only surfaces as a panic or debug assertion deep inside preprocessing or customization.
+//! This module works on the raw arrays instead of the engine graph types, so even graphs which could not be constructed can be checked.
+//! `validate` collects all problems into a `Report`, the `GraphData` methods repair some of them.
+
+use rust_road_router::{
+    datastr::graph::{time_dependent::Timestamp, *},
+    io::*,
+};
+use std::{fmt, path::Path};
+
+/// Raw arrays of a graph directory.
+/// Everything except the topology is optional.
+#[derive(Debug, Clone, Default)]
+pub struct GraphData {
+    pub first_out: Vec<EdgeId>,
+    pub head: Vec<NodeId>,
+    pub travel_time: Option<Vec<Weight>>,
+    pub geo_distance: Option<Vec<Weight>>,
+    pub ipps: Option<IppData>,
+    pub latitude: Option<Vec<f32>>,
+    pub longitude: Option<Vec<f32>>,
+}
+
+/// Interpolation points of time-dependent travel time functions in milliseconds.
+#[derive(Debug, Clone, Default)]
+pub struct IppData {
+    pub first_ipp_of_arc: Vec<u32>,
+    pub ipp_departure_time: Vec<Timestamp>,
+    pub ipp_travel_time: Vec<Weight>,
+}
+
+/// A single problem found in the data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    EmptyFirstOut,
+    FirstOutStart(EdgeId),
+    FirstOutEnd { last: EdgeId, num_arcs: usize },
+    FirstOutNotSorted { node: NodeId },
+    HeadOutOfRange { edge: EdgeId, head: NodeId },
+    WrongLength { name: &'static str, expected: usize, actual: usize },
+    ZeroTravelTime { edge: EdgeId },
+    IppRangeInvalid { edge: EdgeId },
+    EmptyTravelTimeFunction { edge: EdgeId },
+    IppNotSorted { edge: EdgeId, ipp: usize },
+    IppOutsidePeriod { edge: EdgeId, ipp: usize, at: Timestamp },
+    FifoViolation { edge: EdgeId, ipp: usize },
+    FifoViolationAcrossPeriod { edge: EdgeId },
+    NotPeriodic { edge: EdgeId },
+    InvalidCoordinate { node: NodeId, lat: f32, lng: f32 },
+    NotStronglyConnected { num_components: usize, nodes_outside_largest: usize },
+}
+
+impl Issue {
+    /// Short name of the kind of issue, useful for summaries.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Issue::EmptyFirstOut | Issue::FirstOutStart(_) | Issue::FirstOutEnd { .. } | Issue::FirstOutNotSorted { .. } => "first_out",
+            Issue::HeadOutOfRange { .. } => "head",
+            Issue::WrongLength { .. } => "length",
+            Issue::ZeroTravelTime { .. } => "zero_travel_time",
+            Issue::IppRangeInvalid { .. } | Issue::EmptyTravelTimeFunction { .. } => "first_ipp_of_arc",
+            Issue::IppNotSorted { .. } => "ipp_order",
+            Issue::IppOutsidePeriod { .. } | Issue::NotPeriodic { .. } => "period",
+            Issue::FifoViolation { .. } | Issue::FifoViolationAcrossPeriod { .. } => "fifo",
+            Issue::InvalidCoordinate { .. } => "coordinates",
+            Issue::NotStronglyConnected { .. } => "connectivity",
+        }
+    }
+
+    /// Structural issues make all further checks meaningless.
+    pub fn is_structural(&self) -> bool {
+        matches!(
+            self,
+            Issue::EmptyFirstOut
+                | Issue::FirstOutStart(_)
+                | Issue::FirstOutEnd { .. }
+                | Issue::FirstOutNotSorted { .. }
+                | Issue::HeadOutOfRange { .. }
+                | Issue::WrongLength { .. }
+                | Issue::IppRangeInvalid { .. }
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::EmptyFirstOut => write!(f, "first_out is empty"),
+            Issue::FirstOutStart(first) => write!(f, "first_out starts with {} instead of 0", first),
+            Issue::FirstOutEnd { last, num_arcs } => write!(f, "first_out ends with {} but there are {} arcs", last, num_arcs),
+            Issue::FirstOutNotSorted { node } => write!(f, "first_out decreases at node {}", node),
+            Issue::HeadOutOfRange { edge, head } => write!(f, "head of edge {} is {} which is not a valid node", edge, head),
+            Issue::WrongLength { name, expected, actual } => write!(f, "{} has {} elements, expected {}", name, actual, expected),
+            Issue::ZeroTravelTime { edge } => write!(f, "edge {} has zero travel time", edge),
+            Issue::IppRangeInvalid { edge } => write!(f, "first_ipp_of_arc of edge {} points out of the ipp arrays or decreases", edge),
+            Issue::EmptyTravelTimeFunction { edge } => write!(f, "edge {} has no interpolation points", edge),
+            Issue::IppNotSorted { edge, ipp } => write!(f, "departure times of edge {} not strictly increasing at ipp {}", edge, ipp),
+            Issue::IppOutsidePeriod { edge, ipp, at } => write!(f, "ipp {} of edge {} departs at {} which is outside the period", ipp, edge, at),
+            Issue::FifoViolation { edge, ipp } => write!(f, "edge {} violates the FIFO property between ipps {} and {}", edge, ipp, ipp + 1),
+            Issue::FifoViolationAcrossPeriod { edge } => write!(f, "edge {} violates the FIFO property across the end of the period", edge),
+            Issue::NotPeriodic { edge } => write!(f, "edge {} has different travel times at the start and end of the period", edge),
+            Issue::InvalidCoordinate { node, lat, lng } => write!(f, "node {} has invalid coordinate ({}, {})", node, lat, lng),
+            Issue::NotStronglyConnected {
+                num_components,
+                nodes_outside_largest,
+            } => write!(
+                f,
+                "graph has {} strongly connected components, {} nodes are outside of the largest one",
+                num_components, nodes_outside_largest
+            ),
+        }
+    }
+}
+
+/// Strongly connected components of a graph.
+#[derive(Debug, Clone)]
+pub struct Components {
+    /// Component id of each node.
+    pub component: Vec<u32>,
+    pub num_components: usize,
+    pub largest: u32,
+    pub largest_size: usize,
+}
+
+/// Result of `validate`.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+    /// Only computed when the structure of the graph is valid.
+    pub components: Option<Components>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Number of issues per kind, in order of first occurrence.
+    pub fn summary(&self) -> Vec<(&'static str, usize)> {
+        let mut summary: Vec<(&'static str, usize)> = Vec::new();
+        for issue in &self.issues {
+            match summary.iter_mut().find(|(kind, _)| *kind == issue.kind()) {
+                Some((_, count)) => *count += 1,
+                None => summary.push((issue.kind(), 1)),
+            }
+        }
+        summary
+    }
+}
+
+impl GraphData {
+    /// Load a graph directory. Only `first_out` and `head` are required.
+    pub fn load_from_dir(path: &Path) -> std::io::Result<Self> {
+        fn optional<T: Default + Copy + 'static>(path: &Path, name: &str) -> std::io::Result<Option<Vec<T>>> {
+            let file = path.join(name);
+            if file.exists() {
+                Ok(Some(Vec::load_from(file)?))
+            } else {
+                Ok(None)
+            }
+        }
+
+        let ipps = match (
+            optional(path, "first_ipp_of_arc")?,
+            optional(path, "ipp_departure_time")?,
+            optional(path, "ipp_travel_time")?,
+        ) {
+            (Some(first_ipp_of_arc), Some(ipp_departure_time), Some(ipp_travel_time)) => Some(IppData {
+                first_ipp_of_arc,
+                ipp_departure_time,
+                ipp_travel_time,
+            }),
+            _ => None,
+        };
+
+        Ok(Self {
+            first_out: Vec::load_from(path.join("first_out"))?,
+            head: Vec::load_from(path.join("head"))?,
+            travel_time: optional(path, "travel_time")?,
+            geo_distance: optional(path, "geo_distance")?,
+            ipps,
+            latitude: optional(path, "latitude")?,
+            longitude: optional(path, "longitude")?,
+        })
+    }
+
+    /// Write all present arrays into a graph directory.
+    pub fn write_to_dir(&self, path: &Path) -> std::io::Result<()> {
+        if !path.exists() {
+            std::fs::create_dir_all(path)?;
+        }
+        self.first_out.write_to(&path.join("first_out"))?;
+        self.head.write_to(&path.join("head"))?;
+        if let Some(travel_time) = &self.travel_time {
+            travel_time.write_to(&path.join("travel_time"))?;
+        }
+        if let Some(geo_distance) = &self.geo_distance {
+            geo_distance.write_to(&path.join("geo_distance"))?;
+        }
+        if let Some(ipps) = &self.ipps {
+            ipps.first_ipp_of_arc.write_to(&path.join("first_ipp_of_arc"))?;
+            ipps.ipp_departure_time.write_to(&path.join("ipp_departure_time"))?;
+            ipps.ipp_travel_time.write_to(&path.join("ipp_travel_time"))?;
+        }
+        if let Some(latitude) = &self.latitude {
+            latitude.write_to(&path.join("latitude"))?;
+        }
+        if let Some(longitude) = &self.longitude {
+            longitude.write_to(&path.join("longitude"))?;
+        }
+        Ok(())
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.first_out.len().saturating_sub(1)
+    }
+
+    pub fn num_arcs(&self) -> usize {
+        self.head.len()
+    }
+
+    fn ipp_range(ipps: &IppData, edge: usize) -> std::ops::Range<usize> {
+        ipps.first_ipp_of_arc[edge] as usize..ipps.first_ipp_of_arc[edge + 1] as usize
+    }
+
+    /// Replace zero travel times by 1ms, both for static and time-dependent weights.
+    /// Returns the number of changed values.
+    pub fn fix_zero_travel_times(&mut self) -> usize {
+        let mut fixed = 0;
+        let static_weights = self.travel_time.iter_mut().flatten();
+        let td_weights = self.ipps.iter_mut().flat_map(|ipps| ipps.ipp_travel_time.iter_mut());
+        for tt in static_weights.chain(td_weights) {
+            if *tt == 0 {
+                *tt = 1;
+                fixed += 1;
+            }
+        }
+        fixed
+    }
+
+    /// Restore the FIFO property by raising travel times where a later departure would arrive earlier.
+    /// This includes the wrap around from the last point to the first one of the next period, which may raise the first travel time.
+    /// A final point at the period is dropped, `TDGraph::new` adds one consistent with the first point.
+    /// It only counts as a change when it did not match the first point.
+    /// Expects structurally valid data with sorted departure times within the period.
+    /// Returns the number of changed travel time functions.
+    pub fn fifoize(&mut self, period: Timestamp) -> usize {
+        let num_arcs = self.num_arcs();
+        let ipps = match &mut self.ipps {
+            Some(ipps) => ipps,
+            None => return 0,
+        };
+
+        let mut changed = 0;
+        let mut first_ipp_of_arc = Vec::with_capacity(num_arcs + 1);
+        let mut ipp_departure_time = Vec::with_capacity(ipps.ipp_departure_time.len());
+        let mut ipp_travel_time = Vec::with_capacity(ipps.ipp_travel_time.len());
+        first_ipp_of_arc.push(0);
+
+        for edge in 0..num_arcs {
+            let range = Self::ipp_range(ipps, edge);
+            let start = ipp_departure_time.len();
+            ipp_departure_time.extend_from_slice(&ipps.ipp_departure_time[range.clone()]);
+            ipp_travel_time.extend_from_slice(&ipps.ipp_travel_time[range]);
+
+            let mut edge_changed = false;
+            if ipp_departure_time.len() - start > 1 && *ipp_departure_time.last().unwrap() == period {
+                edge_changed |= *ipp_travel_time.last().unwrap() != ipp_travel_time[start];
+                ipp_departure_time.pop();
+                ipp_travel_time.pop();
+            }
+
+            let departure = &ipp_departure_time[start..];
+            let travel_time = &mut ipp_travel_time[start..];
+            edge_changed |= make_fifo(departure, travel_time);
+            // The last arrival does not grow when raising the first one to it (minus the period), so a second pass suffices.
+            if let Some(last) = departure.len().checked_sub(1) {
+                let wrapped_arrival = u64::from(departure[last]) + u64::from(travel_time[last]);
+                if u64::from(departure[0]) + u64::from(period) + u64::from(travel_time[0]) < wrapped_arrival {
+                    travel_time[0] = (wrapped_arrival - u64::from(period) - u64::from(departure[0])) as Weight;
+                    make_fifo(departure, travel_time);
+                    edge_changed = true;
+                }
+            }
+
+            changed += edge_changed as usize;
+            first_ipp_of_arc.push(ipp_departure_time.len() as u32);
+        }
+
+        *ipps = IppData {
+            first_ipp_of_arc,
+            ipp_departure_time,
+            ipp_travel_time,
+        };
+        changed
+    }
+
+    /// Extract the subgraph induced by the largest strongly connected component.
+    /// Returns the subgraph, the old id of each new node and the old id of each new edge.
+    pub fn largest_scc_subgraph(&self, components: &Components) -> (GraphData, Vec<NodeId>, Vec<EdgeId>) {
+        let in_largest = |node: NodeId| components.component[node as usize] == components.largest;
+
+        let new_to_old_node: Vec<NodeId> = (0..self.num_nodes() as NodeId).filter(|&node| in_largest(node)).collect();
+        let mut old_to_new_node = vec![INFINITY; self.num_nodes()];
+        for (new, &old) in new_to_old_node.iter().enumerate() {
+            old_to_new_node[old as usize] = new as NodeId;
+        }
+
+        let mut first_out = Vec::with_capacity(new_to_old_node.len() + 1);
+        let mut head = Vec::new();
+        let mut new_to_old_edge = Vec::new();
+        first_out.push(0);
+        for &node in &new_to_old_node {
+            for edge in self.first_out[node as usize]..self.first_out[node as usize + 1] {
+                let old_head = self.head[edge as usize];
+                if in_largest(old_head) {
+                    head.push(old_to_new_node[old_head as usize]);
+                    new_to_old_edge.push(edge);
+                }
+            }
+            first_out.push(head.len() as EdgeId);
+        }
+
+        let per_edge = |values: &Vec<Weight>| new_to_old_edge.iter().map(|&edge| values[edge as usize]).collect();
+        let per_node = |values: &Vec<f32>| new_to_old_node.iter().map(|&node| values[node as usize]).collect();
+
+        let ipps = self.ipps.as_ref().map(|ipps| {
+            let mut new_ipps = IppData::default();
+            new_ipps.first_ipp_of_arc.push(0);
+            for &edge in &new_to_old_edge {
+                let range = Self::ipp_range(ipps, edge as usize);
+                new_ipps.ipp_departure_time.extend_from_slice(&ipps.ipp_departure_time[range.clone()]);
+                new_ipps.ipp_travel_time.extend_from_slice(&ipps.ipp_travel_time[range]);
+                new_ipps.first_ipp_of_arc.push(new_ipps.ipp_departure_time.len() as u32);
+            }
+            new_ipps
+        });
+
+        let subgraph = GraphData {
+            first_out,
+            head,
+            travel_time: self.travel_time.as_ref().map(per_edge),
+            geo_distance: self.geo_distance.as_ref().map(per_edge),
+            ipps,
+            latitude: self.latitude.as_ref().map(per_node),
+            longitude: self.longitude.as_ref().map(per_node),
+        };
+
+        (subgraph, new_to_old_node, new_to_old_edge)
+    }
+}
+
+/// Check all invariants of the graph data.
+/// `period` is the length of the period of time-dependent travel time functions in milliseconds.
+pub fn validate(graph: &GraphData, period: Timestamp) -> Report {
+    let mut report = Report::default();
+    check_structure(graph, &mut report.issues);
+
+    if report.issues.iter().any(Issue::is_structural) {
+        return report;
+    }
+
+    if let Some(travel_time) = &graph.travel_time {
+        for (edge, &tt) in travel_time.iter().enumerate() {
+            if tt == 0 {
+                report.issues.push(Issue::ZeroTravelTime { edge: edge as EdgeId });
+            }
+        }
+    }
+
+    if let Some(ipps) = &graph.ipps {
+        for edge in 0..graph.num_arcs() {
+            check_travel_time_function(ipps, edge, period, &mut report.issues);
+        }
+    }
+
+    if let (Some(lat), Some(lng)) = (&graph.latitude, &graph.longitude) {
+        for (node, (&lat, &lng)) in lat.iter().zip(lng.iter()).enumerate() {
+            if !lat.is_finite() || !lng.is_finite() || lat.abs() > 90.0 || lng.abs() > 180.0 {
+                report.issues.push(Issue::InvalidCoordinate {
+                    node: node as NodeId,
+                    lat,
+                    lng,
+                });
+            }
+        }
+    }
+
+    let components = strongly_connected_components(&graph.first_out, &graph.head);
+    if components.num_components > 1 {
+        report.issues.push(Issue::NotStronglyConnected {
+            num_components: components.num_components,
+            nodes_outside_largest: graph.num_nodes() - components.largest_size,
+        });
+    }
+    report.components = Some(components);
+
+    report
+}
+
+fn check_structure(graph: &GraphData, issues: &mut Vec<Issue>) {
+    if graph.first_out.is_empty() {
+        issues.push(Issue::EmptyFirstOut);
+        return;
+    }
+    let n = graph.num_nodes();
+    let m = graph.num_arcs();
+
+    if graph.first_out[0] != 0 {
+        issues.push(Issue::FirstOutStart(graph.first_out[0]));
+    }
+    if *graph.first_out.last().unwrap() as usize != m {
+        issues.push(Issue::FirstOutEnd {
+            last: *graph.first_out.last().unwrap(),
+            num_arcs: m,
+        });
+    }
+    for (node, window) in graph.first_out.windows(2).enumerate() {
+        if window[0] > window[1] {
+            issues.push(Issue::FirstOutNotSorted { node: node as NodeId });
+        }
+    }
+    for (edge, &head) in graph.head.iter().enumerate() {
+        if head as usize >= n {
+            issues.push(Issue::HeadOutOfRange { edge: edge as EdgeId, head });
+        }
+    }
+
+    let mut check_len = |name, expected, actual| {
+        if expected != actual {
+            issues.push(Issue::WrongLength { name, expected, actual });
+        }
+    };
+    if let Some(travel_time) = &graph.travel_time {
+        check_len("travel_time", m, travel_time.len());
+    }
+    if let Some(geo_distance) = &graph.geo_distance {
+        check_len("geo_distance", m, geo_distance.len());
+    }
+    if let Some(lat) = &graph.latitude {
+        check_len("latitude", n, lat.len());
+    }
+    if let Some(lng) = &graph.longitude {
+        check_len("longitude", n, lng.len());
+    }
+    if let Some(ipps) = &graph.ipps {
+        check_len("first_ipp_of_arc", m + 1, ipps.first_ipp_of_arc.len());
+        check_len("ipp_travel_time", ipps.ipp_departure_time.len(), ipps.ipp_travel_time.len());
+
+        if ipps.first_ipp_of_arc.len() == m + 1 {
+            for edge in 0..m {
+                let range = GraphData::ipp_range(ipps, edge);
+                if range.start > range.end || range.end > ipps.ipp_departure_time.len() || range.end > ipps.ipp_travel_time.len() {
+                    issues.push(Issue::IppRangeInvalid { edge: edge as EdgeId });
+                }
+            }
+        }
+    }
+}
+
+fn check_travel_time_function(ipps: &IppData, edge: usize, period: Timestamp, issues: &mut Vec<Issue>) {
+    let range = GraphData::ipp_range(ipps, edge);
+    let edge_id = edge as EdgeId;
+    if range.is_empty() {
+        issues.push(Issue::EmptyTravelTimeFunction { edge: edge_id });
+        return;
+    }
+
+    let departure = &ipps.ipp_departure_time[range.clone()];
+    let travel_time = &ipps.ipp_travel_time[range.clone()];
+
+    for (i, (&at, &tt)) in departure.iter().zip(travel_time.iter()).enumerate() {
+        if at > period {
+            issues.push(Issue::IppOutsidePeriod {
+                edge: edge_id,
+                ipp: range.start + i,
+                at,
+            });
+        }
+        if tt == 0 {
+            issues.push(Issue::ZeroTravelTime { edge: edge_id });
+        }
+    }
+
+    for i in 1..departure.len() {
+        if departure[i - 1] >= departure[i] {
+            issues.push(Issue::IppNotSorted {
+                edge: edge_id,
+                ipp: range.start + i,
+            });
+        } else if u64::from(departure[i]) + u64::from(travel_time[i]) < u64::from(departure[i - 1]) + u64::from(travel_time[i - 1]) {
+            issues.push(Issue::FifoViolation {
+                edge: edge_id,
+                ipp: range.start + i - 1,
+            });
+        }
+    }
+
+    if departure.len() > 1 && *departure.last().unwrap() == period {
+        if *travel_time.last().unwrap() != travel_time[0] {
+            issues.push(Issue::NotPeriodic { edge: edge_id });
+        }
+    } else if departure.windows(2).all(|w| w[0] < w[1]) {
+        // without an explicit point at the period, the first point repeated one period later has to be reached last
+        let last = departure.len() - 1;
+        if u64::from(departure[0]) + u64::from(period) + u64::from(travel_time[0]) < u64::from(departure[last]) + u64::from(travel_time[last]) {
+            issues.push(Issue::FifoViolationAcrossPeriod { edge: edge_id });
+        }
+    }
+}
+
+// Raise travel times where a departure arrives earlier than the one before it, returns whether anything changed.
+fn make_fifo(departure: &[Timestamp], travel_time: &mut [Weight]) -> bool {
+    let mut changed = false;
+    for i in 1..departure.len() {
+        let earliest_arrival = departure[i - 1] + travel_time[i - 1];
+        if departure[i] + travel_time[i] < earliest_arrival {
+            travel_time[i] = earliest_arrival - departure[i];
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Tarjans algorithm, iteratively to avoid stack overflows on large graphs.
+pub fn strongly_connected_components(first_out: &[EdgeId], head: &[NodeId]) -> Components {
+    let n = first_out.len().saturating_sub(1);
+    const UNVISITED: u32 = u32::MAX;
+
+    let mut index = vec![UNVISITED; n];
+    let mut lowlink = vec![0u32; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut component = vec![UNVISITED; n];
+    let mut num_components = 0u32;
+    let mut next_index = 0u32;
+    // (node, next edge to explore)
+    let mut call_stack: Vec<(usize, usize)> = Vec::new();
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        call_stack.push((root, first_out[root] as usize));
+        index[root] = next_index;
+        lowlink[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(node, edge)) = call_stack.last() {
+            if edge < first_out[node + 1] as usize {
+                call_stack.last_mut().unwrap().1 += 1;
+                let next = head[edge] as usize;
+                if index[next] == UNVISITED {
+                    index[next] = next_index;
+                    lowlink[next] = next_index;
+                    next_index += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    call_stack.push((next, first_out[next] as usize));
+                } else if on_stack[next] {
+                    lowlink[node] = std::cmp::min(lowlink[node], index[next]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                lowlink[parent] = std::cmp::min(lowlink[parent], lowlink[node]);
+            }
+            if lowlink[node] == index[node] {
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component[member] = num_components;
+                    if member == node {
+                        break;
+                    }
+                }
+                num_components += 1;
+            }
+        }
+    }
+
+    let mut sizes = vec![0usize; num_components as usize];
+    for &c in &component {
+        sizes[c as usize] += 1;
+    }
+    let (largest, largest_size) = sizes
+        .iter()
+        .enumerate()
+        .max_by_key(|&(_, size)| *size)
+        .map(|(c, &size)| (c as u32, size))
+        .unwrap_or((0, 0));
+
+    Components {
+        component,
+        num_components: num_components as usize,
+        largest,
+        largest_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 <-> 1 <-> 2, 2 -> 3, 3 has no outgoing edges
+    fn graph_with_island() -> GraphData {
+        GraphData {
+            first_out: vec![0, 1, 3, 5, 5],
+            head: vec![1, 0, 2, 1, 3],
+            travel_time: Some(vec![1, 2, 3, 0, 5]),
+            latitude: Some(vec![49.0, 49.1, 49.2, 91.0]),
+            longitude: Some(vec![8.4, 8.4, 8.4, 8.4]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_issues_and_extracts_largest_scc() {
+        let mut graph = graph_with_island();
+        let report = validate(&graph, 86_400_000);
+        assert!(report.issues.contains(&Issue::ZeroTravelTime { edge: 3 }));
+        assert!(report.issues.contains(&Issue::NotStronglyConnected {
+            num_components: 2,
+            nodes_outside_largest: 1
+        }));
+        assert!(matches!(report.issues[..], [.., Issue::InvalidCoordinate { node: 3, .. }, _]));
+
+        assert_eq!(graph.fix_zero_travel_times(), 1);
+        let (subgraph, nodes, edges) = graph.largest_scc_subgraph(report.components.as_ref().unwrap());
+        assert_eq!(nodes, vec![0, 1, 2]);
+        assert_eq!(edges, vec![0, 1, 2, 3]);
+        assert_eq!(subgraph.first_out, vec![0, 1, 3, 4]);
+        assert!(validate(&subgraph, 86_400_000).is_valid());
+    }
+
+    #[test]
+    fn detects_and_repairs_fifo_violations() {
+        let mut graph = GraphData {
+            first_out: vec![0, 1, 2],
+            head: vec![1, 0],
+            ipps: Some(IppData {
+                first_ipp_of_arc: vec![0, 3, 4],
+                ipp_departure_time: vec![0, 1000, 100_000, 0],
+                ipp_travel_time: vec![5000, 2000, 6000, 10],
+            }),
+            ..Default::default()
+        };
+        let report = validate(&graph, 100_000);
+        assert_eq!(report.issues, vec![Issue::FifoViolation { edge: 0, ipp: 0 }, Issue::NotPeriodic { edge: 0 }]);
+
+        assert_eq!(graph.fifoize(100_000), 1);
+        let ipps = graph.ipps.as_ref().unwrap();
+        assert_eq!(ipps.first_ipp_of_arc, vec![0, 2, 3]);
+        assert_eq!(ipps.ipp_travel_time, vec![5000, 4000, 10]);
+        assert!(validate(&graph, 100_000).is_valid());
+    }
+
+    #[test]
+    fn repairs_fifo_violations_across_the_period() {
+        let mut graph = GraphData {
+            first_out: vec![0, 1, 2],
+            head: vec![1, 0],
+            ipps: Some(IppData {
+                first_ipp_of_arc: vec![0, 3, 5],
+                // departing at 90s arrives at 110s, later than departing at 100s (0s of the next period)
+                ipp_departure_time: vec![0, 500, 90_000, 0, 100_000],
+                ipp_travel_time: vec![1000, 1000, 20_000, 10, 10],
+            }),
+            ..Default::default()
+        };
+        let report = validate(&graph, 100_000);
+        assert_eq!(report.issues, vec![Issue::FifoViolationAcrossPeriod { edge: 0 }]);
+
+        // the matching point at the period is dropped, but that does not count as a change
+        assert_eq!(graph.fifoize(100_000), 1);
+        let ipps = graph.ipps.as_ref().unwrap();
+        assert_eq!(ipps.first_ipp_of_arc, vec![0, 3, 4]);
+        assert_eq!(ipps.ipp_departure_time, vec![0, 500, 90_000, 0]);
+        assert_eq!(ipps.ipp_travel_time, vec![10_000, 9500, 20_000, 10]);
+        assert!(validate(&graph, 100_000).is_valid());
+    }
+
+    #[test]
+    fn components_of_empty_graphs() {
+        for first_out in [&[][..], &[0][..]] {
+            let components = strongly_connected_components(first_out, &[]);
+            assert_eq!(components.num_components, 0);
+            assert_eq!(components.largest_size, 0);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+// Small local web viewer for graphs, routes and traffic data.
+// Serves a bundled single page app (no external tiles or scripts) on localhost.
+// Takes a graph directory (RoutingKit format with `latitude` and `longitude`) and an optional port (default 8080).
+//
+// The page loads the graph once and then requests data on demand:
+// - `/api/graph`: coordinates and adjacency array
+// - `/api/edge_values?name=<file>&type=u32|f32|f64`: any per edge vector in the graph directory, e.g. `travel_time`
+// - `/api/meandata?file=<path>&attribute=<attr>`: per interval edge values of a SUMO meandata dump, e.g. `density` or `traveltime`
+// - `/api/query?from=<node>&to=<node>`: Dijkstra path on `travel_time` and the settled nodes in order
+// - `/api/separators?max_level=<level>`: nested dissection separators from the `cch` subdirectory
+
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    io::{prelude::*, BufReader},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+};
+
+use conversion::{
+    sumo::{meandata::Edge, meandata_reader::SumoMeandataReader, FileReader},
+    FILE_EDGE_INDICES_TO_ID, FILE_LATITUDE, FILE_LONGITUDE,
+};
+use rust_road_router::{
+    algo::{customizable_contraction_hierarchy::*, dijkstra::*},
+    cli::CliErr,
+    datastr::graph::*,
+    io::*,
+    report::json,
+};
+use serde_json::Value;
+
+const INDEX_HTML: &str = include_str!("../../assets/viewer.html");
+const MAX_SEARCH_SPACE_SIZE: usize = 100_000;
+
+struct Viewer {
+    path: PathBuf,
+    graph: OwnedGraph,
+    lat: Vec<f32>,
+    lng: Vec<f32>,
+    dijkstra_data: DijkstraData<Weight>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let arg = &args.next().ok_or(CliErr("No directory arg given"))?;
+    let path = Path::new(arg);
+    let port = args.next().map(|port| port.parse::<u16>()).transpose()?.unwrap_or(8080);
+
+    let graph = WeightedGraphReconstructor("travel_time").reconstruct_from(&path)?;
+    let mut viewer = Viewer {
+        path: path.to_path_buf(),
+        lat: Vec::load_from(path.join(FILE_LATITUDE))?,
+        lng: Vec::load_from(path.join(FILE_LONGITUDE))?,
+        dijkstra_data: DijkstraData::new(graph.num_nodes()),
+        graph,
+    };
+
+    // Only bind to the loopback interface, this is not meant to be exposed.
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("Serving {} on http://127.0.0.1:{}/", path.display(), port);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Connection failed: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = viewer.handle(stream) {
+            eprintln!("Request failed: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+impl Viewer {
+    fn handle(&mut self, mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
+        let mut request_line = String::new();
+        BufReader::new(&mut stream).read_line(&mut request_line)?;
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let (route, query) = target.split_once('?').unwrap_or((target, ""));
+        let params = parse_query(query);
+
+        let response = match route {
+            "/" | "/index.html" => Ok(("text/html; charset=utf-8", INDEX_HTML.to_string())),
+            "/api/graph" => Ok(("application/json", self.graph_json().to_string())),
+            "/api/edge_values" => self.edge_values(&params).map(|v| ("application/json", v.to_string())),
+            "/api/meandata" => self.meandata(&params).map(|v| ("application/json", v.to_string())),
+            "/api/query" => self.query(&params).map(|v| ("application/json", v.to_string())),
+            "/api/separators" => self.separators(&params).map(|v| ("application/json", v.to_string())),
+            _ => {
+                return respond(&mut stream, "404 Not Found", "text/plain", "Not found");
+            }
+        };
+
+        match response {
+            Ok((content_type, body)) => respond(&mut stream, "200 OK", content_type, &body),
+            Err(e) => respond(&mut stream, "400 Bad Request", "text/plain", &e.to_string()),
+        }
+    }
+
+    fn graph_json(&self) -> Value {
+        json!({
+            "lat": self.lat,
+            "lng": self.lng,
+            "first_out": self.graph.first_out(),
+            "head": self.graph.head(),
+        })
+    }
+
+    fn edge_values(&self, params: &HashMap<String, String>) -> Result<Value, Box<dyn Error>> {
+        let name = params.get("name").ok_or(CliErr("No name param given"))?;
+        // do not allow escaping the graph directory
+        if name.contains('/') || name.contains("..") {
+            return Err(Box::new(CliErr("Invalid file name")));
+        }
+        let file = self.path.join(name);
+        let values: Vec<f64> = match params.get("type").map(String::as_str).unwrap_or("u32") {
+            "u32" => Vec::<u32>::load_from(file)?.into_iter().map(f64::from).collect(),
+            "f32" => Vec::<f32>::load_from(file)?.into_iter().map(f64::from).collect(),
+            "f64" => Vec::<f64>::load_from(file)?,
+            _ => return Err(Box::new(CliErr("Unknown type param"))),
+        };
+        if values.len() != self.graph.num_arcs() {
+            return Err(Box::new(CliErr("File does not contain one value per edge")));
+        }
+        Ok(json!({ "values": values }))
+    }
+
+    fn meandata(&self, params: &HashMap<String, String>) -> Result<Value, Box<dyn Error>> {
+        let file = params.get("file").ok_or(CliErr("No file param given"))?;
+        let attribute = params.get("attribute").map(String::as_str).unwrap_or("density");
+        let extract: fn(&Edge) -> Option<f64> = match attribute {
+            "traveltime" => |e| e.traveltime,
+            "density" => |e| e.density,
+            "laneDensity" => |e| e.lane_density,
+            "speed" => |e| e.speed,
+            "sampledSeconds" => |e| e.sampled_seconds,
+            "entered" => |e| e.entered.map(f64::from),
+            "left" => |e| e.left.map(f64::from),
+            _ => return Err(Box::new(CliErr("Unknown meandata attribute"))),
+        };
+
+        let edge_ids = read_strings_from_file(self.path.join(FILE_EDGE_INDICES_TO_ID))?;
+        let edge_idx: HashMap<&str, usize> = edge_ids.iter().enumerate().map(|(idx, id)| (id.as_str(), idx)).collect();
+        let meandata = SumoMeandataReader::read(Path::new(file))?;
+
+        let intervals: Vec<Value> = meandata
+            .intervals
+            .iter()
+            .map(|interval| {
+                let mut values = vec![None; self.graph.num_arcs()];
+                for edge in &interval.edges {
+                    if let Some(&idx) = edge_idx.get(edge.id.as_str()) {
+                        values[idx] = extract(edge);
+                    }
+                }
+                json!({ "begin": interval.begin, "end": interval.end, "values": values })
+            })
+            .collect();
+
+        Ok(json!({ "attribute": attribute, "intervals": intervals }))
+    }
+
+    fn query(&mut self, params: &HashMap<String, String>) -> Result<Value, Box<dyn Error>> {
+        let from = params.get("from").ok_or(CliErr("No from param given"))?.parse::<NodeId>()?;
+        let to = params.get("to").ok_or(CliErr("No to param given"))?.parse::<NodeId>()?;
+        if from as usize >= self.graph.num_nodes() || to as usize >= self.graph.num_nodes() {
+            return Err(Box::new(CliErr("Node id out of range")));
+        }
+
+        let mut ops = DefaultOps();
+        let mut dijkstra = DijkstraRun::query(&self.graph, &mut self.dijkstra_data, &mut ops, DijkstraInit::from(from));
+        let mut search_space = Vec::new();
+        let mut distance = None;
+        while let Some(node) = dijkstra.next() {
+            if search_space.len() < MAX_SEARCH_SPACE_SIZE {
+                search_space.push(node);
+            }
+            if node == to {
+                distance = Some(*dijkstra.tentative_distance(node));
+                break;
+            }
+        }
+
+        let path = distance.map(|_| self.dijkstra_data.node_path(from, to)).unwrap_or_default();
+        Ok(json!({ "distance": distance, "path": path, "search_space": search_space }))
+    }
+
+    fn separators(&self, params: &HashMap<String, String>) -> Result<Value, Box<dyn Error>> {
+        let max_level = params.get("max_level").map(|l| l.parse::<usize>()).transpose()?.unwrap_or(5);
+        let cch = CCHReconstrctor(&self.graph).reconstruct_from(&self.path.join("cch"))?;
+
+        let mut separators = Vec::new();
+        collect_separators(cch.separators(), &cch, 0, max_level, &mut separators);
+        Ok(json!({ "separators": separators }))
+    }
+}
+
+fn collect_separators(sep_tree: &separator_decomposition::SeparatorTree, cch: &CCH, level: usize, max_level: usize, separators: &mut Vec<Value>) {
+    if level > max_level {
+        return;
+    }
+    let nodes: Vec<NodeId> = sep_tree.nodes.iter().map(|rank| cch.node_order().node(rank)).collect();
+    separators.push(json!({ "level": level, "nodes": nodes }));
+    for child in &sep_tree.children {
+        collect_separators(child, cch, level + 1, max_level, separators);
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    Ok(())
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            // malformed escapes are kept as they are
+            b'%' if i + 2 < bytes.len() && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_query_strings() {
+        let query = parse_query("from=1&to=&name=a+b%20c&&flag");
+        assert_eq!(query.len(), 4);
+        assert_eq!(query["from"], "1");
+        assert_eq!(query["to"], "");
+        assert_eq!(query["name"], "a b c");
+        assert_eq!(query["flag"], "");
+        assert_eq!(parse_query("a%3Db=c%26d")["a=b"], "c&d");
+    }
+
+    #[test]
+    fn keeps_malformed_escapes() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%4"), "%4");
+        assert_eq!(percent_decode("%zz%41"), "%zzA");
+        assert_eq!(percent_decode("%%41"), "%A");
+        assert_eq!(percent_decode("%+1"), "% 1");
+    }
+
+    #[test]
+    fn decodes_multi_byte_utf8() {
+        assert_eq!(percent_decode("Stra%C3%9Fe"), "Straße");
+        assert_eq!(percent_decode("%E2%82%AC+%f0%9f%9a%97"), "€ 🚗");
+        assert_eq!(percent_decode("café"), "café");
+        // invalid sequences are replaced
+        assert_eq!(percent_decode("%C3"), "\u{FFFD}");
+    }
+}
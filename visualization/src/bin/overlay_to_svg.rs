@@ -7,7 +7,7 @@ use std::{env, error::Error, path::Path};
 use rust_road_router::{
     algo::contraction_hierarchy,
     cli::CliErr,
-    datastr::{graph::*, node_order::*},
+    datastr::{graph::*, node_order::*, spatial_index::*},
     io::*,
 };
 
@@ -30,39 +30,42 @@ fn main() -> Result<(), Box<dyn Error>> {
     let lat = Vec::<f32>::load_from(path.join("latitude"))?;
     let lng = Vec::<f32>::load_from(path.join("longitude"))?;
 
-    let in_bounding_box = |node| lat[node] >= min_lat && lat[node] <= max_lat && lng[node] >= min_lon && lng[node] <= max_lon;
+    let bounding_box = BoundingBox::new(min_lat as f64, min_lon as f64, max_lat as f64, max_lon as f64);
+    let in_bounding_box = |node: usize| bounding_box.contains(lat[node] as f64, lng[node] as f64);
+    // only nodes in the box which remain in the overlay, ordered by rank
+    let mut nodes = NodeGridIndex::new(&lat, &lng).nodes_in_bbox(&bounding_box);
+    nodes.retain(|&node| node_order.rank(node) as usize >= contraction_count);
+    nodes.sort_by_key(|&node| node_order.rank(node));
 
     let (up, down) = contraction_hierarchy::overlay(&graph, node_order.clone(), contraction_count);
 
     println!("<svg version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" viewBox=\"{} {} {} {}\" style=\"transform: scale(1,-1);\" preserveAspectRatio=\"none\">", min_lon, min_lat, max_lon - min_lon, max_lat - min_lat);
     println!("<g>");
 
-    for &node in &node_order.order()[contraction_count..] {
+    for node in nodes {
         let node = node as usize;
-        if in_bounding_box(node) {
-            println!(
-                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"node\" />",
-                lng[node], lat[node], lng[node], lat[node]
-            );
+        println!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"node\" />",
+            lng[node], lat[node], lng[node], lat[node]
+        );
 
-            for link in LinkIterable::<Link>::link_iter(&up, node_order.rank(node as NodeId)) {
-                let link_node = node_order.node(link.node) as usize;
-                if in_bounding_box(link_node) {
-                    println!(
-                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"arc\" />",
-                        lng[node], lat[node], lng[link_node], lat[link_node]
-                    );
-                }
+        for link in LinkIterable::<Link>::link_iter(&up, node_order.rank(node as NodeId)) {
+            let link_node = node_order.node(link.node) as usize;
+            if in_bounding_box(link_node) {
+                println!(
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"arc\" />",
+                    lng[node], lat[node], lng[link_node], lat[link_node]
+                );
             }
+        }
 
-            for link in LinkIterable::<Link>::link_iter(&down, node_order.rank(node as NodeId)) {
-                let link_node = node_order.node(link.node) as usize;
-                if in_bounding_box(link_node) {
-                    println!(
-                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"arc\" />",
-                        lng[link_node], lat[link_node], lng[node], lat[node]
-                    );
-                }
+        for link in LinkIterable::<Link>::link_iter(&down, node_order.rank(node as NodeId)) {
+            let link_node = node_order.node(link.node) as usize;
+            if in_bounding_box(link_node) {
+                println!(
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"arc\" />",
+                    lng[link_node], lat[link_node], lng[node], lat[node]
+                );
             }
         }
     }
@@ -0,0 +1,84 @@
+// Utility binary to export a shortest path, the CCH search spaces of its end points and an isochrone around the source
+// as GeoJSON (and the path additionally as GPX) for inspection in QGIS or kepler.gl.
+// Takes a graph directory (with `cch_perm`), source and target node ids, an output file name without extension
+// and an optional isochrone budget in the unit of `travel_time`.
+
+use std::{env, error::Error, path::Path};
+
+use rust_road_router::{
+    algo::{
+        customizable_contraction_hierarchy::{query::Server, *},
+        isochrone::CCHIsochrone,
+        *,
+    },
+    cli::CliErr,
+    datastr::{graph::*, node_order::NodeOrder},
+    export::{geojson::FeatureCollection, gpx::GpxTracks},
+    io::*,
+    report::json,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+
+    let arg = &args.next().ok_or(CliErr("No directory arg given"))?;
+    let path = Path::new(arg);
+    let from = args.next().ok_or(CliErr("No source arg given"))?.parse::<NodeId>()?;
+    let to = args.next().ok_or(CliErr("No target arg given"))?.parse::<NodeId>()?;
+    let output = args.next().ok_or(CliErr("No output file arg given"))?;
+    let budget = args.next().map(|budget| budget.parse::<Weight>()).transpose()?;
+
+    let graph = WeightedGraphReconstructor("travel_time").reconstruct_from(&path)?;
+    let lat = Vec::<f32>::load_from(path.join("latitude"))?;
+    let lng = Vec::<f32>::load_from(path.join("longitude"))?;
+    let order = NodeOrder::from_node_order(Vec::load_from(path.join("cch_perm"))?);
+    let cch = CCH::fix_order_and_build(&graph, order);
+    let customized = customize(&cch, &graph);
+
+    let mut features = FeatureCollection::new(&lat, &lng);
+    let mut tracks = GpxTracks::new(&lat, &lng);
+
+    features.add_cch_search_space(&cch, from, json!({ "kind": "forward_search_space" }));
+    features.add_cch_search_space(&cch, to, json!({ "kind": "backward_search_space" }));
+
+    if let Some(budget) = budget {
+        let mut isochrone = CCHIsochrone::new(&customized);
+        let result = isochrone.query(&graph, from, budget);
+        features.add_isochrone(&result, json!({ "budget": budget }));
+    }
+
+    let mut server = Server::new(customized);
+    if let Some(mut result) = server.query(Query { from, to }).found() {
+        let distance = result.distance();
+        let node_path = result.node_path();
+        features.add_node_path(&node_path, json!({ "kind": "path", "travel_time": distance }));
+
+        let mut dist = 0;
+        let mut timed_path = vec![(node_path[0], 0.0)];
+        for (i, nodes) in node_path.windows(2).enumerate() {
+            let edge = graph
+                .edge_indices(nodes[0], nodes[1])
+                .min_by_key(|&EdgeIdT(e)| graph.weight()[e as usize])
+                .unwrap()
+                .0;
+            let weight = graph.weight()[edge as usize];
+            features.add_edge(nodes[0], nodes[1], edge, |props| {
+                props.insert("kind".to_string(), json!("path_edge"));
+                props.insert("index".to_string(), json!(i));
+                props.insert("departure".to_string(), json!(dist));
+                props.insert("travel_time".to_string(), json!(weight));
+            });
+            dist += weight;
+            // travel_time is in ms
+            timed_path.push((nodes[1], f64::from(dist) / 1000.0));
+        }
+        tracks.add_timed_node_path(&format!("{} -> {}", from, to), timed_path);
+    } else {
+        eprintln!("No path from {} to {}", from, to);
+    }
+
+    features.write_to(&format!("{}.geojson", output))?;
+    tracks.write_to(&format!("{}.gpx", output), 0)?;
+
+    Ok(())
+}